@@ -0,0 +1,728 @@
+//! Assembler for the [Octo](https://github.com/JohnEarnest/Octo) language, so programs written for Octo can be run
+//! directly. Supported are all Chip-8 instructions, labels, `if`/`then`, `if`/`begin`/`else`/`end`,
+//! `loop`/`while`/`again`, raw bytes and the directives `:alias`, `:const`, `:calc`, `:byte`, `:org`, `:unpack` and
+//! `:macro`.
+//!
+//! Macros take a fixed number of arguments, which are substituted token by token into their body:
+//!
+//! ```text
+//! :macro move-right REG AMOUNT { REG += AMOUNT }
+//! move-right v1 2
+//! ```
+//!
+//! Like in Octo, a program starts at the label `main`. `:calc` expressions are evaluated right to left without
+//! operator precedence, so `{ 2 * 3 + 1 }` is `8`. Use parentheses to group.
+
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+use crate::bus::MEMORY_SIZE;
+use crate::chip8::PROGRAM_START;
+use crate::symbols::Symbols;
+
+/// Maximum number of macro expansions, to stop macros that expand themselves forever.
+const MAX_MACRO_EXPANSIONS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Line {line}: {message}")]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The result of assembling a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    /// The program, to be loaded at [`PROGRAM_START`].
+    pub rom: Vec<u8>,
+    /// Addresses of all labels.
+    pub labels: BTreeMap<String, u16>,
+    pub source_map: SourceMap,
+}
+
+/// Maps the addresses of assembled instructions back to the source, for source-level debugging.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Source line (starting at 1) of each instruction by address.
+    lines: BTreeMap<usize, usize>,
+    source: Vec<String>,
+}
+
+impl SourceMap {
+    /// The number and text of the source line the instruction at `addr` was assembled from.
+    pub fn line(&self, addr: usize) -> Option<(usize, &str)> {
+        let line = *self.lines.get(&addr)?;
+        Some((line, self.source.get(line - 1).map_or("", String::as_str)))
+    }
+
+    /// The address of the first instruction assembled from `line`, or from the next line with code if `line` has
+    /// none.
+    pub fn addr(&self, line: usize) -> Option<usize> {
+        self.lines.iter()
+            .filter(|(_, &instruction_line)| instruction_line >= line)
+            .min_by_key(|(&addr, &instruction_line)| (instruction_line, addr))
+            .map(|(&addr, _)| addr)
+    }
+}
+
+impl Assembly {
+    /// The labels as symbols, e.g. to write a symbol file for debugging.
+    pub fn symbols(&self) -> Symbols {
+        let mut symbols = Symbols::new();
+        for (name, &addr) in &self.labels {
+            symbols.insert(addr as usize, name.as_str());
+        }
+        symbols
+    }
+}
+
+/// Assembles Octo source code into a ROM.
+pub fn assemble(source: &str) -> Result<Assembly, AssembleError> {
+    let mut assembler = Assembler::new(tokenize(source));
+    assembler.program()?;
+    let source_map = SourceMap { lines: assembler.lines, source: source.lines().map(str::to_string).collect() };
+    Ok(Assembly { rom: assembler.rom, labels: assembler.labels, source_map })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// Splits the source into whitespace separated tokens, dropping `#` comments. Braces and parentheses are always
+/// tokens of their own.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        let mut start = None;
+        for (pos, c) in code.char_indices().chain(std::iter::once((code.len(), ' '))) {
+            let is_bracket = matches!(c, '{' | '}' | '(' | ')');
+            if c.is_whitespace() || is_bracket {
+                if let Some(start) = start.take() {
+                    tokens.push(Token { text: &code[start..pos], line: i + 1 });
+                }
+                if is_bracket {
+                    tokens.push(Token { text: &code[pos..pos + 1], line: i + 1 });
+                }
+            } else if start.is_none() {
+                start = Some(pos);
+            }
+        }
+    }
+    tokens
+}
+
+/// Where a label's address has to be filled in once it is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FixupKind {
+    /// The lower 12 bits of the opcode at the address, like in `1NNN`.
+    Address,
+    /// The lower nibble of the byte at the address gets the upper 4 bits of the address (for `:unpack`).
+    HighNibble,
+    /// The byte at the address gets the lower 8 bits of the address (for `:unpack`).
+    LowByte,
+}
+
+#[derive(Debug)]
+struct Fixup {
+    addr: usize,
+    label: String,
+    line: usize,
+    kind: FixupKind,
+}
+
+/// An open control flow structure, waiting for its `else`, `end` or `again`.
+#[derive(Debug)]
+enum Flow {
+    /// `if ... begin`, with the address of the jump to the `else` or `end`.
+    Begin { jump: usize },
+    /// `else`, with the address of the jump to the `end`.
+    Else { jump: usize },
+    /// `loop`, with the start address and the addresses of the jumps out of the loop by `while`.
+    Loop { start: usize, exits: Vec<usize> },
+}
+
+/// A macro defined with `:macro`.
+#[derive(Debug)]
+struct Macro<'a> {
+    params: Vec<&'a str>,
+    body: Vec<Token<'a>>,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    /// Address the next byte is written to.
+    here: usize,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, i64>,
+    /// Register aliases defined with `:alias`.
+    aliases: HashMap<String, u8>,
+    macros: HashMap<&'a str, Macro<'a>>,
+    macro_expansions: usize,
+    fixups: Vec<Fixup>,
+    flow: Vec<Flow>,
+    /// Source line of the statement being assembled.
+    statement_line: usize,
+    /// Source line of each instruction by address.
+    lines: BTreeMap<usize, usize>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            macro_expansions: 0,
+            fixups: Vec::new(),
+            flow: Vec::new(),
+            statement_line: 0,
+            lines: BTreeMap::new(),
+        }
+    }
+
+    fn program(&mut self) -> Result<(), AssembleError> {
+        // Execution starts at `main`, so jump there unless it is the very first thing in the program
+        let defines_main = self.tokens.windows(2).any(|pair| pair[0].text == ":" && pair[1].text == "main");
+        let starts_with_main = self.tokens.len() >= 2 && self.tokens[0].text == ":" && self.tokens[1].text == "main";
+        if defines_main && !starts_with_main {
+            self.emit_fixup(0x1000, "main", 0, FixupKind::Address)?;
+        }
+
+        while self.pos < self.tokens.len() {
+            self.statement_line = self.tokens[self.pos].line;
+            self.statement()?;
+        }
+
+        if let Some(flow) = self.flow.last() {
+            let expected = match flow {
+                Flow::Begin { .. } | Flow::Else { .. } => "end",
+                Flow::Loop { .. } => "again",
+            };
+            return Err(self.error(format!("Missing `{}`", expected)));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let addr = *self.labels.get(&fixup.label).ok_or_else(|| AssembleError {
+                line: fixup.line,
+                message: format!("Undefined label `{}`", fixup.label),
+            })?;
+            self.patch(fixup.addr, addr, fixup.kind);
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name.text.to_string(), self.here as u16).is_some() {
+                    return Err(self.error(format!("Label `{}` is defined twice", name.text)));
+                }
+            },
+            ":alias" => {
+                let name = self.next()?.text.to_string();
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            },
+            ":const" => {
+                let name = self.next()?.text.to_string();
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            },
+            ":calc" => {
+                let name = self.next()?.text.to_string();
+                self.expect("{")?;
+                let value = self.expression()?;
+                self.expect("}")?;
+                self.constants.insert(name, value);
+            },
+            ":byte" => {
+                let value = match self.peek_text() {
+                    Some("{") => {
+                        self.next()?;
+                        let value = self.expression()?;
+                        self.expect("}")?;
+                        value
+                    },
+                    _ => self.value()?,
+                };
+                self.emit_byte(self.byte(value)?)?;
+            },
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let addr = self.value()?;
+                self.here = self.address_value(addr)? as usize;
+            },
+            ":unpack" => {
+                let nibble = self.value()? as u16 & 0xF;
+                let label = self.next()?;
+                // v0 := nibble and high part of the address, v1 := low part of the address
+                self.emit_address(0x6000 | nibble << 4, label, FixupKind::HighNibble)?;
+                self.emit_address(0x6100, label, FixupKind::LowByte)?;
+            },
+            "clear" => self.emit_word(0x00E0)?,
+            "return" | ";" => self.emit_word(0x00EE)?,
+            "jump" => self.emit_target(0x1000)?,
+            "jump0" => self.emit_target(0xB000)?,
+            "native" => self.emit_target(0x0000)?,
+            "bcd" => self.emit_x(0xF033)?,
+            "save" => self.emit_x(0xF055)?,
+            "load" => self.emit_x(0xF065)?,
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let height = self.value()?;
+                if !(0..=15).contains(&height) {
+                    return Err(self.error(format!("Sprite height {} is out of range", height)));
+                }
+                self.emit_word(0xD000 | x << 8 | y << 4 | height as u16)?;
+            },
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let opcode = if token.text == "delay" { 0xF015 } else { 0xF018 };
+                self.emit_x(opcode)?;
+            },
+            "i" => self.i_statement()?,
+            "if" => self.if_statement()?,
+            "else" => match self.flow.pop() {
+                Some(Flow::Begin { jump }) => {
+                    let end_jump = self.here;
+                    self.emit_word(0x1000)?;
+                    self.patch(jump, self.here as u16, FixupKind::Address);
+                    self.flow.push(Flow::Else { jump: end_jump });
+                },
+                _ => return Err(self.error("`else` without `if ... begin`")),
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::Begin { jump }) | Some(Flow::Else { jump }) => {
+                    self.patch(jump, self.here as u16, FixupKind::Address);
+                },
+                _ => return Err(self.error("`end` without `if ... begin`")),
+            },
+            "loop" => self.flow.push(Flow::Loop { start: self.here, exits: Vec::new() }),
+            "while" => {
+                let skip = self.condition()?;
+                let exit = self.here + 2;
+                self.emit_word(skip)?;
+                self.emit_word(0x1000)?;
+                match self.flow.iter_mut().rev().find(|flow| matches!(flow, Flow::Loop { .. })) {
+                    Some(Flow::Loop { exits, .. }) => exits.push(exit),
+                    _ => return Err(self.error("`while` outside of `loop`")),
+                }
+            },
+            "again" => match self.flow.pop() {
+                Some(Flow::Loop { start, exits }) => {
+                    self.emit_word(0x1000 | start as u16)?;
+                    for exit in exits {
+                        self.patch(exit, self.here as u16, FixupKind::Address);
+                    }
+                },
+                _ => return Err(self.error("`again` without `loop`")),
+            },
+            _ if self.is_register(token.text) => {
+                self.pos -= 1;
+                self.register_statement()?;
+            },
+            _ if parse_number(token.text).is_some() || self.constants.contains_key(token.text) => {
+                self.pos -= 1;
+                let value = self.value()?;
+                self.emit_byte(self.byte(value)?)?;
+            },
+            _ if self.macros.contains_key(token.text) => self.expand_macro(token)?,
+            _ if token.text.starts_with(':') => {
+                return Err(self.error(format!("Unknown directive `{}`", token.text)));
+            },
+            // Everything else is a call of the subroutine with that name
+            _ => self.emit_address(0x2000, token, FixupKind::Address)?,
+        }
+        Ok(())
+    }
+
+    /// `:macro name params... { body }`. Braces inside the body have to be balanced.
+    fn define_macro(&mut self) -> Result<(), AssembleError> {
+        let name = self.next()?.text;
+        let mut params = Vec::new();
+        loop {
+            match self.next()?.text {
+                "{" => break,
+                param => params.push(param),
+            }
+        }
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            match token.text {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => {},
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    /// Replaces the invocation of the macro `name` with its body, in which the parameters are replaced by the
+    /// arguments following the invocation.
+    fn expand_macro(&mut self, name: Token<'a>) -> Result<(), AssembleError> {
+        self.macro_expansions += 1;
+        if self.macro_expansions > MAX_MACRO_EXPANSIONS {
+            return Err(self.error(format!("Too many macro expansions, does `{}` expand itself?", name.text)));
+        }
+        let param_count = self.macros[name.text].params.len();
+        let args = (0..param_count).map(|_| self.next()).collect::<Result<Vec<_>, _>>()?;
+        let definition = &self.macros[name.text];
+        let expanded: Vec<Token<'a>> = definition.body.iter()
+            .map(|token| match definition.params.iter().position(|&param| param == token.text) {
+                Some(i) => args[i],
+                None => *token,
+            })
+            .collect();
+        self.tokens.splice(self.pos..self.pos, expanded);
+        Ok(())
+    }
+
+    /// `vx := ...`, `vx += ...` and so on.
+    fn register_statement(&mut self) -> Result<(), AssembleError> {
+        let x = self.register()? as u16;
+        let operator = self.next()?;
+        let xy = |opcode: u16, y: u8| opcode | x << 8 | (y as u16) << 4;
+        let opcode = match operator.text {
+            ":=" => match self.peek_text() {
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.value()?;
+                    0xC000 | x << 8 | self.byte(mask)? as u16
+                },
+                Some("delay") => {
+                    self.next()?;
+                    0xF007 | x << 8
+                },
+                Some("key") => {
+                    self.next()?;
+                    0xF00A | x << 8
+                },
+                Some(text) if self.is_register(text) => xy(0x8000, self.register()?),
+                _ => {
+                    let value = self.value()?;
+                    0x6000 | x << 8 | self.byte(value)? as u16
+                },
+            },
+            "+=" => match self.peek_text() {
+                Some(text) if self.is_register(text) => xy(0x8004, self.register()?),
+                _ => {
+                    let value = self.value()?;
+                    0x7000 | x << 8 | self.byte(value)? as u16
+                },
+            },
+            "-=" => match self.peek_text() {
+                Some(text) if self.is_register(text) => xy(0x8005, self.register()?),
+                _ => {
+                    let value = self.value()?;
+                    0x7000 | x << 8 | self.byte(-value)? as u16
+                },
+            },
+            "|=" => xy(0x8001, self.register()?),
+            "&=" => xy(0x8002, self.register()?),
+            "^=" => xy(0x8003, self.register()?),
+            ">>=" => xy(0x8006, self.register()?),
+            "=-" => xy(0x8007, self.register()?),
+            "<<=" => xy(0x800E, self.register()?),
+            other => return Err(self.error(format!("Unknown operator `{}`", other))),
+        };
+        self.emit_word(opcode)
+    }
+
+    /// `i := addr`, `i := hex vx` and `i += vx`.
+    fn i_statement(&mut self) -> Result<(), AssembleError> {
+        match self.next()?.text {
+            ":=" if self.peek_text() == Some("hex") => {
+                self.next()?;
+                self.emit_x(0xF029)
+            },
+            ":=" => self.emit_target(0xA000),
+            "+=" => self.emit_x(0xF01E),
+            other => Err(self.error(format!("Unknown operator `{}` for i", other))),
+        }
+    }
+
+    /// `if cond then statement` and `if cond begin ... end`.
+    fn if_statement(&mut self) -> Result<(), AssembleError> {
+        let skip_if_true = self.condition()?;
+        match self.next()?.text {
+            "then" => self.emit_word(invert_skip(skip_if_true)),
+            "begin" => {
+                self.emit_word(skip_if_true)?;
+                self.flow.push(Flow::Begin { jump: self.here });
+                self.emit_word(0x1000)
+            },
+            other => Err(self.error(format!("Expected `then` or `begin`, found `{}`", other))),
+        }
+    }
+
+    /// Parses a condition like `v0 == 5` or `v1 key` and returns the instruction that skips if it is true.
+    fn condition(&mut self) -> Result<u16, AssembleError> {
+        let x = self.register()? as u16;
+        let operator = self.next()?;
+        match operator.text {
+            "key" => return Ok(0xE09E | x << 8),
+            "-key" => return Ok(0xE0A1 | x << 8),
+            _ => {},
+        }
+        let is_register = self.peek_text().is_some_and(|text| self.is_register(text));
+        let (equal, not_equal) = match is_register {
+            true => {
+                let y = self.register()? as u16;
+                (0x5000 | x << 8 | y << 4, 0x9000 | x << 8 | y << 4)
+            },
+            false => {
+                let value = self.value()?;
+                let byte = self.byte(value)? as u16;
+                (0x3000 | x << 8 | byte, 0x4000 | x << 8 | byte)
+            },
+        };
+        match operator.text {
+            "==" => Ok(equal),
+            "!=" => Ok(not_equal),
+            other => Err(self.error(format!("Unsupported comparison `{}`", other))),
+        }
+    }
+
+    /// Evaluates a `:calc` expression up to the closing brace, right to left.
+    fn expression(&mut self) -> Result<i64, AssembleError> {
+        let left = match self.next()?.text {
+            "(" => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                value
+            },
+            "-" => return Ok(-self.expression()?),
+            "~" => return Ok(!self.expression()?),
+            "!" => return Ok((self.expression()? == 0) as i64),
+            _ => {
+                self.pos -= 1;
+                self.term()?
+            },
+        };
+        let operator = match self.peek_text() {
+            None | Some("}") | Some(")") => return Ok(left),
+            Some(operator) => operator,
+        };
+        self.next()?;
+        let right = self.expression()?;
+        let value = match operator {
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" | "%" if right == 0 => return Err(self.error("Division by zero")),
+            "/" => left / right,
+            "%" => left % right,
+            "&" => left & right,
+            "|" => left | right,
+            "^" => left ^ right,
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as i64,
+            ">" => (left > right) as i64,
+            "<=" => (left <= right) as i64,
+            ">=" => (left >= right) as i64,
+            "==" => (left == right) as i64,
+            "!=" => (left != right) as i64,
+            other => return Err(self.error(format!("Unknown operator `{}`", other))),
+        };
+        Ok(value)
+    }
+
+    /// A number, constant or label inside a `:calc` expression.
+    fn term(&mut self) -> Result<i64, AssembleError> {
+        let token = self.next()?;
+        if token.text == "HERE" {
+            return Ok(self.here as i64);
+        }
+        if let Some(&addr) = self.labels.get(token.text) {
+            return Ok(addr as i64);
+        }
+        self.pos -= 1;
+        self.value()
+    }
+
+    /// A number or constant.
+    fn value(&mut self) -> Result<i64, AssembleError> {
+        let token = self.next()?;
+        parse_number(token.text)
+            .or_else(|| self.constants.get(token.text).copied())
+            .ok_or_else(|| self.error(format!("Expected a number, found `{}`", token.text)))
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        register_number(token.text)
+            .or_else(|| self.aliases.get(token.text).copied())
+            .ok_or_else(|| self.error(format!("Expected a register, found `{}`", token.text)))
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        register_number(text).is_some() || self.aliases.contains_key(text)
+    }
+
+    fn byte(&self, value: i64) -> Result<u8, AssembleError> {
+        match value {
+            -128..=255 => Ok(value as u8),
+            _ => Err(self.error(format!("{} doesn't fit into a byte", value))),
+        }
+    }
+
+    fn address_value(&self, value: i64) -> Result<u16, AssembleError> {
+        match value {
+            0..=0xFFF => Ok(value as u16),
+            _ => Err(self.error(format!("{} is not a valid address", value))),
+        }
+    }
+
+    /// Emits `opcode` with the register of the next token as `X`.
+    fn emit_x(&mut self, opcode: u16) -> Result<(), AssembleError> {
+        let x = self.register()? as u16;
+        self.emit_word(opcode | x << 8)
+    }
+
+    /// Emits `opcode` with the address (a number, constant or label) of the next token as `NNN`.
+    fn emit_target(&mut self, opcode: u16) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        self.emit_address(opcode, token, FixupKind::Address)
+    }
+
+    /// Emits `opcode` combined with the address of `target`, which may be a label defined later.
+    fn emit_address(&mut self, opcode: u16, target: Token<'_>, kind: FixupKind) -> Result<(), AssembleError> {
+        let addr = self.here;
+        self.emit_word(opcode)?;
+        match parse_number(target.text).or_else(|| self.constants.get(target.text).copied()) {
+            Some(value) => {
+                let value = self.address_value(value)?;
+                self.patch(addr, value, kind);
+                Ok(())
+            },
+            None => {
+                self.fixups.push(Fixup { addr, label: target.text.to_string(), line: target.line, kind });
+                Ok(())
+            },
+        }
+    }
+
+    fn emit_fixup(&mut self, opcode: u16, label: &str, line: usize, kind: FixupKind) -> Result<(), AssembleError> {
+        self.fixups.push(Fixup { addr: self.here, label: label.to_string(), line, kind });
+        self.emit_word(opcode)
+    }
+
+    fn emit_word(&mut self, word: u16) -> Result<(), AssembleError> {
+        // The jump to `main` doesn't belong to any line
+        if self.statement_line > 0 {
+            self.lines.insert(self.here, self.statement_line);
+        }
+        let [upper, lower] = word.to_be_bytes();
+        self.emit_byte(upper)?;
+        self.emit_byte(lower)
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), AssembleError> {
+        if !(PROGRAM_START..MEMORY_SIZE).contains(&self.here) {
+            return Err(self.error(format!("Address {:#X} is outside of the program memory", self.here)));
+        }
+        let offset = self.here - PROGRAM_START;
+        if self.rom.len() <= offset {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// Fills `value` into the instruction at `addr`.
+    fn patch(&mut self, addr: usize, value: u16, kind: FixupKind) {
+        let offset = addr - PROGRAM_START;
+        match kind {
+            FixupKind::Address => {
+                self.rom[offset] = (self.rom[offset] & 0xF0) | (value >> 8) as u8 & 0x0F;
+                self.rom[offset + 1] = value as u8;
+            },
+            FixupKind::HighNibble => self.rom[offset + 1] |= (value >> 8) as u8 & 0x0F,
+            FixupKind::LowByte => self.rom[offset + 1] = value as u8,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AssembleError> {
+        let token = self.tokens.get(self.pos).copied().ok_or_else(|| self.error("Unexpected end of source"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek_text(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        match token.text == expected {
+            true => Ok(()),
+            false => Err(self.error(format!("Expected `{}`, found `{}`", expected, token.text))),
+        }
+    }
+
+    /// An error at the line of the last consumed token.
+    fn error(&self, message: impl Into<String>) -> AssembleError {
+        let line = self.tokens.get(self.pos.saturating_sub(1)).map_or(0, |token| token.line);
+        AssembleError { line, message: message.into() }
+    }
+}
+
+/// Parses decimal, hexadecimal (`0x`) and binary (`0b`) numbers, optionally negative.
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// The number of the register `v0` to `vf`.
+fn register_number(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+    match digit.len() {
+        1 => u8::from_str_radix(digit, 16).ok(),
+        _ => None,
+    }
+}
+
+/// Turns an instruction that skips if a condition is true into one that skips if it is false.
+fn invert_skip(opcode: u16) -> u16 {
+    match opcode & 0xF0FF {
+        0xE09E => opcode & 0xFF00 | 0xA1,
+        0xE0A1 => opcode & 0xFF00 | 0x9E,
+        _ => match opcode >> 12 {
+            0x3 => opcode & 0x0FFF | 0x4000,
+            0x4 => opcode & 0x0FFF | 0x3000,
+            0x5 => opcode & 0x0FFF | 0x9000,
+            0x9 => opcode & 0x0FFF | 0x5000,
+            _ => opcode,
+        },
+    }
+}
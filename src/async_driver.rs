@@ -0,0 +1,76 @@
+//! Runs the emulation inside tokio-based async hosts, e.g. servers. [`Chip8Async::run`] awaits the next frame and key
+//! events instead of blocking the thread with [`std::thread::sleep`] like [`Chip8::run`].
+//!
+//! A [`Chip8`] can't be sent between threads, so the futures have to run on a single-threaded runtime or in a
+//! [`tokio::task::LocalSet`]. Hooks are still called synchronously, so they shouldn't block, e.g. by waiting for a
+//! debugger command.
+
+use std::ops::ControlFlow;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{self, MissedTickBehavior};
+use crate::chip8::FRAME_DURATION;
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error, RunStatus};
+
+pub use crate::input::KeyEvent;
+
+/// Drives a [`Chip8`] at 60 frames per second on an async runtime.
+#[derive(Debug)]
+pub struct Chip8Async {
+    chip8: Chip8,
+    /// Key events applied between frames, or `None` if the keypad is set through [`Chip8Async::chip8_mut`].
+    keys: Option<UnboundedReceiver<KeyEvent>>,
+}
+
+impl Chip8Async {
+    pub fn new(chip8: Chip8) -> Self {
+        Self { chip8, keys: None }
+    }
+
+    /// Presses and releases keys as events arrive from `keys`, e.g. sent by a WebSocket handler.
+    pub fn set_keys(&mut self, keys: UnboundedReceiver<KeyEvent>) {
+        self.keys = Some(keys);
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    pub fn into_inner(self) -> Chip8 {
+        self.chip8
+    }
+
+    /// Runs like [`Chip8::run`] according to the [`crate::RunPolicy`], but without printing the display.
+    pub async fn run(&mut self) -> Result<RunStatus, Chip8Error> {
+        self.run_with_hooks(&mut ()).await
+    }
+
+    /// Like [`Chip8Async::run`], but calls `hooks` after every instruction and every frame.
+    pub async fn run_with_hooks(&mut self, hooks: &mut impl Hooks) -> Result<RunStatus, Chip8Error> {
+        let mut frames = time::interval(FRAME_DURATION);
+        // Fell behind, e.g. because the runtime was busy. Continue from now instead of rushing to catch up.
+        frames.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = frames.tick() => {
+                    if let ControlFlow::Break(status) = self.chip8.run_policy_frame(hooks, false)? {
+                        return Ok(status);
+                    }
+                },
+                Some(event) = recv_key(&mut self.keys) => self.chip8.set_key_state(event.key, event.pressed),
+            }
+        }
+    }
+}
+
+/// Waits for the next key event. Returns `None` right away without a receiver or after all senders were dropped.
+async fn recv_key(keys: &mut Option<UnboundedReceiver<KeyEvent>>) -> Option<KeyEvent> {
+    match keys {
+        Some(keys) => keys.recv().await,
+        None => None,
+    }
+}
@@ -0,0 +1,286 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Sample rate of the generated audio in Hz.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Something that makes the sound of the Chip-8, e.g. audio output, the terminal bell or an LED. The interpreter
+/// calls it whenever the sound timer changes between zero and non-zero, see [`crate::Chip8::set_beeper`].
+pub trait Beeper: fmt::Debug {
+    /// Starts (`true`) or stops (`false`) the sound.
+    fn set_active(&mut self, active: bool);
+
+    /// Whether the beeper makes a sound, so that the [`Mixer`] silences it. Visual beepers like a flashing screen
+    /// return `false` and stay active while muted.
+    fn is_audible(&self) -> bool {
+        true
+    }
+}
+
+/// Shape of one period of the beep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    /// The harsh tone of the original hardware.
+    #[default]
+    Square,
+    Triangle,
+    /// A pure, soft tone.
+    Sine,
+}
+
+impl Waveform {
+    /// The value of the wave at `phase` (from 0 to 1) between -1 and 1. The square wave is high for the first
+    /// `duty_cycle` of the period.
+    pub fn sample(self, phase: f64, duty_cycle: f64) -> f64 {
+        match self {
+            Self::Square if phase < duty_cycle => 1.0,
+            Self::Square => -1.0,
+            Self::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Self::Sine => (phase * 2.0 * PI).sin(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    /// Parses `square`, `triangle` or `sine`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Self::Square),
+            "triangle" => Ok(Self::Triangle),
+            "sine" => Ok(Self::Sine),
+            _ => Err(format!("Invalid waveform {:?}, expected square, triangle or sine", s)),
+        }
+    }
+}
+
+/// How the beep sounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// In Hz, 440 by default.
+    pub frequency: f64,
+    pub waveform: Waveform,
+    /// From 0 (silent) to 1 (loudest), a quarter by default so that it isn't too loud.
+    pub volume: f64,
+    /// Fraction of the period in which the square wave is high, from 0 to 1. Half by default, lower values sound
+    /// thinner. Only affects [`Waveform::Square`].
+    pub duty_cycle: f64,
+}
+
+impl Tone {
+    /// Checks that the frequency can be generated at `sample_rate`, and that volume and duty cycle are between 0
+    /// and 1.
+    pub fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        let nyquist = sample_rate as f64 / 2.0;
+        if !(self.frequency > 0.0 && self.frequency < nyquist) {
+            return Err(format!("The frequency has to be between 0 and {} Hz", nyquist));
+        }
+        if !(0.0..=1.0).contains(&self.volume) {
+            return Err(String::from("The volume has to be between 0 and 100%"));
+        }
+        if !(0.0..=1.0).contains(&self.duty_cycle) {
+            return Err(String::from("The duty cycle has to be between 0 and 100%"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self { frequency: 440.0, waveform: Waveform::Square, volume: 0.25, duty_cycle: 0.5 }
+    }
+}
+
+/// Generates the beeper sound as samples of a [`Tone`]. The phase is kept across calls, so consecutive frames don't
+/// click.
+#[derive(Debug, Clone)]
+pub struct Synth {
+    sample_rate: u32,
+    tone: Tone,
+    /// Position in the current period, from 0 to 1.
+    phase: f64,
+    /// Fraction of the sample rate not yet emitted, so that frames average out to exactly `sample_rate` samples.
+    pending_samples: f64,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32, tone: Tone) -> Self {
+        Self { sample_rate, tone, phase: 0.0, pending_samples: 0.0 }
+    }
+
+    pub fn tone(&self) -> &Tone {
+        &self.tone
+    }
+
+    /// Changes the tone, continuing at the current phase.
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.tone = tone;
+    }
+
+    /// Appends the samples for one frame of `1 / fps` seconds to `out`. While `beeping` is false, silence is
+    /// generated.
+    pub fn frame(&mut self, beeping: bool, fps: u32, out: &mut Vec<i16>) {
+        self.pending_samples += self.sample_rate as f64 / fps as f64;
+        let samples = self.pending_samples as usize;
+        self.pending_samples -= samples as f64;
+        let step = self.tone.frequency / self.sample_rate as f64;
+        let amplitude = self.tone.volume.clamp(0.0, 1.0) * i16::MAX as f64;
+        for _ in 0..samples {
+            let sample = match beeping {
+                false => 0,
+                true => (self.tone.waveform.sample(self.phase, self.tone.duty_cycle) * amplitude) as i16,
+            };
+            out.push(sample);
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+/// The last stage of the audio output: the master volume and muting, applied to everything the emulator plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mixer {
+    /// From 0 (silent) to 1 (unchanged).
+    volume: f64,
+    muted: bool,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// Sets the volume from 0 (silent) to 1 (unchanged), clamping values outside.
+    pub fn set_volume(&mut self, volume: f64) {
+        self.volume = if volume.is_nan() { 1.0 } else { volume.clamp(0.0, 1.0) };
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Mutes or unmutes without changing the volume.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// Whether anything can be heard, i.e. not muted and the volume isn't 0.
+    pub fn is_audible(&self) -> bool {
+        self.gain() > 0.0
+    }
+
+    /// Factor the samples are multiplied with.
+    pub fn gain(&self) -> f64 {
+        if self.muted { 0.0 } else { self.volume }
+    }
+
+    /// Applies the volume to `samples`.
+    pub fn apply(&self, samples: &mut [i16]) {
+        let gain = self.gain();
+        if gain == 1.0 {
+            return;
+        }
+        for sample in samples {
+            *sample = (*sample as f64 * gain) as i16;
+        }
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self { volume: 1.0, muted: false }
+    }
+}
+
+/// Converts audio from the rate it's generated at, like [`SAMPLE_RATE`], to the rate of the output, e.g. 48 kHz of
+/// a sound card or 16 kHz of a Bluetooth headset. Interpolates linearly between the input samples, and keeps its
+/// position across calls, so that audio can be converted in chunks, e.g. frame by frame.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    /// Position of the next output sample in the input, where 0 is the last sample of the previous chunk and 1 the
+    /// first sample of the next chunk.
+    position: f64,
+    /// Last sample of the previous chunk.
+    previous: i16,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        Self { input_rate, output_rate, position: 1.0, previous: 0 }
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Appends `input` converted to the output rate to `out`. The last input sample is only interpolated with when
+    /// the next chunk arrives.
+    pub fn process(&mut self, input: &[i16], out: &mut Vec<i16>) {
+        if self.input_rate == self.output_rate {
+            out.extend_from_slice(input);
+            return;
+        }
+        let step = self.input_rate as f64 / self.output_rate as f64;
+        let previous = self.previous;
+        let sample = |index: usize| if index == 0 { previous } else { input[index - 1] };
+        loop {
+            let index = self.position as usize;
+            let fraction = self.position - index as f64;
+            let sample = match (index > input.len(), index == input.len()) {
+                (false, false) => {
+                    let (current, next) = (sample(index) as f64, sample(index + 1) as f64);
+                    (current + (next - current) * fraction).round() as i16
+                },
+                (false, true) if fraction == 0.0 => sample(index),
+                _ => break,
+            };
+            out.push(sample);
+            self.position += step;
+        }
+        if let Some(&last) = input.last() {
+            self.previous = last;
+            self.position -= input.len() as f64;
+        }
+    }
+}
+
+/// Writes `samples` as 16 bit mono WAV file.
+pub fn write_wav(path: impl AsRef<Path>, sample_rate: u32, samples: &[i16]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let data_len = (samples.len() * 2) as u32;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&1u16.to_le_bytes())?; // Mono
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?; // Bytes per second
+    out.write_all(&2u16.to_le_bytes())?; // Bytes per sample
+    out.write_all(&16u16.to_le_bytes())?; // Bits per sample
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        out.write_all(&sample.to_le_bytes())?;
+    }
+    out.flush()
+}
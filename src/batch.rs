@@ -0,0 +1,129 @@
+//! Runs every ROM in a directory headless for a fixed number of frames, as a quick compatibility smoke test after
+//! changes to the interpreter.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use crate::input_script::InputScript;
+use crate::{compat, rom, Chip8, Chip8Error, Metrics, RunStatus};
+
+/// How running a ROM ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Ran for all frames.
+    Completed,
+    /// Stopped with an error at the instruction at `pc`.
+    Error { error: Chip8Error, pc: usize },
+    /// Reached a jump to itself at `pc`, which ROMs use to stop.
+    InfiniteLoop { pc: usize },
+    /// The file couldn't be loaded as a ROM.
+    InvalidRom(String),
+}
+
+/// The result of running one ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomResult {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub metrics: Metrics,
+}
+
+impl fmt::Display for RomResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        match &self.outcome {
+            Outcome::Completed => write!(f, "ok")?,
+            Outcome::Error { error, pc } => write!(f, "error at {:#05X}: {}", pc, error)?,
+            Outcome::InfiniteLoop { pc } => write!(f, "infinite loop at {:#05X}", pc)?,
+            Outcome::InvalidRom(message) => return write!(f, "can't load: {}", message),
+        }
+        write!(
+            f,
+            " ({} frames, {} instructions, {} draw calls)",
+            self.metrics.frames, self.metrics.instructions, self.metrics.draw_calls,
+        )
+    }
+}
+
+/// Runs `program` for `frames` frames with the settings of the compatibility table, if it's known there.
+pub fn run_rom(program: &[u8], frames: u64) -> (Outcome, Metrics) {
+    run_rom_with_input(program, frames, &mut InputScript::default())
+}
+
+/// Like [`run_rom`], but presses and releases keys according to `input`.
+pub fn run_rom_with_input(program: &[u8], frames: u64, input: &mut InputScript) -> (Outcome, Metrics) {
+    let mut chip8 = Chip8::new(program);
+    // Fixed seed, so that the results are reproducible
+    chip8.set_seed(0);
+    if let Some(profile) = compat::lookup(program) {
+        profile.apply(&mut chip8);
+    }
+    for _ in 0..frames {
+        input.apply(&mut chip8);
+        match chip8.run_frame_with_hooks(&mut ()) {
+            Ok(ControlFlow::Break(RunStatus::Halted { pc })) => return (Outcome::InfiniteLoop { pc }, *chip8.metrics()),
+            Ok(_) => {},
+            Err(error) => {
+                // The failed instruction is the last one in the history, even if the error doesn't name its address
+                let pc = chip8.history.back().map_or(chip8.pc, |&(pc, _)| pc);
+                return (Outcome::Error { error, pc }, *chip8.metrics());
+            },
+        }
+    }
+    (Outcome::Completed, *chip8.metrics())
+}
+
+/// Runs every file in `dir` as a ROM for `frames` frames, in alphabetical order. Subdirectories are skipped.
+pub fn run_dir(dir: impl AsRef<Path>, frames: u64) -> io::Result<Vec<RomResult>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    let results = paths.into_iter()
+        .map(|path| {
+            let (outcome, metrics) = match rom::load(&path.to_string_lossy()) {
+                Ok(rom) => run_rom(&rom.bytes, frames),
+                Err(err) => (Outcome::InvalidRom(err.to_string()), Metrics::default()),
+            };
+            RomResult { path, outcome, metrics }
+        })
+        .collect();
+    Ok(results)
+}
+
+/// Counts of the outcomes of a batch run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub completed: usize,
+    pub errors: usize,
+    pub infinite_loops: usize,
+    pub invalid: usize,
+}
+
+impl Summary {
+    pub fn of(results: &[RomResult]) -> Self {
+        let count = |matches: fn(&Outcome) -> bool| results.iter().filter(|result| matches(&result.outcome)).count();
+        Self {
+            completed: count(|outcome| matches!(outcome, Outcome::Completed)),
+            errors: count(|outcome| matches!(outcome, Outcome::Error { .. })),
+            infinite_loops: count(|outcome| matches!(outcome, Outcome::InfiniteLoop { .. })),
+            invalid: count(|outcome| matches!(outcome, Outcome::InvalidRom(_))),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ok, {} errors, {} infinite loops, {} invalid",
+            self.completed, self.errors, self.infinite_loops, self.invalid,
+        )
+    }
+}
@@ -0,0 +1,66 @@
+//! Throughput benchmark, running a ROM headless as fast as possible.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+use crate::{Chip8, Chip8Error};
+
+/// Frames per second of the real hardware, to express the speed relative to it.
+const REAL_FRAME_RATE: f64 = 60.0;
+
+/// How much `chip8` executed in how much time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub instructions: u64,
+    pub frames: u64,
+    pub elapsed: Duration,
+    /// The error that stopped the benchmark before the time was up.
+    pub error: Option<Chip8Error>,
+}
+
+impl BenchResult {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        writeln!(f, "{} instructions and {} frames in {:.2} s", self.instructions, self.frames, secs)?;
+        writeln!(f, "{:.0} instructions/s", self.instructions_per_second())?;
+        write!(
+            f,
+            "{:.0} frames/s ({:.1}x real time)",
+            self.frames_per_second(),
+            self.frames_per_second() / REAL_FRAME_RATE,
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, "\nStopped early: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `chip8` frame by frame without rendering or waiting until `duration` has passed or an error occurs.
+pub fn run(chip8: &mut Chip8, duration: Duration) -> BenchResult {
+    let start_metrics = *chip8.metrics();
+    let start = Instant::now();
+    let mut error = None;
+    while start.elapsed() < duration {
+        if let Err(err) = chip8.run_frame() {
+            error = Some(err);
+            break;
+        }
+    }
+    let elapsed = start.elapsed();
+    BenchResult {
+        instructions: chip8.metrics().instructions - start_metrics.instructions,
+        frames: chip8.metrics().frames - start_metrics.frames,
+        elapsed,
+        error,
+    }
+}
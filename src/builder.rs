@@ -0,0 +1,132 @@
+use crate::bus::Bus;
+use crate::compat::Profile;
+use crate::{Chip8, Quirks, RunPolicy};
+
+/// Configures a [`Chip8`] in one expression, e.g.
+/// `Chip8::builder().rom(&rom).quirks(quirks).seed(42).build()`. Settings that aren't given keep the defaults of
+/// [`Chip8::new`], and can still be changed with the setters afterwards.
+#[derive(Debug, Default)]
+pub struct Chip8Builder {
+    rom: Vec<u8>,
+    load_addr: Option<usize>,
+    entry: Option<usize>,
+    /// Loaded after the ROM, in the order given.
+    segments: Vec<(usize, Vec<u8>)>,
+    bus: Option<Box<dyn Bus>>,
+    quirks: Option<Quirks>,
+    instructions_per_frame: Option<u32>,
+    seed: Option<u64>,
+    stack_depth: Option<u8>,
+    run_policy: Option<RunPolicy>,
+}
+
+impl Chip8 {
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+}
+
+impl Chip8Builder {
+    /// The program, loaded at [`crate::PROGRAM_START`] unless [`Chip8Builder::load_addr`] is given.
+    pub fn rom(mut self, rom: &[u8]) -> Self {
+        self.rom = rom.to_vec();
+        self
+    }
+
+    /// Loads the ROM at `addr` instead of [`crate::PROGRAM_START`], and starts there unless
+    /// [`Chip8Builder::entry`] is given. [`Chip8Builder::build`] panics if the ROM doesn't fit behind `addr`.
+    pub fn load_addr(mut self, addr: usize) -> Self {
+        self.load_addr = Some(addr);
+        self
+    }
+
+    /// Starts executing at `addr`, see [`Chip8::set_entry`].
+    pub fn entry(mut self, addr: usize) -> Self {
+        self.entry = Some(addr);
+        self
+    }
+
+    /// Loads `bytes` at `addr` after the ROM, e.g. a patched data table or pre-seeded RAM. Later segments overwrite
+    /// earlier ones and the ROM where they overlap. [`Chip8Builder::build`] panics if a segment doesn't fit.
+    pub fn segment(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.segments.push((addr, bytes.to_vec()));
+        self
+    }
+
+    /// Accesses memory through `bus`, see [`Chip8::with_bus`].
+    pub fn bus(mut self, bus: impl Bus + 'static) -> Self {
+        self.bus = Some(Box::new(bus));
+        self
+    }
+
+    /// Uses the quirks and speed of a known ROM, e.g. from [`crate::compat::lookup`]. Later calls of
+    /// [`Chip8Builder::quirks`] and [`Chip8Builder::instructions_per_frame`] override them.
+    pub fn profile(mut self, profile: &Profile) -> Self {
+        self.quirks = Some(profile.quirks);
+        self.instructions_per_frame = Some(profile.tickrate);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// See [`Chip8::set_instructions_per_frame`].
+    pub fn instructions_per_frame(mut self, instructions_per_frame: u32) -> Self {
+        self.instructions_per_frame = Some(instructions_per_frame);
+        self
+    }
+
+    /// See [`Chip8::set_seed`]. Without a seed, the random numbers differ on every run.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// See [`Chip8::set_stack_depth`].
+    pub fn stack_depth(mut self, depth: u8) -> Self {
+        self.stack_depth = Some(depth);
+        self
+    }
+
+    /// See [`Chip8::set_run_policy`].
+    pub fn run_policy(mut self, run_policy: RunPolicy) -> Self {
+        self.run_policy = Some(run_policy);
+        self
+    }
+
+    pub fn build(self) -> Chip8 {
+        let rom = if self.load_addr.is_some() { &[][..] } else { &self.rom[..] };
+        let mut chip8 = match self.bus {
+            Some(bus) => Chip8::with_bus(rom, bus),
+            None => Chip8::new(rom),
+        };
+        if let Some(addr) = self.load_addr {
+            chip8.load(addr, &self.rom).unwrap_or_else(|err| panic!("{}", err));
+            chip8.set_entry(addr);
+        }
+        for (addr, bytes) in &self.segments {
+            chip8.load(*addr, bytes).unwrap_or_else(|err| panic!("{}", err));
+        }
+        if let Some(entry) = self.entry {
+            chip8.set_entry(entry);
+        }
+        if let Some(quirks) = self.quirks {
+            chip8.set_quirks(quirks);
+        }
+        if let Some(instructions_per_frame) = self.instructions_per_frame {
+            chip8.set_instructions_per_frame(instructions_per_frame);
+        }
+        if let Some(seed) = self.seed {
+            chip8.set_seed(seed);
+        }
+        if let Some(depth) = self.stack_depth {
+            chip8.set_stack_depth(depth);
+        }
+        if let Some(run_policy) = self.run_policy {
+            chip8.set_run_policy(run_policy);
+        }
+        chip8
+    }
+}
@@ -0,0 +1,85 @@
+//! Memory access of the interpreter. All instructions read and write memory through a [`Bus`], so extensions can map
+//! peripherals, switch ROM banks or watch regions by wrapping the default flat [`Memory`] in their own bus and
+//! passing it to [`crate::Chip8::with_bus`].
+
+use std::fmt;
+
+/// Size of the address space in bytes.
+pub const MEMORY_SIZE: usize = 4096;
+
+/// The flat memory used by [`crate::Chip8::new`].
+pub type Memory = [u8; MEMORY_SIZE];
+
+pub trait Bus: fmt::Debug {
+    /// Returns the byte at `addr` without side effects. Used for fetching instructions and by debuggers.
+    fn peek(&self, addr: usize) -> u8;
+
+    /// Returns the byte at `addr` for an instruction like `FX65`. Override this if reading has side effects, e.g.
+    /// consuming input.
+    fn read(&mut self, addr: usize) -> u8 {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: usize, value: u8);
+
+    /// The memory behind the bus as one slice, e.g. for snapshots. Peripherals without memory of their own show the
+    /// memory they're mapped over.
+    fn memory(&self) -> &[u8];
+
+    /// Number of addressable bytes.
+    fn size(&self) -> usize {
+        MEMORY_SIZE
+    }
+
+    /// Sets all memory to zero for [`crate::Chip8::reset`]. Override this if writing has side effects, e.g. sending
+    /// output, which a reset shouldn't trigger.
+    fn clear(&mut self) {
+        for addr in 0..self.size() {
+            self.write(addr, 0);
+        }
+    }
+}
+
+impl Bus for Memory {
+    fn peek(&self, addr: usize) -> u8 {
+        self[addr]
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        self[addr] = value;
+    }
+
+    fn memory(&self) -> &[u8] {
+        self
+    }
+
+    fn clear(&mut self) {
+        self.fill(0);
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    fn peek(&self, addr: usize) -> u8 {
+        (**self).peek(addr)
+    }
+
+    fn read(&mut self, addr: usize) -> u8 {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        (**self).write(addr, value)
+    }
+
+    fn memory(&self) -> &[u8] {
+        (**self).memory()
+    }
+
+    fn size(&self) -> usize {
+        (**self).size()
+    }
+
+    fn clear(&mut self) {
+        (**self).clear()
+    }
+}
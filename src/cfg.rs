@@ -0,0 +1,145 @@
+//! Control-flow graph of a ROM, built from the reachable instructions found by the [`Disassembly`]. The graph can be
+//! exported in the DOT format of [Graphviz](https://graphviz.org) to visualize the structure of unknown ROMs, e.g.
+//! with `chip8 cfg game.ch8 | dot -Tsvg > game.svg`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use crate::disassembler::{mnemonic, Disassembly};
+use crate::instruction::Instruction;
+
+/// How control gets from one basic block to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Continues with the next instruction, also after a skip that wasn't taken.
+    Next,
+    /// A skip instruction skipped the next instruction.
+    Skip,
+    Jump,
+    Call,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// Instructions that are always executed one after another. Only the first one is jumped to and only the last one
+/// branches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// Address and opcode of the instructions.
+    pub instructions: Vec<(usize, u16)>,
+    pub edges: Vec<Edge>,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    /// The basic blocks by their start address.
+    pub blocks: BTreeMap<usize, BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    /// Splits the reachable instructions of `disassembly` into basic blocks.
+    pub fn new(disassembly: &Disassembly) -> Self {
+        let instructions: Vec<(usize, u16, Instruction)> = disassembly.instructions().collect();
+        let mut leaders = BTreeSet::new();
+        leaders.extend(instructions.first().map(|&(addr, _, _)| addr));
+        for &(addr, _, instruction) in &instructions {
+            if let Some(edges) = branches(addr, instruction) {
+                leaders.extend(edges.iter().map(|edge| edge.target));
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<BasicBlock> = None;
+        for &(addr, opcode, instruction) in &instructions {
+            // A block also ends before a jump target and before data
+            let continues = current.as_ref().is_some_and(|block| block.end() == addr && !leaders.contains(&addr));
+            if !continues {
+                if let Some(mut finished) = current.take() {
+                    if finished.end() == addr {
+                        finished.edges.push(Edge { target: addr, kind: EdgeKind::Next });
+                    }
+                    blocks.insert(finished.start, finished);
+                }
+                current = Some(BasicBlock {
+                    start: addr,
+                    instructions: Vec::new(),
+                    edges: Vec::new(),
+                    label: disassembly.label(addr).map(str::to_string),
+                });
+            }
+            current.as_mut().expect("A block was started above").instructions.push((addr, opcode));
+            if let Some(edges) = branches(addr, instruction) {
+                let mut finished = current.take().expect("A block was started above");
+                finished.edges = edges.into_iter().filter(|edge| disassembly.is_code(edge.target)).collect();
+                blocks.insert(finished.start, finished);
+            }
+        }
+        if let Some(finished) = current {
+            blocks.insert(finished.start, finished);
+        }
+        Self { blocks }
+    }
+
+    /// Formats the graph in the DOT language of Graphviz. Calls are dashed, skips are labeled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            // `\l` ends a left-aligned line
+            let mut label = String::new();
+            if let Some(name) = &block.label {
+                label.push_str(&format!("{}:\\l", escape(name)));
+            }
+            for &(addr, opcode) in &block.instructions {
+                let mnemonic = mnemonic(opcode).unwrap_or_default();
+                label.push_str(&format!("{:#05X}: {}\\l", addr, escape(&mnemonic)));
+            }
+            dot.push_str(&format!("    b_{:03X} [label=\"{}\"];\n", block.start, label));
+        }
+        for block in self.blocks.values() {
+            for edge in &block.edges {
+                let attributes = match edge.kind {
+                    EdgeKind::Next | EdgeKind::Jump => "",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Call => " [style=dashed]",
+                };
+                dot.push_str(&format!("    b_{:03X} -> b_{:03X}{};\n", block.start, edge.target, attributes));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl BasicBlock {
+    /// Address after the last instruction.
+    fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, |&(addr, _)| addr + 2)
+    }
+}
+
+/// The possible successors of the instruction at `addr` if it branches, `None` if it continues with the next
+/// instruction. Returns no successors for `RET` and `BNNN`, whose targets aren't known statically.
+fn branches(addr: usize, instruction: Instruction) -> Option<Vec<Edge>> {
+    let next = Edge { target: addr + 2, kind: EdgeKind::Next };
+    let edges = match instruction {
+        Instruction::Ret | Instruction::JumpV0 { .. } => vec![],
+        Instruction::Jump { addr: target } => vec![Edge { target: target as usize, kind: EdgeKind::Jump }],
+        Instruction::Call { addr: target } => vec![Edge { target: target as usize, kind: EdgeKind::Call }, next],
+        Instruction::SkipEqByte { .. }
+        | Instruction::SkipNeByte { .. }
+        | Instruction::SkipEqReg { .. }
+        | Instruction::SkipNeReg { .. }
+        | Instruction::SkipKey { .. }
+        | Instruction::SkipNotKey { .. } => vec![next, Edge { target: addr + 4, kind: EdgeKind::Skip }],
+        _ => return None,
+    };
+    Some(edges)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,240 @@
+//! Cheats that freeze memory addresses, i.e. write a fixed value to them over and over, e.g. for infinite lives. Cheat
+//! tables are TOML files with one `[[freeze]]` table per address:
+//!
+//! ```toml
+//! [[freeze]]
+//! name = "Infinite lives"  # optional
+//! address = 0x3F0
+//! value = 3
+//! every = "frame"  # or "instruction" for values the game reads right after writing them, "frame" by default
+//! ```
+//!
+//! To find the address of e.g. the lives, a [`MemorySearch`] starts with all addresses holding the current number of
+//! lives, or with all addresses if the number isn't shown. After losing a life, searching for the new number or for
+//! decreased values narrows the addresses down, until only a few are left to try freezing.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item};
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+/// When a frozen value is written again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+#[cfg_attr(feature = "websocket", serde(rename_all = "snake_case"))]
+pub enum Every {
+    Instruction,
+    Frame,
+}
+
+/// A memory address frozen to a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct Freeze {
+    /// Shown to the user, may be empty.
+    pub name: String,
+    pub addr: usize,
+    pub value: u8,
+    pub every: Every,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CheatTable {
+    /// Ordered by address, at most one per address.
+    freezes: Vec<Freeze>,
+}
+
+impl CheatTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a cheat table file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parses the contents of a cheat table file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let document: DocumentMut = text.parse().map_err(|err| format!("Invalid TOML: {}", err))?;
+        let mut table = Self::new();
+        let freezes = match document.get("freeze") {
+            Some(item) => item.as_array_of_tables().ok_or("`freeze` has to be an array of tables like [[freeze]]")?,
+            None => return Ok(table),
+        };
+        for (i, freeze) in freezes.iter().enumerate() {
+            let field = |key: &str| freeze.get(key);
+            let integer = |key: &str| match field(key) {
+                Some(item) => item.as_integer().ok_or(format!("Freeze {}: `{}` has to be a number", i + 1, key)),
+                None => Err(format!("Freeze {}: `{}` is missing", i + 1, key)),
+            };
+            let addr = usize::try_from(integer("address")?)
+                .map_err(|_| format!("Freeze {}: The address can't be negative", i + 1))?;
+            let value = u8::try_from(integer("value")?)
+                .map_err(|_| format!("Freeze {}: The value has to be between 0 and 255", i + 1))?;
+            let every = match field("every").map(Item::as_str) {
+                None | Some(Some("frame")) => Every::Frame,
+                Some(Some("instruction")) => Every::Instruction,
+                Some(_) => return Err(format!("Freeze {}: `every` has to be \"frame\" or \"instruction\"", i + 1)),
+            };
+            let name = match field("name") {
+                Some(item) => item.as_str().ok_or(format!("Freeze {}: `name` has to be a string", i + 1))?.to_string(),
+                None => String::new(),
+            };
+            table.freeze(Freeze { name, addr, value, every });
+        }
+        Ok(table)
+    }
+
+    /// The frozen addresses in ascending order.
+    pub fn freezes(&self) -> &[Freeze] {
+        &self.freezes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.freezes.is_empty()
+    }
+
+    /// Freezes an address, replacing an earlier freeze of the same address.
+    pub fn freeze(&mut self, freeze: Freeze) {
+        match self.freezes.binary_search_by_key(&freeze.addr, |frozen| frozen.addr) {
+            Ok(index) => self.freezes[index] = freeze,
+            Err(index) => self.freezes.insert(index, freeze),
+        }
+    }
+
+    /// Unfreezes `addr`. Returns whether it was frozen.
+    pub fn unfreeze(&mut self, addr: usize) -> bool {
+        let len = self.freezes.len();
+        self.freezes.retain(|freeze| freeze.addr != addr);
+        self.freezes.len() != len
+    }
+
+    /// Writes the values frozen with `every` into the memory of `chip8`.
+    pub fn apply(&self, chip8: &mut Chip8, every: Every) {
+        write(chip8, self.freezes.iter().filter(|freeze| freeze.every == every));
+    }
+
+    /// Writes all frozen values into the memory of `chip8`, e.g. after every instruction while debugging.
+    pub fn apply_all(&self, chip8: &mut Chip8) {
+        write(chip8, self.freezes.iter());
+    }
+}
+
+/// Writes the values of `freezes` into memory. Addresses outside of memory are skipped.
+fn write<'a>(chip8: &mut Chip8, freezes: impl Iterator<Item = &'a Freeze>) {
+    for freeze in freezes {
+        if freeze.addr < chip8.bus.size() {
+            chip8.poke(freeze.addr, freeze.value);
+        }
+    }
+}
+
+impl Hooks for CheatTable {
+    fn after_instruction(&mut self, chip8: &mut Chip8, _pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.apply(chip8, Every::Instruction);
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.apply(chip8, Every::Frame);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Which values a [`MemorySearch`] keeps, comparing the current value of an address with the one of the previous
+/// search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Deserialize))]
+#[cfg_attr(feature = "websocket", serde(rename_all = "snake_case"))]
+pub enum SearchFilter {
+    /// Every value, to start a search for a value that isn't known.
+    Any,
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl SearchFilter {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Equal(value) => current == value,
+            Self::Changed => current != previous,
+            Self::Unchanged => current == previous,
+            Self::Increased => current > previous,
+            Self::Decreased => current < previous,
+        }
+    }
+
+    /// Whether the filter compares with the previous search, so that it can't start a search.
+    pub fn is_relative(self) -> bool {
+        !matches!(self, Self::Any | Self::Equal(_))
+    }
+}
+
+/// An address found by a [`MemorySearch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct SearchMatch {
+    pub addr: usize,
+    /// The value at the time of the last search.
+    pub value: u8,
+}
+
+/// Searches the memory for an unknown address by narrowing down the candidates with every search.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemorySearch {
+    /// `None` before the first search.
+    matches: Option<Vec<SearchMatch>>,
+}
+
+impl MemorySearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the addresses whose value in the memory of `chip8` matches `filter`, or starts with all addresses of
+    /// the memory matching it. Relative filters like [`SearchFilter::Decreased`] can't start a search.
+    pub fn search(&mut self, chip8: &Chip8, filter: SearchFilter) -> Result<&[SearchMatch], String> {
+        let matches = match self.matches.take() {
+            Some(matches) => matches.into_iter()
+                .filter_map(|found| {
+                    let value = chip8.bus.peek(found.addr);
+                    filter.matches(found.value, value).then_some(SearchMatch { addr: found.addr, value })
+                })
+                .collect(),
+            None if filter.is_relative() => {
+                return Err(String::from("Start the search with a value or with all addresses first"));
+            },
+            None => (0..chip8.bus.size())
+                .map(|addr| SearchMatch { addr, value: chip8.bus.peek(addr) })
+                .filter(|found| filter.matches(found.value, found.value))
+                .collect(),
+        };
+        Ok(self.matches.insert(matches))
+    }
+
+    /// The addresses found by the last search, in ascending order.
+    pub fn matches(&self) -> &[SearchMatch] {
+        self.matches.as_deref().unwrap_or_default()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.matches.is_some()
+    }
+
+    /// Forgets the matches, so that the next search starts with all addresses again.
+    pub fn reset(&mut self) {
+        self.matches = None;
+    }
+}
@@ -0,0 +1,1569 @@
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use crate::audio::{Beeper, Mixer};
+use crate::bus::{Bus, MEMORY_SIZE};
+use crate::display::{self, Afterglow, Framebuffer, Rect};
+use crate::font::{Font, DIGIT_HEIGHT, FONT_ADDR, FONT_SIZE};
+use crate::hooks::Hooks;
+use crate::instruction::Instruction;
+use crate::memcheck::{MemoryCheck, ProtectedWrite, WriteProtection};
+use crate::metrics::Metrics;
+use crate::profile::Profiler;
+use crate::quirks::Quirks;
+use crate::snapshot::Snapshot;
+use crate::trace::{Divergence, JsonTracer, MemoryAccess, MemoryTracer, ReferenceTrace, TraceEntry};
+
+/// Width of the display in pixels.
+pub const DISPLAY_WIDTH: usize = 64;
+/// Height of the display in pixels.
+pub const DISPLAY_HEIGHT: usize = 32;
+
+/// Programs are loaded at this address. Everything below is reserved for the interpreter.
+pub const PROGRAM_START: usize = 0x200;
+/// Maximum size of a program, i.e. the memory from [`PROGRAM_START`] to the end.
+pub const MAX_PROGRAM_SIZE: usize = MEMORY_SIZE - PROGRAM_START;
+
+/// Number of recently executed instructions kept for crash reports.
+const HISTORY_LEN: usize = 8;
+
+/// Number of nested subroutine calls unless configured otherwise, like on the SUPER-CHIP.
+pub const DEFAULT_STACK_DEPTH: u8 = 16;
+
+/// The timers tick and frames are rendered at 60 Hz.
+pub(crate) const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Things to mention:
+/// * vx means register number x.
+/// * nn is a constant number (called `number_in`) supplied in the opcode.
+#[derive(Debug)]
+pub struct Chip8 {
+    /// Memory, accessed by all instructions.
+    pub(crate) bus: Box<dyn Bus>,
+    /// Registers (V) called V0, V1, ..., V9, VA, VB, ..., VF (hex number of the register is appended).
+    pub(crate) registers: [u8; 16],
+    /// 16 bit address register (I).
+    pub(crate) address_register: u16,
+    /// Program counter (PC).
+    pub(crate) pc: usize,
+    /// Where the program starts, and restarts after [`Chip8::reset`].
+    entry: usize,
+    /// Everything written by [`Chip8::new`] and [`Chip8::load`] in order, which [`Chip8::reset`] writes again.
+    loaded: Vec<(usize, Vec<u8>)>,
+    /// The font in memory, which [`Chip8::reset`] writes again.
+    font: Font,
+
+    /// Return addresses of the active subroutine calls in `stack[1..=stack_pointer]`. The length is the stack depth
+    /// plus one.
+    pub(crate) stack: Vec<usize>,
+    pub(crate) stack_pointer: u8,
+
+    /// The display as one bit per pixel, written by the instructions. See [`Framebuffer`].
+    pub(crate) display: Framebuffer,
+    /// The display as it was when [`Chip8::take_dirty_rects`] was last called.
+    front_display: Framebuffer,
+    /// Decides how pixels are shown if flicker reduction or phosphor decay is enabled.
+    afterglow: Option<Afterglow>,
+    /// Keys currently pressed by the user. Bit `n` is set if key `n` is pressed.
+    pub(crate) keypad: u16,
+    /// Keys the program checked since the last frame with `EX9E`, `EXA1` or `FX0A`. Bit `n` is set if key `n` was
+    /// checked.
+    pub(crate) polled_keys: u16,
+    /// Register `FX0A` stores the next pressed key in, while it waits for one.
+    waiting_for_key: Option<u8>,
+    /// Keys pressed since `FX0A` started waiting. Bit `n` is set if key `n` was pressed, even if it was released
+    /// again before the next instruction.
+    pressed_while_waiting: u16,
+
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    /// Instructions executed since the timers last ticked. The timers tick once per
+    /// [`Chip8::instructions_per_frame`] instructions, i.e. at 60 Hz of emulated time regardless of the speed.
+    pub(crate) cycles_since_tick: u32,
+    /// Makes the sound while the sound timer is non-zero.
+    beeper: Option<Box<dyn Beeper>>,
+    /// Whether the sound timer was non-zero when the beeper was last updated.
+    beeping: bool,
+    /// Whether the beeper was last told to be active, which an audible one isn't while the mixer is muted.
+    beeper_active: bool,
+    mixer: Mixer,
+
+    /// Rows of the display that changed since the last frame. Bit `y` is set if row `y` changed.
+    pub(crate) dirty_rows: u32,
+    /// What happened during the current instruction, returned by [`Chip8::step`].
+    step_events: StepEvents,
+
+    /// Seed the random number generator was last seeded with.
+    pub(crate) seed: u64,
+    /// State of the xorshift random number generator used by `CXNN`.
+    pub(crate) rng_state: u64,
+
+    pub(crate) quirks: Quirks,
+    /// Number of instructions executed per frame, i.e. the speed of the emulated CPU.
+    pub(crate) instructions_per_frame: u32,
+    /// When [`Chip8::run_with_hooks`] returns.
+    run_policy: RunPolicy,
+    /// Whether [`Chip8::run_with_hooks`] prints the display as character art.
+    text_output: bool,
+
+    /// PC and opcode of the last [`HISTORY_LEN`] instructions, oldest first. The last one is the instruction that
+    /// is executing or failed.
+    pub(crate) history: VecDeque<(usize, u16)>,
+
+    /// Collects per-opcode execution statistics if profiling is enabled.
+    profiler: Option<Profiler>,
+    /// Reports reads of uninitialized memory if the memory check is enabled.
+    memory_check: Option<MemoryCheck>,
+    write_protection: WriteProtection,
+    /// The first write of the program to each protected address, see [`Chip8::protected_writes`].
+    protected_writes: Vec<ProtectedWrite>,
+    metrics: Metrics,
+    /// Writes the machine state after every instruction if tracing is enabled.
+    tracer: Option<JsonTracer>,
+    /// Writes every memory access of the instructions if memory tracing is enabled.
+    memory_tracer: Option<MemoryTracer>,
+    /// Compares the machine state after every instruction with a known good trace if differential testing is
+    /// enabled.
+    reference: Option<ReferenceTrace>,
+}
+
+/// Why [`Chip8::run_with_hooks`] or [`Chip8::run_frame_with_hooks`] stopped without an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// A hook stopped the emulation, e.g. because the user quit.
+    Stopped,
+    /// The program jumped to itself at `pc`, which ROMs do to signal that they are done. Nothing changes anymore.
+    Halted { pc: usize },
+    /// The number of instructions of [`RunPolicy::Instructions`] was executed.
+    InstructionLimit,
+}
+
+/// Something that happened during an instruction, which frontends may react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepEvent {
+    /// The display was drawn to or cleared.
+    DisplayUpdated,
+    /// `FX18` set the sound timer while it was zero.
+    SoundStarted,
+    /// The sound timer ran out at a timer tick, or `FX18` set it to zero.
+    SoundStopped,
+    /// `FX0A` found no pressed key and is executed again next time.
+    WaitingForKey,
+}
+
+impl StepEvent {
+    const ALL: [StepEvent; 4] =
+        [StepEvent::DisplayUpdated, StepEvent::SoundStarted, StepEvent::SoundStopped, StepEvent::WaitingForKey];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The set of [`StepEvent`]s of an instruction, returned by [`Chip8::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StepEvents(u8);
+
+impl StepEvents {
+    pub fn contains(self, event: StepEvent) -> bool {
+        self.0 & event.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = StepEvent> {
+        StepEvent::ALL.iter().copied().filter(move |&event| self.contains(event))
+    }
+
+    fn insert(&mut self, event: StepEvent) {
+        self.0 |= event.bit();
+    }
+}
+
+/// When [`Chip8::run_with_hooks`] returns, besides errors and hooks stopping the emulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunPolicy {
+    /// Run until a hook stops the emulation, e.g. because the user quit. A halted program keeps being displayed.
+    Forever,
+    /// Run until the given number of instructions was executed in total, counted by [`Metrics::instructions`].
+    Instructions(u64),
+    /// Run until the program halts or a hook stops the emulation, e.g. at a breakpoint.
+    #[default]
+    UntilHalt,
+}
+
+impl FromStr for RunPolicy {
+    type Err = String;
+
+    /// Parses `forever`, `until-halt` or a number of instructions.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forever" => Ok(Self::Forever),
+            "until-halt" => Ok(Self::UntilHalt),
+            _ => s.parse().map(Self::Instructions).map_err(|_| {
+                format!("Invalid run policy {:?}, expected forever, until-halt or a number of instructions", s)
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Chip8Error {
+    #[error("Encountered illegal instruction {opcode:#X} at PC={pc}")]
+    IllegalInstruction {
+        opcode: u16,
+        pc: usize
+    },
+
+    #[error("Stack overflow")]
+    StackOverflow,
+
+    #[error("Returned from a subroutine without a call at PC={pc}")]
+    StackUnderflow {
+        pc: usize,
+    },
+
+    #[error("Machine routine nr.{0} called, but is not implemented")]
+    UnknownMachineRoutine(u16),
+
+    #[error("Memory access at {addr:#X} is out of bounds at PC={pc}")]
+    MemoryOutOfBounds {
+        addr: usize,
+        pc: usize,
+    },
+
+    #[error("Write to the interpreter memory at {addr:#X} at PC={pc}")]
+    ProtectedWrite {
+        addr: usize,
+        pc: usize,
+    },
+
+    #[error("Can't write trace: {0}")]
+    Trace(String),
+
+    #[error("Can't read the reference trace: {0}")]
+    ReferenceTrace(String),
+
+    #[error("{0}")]
+    Divergence(Box<Divergence>),
+
+    #[error("Hook failed: {0}")]
+    Hook(String),
+}
+
+impl Chip8Error {
+    /// Whether the emulation can continue with the next instruction after this error, e.g. to skip an illegal
+    /// instruction.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Chip8Error::IllegalInstruction { .. }
+                | Chip8Error::UnknownMachineRoutine(_)
+                | Chip8Error::ProtectedWrite { .. }
+        )
+    }
+}
+
+impl Chip8 {
+    pub fn new(program: &[u8]) -> Self {
+        Self::with_bus(program, [0; MEMORY_SIZE])
+    }
+
+    /// Like [`Chip8::new`], but accesses memory through `bus` instead of a flat array. The font and `program` are
+    /// written to `bus`.
+    pub fn with_bus(program: &[u8], bus: impl Bus + 'static) -> Self {
+        let mut chip8 = Self {
+            bus: Box::new(bus),
+            registers: Default::default(),
+            address_register: 0,
+            pc: PROGRAM_START,
+            entry: PROGRAM_START,
+            loaded: Vec::new(),
+            font: Font::default(),
+            stack: vec![0; DEFAULT_STACK_DEPTH as usize + 1],
+            stack_pointer: 0,
+            display: Framebuffer::default(),
+            front_display: Framebuffer::default(),
+            afterglow: None,
+            keypad: 0,
+            polled_keys: 0,
+            waiting_for_key: None,
+            pressed_while_waiting: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            cycles_since_tick: 0,
+            beeper: None,
+            beeping: false,
+            beeper_active: false,
+            mixer: Mixer::default(),
+            dirty_rows: u32::MAX,
+            step_events: StepEvents::default(),
+            seed: 0,
+            rng_state: 0,
+            quirks: Quirks::default(),
+            instructions_per_frame: 1,
+            run_policy: RunPolicy::default(),
+            text_output: true,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            profiler: None,
+            memory_check: None,
+            write_protection: WriteProtection::default(),
+            protected_writes: Vec::new(),
+            metrics: Metrics::default(),
+            tracer: None,
+            memory_tracer: None,
+            reference: None,
+        };
+
+        // Seed from the clock so every run is different unless a seed is set explicitly
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        chip8.set_seed(nanos);
+
+        chip8.set_font(&Font::default());
+
+        // Copy program to memory starting by memory address 512
+        for (i, &byte) in program.iter().enumerate() {
+            chip8.bus.write(PROGRAM_START + i, byte);
+        }
+        if !program.is_empty() {
+            chip8.loaded.push((PROGRAM_START, program.to_vec()));
+        }
+        chip8
+    }
+
+    /// Writes `bytes` into memory from `addr` on, e.g. a ROM built for another address than [`PROGRAM_START`] or data
+    /// to patch a ROM with.
+    pub fn load(&mut self, addr: usize, bytes: &[u8]) -> Result<(), String> {
+        if addr + bytes.len() > self.bus.size() {
+            let space = self.bus.size().saturating_sub(addr);
+            return Err(format!("{} bytes don't fit into memory at {:#05X}, only {} do", bytes.len(), addr, space));
+        }
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.poke(addr + i, byte);
+        }
+        self.loaded.push((addr, bytes.to_vec()));
+        Ok(())
+    }
+
+    /// Writes `value` to `addr`, which counts as initializing it for the memory check. Everything but clearing the
+    /// memory writes through this, also from outside of the program like cheats.
+    pub(crate) fn poke(&mut self, addr: usize, value: u8) {
+        self.bus.write(addr, value);
+        if let Some(memory_check) = &mut self.memory_check {
+            memory_check.write(addr);
+        }
+    }
+
+    /// Restarts the program as if it was just loaded (a hard reset). The registers, timers, stack, display and memory
+    /// are cleared, the font and everything loaded by [`Chip8::new`] and [`Chip8::load`] are written to memory again,
+    /// and the program starts at its entry point (see [`Chip8::set_entry`]). Settings like the quirks, the speed and
+    /// the seed are kept, the random numbers repeat from the seed.
+    pub fn reset(&mut self) {
+        self.bus.clear();
+        if let Some(memory_check) = &mut self.memory_check {
+            memory_check.clear();
+        }
+        let font = self.font;
+        self.set_font(&font);
+        let loaded = std::mem::take(&mut self.loaded);
+        for (addr, bytes) in &loaded {
+            for (i, &byte) in bytes.iter().enumerate() {
+                self.poke(addr + i, byte);
+            }
+        }
+        self.loaded = loaded;
+        self.registers = [0; 16];
+        self.address_register = 0;
+        self.polled_keys = 0;
+        self.set_seed(self.seed);
+        self.history.clear();
+        self.soft_reset();
+    }
+
+    /// Restarts the program at its entry point, but keeps the memory and the registers, e.g. for games that keep
+    /// high scores in memory. Only the display, the stack and the timers are cleared, unlike [`Chip8::reset`].
+    pub fn soft_reset(&mut self) {
+        self.set_pc(self.entry);
+        self.stack.iter_mut().for_each(|addr| *addr = 0);
+        self.stack_pointer = 0;
+        self.display = Framebuffer::default();
+        if let Some(afterglow) = &mut self.afterglow {
+            afterglow.reset(&self.display);
+        }
+        self.request_redraw();
+        self.pressed_while_waiting = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.cycles_since_tick = 0;
+        self.update_beeper();
+    }
+
+    /// Runs `rom` headless for `cycles` instructions with the random number generator seeded with `seed`, and
+    /// returns a hash of the final machine state. The hash only changes if the behavior of the interpreter does, so
+    /// pinned hashes catch unintended changes. Execution stops early at the first error.
+    pub fn run_hash(rom: &[u8], cycles: u64, seed: u64) -> u64 {
+        let mut chip8 = Self::new(rom);
+        chip8.set_seed(seed);
+        for _ in 0..cycles {
+            if chip8.step().is_err() {
+                break;
+            }
+        }
+        Snapshot::of(&chip8).hash()
+    }
+
+    /// Loads an opcode from memory by fetching two bytes and combing them in big-endian fashion.
+    fn load_opcode(&self) -> Result<u16, Chip8Error> {
+        if self.pc + 1 >= self.bus.size() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: self.pc, pc: self.pc });
+        }
+        // Instructions are stored in big endian, so the most significant byte is placed at the byte with the lowest
+        // address.
+        let upper = self.bus.peek(self.pc);
+        let lower = self.bus.peek(self.pc + 1);
+        Ok(u16::from_be_bytes([upper, lower]))
+    }
+
+    /// Reads the byte at `addr` for the instruction currently being executed.
+    fn read_mem(&mut self, addr: usize) -> Result<u8, Chip8Error> {
+        self.check_mem_bounds(addr)?;
+        if let Some(memory_check) = &mut self.memory_check {
+            memory_check.read(self.pc - 2, addr);
+        }
+        let value = self.bus.read(addr);
+        self.trace_mem(addr, value, false)?;
+        Ok(value)
+    }
+
+    /// Writes the byte at `addr` for the instruction currently being executed.
+    fn write_mem(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        // The PC already points to the next instruction
+        self.checked_poke(addr, value, self.pc - 2)?;
+        self.trace_mem(addr, value, true)
+    }
+
+    /// Like [`Chip8::poke`], but fails if `addr` is beyond memory and applies the write protection, blaming the
+    /// instruction at `pc`.
+    fn checked_poke(&mut self, addr: usize, value: u8, pc: usize) -> Result<(), Chip8Error> {
+        if addr >= self.bus.size() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr, pc });
+        }
+        if self.write_protection.protects(addr) {
+            match self.write_protection {
+                WriteProtection::Error => return Err(Chip8Error::ProtectedWrite { addr, pc }),
+                _ if self.protected_writes.iter().any(|write| write.addr == addr) => {},
+                _ => self.protected_writes.push(ProtectedWrite { pc, addr, value }),
+            }
+        }
+        self.poke(addr, value);
+        Ok(())
+    }
+
+    /// Writes a memory access of the instruction currently being executed to the memory tracer, if enabled.
+    fn trace_mem(&mut self, addr: usize, value: u8, write: bool) -> Result<(), Chip8Error> {
+        if let (Some(tracer), Some(&(pc, opcode))) = (&mut self.memory_tracer, self.history.back()) {
+            let access = MemoryAccess { pc, opcode, addr, value, write };
+            tracer.trace(&access).map_err(|err| Chip8Error::Trace(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn check_mem_bounds(&self, addr: usize) -> Result<(), Chip8Error> {
+        match addr < self.bus.size() {
+            true => Ok(()),
+            // The PC already points to the next instruction
+            false => Err(Chip8Error::MemoryOutOfBounds { addr, pc: self.pc - 2 }),
+        }
+    }
+
+    /// Prints the rows of the display that changed since the last frame. Unchanged rows are skipped, so slow
+    /// terminals aren't flooded with output.
+    fn print_display(&self) {
+        if self.dirty_rows == 0 {
+            return;
+        }
+        let mut output = String::new();
+        for (y, &row) in self.shown_framebuffer().rows().iter().enumerate() {
+            if (self.dirty_rows >> y) & 1 == 1 {
+                output.push_str(&display::row_art(row));
+            }
+            // Explicit carriage return, because the terminal may be in raw mode
+            output.push_str("\r\n");
+        }
+        // Go up to the beginning of the display with ansi escape code
+        output.push_str(&"\x1b[F".repeat(DISPLAY_HEIGHT));
+        print!("{}", output);
+    }
+
+    /// Starts counting executions and wall time per opcode class and per PC.
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Reports instructions reading memory that was never written, see [`crate::memcheck`]. The font and everything
+    /// loaded so far count as written, so enable it before running the program.
+    pub fn enable_memory_check(&mut self) {
+        let mut memory_check = MemoryCheck::new(self.bus.size());
+        let loaded = self.loaded.iter().flat_map(|(addr, bytes)| *addr..addr + bytes.len());
+        for addr in (FONT_ADDR..FONT_ADDR + FONT_SIZE).chain(loaded) {
+            memory_check.write(addr);
+        }
+        self.memory_check = Some(memory_check);
+    }
+
+    /// The memory check, if it was enabled with [`Chip8::enable_memory_check`].
+    pub fn memory_check(&self) -> Option<&MemoryCheck> {
+        self.memory_check.as_ref()
+    }
+
+    /// Decides what happens when the program writes to the memory of the interpreter below [`PROGRAM_START`], which
+    /// holds the font. By default, the writes are allowed, but reported by [`Chip8::protected_writes`].
+    pub fn set_write_protection(&mut self, write_protection: WriteProtection) {
+        self.write_protection = write_protection;
+    }
+
+    pub fn write_protection(&self) -> WriteProtection {
+        self.write_protection
+    }
+
+    /// The writes of the program to the memory of the interpreter, the first one to every address. Only collected
+    /// with [`WriteProtection::Warn`].
+    pub fn protected_writes(&self) -> &[ProtectedWrite] {
+        &self.protected_writes
+    }
+
+    /// The profiler, if profiling was enabled with [`Chip8::enable_profiling`].
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Writes one [`TraceEntry`] per executed instruction to `tracer`.
+    pub fn set_tracer(&mut self, tracer: JsonTracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Writes every read and write of memory by an instruction to `tracer`. Fetching instructions isn't traced.
+    pub fn set_memory_tracer(&mut self, tracer: MemoryTracer) {
+        self.memory_tracer = Some(tracer);
+    }
+
+    /// Compares the state after every instruction with the next entry of `reference`. Stops with
+    /// [`Chip8Error::Divergence`] at the first difference.
+    pub fn set_reference_trace(&mut self, reference: ReferenceTrace) {
+        self.reference = Some(reference);
+    }
+
+    /// Whether the pixel at (`x`, `y`) is set. Coordinates outside of the display wrap around.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display.pixel(x, y)
+    }
+
+    /// The whole display, e.g. to print it with `{}`.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.display
+    }
+
+    /// The display as frontends show it, which differs from [`Chip8::framebuffer`] with flicker reduction. Pixels
+    /// fading out with phosphor decay are unset.
+    pub fn shown_framebuffer(&self) -> &Framebuffer {
+        match &self.afterglow {
+            Some(afterglow) => afterglow.shown(),
+            None => &self.display,
+        }
+    }
+
+    /// The brightness of the shown pixel at (`x`, `y`) from 0 (off) to 255 (set), which is in between for pixels
+    /// fading out with phosphor decay. Coordinates outside of the display wrap around.
+    pub fn pixel_brightness(&self, x: usize, y: usize) -> u8 {
+        match &self.afterglow {
+            Some(afterglow) => afterglow.brightness(x, y),
+            None if self.display.pixel(x, y) => u8::MAX,
+            None => 0,
+        }
+    }
+
+    /// Reduces flicker for photosensitive users, by showing cleared pixels only after they stayed off for `frames`
+    /// consecutive frames. Only changes what frontends show (see [`Chip8::shown_framebuffer`]), not the display the
+    /// program draws on. 0 or 1 frames disable it.
+    pub fn set_flicker_reduction(&mut self, frames: u8) {
+        self.update_afterglow(|afterglow| afterglow.set_flicker_frames(frames));
+    }
+
+    /// Fades out cleared pixels over `frames` frames, emulating the slow phosphor of original displays. Frontends
+    /// showing shades of gray use [`Chip8::pixel_brightness`]. 0 frames disable it.
+    pub fn set_phosphor_decay(&mut self, frames: u8) {
+        self.update_afterglow(|afterglow| afterglow.set_decay_frames(frames));
+    }
+
+    fn update_afterglow(&mut self, update: impl FnOnce(&mut Afterglow)) {
+        let display = self.display;
+        let afterglow = self.afterglow.get_or_insert_with(|| Afterglow::new(&display));
+        update(afterglow);
+        if !afterglow.is_enabled() {
+            self.afterglow = None;
+        }
+        self.request_redraw();
+    }
+
+    /// Updates the shown display with flicker reduction and phosphor decay at the end of a frame.
+    fn update_shown(&mut self) {
+        if let Some(afterglow) = &mut self.afterglow {
+            self.dirty_rows |= afterglow.update(&self.display);
+        }
+    }
+
+    /// Prints the whole display on the next frame instead of only the changed rows, e.g. after something else was
+    /// printed to the terminal.
+    pub fn request_redraw(&mut self) {
+        self.dirty_rows = u32::MAX;
+    }
+
+    /// Returns the regions of the display that changed since the last call and remembers the current display for
+    /// the next one. Graphical frontends can use this to only upload the changed regions per frame.
+    pub fn take_dirty_rects(&mut self) -> Vec<Rect> {
+        let rects = display::dirty_rects(&self.front_display, &self.display);
+        self.front_display = self.display;
+        rects
+    }
+
+    /// Decrements the delay and sound timer, which happens 60 times per second. Called automatically after every
+    /// [`Chip8::instructions_per_frame`] instructions.
+    pub fn tick_timers(&mut self) {
+        self.cycles_since_tick = 0;
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.update_beeper();
+    }
+
+    /// Tells `beeper` whenever the sound timer starts or stops.
+    pub fn set_beeper(&mut self, beeper: impl Beeper + 'static) {
+        self.beeper = Some(Box::new(beeper));
+        self.beeper_active = false;
+        self.update_beeper();
+    }
+
+    /// The volume of the audio output. Recordings of the sound are scaled by it, and the beeper stays silent while
+    /// it's muted.
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
+        self.update_beeper();
+    }
+
+    /// Starts or stops the beeper if the sound timer changed between zero and non-zero, or if the mixer was muted
+    /// or unmuted. Only audible beepers are silenced by the mixer.
+    fn update_beeper(&mut self) {
+        let active = self.sound_timer > 0;
+        if active != self.beeping {
+            self.beeping = active;
+            self.step_events.insert(if active { StepEvent::SoundStarted } else { StepEvent::SoundStopped });
+        }
+        if let Some(beeper) = &mut self.beeper {
+            let on = active && (!beeper.is_audible() || self.mixer.is_audible());
+            if on != self.beeper_active {
+                self.beeper_active = on;
+                beeper.set_active(on);
+            }
+        }
+    }
+
+    /// Seeds the random number generator, so that `CXNN` produces the same numbers on every run.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        // Xorshift gets stuck at zero, so avoid that state
+        self.rng_state = seed.max(1);
+    }
+
+    /// The seed the random number generator was last seeded with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the next pseudo random number (xorshift64*).
+    fn next_random(&mut self) -> u8 {
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        (self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    /// Whether `key` is currently pressed. Keys outside of `0x0..=0xF` are never pressed.
+    pub(crate) fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad.checked_shr(key as u32).is_some_and(|keys| keys & 1 == 1)
+    }
+
+    /// Presses or releases `key`. Keys outside of `0x0..=0xF` are ignored.
+    pub fn set_key_state(&mut self, key: u8, pressed: bool) {
+        let mask = 1u16.checked_shl(key as u32).unwrap_or(0);
+        match pressed {
+            true => self.set_keypad(self.keypad | mask),
+            false => self.set_keypad(self.keypad & !mask),
+        }
+    }
+
+    /// Sets the state of all keys at once. Bit `n` of `mask` is set if key `n` is pressed.
+    pub fn set_keypad(&mut self, mask: u16) {
+        if self.waiting_for_key.is_some() {
+            self.pressed_while_waiting |= mask & !self.keypad;
+        }
+        self.keypad = mask;
+    }
+
+    /// The register `FX0A` will store the next pressed key in, while it waits for a key press. The program counter
+    /// stays at `FX0A` while waiting, and the program continues with the first key pressed with
+    /// [`Chip8::set_key_state`] or [`Chip8::set_keypad`], even if it's released before the next instruction.
+    pub fn waiting_for_key(&self) -> Option<u8> {
+        self.waiting_for_key
+    }
+
+    /// The keys currently pressed. Bit `n` is set if key `n` is pressed.
+    pub fn keypad(&self) -> u16 {
+        self.keypad
+    }
+
+    /// The keys the program checked during the current frame, which tells which keys a game reacts to. Bit `n` is
+    /// set if key `n` was checked.
+    pub fn polled_keys(&self) -> u16 {
+        self.polled_keys
+    }
+
+    /// Like [`Chip8::is_key_pressed`], but remembers that the program checked `key`.
+    fn poll_key(&mut self, key: u8) -> bool {
+        self.polled_keys |= 1u16.checked_shl(key as u32).unwrap_or(0);
+        self.is_key_pressed(key)
+    }
+
+    /// Writes `font` to memory, replacing the digit sprites of the default font.
+    pub fn set_font(&mut self, font: &Font) {
+        self.font = *font;
+        for (i, &byte) in font.sprites().iter().enumerate() {
+            self.poke(FONT_ADDR + i, byte);
+        }
+    }
+
+    /// Selects the behaviors that differ between Chip-8 implementations.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Sets how many subroutine calls can be nested before [`Chip8Error::StackOverflow`]. Calls beyond a smaller
+    /// depth are dropped.
+    pub fn set_stack_depth(&mut self, depth: u8) {
+        self.stack.resize(depth as usize + 1, 0);
+        self.stack_pointer = self.stack_pointer.min(depth);
+    }
+
+    pub fn stack_depth(&self) -> u8 {
+        (self.stack.len() - 1) as u8
+    }
+
+    /// Sets how many instructions are executed per frame (at least one). Frames are rendered at 60 Hz.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Sets when [`Chip8::run`] and [`Chip8::run_with_hooks`] return. Stepping and running single frames isn't
+    /// affected.
+    pub fn set_run_policy(&mut self, run_policy: RunPolicy) {
+        self.run_policy = run_policy;
+    }
+
+    pub fn run_policy(&self) -> RunPolicy {
+        self.run_policy
+    }
+
+    /// Sets whether [`Chip8::run`] and [`Chip8::run_with_hooks`] print the display as character art, which is on by
+    /// default. Turn it off for hooks that render the display themselves, e.g. [`crate::sixel::SixelRenderer`].
+    pub fn set_text_output(&mut self, text_output: bool) {
+        self.text_output = text_output;
+    }
+
+    /// The program counter, i.e. the address of the next instruction.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Starts the program at `entry` instead of [`PROGRAM_START`], now and after every [`Chip8::reset`].
+    pub fn set_entry(&mut self, entry: usize) {
+        self.entry = entry;
+        self.set_pc(entry);
+    }
+
+    /// Where the program starts, see [`Chip8::set_entry`].
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Continues execution at `pc`, e.g. to skip an instruction in a debugger.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+        // Jumping away from FX0A stops waiting
+        self.waiting_for_key = None;
+    }
+
+    /// The address register I.
+    pub fn i(&self) -> u16 {
+        self.address_register
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.address_register = i;
+    }
+
+    /// The register `vn`. Panics if `n` isn't in `0x0..=0xF`.
+    pub fn register(&self, n: u8) -> u8 {
+        self.registers[n as usize]
+    }
+
+    /// Sets the register `vn` to `value`. Panics if `n` isn't in `0x0..=0xF`.
+    pub fn set_register(&mut self, n: u8, value: u8) {
+        self.registers[n as usize] = value;
+    }
+
+    /// The registers V0 to VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// The return addresses of the active subroutine calls, the innermost last.
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[1..=self.stack_pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Sets the sound timer, which starts or stops the beeper like `FX18`.
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+        self.update_beeper();
+    }
+
+    /// The whole memory, see [`Bus::memory`]. Use [`Chip8::write_memory`] to change it.
+    pub fn memory(&self) -> &[u8] {
+        self.bus.memory()
+    }
+
+    /// Writes `bytes` to memory starting at `addr` like an instruction would: Writes beyond memory fail with
+    /// [`Chip8Error::MemoryOutOfBounds`], the [`WriteProtection`] applies, and the memory check counts the bytes as
+    /// initialized. Errors name the current PC. Bytes before a failed write stay written.
+    pub fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), Chip8Error> {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.checked_poke(addr + i, byte, self.pc)?;
+        }
+        Ok(())
+    }
+
+    /// The memory, e.g. to inspect it between steps.
+    pub fn bus(&self) -> &dyn Bus {
+        self.bus.as_ref()
+    }
+
+    /// The memory for peripherals. Writes through it bypass the write protection and the memory check, use
+    /// [`Chip8::write_memory`] for those.
+    pub fn bus_mut(&mut self) -> &mut dyn Bus {
+        self.bus.as_mut()
+    }
+
+    /// Counters of executed instructions, rendered frames, draw calls and sprite collisions.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn run(&mut self) -> Result<RunStatus, Chip8Error> {
+        self.run_with_hooks(&mut ())
+    }
+
+    /// Like [`Chip8::run`], but calls `hooks` after every instruction and every frame. Returns according to the
+    /// [`RunPolicy`].
+    pub fn run_with_hooks(&mut self, hooks: &mut impl Hooks) -> Result<RunStatus, Chip8Error> {
+        let mut next_frame = Instant::now();
+        loop {
+            if let ControlFlow::Break(status) = self.run_policy_frame(hooks, true)? {
+                return Ok(status);
+            }
+            // Sleep until the next frame is due, so that the time spent executing doesn't slow down the emulation
+            next_frame += FRAME_DURATION;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(remaining) => thread::sleep(remaining),
+                // Fell behind, e.g. while paused in the debugger. Continue from now instead of rushing to catch up.
+                None => next_frame = Instant::now(),
+            }
+        }
+    }
+
+    /// Executes one frame of [`Chip8::run_with_hooks`] according to the [`RunPolicy`], and prints the display if
+    /// `print` is set. Breaks when running should stop.
+    pub(crate) fn run_policy_frame(&mut self, hooks: &mut impl Hooks, print: bool)
+        -> Result<ControlFlow<RunStatus>, Chip8Error>
+    {
+        let limit = match self.run_policy {
+            RunPolicy::Instructions(instructions) => {
+                match instructions.checked_sub(self.metrics.instructions).filter(|&left| left > 0) {
+                    Some(left) => Some(left),
+                    None => return Ok(ControlFlow::Break(RunStatus::InstructionLimit)),
+                }
+            },
+            RunPolicy::Forever | RunPolicy::UntilHalt => None,
+        };
+        match self.exec_frame(hooks, limit)? {
+            ControlFlow::Break(RunStatus::Halted { .. }) if self.run_policy == RunPolicy::Forever => {},
+            ControlFlow::Break(status) => return Ok(ControlFlow::Break(status)),
+            ControlFlow::Continue(()) => {},
+        }
+        self.update_shown();
+        if print && self.text_output {
+            self.print_display();
+        }
+        self.metrics.frames += 1;
+        if hooks.after_frame(self)?.is_break() {
+            return Ok(ControlFlow::Break(RunStatus::Stopped));
+        }
+        self.end_frame();
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Executes one frame as fast as possible, without rendering the display or waiting for the next frame. For
+    /// benchmarks and headless runs.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        // Halting only ends the frame early, the next one halts again
+        let _ = self.run_frame_with_hooks(&mut ())?;
+        Ok(())
+    }
+
+    /// Like [`Chip8::run_frame`], but calls `hooks` after every instruction and at the end of the frame. Frontends
+    /// that render the display themselves can drive the emulation with this. Returns why the frame stopped early,
+    /// i.e. because a hook stopped it or the program halted.
+    pub fn run_frame_with_hooks(&mut self, hooks: &mut impl Hooks) -> Result<ControlFlow<RunStatus>, Chip8Error> {
+        if let ControlFlow::Break(status) = self.exec_frame(hooks, None)? {
+            return Ok(ControlFlow::Break(status));
+        }
+        self.update_shown();
+        self.metrics.frames += 1;
+        if hooks.after_frame(self)?.is_break() {
+            return Ok(ControlFlow::Break(RunStatus::Stopped));
+        }
+        self.end_frame();
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Executes the instructions of one frame, but at most `limit`, and calls `hooks` after each of them.
+    fn exec_frame(&mut self, hooks: &mut impl Hooks, limit: Option<u64>) -> Result<ControlFlow<RunStatus>, Chip8Error> {
+        let instructions = limit.map_or(self.instructions_per_frame as u64, |limit| {
+            limit.min(self.instructions_per_frame as u64)
+        });
+        for _ in 0..instructions {
+            let pc = self.pc;
+            let opcode = self.load_opcode()?;
+            self.step()?;
+            if hooks.after_instruction(self, pc, opcode)?.is_break() {
+                return Ok(ControlFlow::Break(RunStatus::Stopped));
+            }
+            if opcode == 0x1000 | pc as u16 {
+                // Only tell the hooks once, when running on after halting
+                if self.history.iter().rev().nth(1) != Some(&(pc, opcode)) {
+                    hooks.halted(self, pc)?;
+                }
+                return Ok(ControlFlow::Break(RunStatus::Halted { pc }));
+            }
+            // Drawing waits for the vertical blank interrupt, which ends the frame
+            if self.quirks.vblank && opcode & 0xF000 == 0xD000 {
+                // The timers tick at the end of every frame, also if it ended early
+                if self.cycles_since_tick > 0 {
+                    self.tick_timers();
+                }
+                break;
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Resets the per-frame state after the frame was rendered.
+    fn end_frame(&mut self) {
+        self.dirty_rows = 0;
+        self.polled_keys = 0;
+    }
+
+    /// Executes the next instruction and returns what happened, including timer ticks after it. The instruction is
+    /// recorded in the profiler, written to the trace and compared with the reference trace, if enabled.
+    pub fn step(&mut self) -> Result<StepEvents, Chip8Error> {
+        self.step_events = StepEvents::default();
+        if self.profiler.is_none() && self.tracer.is_none() && self.reference.is_none() {
+            self.exec_instruction()?;
+            return Ok(self.step_events);
+        }
+        let pc = self.pc;
+        let opcode = self.load_opcode()?;
+        let start = Instant::now();
+        let result = self.exec_instruction();
+        let elapsed = start.elapsed();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, opcode, elapsed);
+        }
+        result?;
+        if self.tracer.is_none() && self.reference.is_none() {
+            return Ok(self.step_events);
+        }
+        let entry = self.trace_entry(pc, opcode);
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(&entry).map_err(|err| Chip8Error::Trace(err.to_string()))?;
+        }
+        if let Some(reference) = &mut self.reference {
+            let divergence = reference.check(&entry)
+                .map_err(|err| Chip8Error::ReferenceTrace(err.to_string()))?;
+            if let Some(divergence) = divergence {
+                return Err(Chip8Error::Divergence(Box::new(divergence)));
+            }
+        }
+        Ok(self.step_events)
+    }
+
+    fn trace_entry(&self, pc: usize, opcode: u16) -> TraceEntry {
+        TraceEntry {
+            pc,
+            opcode,
+            registers: self.registers,
+            i: self.address_register,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    fn exec_instruction(&mut self) -> Result<(), Chip8Error> {
+        if let Some(vx) = self.waiting_for_key {
+            self.resume_on_key_press(vx as usize);
+            self.count_cycle();
+            return Ok(());
+        }
+        let opcode = self.load_opcode()?;
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((self.pc, opcode));
+        self.pc += 2;
+
+        // Decode like the disassembler, so that both agree on which opcodes are illegal
+        let instruction = Instruction::decode(opcode)
+            .map_err(|_| Chip8Error::IllegalInstruction { opcode, pc: self.pc })?;
+        let result = match instruction {
+            Instruction::Sys { .. } => self.call_machine_routine(opcode),
+            Instruction::Cls => self.clear_display(),
+            Instruction::Ret => self.subroutine_return(),
+            Instruction::Jump { .. } => self.jump(opcode),
+            Instruction::Call { .. } => self.call_subroutine(opcode),
+            Instruction::SkipEqByte { .. } => self.skip_if_vx_eq_nn(opcode),
+            Instruction::SkipNeByte { .. } => self.skip_if_vx_ne_nn(opcode),
+            Instruction::SkipEqReg { .. } => self.skip_if_vx_eq_vy(opcode),
+            Instruction::LoadByte { .. } => self.set_vx_to_n(opcode),
+            Instruction::AddByte { .. } => self.add_n_to_vx(opcode),
+            Instruction::LoadReg { .. } => self.set_vx_to_vy(opcode),
+            Instruction::Or { .. } => self.set_vx_to_vx_bitor_vy(opcode),
+            Instruction::And { .. } => self.set_vx_to_vx_bitand_vy(opcode),
+            Instruction::Xor { .. } => self.set_vx_to_vx_xor_vy(opcode),
+            Instruction::AddReg { .. } => self.add_vy_to_vx(opcode),
+            Instruction::Sub { .. } => self.subtract_vy_from_vx(opcode),
+            Instruction::Shr { .. } => self.right_shift_vx(opcode),
+            Instruction::Subn { .. } => self.set_vx_to_vy_minus_vx(opcode),
+            Instruction::Shl { .. } => self.left_shift_vx(opcode),
+            Instruction::SkipNeReg { .. } => self.skip_if_vx_ne_vy(opcode),
+            Instruction::LoadI { .. } => self.set_i_addr_to_n(opcode),
+            Instruction::JumpV0 { .. } => self.jump_to_n_plus_v0(opcode),
+            Instruction::Random { .. } => self.set_to_vx_rand_bitand_n(opcode),
+            Instruction::Draw { .. } => self.draw_sprite_at_coordinates_vx_vy_with_height_n(opcode),
+            Instruction::SkipKey { .. } => self.skip_if_key_in_vk_pressed(opcode),
+            Instruction::SkipNotKey { .. } => self.skip_if_key_in_vk_not_pressed(opcode),
+            Instruction::LoadDelay { .. } => self.set_vx_to_delay_timer(opcode),
+            Instruction::WaitKey { .. } => self.wait_for_key_press_and_store_in_vx(opcode),
+            Instruction::SetDelay { .. } => self.set_delay_timer_to_vx(opcode),
+            Instruction::SetSound { .. } => self.set_sound_timer_to_vx(opcode),
+            Instruction::AddI { .. } => self.add_vx_to_i(opcode),
+            Instruction::LoadFont { .. } => self.set_i_to_sprite_addr(opcode),
+            Instruction::Bcd { .. } => self.store_bcd_in_mem(opcode),
+            Instruction::Store { .. } => self.store_v0_to_vx_in_mem(opcode),
+            Instruction::Load { .. } => self.load_v0_to_vx_from_mem(opcode),
+        };
+        if result.is_ok() {
+            self.count_cycle();
+        }
+        result
+    }
+
+    /// Counts an executed instruction, which ticks the timers every [`Chip8::instructions_per_frame`] instructions.
+    fn count_cycle(&mut self) {
+        self.metrics.instructions += 1;
+        self.cycles_since_tick += 1;
+        if self.cycles_since_tick >= self.instructions_per_frame {
+            self.tick_timers();
+        }
+    }
+
+    /// `vx = get_key()`, i.e. waits for a key press and writes that key into register `vx`. Opcode: `FX0A` - `LD
+    /// vx, key`. A key held already is taken right away. Otherwise the interpreter waits for the next key press,
+    /// see [`Chip8::waiting_for_key`].
+    fn wait_for_key_press_and_store_in_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        self.polled_keys = u16::MAX;
+        match (0..16).find(|&key| self.is_key_pressed(key)) {
+            Some(key) => self.registers[vx] = key,
+            None => {
+                self.pc -= 2;
+                self.waiting_for_key = Some(vx as u8);
+                self.pressed_while_waiting = 0;
+                self.step_events.insert(StepEvent::WaitingForKey);
+            },
+        }
+        Ok(())
+    }
+
+    /// Finishes `FX0A` if a key was pressed while waiting, otherwise keeps waiting for an instruction.
+    fn resume_on_key_press(&mut self, vx: usize) {
+        self.polled_keys = u16::MAX;
+        match (0..16).find(|&key| (self.pressed_while_waiting >> key) & 1 == 1) {
+            Some(key) => {
+                self.registers[vx] = key;
+                self.pc += 2;
+                self.waiting_for_key = None;
+            },
+            None => self.step_events.insert(StepEvent::WaitingForKey),
+        }
+    }
+
+    /// `delay_timer = vx`, i.e. sets the delay timer to the value of the register `vx`. Opcode: `FX15` - `LD DT, vx`.
+    fn set_delay_timer_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        self.delay_timer = self.registers[vx];
+        Ok(())
+    }
+
+    /// `sound_timer = vx`, i.e. sets the sound timer to the value of the register `vx`. Opcode: `FX18` - `LD ST, vx`.
+    fn set_sound_timer_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        self.sound_timer = self.registers[vx];
+        self.update_beeper();
+        Ok(())
+    }
+
+    /// `I += vx`, i.e. adds the register `vx` to the address register `I`. Opcode: `FX1E` - `ADD I, vx`.
+    fn add_vx_to_i(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let addr = self.address_register as usize + self.registers[vx as usize] as usize;
+        self.address_register = self.wrap_address(addr)?;
+        Ok(())
+    }
+
+    /// `I = sprite_addr[vx]`, i.e. sets the address register `I` to the address of the sprite for the char in `vx`.
+    /// Opcode: `FX29` - `LD F, vx`.
+    fn set_i_to_sprite_addr(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        // Only the lowest digit counts, like on the COSMAC VIP
+        let sprite_addr = FONT_ADDR + (self.registers[vx] & 0xF) as usize * DIGIT_HEIGHT;
+        self.address_register = sprite_addr as u16;
+        Ok(())
+    }
+
+    /// Writes the binary-coded decimal representation of `vx` with the most significant of the three bcd digits at
+    /// the address `I`, the middle at `I + 1`, the least significant bit at `I + 2`. Opcode: `FX33` - `LD B, vx`.
+    fn store_bcd_in_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        let vx_val = self.registers[vx];
+        let hundreds = vx_val / 100;
+        let tens = (vx_val % 100) / 10;
+        let ones = vx_val % 10;
+        self.write_mem(self.i_plus(0), hundreds)?;
+        self.write_mem(self.i_plus(1), tens)?;
+        self.write_mem(self.i_plus(2), ones)?;
+        Ok(())
+    }
+
+    /// `reg_load(vx, &I)`, i.e. writes the value of memory starting at address `I` to the registers `v0` to `vx`.
+    /// Opcode: `FX65` - `LD vx, [I]`.
+    fn load_v0_to_vx_from_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        for i in 0..=vx {
+            self.registers[i] = self.read_mem(self.i_plus(i))?;
+        }
+        self.increment_i_after_memory_access(vx);
+        Ok(())
+    }
+
+    /// `reg_dump(vx, &I)`, i.e. writes the value of the registers `v0` to `vx` to memory starting at address `I`.
+    /// Opcode: `FX55` -`LD [I], vx`.
+    fn store_v0_to_vx_in_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = ((opcode & 0x0F00) >> 8) as usize;
+        for i in 0..=vx {
+            self.write_mem(self.i_plus(i), self.registers[i])?;
+        }
+        self.increment_i_after_memory_access(vx);
+        Ok(())
+    }
+
+    /// Increments `I` after `FX55` or `FX65` accessed the registers `v0` to `vx`, depending on the quirks.
+    fn increment_i_after_memory_access(&mut self, vx: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let increment = if self.quirks.memory_increment_by_x { vx } else { vx + 1 };
+        let addr = self.address_register as usize + increment;
+        self.address_register = match self.quirks.i_wrap {
+            true => (addr % self.bus.size()) as u16,
+            // Pointing right behind memory is fine as long as nothing is accessed there
+            false => addr as u16,
+        };
+    }
+
+    /// The address `offset` bytes after `I`, which wraps around at the end of memory with the `i_wrap` quirk.
+    fn i_plus(&self, offset: usize) -> usize {
+        let addr = self.address_register as usize + offset;
+        match self.quirks.i_wrap {
+            true => addr % self.bus.size(),
+            false => addr,
+        }
+    }
+
+    /// Wraps `addr` around at the end of memory with the `i_wrap` quirk, otherwise fails if it's beyond memory.
+    fn wrap_address(&self, addr: usize) -> Result<u16, Chip8Error> {
+        match self.quirks.i_wrap {
+            true => Ok((addr % self.bus.size()) as u16),
+            false if addr < self.bus.size() => Ok(addr as u16),
+            // The PC already points to the next instruction
+            false => Err(Chip8Error::MemoryOutOfBounds { addr, pc: self.pc - 2 }),
+        }
+    }
+
+    /// Call machine routine. Opcode: `0NNN` - `SYS addr`.
+    fn call_machine_routine(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let machine_routine_nr = opcode & 0x0FFF;
+        Err(Chip8Error::UnknownMachineRoutine(machine_routine_nr))?;
+        Ok(())
+    }
+
+    /// Clears the display, i.e. sets all bytes to zero. Opcode: `00E0` - `CLS`.
+    fn clear_display(&mut self) -> Result<(), Chip8Error> {
+        self.display = Default::default();
+        self.dirty_rows = u32::MAX;
+        self.step_events.insert(StepEvent::DisplayUpdated);
+        Ok(())
+    }
+
+    /// Return from subroutine. Opcode: `00EE` - `RET`.
+    fn subroutine_return(&mut self) -> Result<(), Chip8Error> {
+        if self.stack_pointer == 0 {
+            // The PC already points to the next instruction
+            return Err(Chip8Error::StackUnderflow { pc: self.pc - 2 });
+        }
+        self.pc = self.stack[self.stack_pointer as usize];
+        self.stack_pointer -= 1;
+        Ok(())
+    }
+
+    /// Set the program counter to NNN. Opcode: `1NNN` - `JP addr`.
+    fn jump(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let jump_addr = opcode & 0x0FFF;
+        self.pc = jump_addr as usize;
+        Ok(())
+    }
+
+    /// Call subroutine. Opcode: `2NNN` - `CALL addr`.
+    fn call_subroutine(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        // Leave the stack pointer alone on overflow, so the stack can still be inspected
+        let stack_pointer = self.stack_pointer.checked_add(1).ok_or(Chip8Error::StackOverflow)?;
+        let stack_frame = self.stack.get_mut(stack_pointer as usize).ok_or(Chip8Error::StackOverflow)?;
+        *stack_frame = self.pc;
+        self.stack_pointer = stack_pointer;
+        let subroutine_mem_addr = opcode & 0x0FFF;
+        self.pc = subroutine_mem_addr as usize;
+        Ok(())
+    }
+
+    /// Skip next instruction if vx (register) == nn (constant in). Opcode: `3XNN` - `SE vx, byte`.
+    fn skip_if_vx_eq_nn(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let register_number = (opcode & 0x0F00) >> 8;
+        let register_value = self.registers[register_number as usize];
+        let number_in = opcode & 0x00FF;
+        if register_value == number_in as u8 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// Skip next instruction if vx (register) != nn (constant in). Opcode: `4XNN` - `SNE vx, byte`.
+    fn skip_if_vx_ne_nn(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let register_number = (opcode & 0x0F00) >> 8;
+        let register_value = self.registers[register_number as usize];
+        let number_in = opcode & 0x00FF;
+        if register_value != number_in as u8 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// Skip next instruction if vx (register) == vy (register). Opcode: `5XY0` - `SE vx, vy`.
+    fn skip_if_vx_eq_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        // Get number of the registers vx and vy
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy_number = (opcode & 0x00F0) >> 4;
+        // Get their values
+        let vx_value = self.registers[vx as usize];
+        let vy_value = self.registers[vy_number as usize];
+        if vx_value == vy_value {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// vx = n., i.e. put value nn into register vx. Opcode: `6XNN` - `LD vx, byte`.
+    fn set_vx_to_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let number_in = opcode & 0x00FF;
+        self.registers[vx as usize] = number_in as u8;
+        Ok(())
+    }
+
+    /// vx += n, i.e. adds the constant n to register vx. Opcode: `7XNN` - `ADD vx, byte`.
+    fn add_n_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let number_in = opcode & 0x00FF;
+        self.registers[vx as usize] = self.registers[vx as usize].wrapping_add(number_in as u8);
+        Ok(())
+    }
+
+    /// vx = vy, i.e. sets register vx to the value of register vy. Opcode: `8XY0` - `LD vx, vy`.
+    fn set_vx_to_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy_number = (opcode & 0x00F0) >> 4;
+        self.registers[vx as usize] = self.registers[vy_number as usize];
+        Ok(())
+    }
+
+    /// vx |= vy, i.e. sets register vx to vx bitwise or vy. Opcode: `8XY1` - `OR vx, vy`.
+    fn set_vx_to_vx_bitor_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        self.registers[vx as usize] |= self.registers[vy as usize];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    /// vx &= vy, i.e. sets register vx to vx bitwise and vy. Opcode: `8XY2` - `AND vx, vy`.
+    fn set_vx_to_vx_bitand_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        self.registers[vx as usize] &= self.registers[vy as usize];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    /// vx ^= vy, i.e. sets register vx to vx xor vy. Opcode: `8XY3` - `XOR vx, vy`.
+    fn set_vx_to_vx_xor_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        self.registers[vx as usize] ^= self.registers[vy as usize];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    /// vx += vy, i.e. sets register vx to vx plus vy, wrapping around. vf is set to 1 on a carry, otherwise to 0.
+    /// Opcode: `8XY4` - `ADD vx, vy`.
+    fn add_vy_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        let (sum, carry) = self.registers[vx as usize].overflowing_add(self.registers[vy as usize]);
+        self.registers[vx as usize] = sum;
+        self.registers[0xF] = carry as u8;
+        Ok(())
+    }
+
+    /// vx -= vy, i.e. sets register vx to vx minus vy, wrapping around. vf is set to 0 on a borrow, otherwise to 1.
+    /// Opcode: `8XY5` - `SUB vx, vy`.
+    fn subtract_vy_from_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        let (difference, borrow) = self.registers[vx as usize].overflowing_sub(self.registers[vy as usize]);
+        self.registers[vx as usize] = difference;
+        self.registers[0xF] = !borrow as u8;
+        Ok(())
+    }
+
+    /// vx >>= 1, i.e. stores the least significant bit of VX in VF and shift the register VX one to the right.
+    /// Opcode: `8XY6` - `SHR vx`. `Y` is a don't care, unless the shift quirk is disabled, in which case `vy` is
+    /// shifted into `vx`.
+    fn right_shift_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let value = self.registers[self.shift_source(opcode)];
+        self.registers[vx as usize] = value >> 1;
+        self.registers[0xF] = value & 0b1;
+        Ok(())
+    }
+
+    /// vx = vy - vx, i.e. sets register vx to vy minus vx, wrapping around. vf is set to 0 on a borrow, otherwise
+    /// to 1. Opcode: `8XY7` - `SUBN vx, vy`.
+    fn set_vx_to_vy_minus_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy = (opcode & 0x00F0) >> 4;
+        let (difference, borrow) = self.registers[vy as usize].overflowing_sub(self.registers[vx as usize]);
+        self.registers[vx as usize] = difference;
+        self.registers[0xF] = !borrow as u8;
+        Ok(())
+    }
+
+    /// vx <<= 1, i.e. stores the most significant bit of VX in VF and shift the register VX one to the left.
+    /// Opcode: `8XYE` - `SHL vx`. `Y` is a don't care, unless the shift quirk is disabled, in which case `vy` is
+    /// shifted into `vx`.
+    fn left_shift_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let value = self.registers[self.shift_source(opcode)];
+        self.registers[vx as usize] = value << 1;
+        self.registers[0xF] = (value & 0x80) >> 7;
+        Ok(())
+    }
+
+    /// The register shifted by `8XY6` and `8XYE`.
+    fn shift_source(&self, opcode: u16) -> usize {
+        match self.quirks.shift {
+            true => ((opcode & 0x0F00) >> 8) as usize,
+            false => ((opcode & 0x00F0) >> 4) as usize,
+        }
+    }
+
+    /// Skip next instruction if vx (register) != vy (register). Opcode: `9XY0` - `SNE vx, vy`.
+    fn skip_if_vx_ne_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        // Get number of the registers vx and vy
+        let vx = (opcode & 0x0F00) >> 8;
+        let vy_number = (opcode & 0x00F0) >> 4;
+        // Get their values
+        let vx_value = self.registers[vx as usize];
+        let vy_value = self.registers[vy_number as usize];
+        if vx_value != vy_value {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// I = n, i.e. sets the I address register to the number n. Opcode: `ANNN` - `LD I, addr`.
+    fn set_i_addr_to_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let n = opcode & 0x0FFF;
+        self.address_register = n;
+        Ok(())
+    }
+
+    /// pc = V0 + n, i.e. jumps to register V0 plus n. Opcode: `BNNN` - `JP V0, addr`. With the jump quirk, register
+    /// vx is used instead of V0, where x is the highest digit of n.
+    fn jump_to_n_plus_v0(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let n = opcode & 0x0FFF;
+        let register = if self.quirks.jump { ((opcode & 0x0F00) >> 8) as usize } else { 0 };
+        self.pc = (self.registers[register] as u16 + n) as usize;
+        Ok(())
+    }
+
+    /// `vx = rand()`, i.e. sets `vx` to a random number combined with a bitwise or with n to limit the maximum value.
+    /// Opcode: `CXNN` - `RND vx, byte`
+    fn set_to_vx_rand_bitand_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        let n = opcode & 0x00FF;
+        let rand = self.next_random();
+        self.registers[vx as usize] = rand & n as u8;
+        Ok(())
+    }
+
+    /// Draws a sprite at the coordinates (vx, vy), so the numbers stored in the registers vx and vy, with height n
+    /// and width 8. The data is fetched from the memory address stored in the register I. Register vf is set to 1 if
+    /// any screen pixels are flipped from set to unset to allow for collision detection.
+    fn draw_sprite_at_coordinates_vx_vy_with_height_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let height = (opcode & 0x000F) as usize;
+        let register_vx = (opcode & 0x0F00) >> 8;
+        let register_vy = (opcode & 0x00F0) >> 4;
+        // Coordinates
+        let x = self.registers[register_vx as usize] as usize % DISPLAY_WIDTH;
+        let y = self.registers[register_vy as usize] as usize % DISPLAY_HEIGHT;
+
+        // Reset collision flag
+        self.registers[0xF] = 0;
+
+        for row in 0..height {
+            // Without wrapping, sprites are clipped at the bottom edge. The clipped rows aren't read from memory.
+            if !self.quirks.wrap && y + row >= DISPLAY_HEIGHT {
+                break;
+            }
+            let sprite = self.read_mem(self.i_plus(row))?;
+            // Move the sprite row to the left edge, then to x. Pixels shifted out at the right edge are clipped or
+            // wrap around to the left edge.
+            let sprite = (sprite as u64) << (DISPLAY_WIDTH - 8);
+            let sprite = match self.quirks.wrap {
+                true => sprite.rotate_right(x as u32),
+                false => sprite >> x,
+            };
+            let local_y = (y + row) % DISPLAY_HEIGHT;
+            if sprite != 0 {
+                self.dirty_rows |= 1 << local_y;
+                self.step_events.insert(StepEvent::DisplayUpdated);
+            }
+            if self.display[local_y] & sprite != 0 {
+                self.registers[0xF] = 1;
+            }
+            self.display[local_y] ^= sprite;
+        }
+
+        self.metrics.draw_calls += 1;
+        if self.registers[0xF] == 1 {
+            self.metrics.collisions += 1;
+        }
+        Ok(())
+    }
+
+    /// Skips the next instruction if the key stored in vx is pressed. Opcode: `EX9E` - `SKP vx`.
+    fn skip_if_key_in_vk_pressed(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        if self.poll_key(self.registers[vx as usize]) {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// Skips the next instruction if the key stored in vx is not pressed. Opcode: `EX9E` - `SKNP vx`.
+    fn skip_if_key_in_vk_not_pressed(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        if !self.poll_key(self.registers[vx as usize]) {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// `vx = get_delay_timer()`, i.e. sets register `vx` to the value of the delay time. Opcode: `CXNN` - `RND vx,
+    /// byte`.
+    fn set_vx_to_delay_timer(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let vx = (opcode & 0x0F00) >> 8;
+        self.registers[vx as usize] = self.delay_timer;
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//! Runs a ROM under two configurations in lockstep, with the same seed and the same input, and finds the first frame
+//! where the machines diverge. Comparing the quirks of two platforms pinpoints which quirk a misbehaving ROM depends
+//! on, especially when the configurations only differ in a single quirk.
+
+use std::fmt;
+use std::ops::ControlFlow;
+use crate::snapshot::{Snapshot, SnapshotDiff};
+use crate::{Chip8, Chip8Error, RunStatus};
+
+/// How the comparison ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Both machines were equal after every frame.
+    Equal { frames: u64 },
+    /// The machines differ after `frame` (counted from 0), in the way `diff` describes (first configuration on the
+    /// left).
+    Diverged { frame: u64, diff: SnapshotDiff },
+    /// At least one machine failed in `frame`, which ends the comparison.
+    Failed { frame: u64, first: Option<Chip8Error>, second: Option<Chip8Error> },
+    /// Both machines halted at the same instruction after being equal up to `frame`.
+    Halted { frame: u64, pc: usize },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Equal { frames } => write!(f, "No divergence in {} frames", frames),
+            Outcome::Diverged { frame, diff } => write!(f, "Diverged in frame {}:\n{}", frame, diff),
+            Outcome::Failed { frame, first, second } => {
+                let describe = |error: &Option<Chip8Error>| match error {
+                    Some(error) => error.to_string(),
+                    None => String::from("ok"),
+                };
+                write!(f, "Failed in frame {}\nFirst: {}\nSecond: {}", frame, describe(first), describe(second))
+            },
+            Outcome::Halted { frame, pc } => {
+                write!(f, "Both halted at {:#05X} in frame {} without diverging", pc, frame)
+            },
+        }
+    }
+}
+
+/// Runs `first` and `second` in lockstep for up to `frames` frames and compares them after every frame. Both get
+/// the same `seed`, and the keys returned by `keypad` for each frame (see [`Chip8::set_keypad`]).
+pub fn run(first: &mut Chip8, second: &mut Chip8, frames: u64, seed: u64, mut keypad: impl FnMut(u64) -> u16)
+    -> Outcome
+{
+    first.set_seed(seed);
+    second.set_seed(seed);
+    for frame in 0..frames {
+        let keys = keypad(frame);
+        first.set_keypad(keys);
+        second.set_keypad(keys);
+        let results = (first.run_frame_with_hooks(&mut ()), second.run_frame_with_hooks(&mut ()));
+        let halted = match results {
+            (Ok(ControlFlow::Break(RunStatus::Halted { pc })), Ok(ControlFlow::Break(RunStatus::Halted { .. }))) => {
+                Some(pc)
+            },
+            (Ok(_), Ok(_)) => None,
+            (first_result, second_result) => {
+                return Outcome::Failed { frame, first: first_result.err(), second: second_result.err() };
+            },
+        };
+        let diff = Snapshot::of(first).diff(&Snapshot::of(second));
+        if !diff.is_empty() {
+            return Outcome::Diverged { frame, diff };
+        }
+        if let Some(pc) = halted {
+            return Outcome::Halted { frame, pc };
+        }
+    }
+    Outcome::Equal { frames }
+}
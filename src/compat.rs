@@ -0,0 +1,55 @@
+//! Settings for known ROMs, looked up by the SHA-1 hash of the ROM in a table bundled into the binary. See
+//! `compat.txt` for the format of the table.
+
+use crate::{rom, Chip8, Quirks};
+
+static TABLE: &str = include_str!("compat.txt");
+
+/// The settings a ROM needs to run correctly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub title: &'static str,
+    /// Platform the ROM was written for, like in the CHIP-8 database.
+    pub platform: &'static str,
+    /// Number of instructions per frame.
+    pub tickrate: u32,
+    pub quirks: Quirks,
+}
+
+impl Profile {
+    /// Applies the settings to `chip8`.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        chip8.set_instructions_per_frame(self.tickrate);
+        chip8.set_quirks(self.quirks);
+    }
+}
+
+/// Looks up the profile of `rom` in the bundled table.
+pub fn lookup(rom: &[u8]) -> Option<Profile> {
+    lookup_sha1(&rom::sha1(rom))
+}
+
+/// Looks up a profile by the SHA-1 hash of the ROM, e.g. from [`rom::RomId`].
+pub fn lookup_sha1(hash: &str) -> Option<Profile> {
+    TABLE.lines()
+        .filter_map(parse_line)
+        .find(|(entry_hash, _)| entry_hash.eq_ignore_ascii_case(hash))
+        .map(|(_, profile)| profile)
+}
+
+/// Parses a line of the table into the hash and the profile. Returns `None` for comments and invalid lines.
+pub fn parse_line(line: &'static str) -> Option<(&'static str, Profile)> {
+    let (settings, title) = line.split_once('#').unwrap_or((line, ""));
+    let mut fields = settings.split_whitespace();
+    let hash = fields.next()?;
+    let platform = fields.next()?;
+    let tickrate = fields.next()?.parse().ok()?;
+    let mut quirks = Quirks::for_platform(platform)?;
+    for quirk in fields {
+        let (name, enabled) = quirk.split_once('=')?;
+        if !quirks.set(name, enabled.parse().ok()?) {
+            return None;
+        }
+    }
+    Some((hash, Profile { title: title.trim(), platform, tickrate, quirks }))
+}
@@ -0,0 +1,127 @@
+//! Settings kept between runs, in a TOML file in the config directory of the user (see [`Config::default_path`]):
+//!
+//! ```toml
+//! [audio]
+//! volume = 0.8  # from 0 (silent) to 1
+//! muted = false
+//!
+//! [keys]  # the host key of every key of the hex keypad, see `chip8 keys`
+//! 0 = "x"
+//! 1 = "1"
+//! # ...
+//! ```
+//!
+//! Missing settings keep their defaults. The file is rewritten when settings change, so comments and unknown keys
+//! aren't kept.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+use crate::audio::Mixer;
+use crate::keymap::Keymap;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Volume of the audio output.
+    pub mixer: Mixer,
+    /// Host keys of the hex keypad, or `None` for the default.
+    pub keymap: Option<Keymap>,
+}
+
+impl Config {
+    /// `chip8/config.toml` in `$XDG_CONFIG_HOME`, or in `~/.config` if it isn't set. `None` if neither is known.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME").filter(|dir| !dir.is_empty())?).join(".config"),
+        };
+        Some(config_dir.join("chip8").join("config.toml"))
+    }
+
+    /// Loads a config file. A missing file gives the default settings.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Parses the contents of a config file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let document: DocumentMut = text.parse().map_err(|err| format!("Invalid TOML: {}", err))?;
+        let mut config = Self::default();
+        if let Some(item) = document.get("keys") {
+            config.keymap = Some(parse_keymap(item)?);
+        }
+        let audio = match document.get("audio") {
+            Some(item) => item.as_table().ok_or("`audio` has to be a table like [audio]")?,
+            None => return Ok(config),
+        };
+        match audio.get("volume").map(number) {
+            Some(Some(volume)) if (0.0..=1.0).contains(&volume) => config.mixer.set_volume(volume),
+            Some(_) => return Err(String::from("`audio.volume` has to be a number from 0 to 1")),
+            None => {},
+        }
+        match audio.get("muted").map(Item::as_bool) {
+            Some(Some(muted)) => config.mixer.set_muted(muted),
+            Some(None) => return Err(String::from("`audio.muted` has to be true or false")),
+            None => {},
+        }
+        Ok(config)
+    }
+
+    /// Writes the config file, creating its directory if needed.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_toml())
+    }
+
+    /// The contents of a config file with these settings.
+    pub fn to_toml(&self) -> String {
+        let mut toml = format!("[audio]\nvolume = {:?}\nmuted = {}\n", self.mixer.volume(), self.mixer.is_muted());
+        if let Some(keymap) = &self.keymap {
+            toml.push_str("\n[keys]\n");
+            for key in 0..16 {
+                let c = match keymap.char_for_key(key) {
+                    c @ ('"' | '\\') => format!("\\{}", c),
+                    c => c.to_string(),
+                };
+                toml.push_str(&format!("{:X} = \"{}\"\n", key, c));
+            }
+        }
+        toml
+    }
+}
+
+/// Parses the `[keys]` table. Keys that aren't listed keep their default.
+fn parse_keymap(item: &Item) -> Result<Keymap, String> {
+    let table = item.as_table().ok_or("`keys` has to be a table like [keys]")?;
+    let mut keymap = Keymap::default();
+    let mut assigned: Vec<char> = Vec::new();
+    for (name, value) in table.iter() {
+        let key = u8::from_str_radix(name, 16).ok().filter(|&key| key < 16 && name.len() == 1)
+            .ok_or_else(|| format!("`keys.{}` isn't a key of the hex keypad, expected 0 to F", name))?;
+        let mut chars = value.as_str().unwrap_or_default().chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c.to_ascii_lowercase(),
+            _ => return Err(format!("`keys.{}` has to be a single character like \"x\"", name)),
+        };
+        if assigned.contains(&c) {
+            return Err(format!("{:?} is assigned to more than one key", c));
+        }
+        assigned.push(c);
+        keymap.set(key, c).map_err(|err| format!("`keys.{}`: {}", name, err))?;
+    }
+    Ok(keymap)
+}
+
+/// A float or an integer.
+fn number(item: &Item) -> Option<f64> {
+    item.as_float().or_else(|| item.as_integer().map(|integer| integer as f64))
+}
@@ -0,0 +1,475 @@
+//! Text frontend for the [`Debugger`], reading one command per line from stdin:
+//!
+//! * `regs`, `bt` (backtrace), `where`, `breakpoints`
+//! * `mem <addr> [len]` shows `len` bytes (default 16) starting at `addr`
+//! * `disasm [addr] [count]` decodes `count` instructions (default 8) starting at `addr` (default the PC)
+//! * `sprite [addr] [len] [height]` draws `len` bytes (default 15) starting at `addr` (default I) as sprites of
+//!   `height` rows (default all bytes in one sprite) next to each other, e.g. to find the height of a sprite table
+//! * `break <addr>`, `delete <addr>`
+//! * `watch-i <start>..<end>`, `unwatch-i <start>..<end>` pause when an instruction sets I into the range
+//! * `watch <expr>`, `unwatch <expr>`, `watches`, where `expr` is like `V3`, `I`, `DT`, `[0x300]` or `[I+2]`
+//! * `step`, `step-back`, `continue`, `pause`
+//! * `freeze <addr> <value>` writes the byte `value` to `addr` after every instruction, `unfreeze <addr>`, `freezes`
+//! * `search <value>` or `search all` starts a memory search, further searches with a value or `changed`,
+//!   `unchanged`, `increased` or `decreased` keep the addresses whose value changed like that, `search reset`
+//! * `seek <n>` goes to the state after the `n`th executed instruction, also forward after going back
+//! * `macro record <name>` records the following commands (which are still executed) until `macro end`,
+//!   `macro run <name>` executes them again and `macro list` shows all macros
+//! * `quit`
+//!
+//! Startup scripts contain the same commands, one per line, which are executed before reading from stdin. Empty lines
+//! and `#` comments are ignored.
+//!
+//! Addresses are hex with `0x` prefix (`0x242`), decimal (`578`), symbol names (`main_loop`) or source lines
+//! (`line:42`). The watch expressions are shown after every `step` and `continue`, changed values marked with `*`.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use crate::cheats::SearchFilter;
+use crate::debugger::{Command, Debugger, Location, PauseReason, Reply, WatchExpr};
+use crate::rewind::Rewind;
+use crate::{Chip8, StepEvent};
+
+/// Number of bytes shown by `mem` if no length is given, and per line of the output.
+const MEM_LEN: usize = 16;
+/// Number of instructions decoded by `disasm` if no count is given.
+const DISASM_COUNT: usize = 8;
+/// Number of bytes shown by `sprite` if no length is given, the highest sprite DXYN can draw.
+const SPRITE_LEN: usize = 15;
+/// Number of sprites shown next to each other by `sprite`.
+const SPRITES_PER_LINE: usize = 8;
+/// Number of addresses listed after a `search`.
+const SEARCH_SHOWN: usize = 16;
+/// Maximum nesting of macros running other macros, to stop macros that run themselves forever.
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// Parses a line like `mem 0x200 32` into a command.
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or("Empty command")?;
+    let mut location = |what: &str| words.next().map(parse_location).ok_or(format!("{} requires an address", what));
+    let command = match name {
+        "step" | "s" => Command::Step,
+        "step-back" | "sb" => Command::StepBack,
+        "seek" => {
+            let word = words.next().ok_or("seek requires an instruction number")?;
+            Command::Seek { position: word.parse().map_err(|_| format!("Expected a number, got {}", word))? }
+        },
+        "continue" | "c" => Command::Continue,
+        "pause" => Command::Pause,
+        "break" | "b" => Command::Break { addr: location("break")? },
+        "delete" | "d" => Command::Delete { addr: location("delete")? },
+        "watch-i" | "unwatch-i" => {
+            let word = words.next().ok_or(format!("{} requires a range like 0x300..0x310", name))?;
+            let (start, end) = parse_range(word)?;
+            match name {
+                "watch-i" => Command::WatchI { start, end },
+                _ => Command::UnwatchI { start, end },
+            }
+        },
+        "breakpoints" => Command::Breakpoints,
+        "regs" | "r" => Command::Regs,
+        "backtrace" | "bt" => Command::Backtrace,
+        "where" | "w" => Command::Where,
+        "mem" | "m" => {
+            let addr = match location("mem")? {
+                Location::Addr(addr) => addr,
+                _ => return Err(String::from("mem requires a numeric address")),
+            };
+            Command::Mem { addr, len: parse_count(words.next(), MEM_LEN)? }
+        },
+        "disasm" | "x" => {
+            let addr = words.next().map(parse_location);
+            Command::Disasm { addr, count: parse_count(words.next(), DISASM_COUNT)? }
+        },
+        "sprite" => {
+            let addr = words.next().map(parse_location);
+            let len = parse_count(words.next(), SPRITE_LEN)?;
+            Command::Sprite { addr, len, height: parse_count(words.next(), len)?.max(1) }
+        },
+        "watch" => Command::Watch { expr: parse_watch_expr(words, "watch")? },
+        "unwatch" => Command::Unwatch { expr: parse_watch_expr(words, "unwatch")? },
+        "watches" => Command::Watches,
+        "freeze" => {
+            let addr = location("freeze")?;
+            let word = words.next().ok_or("freeze requires a value")?;
+            Command::Freeze { addr, value: parse_byte(word)? }
+        },
+        "unfreeze" => Command::Unfreeze { addr: location("unfreeze")? },
+        "freezes" => Command::Freezes,
+        "search" => {
+            let word = words.next()
+                .ok_or("search requires a value, all, changed, unchanged, increased, decreased or reset")?;
+            let filter = match word {
+                "reset" => return Ok(Command::SearchReset),
+                "all" => SearchFilter::Any,
+                "changed" => SearchFilter::Changed,
+                "unchanged" => SearchFilter::Unchanged,
+                "increased" => SearchFilter::Increased,
+                "decreased" => SearchFilter::Decreased,
+                value => SearchFilter::Equal(parse_byte(value)?),
+            };
+            Command::Search { filter }
+        },
+        _ => return Err(format!("Unknown command {}", name)),
+    };
+    Ok(command)
+}
+
+fn parse_location(word: &str) -> Location {
+    if let Some(hex) = word.strip_prefix("0x") {
+        if let Ok(addr) = usize::from_str_radix(hex, 16) {
+            return Location::Addr(addr);
+        }
+    }
+    if let Some(line) = word.strip_prefix("line:").and_then(|line| line.parse().ok()) {
+        return Location::Line { line };
+    }
+    match word.parse() {
+        Ok(addr) => Location::Addr(addr),
+        Err(_) => Location::Symbol(word.to_string()),
+    }
+}
+
+/// Parses the remaining words, so that `[I + 2]` may contain spaces.
+fn parse_watch_expr<'a>(words: impl Iterator<Item = &'a str>, what: &str) -> Result<WatchExpr, String> {
+    let expr: String = words.collect();
+    if expr.is_empty() {
+        return Err(format!("{} requires an expression", what));
+    }
+    expr.parse()
+}
+
+/// Parses a range of addresses like `0x300..0x310`, without the end.
+fn parse_range(word: &str) -> Result<(usize, usize), String> {
+    let addr = |word: &str| match parse_location(word) {
+        Location::Addr(addr) => Ok(addr),
+        _ => Err(format!("Expected an address, got {}", word)),
+    };
+    let (start, end) = word.split_once("..").ok_or(format!("Expected a range like 0x300..0x310, got {}", word))?;
+    Ok((addr(start)?, addr(end)?))
+}
+
+/// Parses a byte in hex with `0x` prefix or decimal.
+fn parse_byte(word: &str) -> Result<u8, String> {
+    let value = match word.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    value.map_err(|_| format!("Expected a value from 0 to 255, got {}", word))
+}
+
+fn parse_count(word: Option<&str>, default: usize) -> Result<usize, String> {
+    word.map_or(Ok(default), |word| word.parse().map_err(|_| format!("Expected a number, got {}", word)))
+}
+
+/// Formats `reply` for humans. `pc` is marked in disassemblies.
+pub fn format_reply(reply: &Reply, pc: usize) -> String {
+    match reply {
+        Reply::Ok => String::from("Ok"),
+        Reply::Breakpoints { addrs, i_ranges } if addrs.is_empty() && i_ranges.is_empty() => {
+            String::from("No breakpoints")
+        },
+        Reply::Breakpoints { addrs, i_ranges } => {
+            let breakpoints = addrs.iter().map(|addr| format!("{:#05X}", addr));
+            let watchpoints = i_ranges.iter().map(|range| format!("I in {:#05X}..{:#05X}", range.start, range.end));
+            breakpoints.chain(watchpoints).collect::<Vec<_>>().join("\n")
+        },
+        Reply::Registers(registers) => registers.to_string(),
+        Reply::Memory { addr, bytes } => {
+            bytes.chunks(MEM_LEN).enumerate()
+                .map(|(n, chunk)| {
+                    let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    format!("{:#05X}: {}", addr + n * MEM_LEN, bytes.join(" "))
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        Reply::Backtrace { frames } => {
+            frames.iter().enumerate()
+                .map(|(n, frame)| format!("#{} {:#05X} {}", n, frame.addr, frame.location))
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        Reply::Where { pc, line: Some(line), source: Some(source) } => {
+            format!("PC={:#05X}, line {}: {}", pc, line, source.trim())
+        },
+        Reply::Where { pc, .. } => format!("PC={:#05X}", pc),
+        Reply::Disassembly { instructions } => {
+            instructions.iter()
+                .map(|instruction| {
+                    let marker = if instruction.addr == pc { "=>" } else { "  " };
+                    let mnemonic = instruction.mnemonic.as_deref().unwrap_or("(illegal)");
+                    format!("{} {:#05X}: {:04X}    {}", marker, instruction.addr, instruction.opcode, mnemonic)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        Reply::Sprite { bytes, .. } if bytes.is_empty() => String::from("No bytes"),
+        Reply::Sprite { addr, bytes, height } => sprite_art(*addr, bytes, *height),
+        Reply::Watches { values } if values.is_empty() => String::from("No watch expressions"),
+        Reply::Watches { values } => {
+            values.iter()
+                .map(|watch| {
+                    let marker = if watch.changed { "*" } else { " " };
+                    let value = match watch.value {
+                        Some(value) if watch.expr.is_addr() => format!("{:#05X}", value),
+                        Some(value) => format!("{:#04X}", value),
+                        None => String::from("out of bounds"),
+                    };
+                    format!("{} {} = {}", marker, watch.expr, value)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        Reply::Freezes { freezes } if freezes.is_empty() => String::from("No frozen addresses"),
+        Reply::Freezes { freezes } => {
+            freezes.iter()
+                .map(|freeze| {
+                    let name = if freeze.name.is_empty() { String::new() } else { format!(" ({})", freeze.name) };
+                    format!("[{:#05X}] = {:#04X}{}", freeze.addr, freeze.value, name)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        Reply::SearchMatches { matches } if matches.is_empty() => String::from("No matches"),
+        Reply::SearchMatches { matches } => {
+            let plural = if matches.len() == 1 { "" } else { "es" };
+            let mut lines = vec![format!("{} match{}", matches.len(), plural)];
+            let shown = matches.iter().take(SEARCH_SHOWN);
+            lines.extend(shown.map(|found| format!("[{:#05X}] = {:#04X}", found.addr, found.value)));
+            if matches.len() > SEARCH_SHOWN {
+                lines.push(format!("... and {} more", matches.len() - SEARCH_SHOWN));
+            }
+            lines.join("\n")
+        },
+        Reply::Error { message } => format!("Error: {}", message),
+    }
+}
+
+/// Draws `bytes` as sprites of `height` rows next to each other, each labeled with its address.
+fn sprite_art(addr: usize, bytes: &[u8], height: usize) -> String {
+    let sprites: Vec<(usize, &[u8])> = bytes.chunks(height).enumerate()
+        .map(|(n, rows)| (addr + n * height, rows))
+        .collect();
+    sprites.chunks(SPRITES_PER_LINE)
+        .map(|sprites| {
+            // Sprites are 8 pixels wide, with 2 columns between them
+            let labels: Vec<String> = sprites.iter()
+                .map(|(addr, _)| format!("{:<8}", format!("{:#05X}", addr)))
+                .collect();
+            let mut lines = vec![labels.join("  ")];
+            for y in 0..height {
+                let rows: Vec<String> = sprites.iter()
+                    .map(|(_, rows)| match rows.get(y) {
+                        Some(row) => (0..8).rev().map(|x| if (row >> x) & 1 == 1 { '█' } else { '.' }).collect(),
+                        None => " ".repeat(8),
+                    })
+                    .collect();
+                lines.push(rows.join("  "));
+            }
+            lines.iter().map(|line| line.trim_end()).collect::<Vec<_>>().join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Debugs `chip8`, which is paused before its next instruction, with the commands of the startup `script` and then
+/// with commands from stdin until `quit` or the end of input. `step` executes one instruction and `continue` runs
+/// until the next breakpoint or error, as fast as possible and without showing the display. The execution is
+/// recorded, so that `step-back` and `seek` can undo instructions.
+pub fn debug(chip8: &mut Chip8, debugger: &mut Debugger, script: &[String]) -> io::Result<()> {
+    let rewind = Rewind::new(chip8);
+    time_travel(chip8, debugger, rewind, script)
+}
+
+/// Like [`debug`], but continues the `rewind` recording of the execution so far, e.g. of a whole session, so that
+/// `seek` can go to any recorded instruction.
+pub fn time_travel(chip8: &mut Chip8, debugger: &mut Debugger, mut rewind: Rewind, script: &[String])
+    -> io::Result<()>
+{
+    repl(script, |command| {
+        let mut note = None;
+        let reply = match command {
+            Command::Step => match chip8.step() {
+                Ok(events) => {
+                    if events.contains(StepEvent::WaitingForKey) {
+                        note = Some(String::from("Waiting for a key press"));
+                    }
+                    debugger.apply_freezes(chip8);
+                    rewind.record(chip8);
+                    debugger.execute(chip8, Command::Where)
+                },
+                Err(err) => Reply::Error { message: err.to_string() },
+            },
+            Command::StepBack => match rewind.step_back(chip8) {
+                Ok(true) => debugger.execute(chip8, Command::Where),
+                Ok(false) => Reply::Error { message: String::from("Reached the start of the recording") },
+                Err(err) => Reply::Error { message: err.to_string() },
+            },
+            Command::Seek { position } => match rewind.seek(chip8, position) {
+                Ok(true) => debugger.execute(chip8, Command::Where),
+                Ok(false) => Reply::Error {
+                    message: format!("Instructions {} to {} are recorded", rewind.start(), rewind.end()),
+                },
+                Err(err) => Reply::Error { message: err.to_string() },
+            },
+            Command::Continue => loop {
+                if let Err(err) = chip8.step() {
+                    break Reply::Error { message: err.to_string() };
+                }
+                debugger.apply_freezes(chip8);
+                rewind.record(chip8);
+                if let Some(reason) = debugger.check(chip8) {
+                    if reason == PauseReason::Watchpoint {
+                        let &(pc, _) = chip8.history.back().expect("An instruction was executed");
+                        let i = chip8.address_register;
+                        note = Some(format!("I={:#05X}, set by the instruction at {:#05X}", i, pc));
+                    }
+                    break debugger.execute(chip8, Command::Where);
+                }
+            },
+            Command::Pause => Reply::Error { message: String::from("Already paused") },
+            command => return format_reply(&debugger.execute(chip8, command), chip8.pc),
+        };
+        let mut output = note.map(|note| note + "\n").unwrap_or_default();
+        output.push_str(&format_reply(&reply, chip8.pc));
+        if !matches!(reply, Reply::Error { .. }) {
+            output.push_str(&format!(" (instruction {} of {})", rewind.position(), rewind.end()));
+        }
+        let watches = debugger.watch_values(chip8);
+        if !watches.is_empty() {
+            output.push('\n');
+            output.push_str(&format_reply(&Reply::Watches { values: watches }, chip8.pc));
+        }
+        output
+    })
+}
+
+/// Inspects `chip8` without executing anything, e.g. after loading a core dump. Executes the startup `script`, then
+/// reads commands from stdin until `quit` or the end of input. Commands that would resume execution are rejected.
+pub fn post_mortem(chip8: &Chip8, debugger: &mut Debugger, script: &[String]) -> io::Result<()> {
+    repl(script, |command| {
+        let reply = match command {
+            Command::Step | Command::StepBack | Command::Seek { .. } | Command::Continue | Command::Pause => {
+                Reply::Error { message: String::from("The machine is frozen, it can only be inspected") }
+            },
+            command => debugger.execute(chip8, command),
+        };
+        format_reply(&reply, chip8.pc)
+    })
+}
+
+/// Executes the lines of `script`, then reads commands from stdin until `quit` or the end of input, and prints what
+/// `execute` returns for them.
+fn repl(script: &[String], mut execute: impl FnMut(Command) -> String) -> io::Result<()> {
+    let mut macros = Macros::default();
+    for line in script {
+        let line = line.split('#').next().unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
+        println!("(chip8) {}", line.trim());
+        if macros.run_line(line, &mut execute, 0).is_break() {
+            return Ok(());
+        }
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if macros.run_line(&line, &mut execute, 0).is_break() {
+            return Ok(());
+        }
+    }
+}
+
+/// Recorded command sequences of a session.
+#[derive(Debug, Default)]
+struct Macros {
+    defined: BTreeMap<String, Vec<String>>,
+    /// Name and lines of the macro being recorded.
+    recording: Option<(String, Vec<String>)>,
+}
+
+impl Macros {
+    /// Executes `line`, which is either a macro command or a debugger command, and prints the result. `depth` is the
+    /// number of macros running. Breaks on `quit`.
+    fn run_line(&mut self, line: &str, execute: &mut impl FnMut(Command) -> String, depth: usize) -> ControlFlow<()> {
+        let line = line.trim();
+        if matches!(line, "quit" | "q") {
+            return ControlFlow::Break(());
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let output = match words.as_slice() {
+            ["macro", "record", name] => match &self.recording {
+                Some((recording, _)) => format!("Error: Already recording macro {}", recording),
+                None => {
+                    self.recording = Some((name.to_string(), Vec::new()));
+                    format!("Recording macro {}, stop with `macro end`", name)
+                },
+            },
+            ["macro", "end"] => match self.recording.take() {
+                Some((name, lines)) => {
+                    let output = format!("Recorded macro {} with {} commands", name, lines.len());
+                    self.defined.insert(name, lines);
+                    output
+                },
+                None => String::from("Error: Not recording a macro"),
+            },
+            ["macro", "list"] if self.defined.is_empty() => String::from("No macros"),
+            ["macro", "list"] => {
+                self.defined.iter()
+                    .map(|(name, lines)| format!("{}: {}", name, lines.join("; ")))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+            ["macro", "run", name] => {
+                let lines = match self.defined.get(*name) {
+                    Some(lines) => lines.clone(),
+                    None => {
+                        println!("Error: Unknown macro {}", name);
+                        return ControlFlow::Continue(());
+                    },
+                };
+                if depth >= MAX_MACRO_DEPTH {
+                    println!("Error: Macros are nested too deeply");
+                    return ControlFlow::Continue(());
+                }
+                self.record(line);
+                for line in lines {
+                    self.run_line(&line, execute, depth + 1)?;
+                }
+                return ControlFlow::Continue(());
+            },
+            ["macro", ..] => {
+                String::from("Error: Expected macro record <name>, macro end, macro run <name> or macro list")
+            },
+            _ => match parse_command(line) {
+                Ok(command) => {
+                    self.record(line);
+                    execute(command)
+                },
+                Err(message) => format_reply(&Reply::Error { message }, 0),
+            },
+        };
+        println!("{}", output);
+        ControlFlow::Continue(())
+    }
+
+    /// Adds `line` to the macro being recorded, if any.
+    fn record(&mut self, line: &str) {
+        if let Some((_, lines)) = &mut self.recording {
+            lines.push(line.to_string());
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Reports of what the machine looked like when the emulation failed, because an error message like "Encountered
+//! illegal instruction at PC=634" alone rarely tells how the program got there.
+
+use std::fmt;
+use crate::debugger::RegisterDump;
+use crate::disassembler;
+use crate::{Chip8, Chip8Error, Framebuffer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    pub error: Chip8Error,
+    pub registers: RegisterDump,
+    /// PC and opcode of the last executed instructions, oldest first. The last one is usually the one that failed.
+    pub history: Vec<(usize, u16)>,
+    /// What the program showed last, which often tells where it was.
+    pub display: Framebuffer,
+}
+
+impl CrashReport {
+    /// Captures the state of `chip8` right after it failed with `error`.
+    pub fn new(chip8: &Chip8, error: Chip8Error) -> Self {
+        Self {
+            error,
+            registers: RegisterDump::of(chip8),
+            history: chip8.history.iter().copied().collect(),
+            display: *chip8.framebuffer(),
+        }
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Error: {}", self.error)?;
+        writeln!(f, "{}", self.registers)?;
+        write!(f, "Last instructions:")?;
+        for &(pc, opcode) in &self.history {
+            let mnemonic = disassembler::mnemonic(opcode).unwrap_or_else(|| String::from("(illegal)"));
+            write!(f, "\n  {:#05X}: {:04X}    {}", pc, opcode, mnemonic)?;
+        }
+        write!(f, "\nDisplay:\n{}", self.display)
+    }
+}
@@ -0,0 +1,126 @@
+//! Metadata about known ROMs from the [CHIP-8 database](https://github.com/chip-8/chip-8-database). Load its
+//! `programs.json` with [`RomDatabase::load`], then look up ROMs by their content to get their title, authors and
+//! the settings they need to run correctly.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::{rom, Chip8, Quirks};
+
+#[derive(Debug, serde::Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    release: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// ROM versions of the program by their SHA-1 hash.
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    /// Instructions per frame.
+    #[serde(default)]
+    tickrate: Option<u32>,
+    /// Platforms the ROM runs on, most suitable first.
+    #[serde(default)]
+    platforms: Vec<String>,
+    /// Quirks that differ from the default quirks of the platform.
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+}
+
+/// What the database knows about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub description: Option<String>,
+    /// Recommended number of instructions per frame.
+    pub tickrate: Option<u32>,
+    /// Recommended quirks, if the ROM targets a known platform.
+    pub quirks: Option<Quirks>,
+}
+
+impl RomInfo {
+    /// Applies the recommended settings to `chip8`.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        if let Some(tickrate) = self.tickrate {
+            chip8.set_instructions_per_frame(tickrate);
+        }
+        if let Some(quirks) = self.quirks {
+            chip8.set_quirks(quirks);
+        }
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.title)?;
+        if !self.authors.is_empty() {
+            write!(f, " by {}", self.authors.join(", "))?;
+        }
+        if let Some(release) = &self.release {
+            write!(f, " ({})", release)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    /// Index into `programs` by SHA-1 hash of the ROM.
+    by_hash: HashMap<String, usize>,
+}
+
+impl RomDatabase {
+    /// Loads the `programs.json` file of the database.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Self::from_json(&json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        let by_hash = programs.iter().enumerate()
+            .flat_map(|(i, program)| program.roms.keys().map(move |hash| (hash.to_ascii_lowercase(), i)))
+            .collect();
+        Ok(Self { programs, by_hash })
+    }
+
+    /// Looks up the ROM by its content.
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        self.lookup_sha1(&rom::sha1(rom))
+    }
+
+    /// Looks up a ROM by its SHA-1 hash, e.g. from [`rom::RomId`].
+    pub fn lookup_sha1(&self, hash: &str) -> Option<RomInfo> {
+        let hash = hash.to_ascii_lowercase();
+        let program = &self.programs[*self.by_hash.get(&hash)?];
+        let rom = program.roms.iter().find(|(key, _)| key.eq_ignore_ascii_case(&hash))?.1;
+        let quirks = rom.platforms.first().and_then(|platform| {
+            let mut quirks = Quirks::for_platform(platform)?;
+            for (name, &enabled) in rom.quirky_platforms.get(platform).into_iter().flatten() {
+                quirks.set(name, enabled);
+            }
+            Some(quirks)
+        });
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            release: program.release.clone(),
+            description: program.description.clone(),
+            tickrate: rom.tickrate,
+            quirks,
+        })
+    }
+}
@@ -0,0 +1,562 @@
+//! Transport independent debugger core. Frontends (like the WebSocket server in [`crate::remote`]) translate their
+//! requests into [`Command`]s and present the returned [`Reply`]s.
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use crate::assembler::SourceMap;
+use crate::cheats::{CheatTable, Every, Freeze, MemorySearch, SearchFilter, SearchMatch};
+use crate::disassembler;
+use crate::symbols::Symbols;
+use crate::Chip8;
+
+/// An operation requested by the user of the debugger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Deserialize))]
+#[cfg_attr(feature = "websocket", serde(tag = "cmd", rename_all = "snake_case"))]
+pub enum Command {
+    /// Execute a single instruction, then pause again.
+    Step,
+    /// Go back to the state before the last executed instruction. Only frontends recording the execution with a
+    /// [`crate::rewind::Rewind`] support this, like [`Command::Seek`].
+    StepBack,
+    /// Go to the state after `position` instructions, counted from the start of the recording.
+    Seek { position: u64 },
+    /// Resume execution until the next breakpoint.
+    Continue,
+    /// Pause execution before the next instruction.
+    Pause,
+    /// Set a breakpoint at `addr`.
+    Break { addr: Location },
+    /// Remove the breakpoint at `addr`.
+    Delete { addr: Location },
+    /// Pause whenever an instruction sets I to an address in `start..end`, or increments it into the range.
+    WatchI { start: usize, end: usize },
+    /// Remove the watchpoint on I for `start..end`.
+    UnwatchI { start: usize, end: usize },
+    /// List all breakpoints and watchpoints.
+    Breakpoints,
+    /// Read all registers.
+    Regs,
+    /// Read `len` bytes of memory starting at `addr`.
+    Mem { addr: usize, len: usize },
+    /// Show the call stack with symbol names.
+    Backtrace,
+    /// Show the current PC and the source line it was assembled from.
+    Where,
+    /// Decode `count` instructions starting at `addr`, or at the PC if `addr` is missing.
+    Disasm { addr: Option<Location>, count: usize },
+    /// Read `len` bytes starting at `addr`, or at I if `addr` is missing, to show them as sprites of `height` rows like
+    /// DXYN draws them.
+    Sprite { addr: Option<Location>, len: usize, height: usize },
+    /// Show the value of `expr` whenever execution pauses.
+    Watch { expr: WatchExpr },
+    /// Stop showing the value of `expr`.
+    Unwatch { expr: WatchExpr },
+    /// Evaluate all watch expressions.
+    Watches,
+    /// Write `value` to `addr` after every instruction, until unfrozen.
+    Freeze { addr: Location, value: u8 },
+    /// Stop writing to `addr`.
+    Unfreeze { addr: Location },
+    /// List all frozen addresses.
+    Freezes,
+    /// Narrow down the addresses of the memory search to those matching `filter`, or start a search.
+    Search { filter: SearchFilter },
+    /// Forget the addresses found by the memory search.
+    SearchReset,
+}
+
+/// A value the debugger shows whenever execution pauses, written like `V3`, `I`, `PC`, `SP`, `DT`, `ST`, `[0x300]`
+/// (the byte at an address) or `[I+2]` (the byte at I plus an offset, following I as it changes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "websocket", serde(try_from = "String", into = "String"))]
+pub enum WatchExpr {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Mem(usize),
+    MemAtI(usize),
+}
+
+impl WatchExpr {
+    /// The current value, or `None` if it's a byte outside of memory.
+    pub fn eval(&self, chip8: &Chip8) -> Option<u16> {
+        let addr = match *self {
+            Self::V(x) => return Some(chip8.registers[x as usize].into()),
+            Self::I => return Some(chip8.address_register),
+            Self::Pc => return Some(chip8.pc as u16),
+            Self::Sp => return Some(chip8.stack_pointer.into()),
+            Self::DelayTimer => return Some(chip8.delay_timer.into()),
+            Self::SoundTimer => return Some(chip8.sound_timer.into()),
+            Self::Mem(addr) => addr,
+            Self::MemAtI(offset) => chip8.address_register as usize + offset,
+        };
+        (addr < chip8.bus.size()).then(|| chip8.bus.peek(addr).into())
+    }
+
+    /// Whether the value is an address, as opposed to a byte.
+    pub fn is_addr(&self) -> bool {
+        matches!(self, Self::I | Self::Pc)
+    }
+}
+
+impl fmt::Display for WatchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V(x) => write!(f, "V{:X}", x),
+            Self::I => write!(f, "I"),
+            Self::Pc => write!(f, "PC"),
+            Self::Sp => write!(f, "SP"),
+            Self::DelayTimer => write!(f, "DT"),
+            Self::SoundTimer => write!(f, "ST"),
+            Self::Mem(addr) => write!(f, "[{:#05X}]", addr),
+            Self::MemAtI(0) => write!(f, "[I]"),
+            Self::MemAtI(offset) => write!(f, "[I+{}]", offset),
+        }
+    }
+}
+
+impl FromStr for WatchExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr: String = s.split_whitespace().collect::<String>().to_ascii_uppercase();
+        let unknown = || format!("Unknown watch expression {}, expected e.g. V3, I, DT, [0x300] or [I+2]", s);
+        let expr = match expr.as_str() {
+            "I" => Self::I,
+            "PC" => Self::Pc,
+            "SP" => Self::Sp,
+            "DT" => Self::DelayTimer,
+            "ST" => Self::SoundTimer,
+            "[I]" => Self::MemAtI(0),
+            _ => {
+                if let Some(x) = expr.strip_prefix('V') {
+                    return u8::from_str_radix(x, 16).ok().filter(|&x| x < 16).map(Self::V).ok_or_else(unknown);
+                }
+                let addr = expr.strip_prefix('[').and_then(|expr| expr.strip_suffix(']')).ok_or_else(unknown)?;
+                match addr.strip_prefix("I+") {
+                    Some(offset) => Self::MemAtI(parse_number(offset).ok_or_else(unknown)?),
+                    None => Self::Mem(parse_number(addr).ok_or_else(unknown)?),
+                }
+            },
+        };
+        Ok(expr)
+    }
+}
+
+impl TryFrom<String> for WatchExpr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<WatchExpr> for String {
+    fn from(expr: WatchExpr) -> Self {
+        expr.to_string()
+    }
+}
+
+/// Parses hex with `0X` prefix (the expression is uppercase) or decimal.
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0X") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// An address in a command, given as number, as name of a symbol or as source line (like `{"line":42}` in JSON).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Deserialize))]
+#[cfg_attr(feature = "websocket", serde(untagged))]
+pub enum Location {
+    Addr(usize),
+    Symbol(String),
+    Line { line: usize },
+}
+
+impl From<usize> for Location {
+    fn from(addr: usize) -> Self {
+        Self::Addr(addr)
+    }
+}
+
+/// The answer of the debugger to a [`Command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+#[cfg_attr(feature = "websocket", serde(tag = "reply", rename_all = "snake_case"))]
+pub enum Reply {
+    Ok,
+    Breakpoints {
+        addrs: Vec<usize>,
+        /// Ranges of the watchpoints on I.
+        i_ranges: Vec<Range<usize>>,
+    },
+    Registers(RegisterDump),
+    Memory { addr: usize, bytes: Vec<u8> },
+    Backtrace { frames: Vec<Frame> },
+    Where {
+        pc: usize,
+        /// Source line of the instruction at the PC, if the program was assembled from source.
+        line: Option<usize>,
+        source: Option<String>,
+    },
+    Disassembly { instructions: Vec<Instruction> },
+    /// Sprites of `height` rows, one byte per row, the last one may be shorter.
+    Sprite { addr: usize, bytes: Vec<u8>, height: usize },
+    Watches { values: Vec<WatchValue> },
+    Freezes { freezes: Vec<Freeze> },
+    /// The addresses found by the memory search so far.
+    SearchMatches { matches: Vec<SearchMatch> },
+    Error { message: String },
+}
+
+/// The value of a [`WatchExpr`] when execution paused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct WatchValue {
+    pub expr: WatchExpr,
+    /// `None` if the expression refers to a byte outside of memory.
+    pub value: Option<u16>,
+    /// Whether the value differs from the one shown before.
+    pub changed: bool,
+}
+
+/// A decoded instruction in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct Instruction {
+    pub addr: usize,
+    pub opcode: u16,
+    /// Like `LD V1, 0x05`, or `None` if the opcode is illegal.
+    pub mnemonic: Option<String>,
+}
+
+/// Snapshot of all registers of the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct RegisterDump {
+    pub pc: usize,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub sp: u8,
+    /// Return addresses, innermost call last.
+    pub stack: Vec<usize>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl RegisterDump {
+    pub fn of(chip8: &Chip8) -> Self {
+        Self {
+            pc: chip8.pc,
+            i: chip8.address_register,
+            v: chip8.registers,
+            sp: chip8.stack_pointer,
+            stack: chip8.stack[1..=chip8.stack_pointer as usize].to_vec(),
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+        }
+    }
+}
+
+/// Formats the registers for humans, e.g. `PC=0x20A I=0x300 SP=1 DT=0 ST=0` followed by the V registers and the
+/// stack on their own lines.
+impl fmt::Display for RegisterDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f, "PC={:#05X} I={:#05X} SP={} DT={} ST={}",
+            self.pc, self.i, self.sp, self.delay_timer, self.sound_timer
+        )?;
+        let v: Vec<String> = self.v.iter().enumerate().map(|(n, value)| format!("V{:X}={:02X}", n, value)).collect();
+        writeln!(f, "{}", v.join(" "))?;
+        let stack: Vec<String> = self.stack.iter().map(|addr| format!("{:#05X}", addr)).collect();
+        write!(f, "Stack: {}", stack.join(" "))
+    }
+}
+
+/// A function on the call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+pub struct Frame {
+    /// The current PC for the innermost frame, otherwise the return address into the frame.
+    pub addr: usize,
+    /// `addr` relative to the closest symbol, like `main_loop+0x4`.
+    pub location: String,
+}
+
+/// Why the debugger paused execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "websocket", derive(serde::Serialize))]
+#[cfg_attr(feature = "websocket", serde(rename_all = "snake_case"))]
+pub enum PauseReason {
+    Breakpoint,
+    /// I was set to an address in the range of a watchpoint.
+    Watchpoint,
+    Step,
+    Pause,
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    i_watchpoints: Vec<Range<usize>>,
+    /// The value of I when the debugger was last checked, to notice changes.
+    last_i: Option<u16>,
+    symbols: Symbols,
+    source_map: SourceMap,
+    /// Pause before the next instruction, because of a step or pause command.
+    pause_requested: Option<PauseReason>,
+    paused: bool,
+    /// Watch expressions with the value shown last.
+    watches: Vec<(WatchExpr, Option<u16>)>,
+    cheats: CheatTable,
+    search: MemorySearch,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `symbols` for breakpoints by name and in backtraces.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Uses `source_map` to set breakpoints by source line and to show the current line.
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = source_map;
+    }
+
+    /// Freezes the addresses of `cheats`, in addition to the ones frozen by [`Command::Freeze`].
+    pub fn set_cheats(&mut self, cheats: CheatTable) {
+        self.cheats = cheats;
+    }
+
+    pub fn cheats(&self) -> &CheatTable {
+        &self.cheats
+    }
+
+    /// Writes the frozen values into the memory of `chip8`. Frontends call it after every instruction, also for
+    /// freezes of cheat tables that are written only once per frame otherwise, so that they hold while stepping.
+    pub fn apply_freezes(&self, chip8: &mut Chip8) {
+        self.cheats.apply_all(chip8);
+    }
+
+    /// The number and text of the source line the instruction at `addr` was assembled from.
+    pub fn source_line(&self, addr: usize) -> Option<(usize, &str)> {
+        self.source_map.line(addr)
+    }
+
+    /// Addresses of the breakpoints in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Evaluates the watch expressions and marks the values that changed since the last evaluation.
+    pub fn watch_values(&mut self, chip8: &Chip8) -> Vec<WatchValue> {
+        self.watches.iter_mut()
+            .map(|(expr, last)| {
+                let value = expr.eval(chip8);
+                let changed = value != *last;
+                *last = value;
+                WatchValue { expr: *expr, value, changed }
+            })
+            .collect()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Checks whether execution should pause before executing the next instruction of `chip8`. Has to be called
+    /// after every instruction to notice all changes of I. If execution should pause, the debugger is paused until a
+    /// [`Command::Step`] or [`Command::Continue`] is executed.
+    pub fn check(&mut self, chip8: &Chip8) -> Option<PauseReason> {
+        let i_watched = self.check_i_watchpoints(chip8);
+        let reason = self.pause_requested.take()
+            .or_else(|| self.breakpoints.contains(&chip8.pc).then_some(PauseReason::Breakpoint))
+            .or_else(|| i_watched.then_some(PauseReason::Watchpoint));
+        self.paused = reason.is_some();
+        reason
+    }
+
+    /// Whether the last instruction set I into the range of a watchpoint. `ANNN` counts even if I doesn't change.
+    fn check_i_watchpoints(&mut self, chip8: &Chip8) -> bool {
+        let i = chip8.address_register;
+        let changed = self.last_i.replace(i) != Some(i)
+            || chip8.history.back().is_some_and(|&(_, opcode)| opcode & 0xF000 == 0xA000);
+        changed && self.i_watchpoints.iter().any(|range| range.contains(&(i as usize)))
+    }
+
+    pub fn execute(&mut self, chip8: &Chip8, command: Command) -> Reply {
+        match command {
+            Command::Step => {
+                self.paused = false;
+                self.pause_requested = Some(PauseReason::Step);
+            },
+            Command::StepBack | Command::Seek { .. } => {
+                return Reply::Error { message: String::from("Time travel isn't supported here") };
+            },
+            Command::Continue => self.paused = false,
+            Command::Pause => self.pause_requested = Some(PauseReason::Pause),
+            Command::Break { addr } => match self.resolve(&addr) {
+                Ok(addr) => {
+                    self.breakpoints.insert(addr);
+                },
+                Err(reply) => return reply,
+            },
+            Command::Delete { addr } => {
+                let addr = match self.resolve(&addr) {
+                    Ok(addr) => addr,
+                    Err(reply) => return reply,
+                };
+                if !self.breakpoints.remove(&addr) {
+                    return Reply::Error { message: format!("No breakpoint at {:#X}", addr) };
+                }
+            },
+            Command::WatchI { start, end } => {
+                if start >= end {
+                    return Reply::Error { message: format!("The range {:#X}..{:#X} is empty", start, end) };
+                }
+                if !self.i_watchpoints.contains(&(start..end)) {
+                    self.i_watchpoints.push(start..end);
+                }
+            },
+            Command::UnwatchI { start, end } => {
+                let len = self.i_watchpoints.len();
+                self.i_watchpoints.retain(|range| *range != (start..end));
+                if self.i_watchpoints.len() == len {
+                    return Reply::Error { message: format!("No watchpoint on I for {:#X}..{:#X}", start, end) };
+                }
+            },
+            Command::Breakpoints => {
+                return Reply::Breakpoints {
+                    addrs: self.breakpoints.iter().copied().collect(),
+                    i_ranges: self.i_watchpoints.clone(),
+                };
+            },
+            Command::Regs => return Reply::Registers(RegisterDump::of(chip8)),
+            Command::Mem { addr, len } => {
+                return match addr.checked_add(len).filter(|&end| end <= chip8.bus.size()) {
+                    Some(end) => Reply::Memory { addr, bytes: (addr..end).map(|addr| chip8.bus.peek(addr)).collect() },
+                    None => Reply::Error { message: format!("Memory range {:#X}+{} is out of bounds", addr, len) },
+                };
+            },
+            Command::Backtrace => return Reply::Backtrace { frames: self.backtrace(chip8) },
+            Command::Where => {
+                let line = self.source_line(chip8.pc);
+                return Reply::Where {
+                    pc: chip8.pc,
+                    line: line.map(|(line, _)| line),
+                    source: line.map(|(_, source)| source.to_string()),
+                };
+            },
+            Command::Disasm { addr, count } => {
+                let start = match addr.map(|addr| self.resolve(&addr)).unwrap_or(Ok(chip8.pc)) {
+                    Ok(start) => start,
+                    Err(reply) => return reply,
+                };
+                let instructions = (0..count)
+                    .map(|n| start + 2 * n)
+                    .take_while(|&addr| addr + 1 < chip8.bus.size())
+                    .map(|addr| {
+                        let opcode = u16::from_be_bytes([chip8.bus.peek(addr), chip8.bus.peek(addr + 1)]);
+                        Instruction { addr, opcode, mnemonic: disassembler::mnemonic(opcode) }
+                    })
+                    .collect();
+                return Reply::Disassembly { instructions };
+            },
+            Command::Sprite { addr, len, height } => {
+                let addr = match addr.map(|addr| self.resolve(&addr)) {
+                    Some(Ok(addr)) => addr,
+                    Some(Err(reply)) => return reply,
+                    None => chip8.address_register as usize,
+                };
+                if height == 0 {
+                    return Reply::Error { message: String::from("Sprites are at least one row high") };
+                }
+                return match addr.checked_add(len).filter(|&end| end <= chip8.bus.size()) {
+                    Some(end) => {
+                        let bytes = (addr..end).map(|addr| chip8.bus.peek(addr)).collect();
+                        Reply::Sprite { addr, bytes, height }
+                    },
+                    None => Reply::Error { message: format!("Memory range {:#X}+{} is out of bounds", addr, len) },
+                };
+            },
+            Command::Watch { expr } => {
+                if let WatchExpr::Mem(addr) = expr {
+                    if addr >= chip8.bus.size() {
+                        return Reply::Error { message: format!("Address {:#X} is out of bounds", addr) };
+                    }
+                }
+                if !self.watches.iter().any(|&(watch, _)| watch == expr) {
+                    self.watches.push((expr, expr.eval(chip8)));
+                }
+            },
+            Command::Unwatch { expr } => {
+                let len = self.watches.len();
+                self.watches.retain(|&(watch, _)| watch != expr);
+                if self.watches.len() == len {
+                    return Reply::Error { message: format!("Not watching {}", expr) };
+                }
+            },
+            Command::Watches => return Reply::Watches { values: self.watch_values(chip8) },
+            Command::Freeze { addr, value } => {
+                let addr = match self.resolve(&addr) {
+                    Ok(addr) => addr,
+                    Err(reply) => return reply,
+                };
+                if addr >= chip8.bus.size() {
+                    return Reply::Error { message: format!("Address {:#X} is out of bounds", addr) };
+                }
+                self.cheats.freeze(Freeze { name: String::new(), addr, value, every: Every::Instruction });
+            },
+            Command::Unfreeze { addr } => {
+                let addr = match self.resolve(&addr) {
+                    Ok(addr) => addr,
+                    Err(reply) => return reply,
+                };
+                if !self.cheats.unfreeze(addr) {
+                    return Reply::Error { message: format!("{:#X} isn't frozen", addr) };
+                }
+            },
+            Command::Freezes => return Reply::Freezes { freezes: self.cheats.freezes().to_vec() },
+            Command::Search { filter } => {
+                return match self.search.search(chip8, filter) {
+                    Ok(matches) => Reply::SearchMatches { matches: matches.to_vec() },
+                    Err(message) => Reply::Error { message },
+                };
+            },
+            Command::SearchReset => self.search.reset(),
+        }
+        Reply::Ok
+    }
+
+    fn resolve(&self, location: &Location) -> Result<usize, Reply> {
+        match location {
+            Location::Addr(addr) => Ok(*addr),
+            Location::Symbol(name) => self.symbols.addr(name)
+                .ok_or_else(|| Reply::Error { message: format!("Unknown symbol {}", name) }),
+            Location::Line { line } => self.source_map.addr(*line)
+                .ok_or_else(|| Reply::Error { message: format!("No code at or after line {}", line) }),
+        }
+    }
+
+    /// The call stack, innermost frame first.
+    fn backtrace(&self, chip8: &Chip8) -> Vec<Frame> {
+        let return_addrs = chip8.stack[1..=chip8.stack_pointer as usize].iter().rev();
+        std::iter::once(&chip8.pc).chain(return_addrs)
+            .map(|&addr| Frame { addr, location: self.symbols.describe(addr) })
+            .collect()
+    }
+}
@@ -0,0 +1,295 @@
+//! Disassembler that follows the control flow from the entry point to tell code from data. Every address reachable
+//! by executing the program (through jumps, calls and both outcomes of skips) is decoded as instruction, everything
+//! else is printed as data bytes. Targets get labels like `sub_242` (calls), `label_20A` (jumps) and `data_300`
+//! (addresses loaded into `I`).
+//!
+//! The disassembly can also be written as Octo source (see [`Syntax::Octo`]), which the [`crate::assembler`]
+//! assembles back into the same ROM.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use crate::chip8::PROGRAM_START;
+use crate::instruction::Instruction;
+use crate::symbols::Symbols;
+
+/// Number of data bytes printed per line.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// How [`Disassembly::format`] writes the disassembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Starts every line with the address.
+    pub addresses: bool,
+    /// Shows the opcode of every instruction.
+    pub bytes: bool,
+    /// Writes mnemonics in lowercase. Octo source is always lowercase.
+    pub lowercase: bool,
+    pub syntax: Syntax,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { addresses: true, bytes: true, lowercase: false, syntax: Syntax::default() }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Mnemonics like `LD V0, 0x05` and data like `db 0x01, 0x02`.
+    #[default]
+    Mnemonics,
+    /// Octo statements like `v0 := 0x05` and data like `0x01 0x02`. Addresses and opcodes become comments.
+    /// Instructions without an Octo statement, like calls of addresses without a label, are written as bytes.
+    Octo,
+}
+
+/// The result of analyzing a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    rom: Vec<u8>,
+    /// Addresses of reachable instructions.
+    code: BTreeSet<usize>,
+    labels: BTreeMap<usize, String>,
+}
+
+impl Disassembly {
+    /// Traces the control flow of `rom`, which is loaded at [`PROGRAM_START`].
+    pub fn analyze(rom: &[u8]) -> Self {
+        let mut disassembly = Self { rom: rom.to_vec(), code: BTreeSet::new(), labels: BTreeMap::new() };
+        let mut pending = vec![PROGRAM_START];
+        let mut data_refs = BTreeSet::new();
+        while let Some(addr) = pending.pop() {
+            let instruction = match Instruction::fetch(&disassembly.rom, addr) {
+                Some((_, Ok(instruction))) if !disassembly.code.contains(&addr) => instruction,
+                // Illegal instructions are most likely data the program never actually executes
+                _ => continue,
+            };
+            disassembly.code.insert(addr);
+            match instruction {
+                Instruction::Ret => {},
+                Instruction::Jump { addr: target } => {
+                    let target = target as usize;
+                    disassembly.labels.entry(target).or_insert_with(|| format!("label_{:03X}", target));
+                    pending.push(target);
+                },
+                Instruction::Call { addr: target } => {
+                    let target = target as usize;
+                    disassembly.labels.insert(target, format!("sub_{:03X}", target));
+                    pending.extend([target, addr + 2]);
+                },
+                Instruction::SkipEqByte { .. }
+                | Instruction::SkipNeByte { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKey { .. }
+                | Instruction::SkipNotKey { .. } => pending.extend([addr + 2, addr + 4]),
+                Instruction::LoadI { addr: target } => {
+                    data_refs.insert(target as usize);
+                    pending.push(addr + 2);
+                },
+                // The target of `BNNN` depends on V0, so it can't be followed
+                Instruction::JumpV0 { .. } => {},
+                _ => pending.push(addr + 2),
+            }
+        }
+        for addr in data_refs {
+            if !disassembly.code.contains(&addr) {
+                disassembly.labels.entry(addr).or_insert_with(|| format!("data_{:03X}", addr));
+            }
+        }
+        disassembly
+    }
+
+    /// Names addresses after `symbols` instead of the generated labels.
+    pub fn set_symbols(&mut self, symbols: &Symbols) {
+        let end = PROGRAM_START + self.rom.len();
+        for (addr, name) in symbols.iter().filter(|&(addr, _)| (PROGRAM_START..end).contains(&addr)) {
+            self.labels.insert(addr, name.to_string());
+        }
+    }
+
+    /// Whether the instruction at `addr` is reachable.
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code.contains(&addr)
+    }
+
+    /// Address, opcode and instruction of the reachable instructions in ascending order.
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, u16, Instruction)> + '_ {
+        self.code.iter().filter_map(move |&addr| {
+            let (opcode, instruction) = Instruction::fetch(&self.rom, addr)?;
+            Some((addr, opcode, instruction.ok()?))
+        })
+    }
+
+    /// The label at `addr`, if something refers to it.
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    fn opcode_at(&self, addr: usize) -> Option<u16> {
+        Instruction::fetch(&self.rom, addr).map(|(opcode, _)| opcode)
+    }
+
+    /// The mnemonic of `opcode`, with addresses replaced by their labels.
+    fn labeled_mnemonic(&self, opcode: u16, lowercase: bool) -> String {
+        let case = |text: String| if lowercase { text.to_lowercase() } else { text };
+        let mnemonic = case(mnemonic(opcode).unwrap_or_default());
+        let nnn = (opcode & 0x0FFF) as usize;
+        match (opcode & 0xF000, self.label(nnn)) {
+            (0x1000 | 0x2000 | 0xA000, Some(label)) => mnemonic.replace(&case(format!("{:#05X}", nnn)), label),
+            _ => mnemonic,
+        }
+    }
+
+    /// Splits the ROM into lines of the disassembly, either a reachable instruction or a run of data bytes. Returns
+    /// the start and end address of each line.
+    fn lines(&self) -> Vec<(usize, usize)> {
+        let end = PROGRAM_START + self.rom.len();
+        let mut lines = Vec::new();
+        let mut addr = PROGRAM_START;
+        while addr < end {
+            if self.opcode_at(addr).is_some() && self.is_code(addr) {
+                lines.push((addr, addr + 2));
+                addr += 2;
+                continue;
+            }
+            // Data runs until the next instruction or label
+            let data_end = (addr + 1..end)
+                .find(|&next| {
+                    self.is_code(next) || self.labels.contains_key(&next) || next - addr == DATA_BYTES_PER_LINE
+                })
+                .unwrap_or(end);
+            lines.push((addr, data_end));
+            addr = data_end;
+        }
+        lines
+    }
+
+    /// Writes the disassembly as text.
+    pub fn format(&self, options: &FormatOptions) -> String {
+        let lines = self.lines();
+        // Labels can only be referred to if they are defined, i.e. at the start of a line
+        let defined_label = |addr: usize| match lines.binary_search_by_key(&addr, |&(start, _)| start) {
+            Ok(_) => self.label(addr),
+            Err(_) => None,
+        };
+        let mut out = String::new();
+        for &(addr, end) in &lines {
+            if let Some(label) = self.label(addr) {
+                match options.syntax {
+                    Syntax::Mnemonics => out.push_str(&format!("{}:\n", label)),
+                    Syntax::Octo => out.push_str(&format!(": {}\n", label)),
+                }
+            }
+            let bytes = &self.rom[addr - PROGRAM_START..end - PROGRAM_START];
+            let opcode = self.opcode_at(addr).filter(|_| self.is_code(addr));
+            let mut columns = Vec::new();
+            if options.addresses {
+                columns.push(format!("{:#05X}:", addr));
+            }
+            if let Some(opcode) = opcode.filter(|_| options.bytes) {
+                columns.push(format!("{:04X}", opcode));
+            }
+            let octo_bytes = || bytes.iter().map(|byte| format!("{:#04X}", byte)).collect::<Vec<_>>().join(" ");
+            let text = match (options.syntax, opcode) {
+                (Syntax::Mnemonics, Some(opcode)) => self.labeled_mnemonic(opcode, options.lowercase),
+                (Syntax::Mnemonics, None) => {
+                    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04X}", byte)).collect();
+                    let data = format!("db {}", bytes.join(", "));
+                    if options.lowercase { data.to_lowercase() } else { data }
+                },
+                (Syntax::Octo, Some(opcode)) => octo_statement(opcode, defined_label).unwrap_or_else(octo_bytes),
+                (Syntax::Octo, None) => octo_bytes(),
+            };
+            let line = match (options.syntax, columns.is_empty()) {
+                (_, true) => text,
+                (Syntax::Mnemonics, false) => match opcode.is_some() && options.bytes {
+                    true => format!("{}    {}", columns.join(" "), text),
+                    false => format!("{} {}", columns.join(" "), text),
+                },
+                (Syntax::Octo, false) => format!("{:<24} # {}", text, columns.join(" ").trim_end_matches(':')),
+            };
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(&FormatOptions::default()))
+    }
+}
+
+/// The Octo statement assembling to `opcode`, with addresses replaced by the labels returned by `label`. Returns
+/// `None` if there is none.
+fn octo_statement<'a>(opcode: u16, label: impl Fn(usize) -> Option<&'a str>) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = (opcode & 0x0FFF) as usize;
+    let target = || label(nnn).map_or_else(|| format!("{:#05X}", nnn), str::to_string);
+    let statement = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "clear".to_string(),
+            0x00EE => "return".to_string(),
+            _ if nn == 0x00 => format!("native {}", target()),
+            _ => return None,
+        },
+        0x1000 => format!("jump {}", target()),
+        // Subroutines can only be called by name
+        0x2000 => label(nnn)?.to_string(),
+        // An `if ... then` skips the next statement if the condition is false
+        0x3000 => format!("if v{:x} != {:#04X} then", x, nn),
+        0x4000 => format!("if v{:x} == {:#04X} then", x, nn),
+        0x5000 if n == 0 => format!("if v{:x} != v{:x} then", x, y),
+        0x6000 => format!("v{:x} := {:#04X}", x, nn),
+        0x7000 => format!("v{:x} += {:#04X}", x, nn),
+        0x8000 => {
+            let operator = match n {
+                0x0 => ":=",
+                0x1 => "|=",
+                0x2 => "&=",
+                0x3 => "^=",
+                0x4 => "+=",
+                0x5 => "-=",
+                0x6 => ">>=",
+                0x7 => "=-",
+                0xE => "<<=",
+                _ => return None,
+            };
+            format!("v{:x} {} v{:x}", x, operator, y)
+        },
+        0x9000 if n == 0 => format!("if v{:x} == v{:x} then", x, y),
+        0xA000 => format!("i := {}", target()),
+        0xB000 => format!("jump0 {}", target()),
+        0xC000 => format!("v{:x} := random {:#04X}", x, nn),
+        0xD000 => format!("sprite v{:x} v{:x} {}", x, y, n),
+        0xE000 => match nn {
+            0x9E => format!("if v{:x} -key then", x),
+            0xA1 => format!("if v{:x} key then", x),
+            _ => return None,
+        },
+        0xF000 => match nn {
+            0x07 => format!("v{:x} := delay", x),
+            0x0A => format!("v{:x} := key", x),
+            0x15 => format!("delay := v{:x}", x),
+            0x18 => format!("buzzer := v{:x}", x),
+            0x1E => format!("i += v{:x}", x),
+            0x29 => format!("i := hex v{:x}", x),
+            0x33 => format!("bcd v{:x}", x),
+            0x55 => format!("save v{:x}", x),
+            0x65 => format!("load v{:x}", x),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(statement)
+}
+
+/// Decodes `opcode` into its mnemonic, e.g. `LD V1, 0x05`. Returns `None` for illegal instructions.
+pub fn mnemonic(opcode: u16) -> Option<String> {
+    Instruction::decode(opcode).ok().map(|instruction| instruction.to_string())
+}
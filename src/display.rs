@@ -0,0 +1,209 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// The display as one `u64` per row with the leftmost pixel in the most significant bit, so that a sprite row is
+/// drawn with a shift and an XOR. Indexing gives the row `y`, see [`Framebuffer::pixel`] for single pixels.
+///
+/// Formats as block characters like the terminal shows it, so that tests can compare readable screen dumps.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Framebuffer {
+    rows: [u64; DISPLAY_HEIGHT],
+}
+
+impl Framebuffer {
+    pub fn from_rows(rows: [u64; DISPLAY_HEIGHT]) -> Self {
+        Self { rows }
+    }
+
+    pub fn rows(&self) -> &[u64; DISPLAY_HEIGHT] {
+        &self.rows
+    }
+
+    /// Whether the pixel at (`x`, `y`) is set. Coordinates outside of the display wrap around.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let (x, y) = (x % DISPLAY_WIDTH, y % DISPLAY_HEIGHT);
+        (self.rows[y] >> (DISPLAY_WIDTH - 1 - x)) & 1 == 1
+    }
+}
+
+impl Index<usize> for Framebuffer {
+    type Output = u64;
+
+    fn index(&self, y: usize) -> &u64 {
+        &self.rows[y]
+    }
+}
+
+impl IndexMut<usize> for Framebuffer {
+    fn index_mut(&mut self, y: usize) -> &mut u64 {
+        &mut self.rows[y]
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    /// One line per row, with `█` for set pixels and spaces for unset ones.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (y, &row) in self.rows.iter().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", row_art(row))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Framebuffer {
+    /// Like [`fmt::Display`], but starting on a new line and with `.` for unset pixels, so that the art lines up in
+    /// assertion messages and the width is visible.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &row in &self.rows {
+            write!(f, "\n{}", row_art(row).replace(' ', "."))?;
+        }
+        Ok(())
+    }
+}
+
+/// Draws the pixels of `row` with `█` for set pixels and spaces for unset ones.
+pub(crate) fn row_art(row: u64) -> String {
+    // Most significant bit first, i.e. from left to right
+    (0..DISPLAY_WIDTH).rev().map(|x| if (row >> x) & 1 == 1 { '█' } else { ' ' }).collect()
+}
+
+/// A rectangular region of the display in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Keeps cleared pixels visible for a while, which makes the flicker of XOR drawing bearable:
+///
+/// * With flicker reduction (for photosensitive users), set pixels are shown right away, but cleared pixels only after
+///   they stayed off for `flicker_frames` consecutive frames. Sprites that are erased and redrawn a few frames later,
+///   like in most games, stay visible instead of strobing.
+/// * With phosphor decay, cleared pixels fade out over `decay_frames` frames, like on the slow phosphor of original
+///   displays.
+#[derive(Debug, Clone)]
+pub(crate) struct Afterglow {
+    flicker_frames: u8,
+    decay_frames: u8,
+    /// Consecutive frames each pixel has been off.
+    off_frames: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    shown: Framebuffer,
+}
+
+impl Afterglow {
+    /// Shows `display`, without any effect enabled yet.
+    pub(crate) fn new(display: &Framebuffer) -> Self {
+        let mut off_frames = [[u8::MAX; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        for (y, row) in off_frames.iter_mut().enumerate() {
+            for (x, off_frames) in row.iter_mut().enumerate() {
+                if display.pixel(x, y) {
+                    *off_frames = 0;
+                }
+            }
+        }
+        Self { flicker_frames: 0, decay_frames: 0, off_frames, shown: *display }
+    }
+
+    /// Clears pixels after they were off for `frames` frames. 0 or 1 frames disable flicker reduction.
+    pub(crate) fn set_flicker_frames(&mut self, frames: u8) {
+        self.flicker_frames = frames;
+    }
+
+    /// Fades out cleared pixels over `frames` frames. 0 frames disable phosphor decay.
+    pub(crate) fn set_decay_frames(&mut self, frames: u8) {
+        self.decay_frames = frames;
+    }
+
+    /// Shows `display` right away, forgetting the pixels that are still fading out, e.g. after a reset.
+    pub(crate) fn reset(&mut self, display: &Framebuffer) {
+        *self = Self { flicker_frames: self.flicker_frames, decay_frames: self.decay_frames, ..Self::new(display) };
+    }
+
+    /// Whether any effect is enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.flicker_frames > 1 || self.decay_frames > 0
+    }
+
+    /// The display to show.
+    pub(crate) fn shown(&self) -> &Framebuffer {
+        &self.shown
+    }
+
+    /// The brightness of the pixel at (`x`, `y`) from 0 (off) to 255 (set). Coordinates outside of the display wrap
+    /// around.
+    pub(crate) fn brightness(&self, x: usize, y: usize) -> u8 {
+        let (x, y) = (x % DISPLAY_WIDTH, y % DISPLAY_HEIGHT);
+        if self.shown.pixel(x, y) {
+            return u8::MAX;
+        }
+        let fading = self.fading_frames(self.off_frames[y][x]) as usize;
+        let decay_frames = self.decay_frames as usize;
+        (u8::MAX as usize * (decay_frames + 1).saturating_sub(fading) / (decay_frames + 1)) as u8
+    }
+
+    /// Number of frames a cleared pixel, which has been off for `off_frames` frames, has been fading out.
+    fn fading_frames(&self, off_frames: u8) -> u8 {
+        off_frames.saturating_sub(self.flicker_frames.saturating_sub(1))
+    }
+
+    /// Ages the pixels with the `display` at the end of a frame. Returns a bit mask of the rows that are shown
+    /// differently now.
+    pub(crate) fn update(&mut self, display: &Framebuffer) -> u32 {
+        let mut changed_rows = 0;
+        for y in 0..DISPLAY_HEIGHT {
+            let row = display.rows[y];
+            let mut shown = self.shown.rows[y] | row;
+            let mut fading = false;
+            for x in 0..DISPLAY_WIDTH {
+                let bit = 1 << (DISPLAY_WIDTH - 1 - x);
+                if row & bit != 0 {
+                    self.off_frames[y][x] = 0;
+                    continue;
+                }
+                let off_frames = self.off_frames[y][x].saturating_add(1);
+                self.off_frames[y][x] = off_frames;
+                if off_frames >= self.flicker_frames {
+                    shown &= !bit;
+                    // The brightness also changes in the frame the pixel goes dark
+                    fading |= self.fading_frames(off_frames) <= self.decay_frames.saturating_add(1);
+                }
+            }
+            if shown != self.shown.rows[y] || fading {
+                changed_rows |= 1 << y;
+            }
+            self.shown.rows[y] = shown;
+        }
+        changed_rows
+    }
+}
+
+/// Returns the regions in which `back` differs from `front`. Changed pixels of consecutive rows are merged into a
+/// single rectangle if their horizontal spans overlap.
+pub(crate) fn dirty_rects(front: &Framebuffer, back: &Framebuffer) -> Vec<Rect> {
+    let mut rects: Vec<Rect> = Vec::new();
+    for (y, (front_row, back_row)) in front.rows.iter().zip(&back.rows).enumerate() {
+        let changed = front_row ^ back_row;
+        if changed == 0 {
+            continue;
+        }
+        let x = changed.leading_zeros() as usize;
+        let width = DISPLAY_WIDTH - changed.trailing_zeros() as usize - x;
+        match rects.last_mut() {
+            Some(rect) if rect.y + rect.height == y && x < rect.x + rect.width && rect.x < x + width => {
+                let right = (rect.x + rect.width).max(x + width);
+                rect.x = rect.x.min(x);
+                rect.width = right - rect.x;
+                rect.height += 1;
+            },
+            _ => rects.push(Rect { x, y, width, height: 1 }),
+        }
+    }
+    rects
+}
+
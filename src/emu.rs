@@ -0,0 +1,226 @@
+//! Runs the emulation on its own thread, so that GUI frontends stay responsive. Frontends send [`EmuCommand`]s and
+//! receive [`EmuEvent`]s over channels, which gives all of them the same protocol:
+//!
+//! ```no_run
+//! use chip8::emu::{EmuCommand, EmuEvent, Emulator};
+//!
+//! let emulator = Emulator::spawn(|chip8| chip8.set_instructions_per_frame(10));
+//! emulator.send(EmuCommand::LoadRom(std::fs::read("game.ch8").unwrap()));
+//! for event in emulator.events() {
+//!     match event {
+//!         EmuEvent::FrameReady { display, .. } => { /* Render the display */ },
+//!         EmuEvent::Halted { .. } | EmuEvent::Error(_) => break,
+//!         _ => {},
+//!     }
+//! }
+//! ```
+
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use crate::audio::Beeper;
+use crate::chip8::{FRAME_DURATION, MAX_PROGRAM_SIZE};
+use crate::snapshot::Snapshot;
+use crate::{Chip8, Chip8Error, Framebuffer, Rect, RunStatus};
+
+/// Tells the emulator thread what to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmuCommand {
+    /// Resets the machine with the ROM and starts running it.
+    LoadRom(Vec<u8>),
+    /// Restarts the loaded ROM, see [`Chip8::reset`].
+    Reset,
+    /// Presses or releases a key of the hex keypad.
+    KeyEvent { key: u8, pressed: bool },
+    /// Stops executing instructions until [`EmuCommand::Resume`]. Loading a ROM doesn't resume.
+    Pause,
+    Resume,
+    /// Replies with [`EmuEvent::StateSaved`].
+    SaveState,
+    /// Ends the thread. Dropping the [`Emulator`] does the same.
+    Quit,
+}
+
+/// Tells frontends what happened on the emulator thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmuEvent {
+    /// A frame was executed and the display changed in `dirty_rects`.
+    FrameReady { display: Box<Framebuffer>, dirty_rects: Vec<Rect> },
+    /// The sound started (`true`) or stopped (`false`).
+    Beep(bool),
+    /// The program jumped to itself at `pc`. Nothing is executed until the next ROM is loaded.
+    Halted { pc: usize },
+    /// An instruction failed. Nothing is executed until the next ROM is loaded.
+    Error(Chip8Error),
+    /// The ROM of [`EmuCommand::LoadRom`] doesn't fit into memory and wasn't loaded.
+    RomTooLarge { size: usize },
+    /// The state of the machine, as requested by [`EmuCommand::SaveState`]. `None` if no ROM was loaded yet.
+    StateSaved(Option<Box<Snapshot>>),
+}
+
+/// Handle to the emulator thread. The thread ends when the handle is dropped.
+#[derive(Debug)]
+pub struct Emulator {
+    commands: Sender<EmuCommand>,
+    events: Receiver<EmuEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Emulator {
+    /// Starts the emulator thread, which waits for a ROM. `configure` is called for every loaded ROM, e.g. to set
+    /// the quirks and the speed. It may replace the beeper, which stops the [`EmuEvent::Beep`] events.
+    pub fn spawn(configure: impl Fn(&mut Chip8) + Send + 'static) -> Self {
+        let (commands, command_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut worker = Worker {
+                commands: command_receiver,
+                events: event_sender,
+                chip8: None,
+                paused: false,
+                stopped: false,
+            };
+            worker.run(configure);
+        });
+        Self { commands, events, thread: Some(thread) }
+    }
+
+    /// Sends `command` to the emulator thread. Returns `false` if the thread ended, e.g. because it panicked.
+    pub fn send(&self, command: EmuCommand) -> bool {
+        self.commands.send(command).is_ok()
+    }
+
+    /// The events of the emulator thread. Frontends with an event loop of their own should poll with
+    /// [`Receiver::try_recv`].
+    pub fn events(&self) -> &Receiver<EmuEvent> {
+        &self.events
+    }
+}
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        let _ = self.commands.send(EmuCommand::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Sends [`EmuEvent::Beep`] whenever the sound starts or stops.
+#[derive(Debug)]
+struct ChannelBeeper(Sender<EmuEvent>);
+
+impl Beeper for ChannelBeeper {
+    fn set_active(&mut self, active: bool) {
+        let _ = self.0.send(EmuEvent::Beep(active));
+    }
+}
+
+/// The state of the emulator thread. The [`Chip8`] lives here, because it can't be sent between threads.
+struct Worker {
+    commands: Receiver<EmuCommand>,
+    events: Sender<EmuEvent>,
+    chip8: Option<Chip8>,
+    paused: bool,
+    /// Whether the program halted or failed.
+    stopped: bool,
+}
+
+impl Worker {
+    /// Handles commands and executes frames at 60 Hz until quit or until the frontend went away.
+    fn run(&mut self, configure: impl Fn(&mut Chip8)) {
+        let mut next_frame = Instant::now();
+        loop {
+            // Wait for commands while there's nothing to execute
+            let running = self.chip8.is_some() && !self.paused && !self.stopped;
+            let command = match running {
+                true => match self.commands.try_recv() {
+                    Ok(command) => Some(command),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return,
+                },
+                false => match self.commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                },
+            };
+            match command {
+                Some(EmuCommand::Quit) => return,
+                Some(command) => {
+                    self.handle(command, &configure);
+                    // Don't catch up on the time spent waiting
+                    if !running {
+                        next_frame = Instant::now();
+                    }
+                    continue;
+                },
+                None => {},
+            }
+            if !self.frame() {
+                return;
+            }
+            // Sleep until the next frame is due, like `Chip8::run_with_hooks`
+            next_frame += FRAME_DURATION;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(remaining) => thread::sleep(remaining),
+                None => next_frame = Instant::now(),
+            }
+        }
+    }
+
+    fn handle(&mut self, command: EmuCommand, configure: &impl Fn(&mut Chip8)) {
+        match command {
+            EmuCommand::LoadRom(rom) if rom.len() > MAX_PROGRAM_SIZE => {
+                let _ = self.events.send(EmuEvent::RomTooLarge { size: rom.len() });
+            },
+            EmuCommand::LoadRom(rom) => {
+                let mut chip8 = Chip8::new(&rom);
+                chip8.set_beeper(ChannelBeeper(self.events.clone()));
+                configure(&mut chip8);
+                self.chip8 = Some(chip8);
+                self.stopped = false;
+            },
+            EmuCommand::Reset => {
+                if let Some(chip8) = &mut self.chip8 {
+                    chip8.reset();
+                    self.stopped = false;
+                }
+            },
+            EmuCommand::KeyEvent { key, pressed } => {
+                if let Some(chip8) = &mut self.chip8 {
+                    chip8.set_key_state(key, pressed);
+                }
+            },
+            EmuCommand::Pause => self.paused = true,
+            EmuCommand::Resume => self.paused = false,
+            EmuCommand::SaveState => {
+                let snapshot = self.chip8.as_ref().map(|chip8| Box::new(Snapshot::of(chip8)));
+                let _ = self.events.send(EmuEvent::StateSaved(snapshot));
+            },
+            EmuCommand::Quit => {},
+        }
+    }
+
+    /// Executes a frame and sends the resulting events. Returns `false` if the frontend went away.
+    fn frame(&mut self) -> bool {
+        let chip8 = self.chip8.as_mut().expect("Only running with a ROM");
+        let result = chip8.run_frame_with_hooks(&mut ());
+        let dirty_rects = chip8.take_dirty_rects();
+        if !dirty_rects.is_empty() {
+            let frame = EmuEvent::FrameReady { display: Box::new(chip8.display), dirty_rects };
+            if self.events.send(frame).is_err() {
+                return false;
+            }
+        }
+        let event = match result {
+            Ok(ControlFlow::Continue(())) => return true,
+            Ok(ControlFlow::Break(RunStatus::Halted { pc })) => EmuEvent::Halted { pc },
+            // Nothing else stops the frame without hooks
+            Ok(ControlFlow::Break(_)) => return true,
+            Err(err) => EmuEvent::Error(err),
+        };
+        self.stopped = true;
+        self.events.send(event).is_ok()
+    }
+}
@@ -0,0 +1,51 @@
+//! Small example programs built into the binary, so that there is something to run without a ROM at hand. They were
+//! written for this project in Octo, their sources are in `roms/`. After changing a source, rebuild the ROM with
+//! `chip8 asm roms/<name>.8o`.
+
+use crate::assembler;
+use crate::rom::Rom;
+
+/// An example program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Example {
+    /// Short name, as accepted by [`named`].
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The assembled program.
+    pub rom: &'static [u8],
+    /// The Octo source of the program.
+    pub source: &'static str,
+}
+
+impl Example {
+    /// The program as if it was loaded from a file, with the symbols of its source for the debuggers.
+    pub fn load(&self) -> Rom {
+        Rom { bytes: self.rom.to_vec(), assembly: assembler::assemble(self.source).ok() }
+    }
+}
+
+pub const EXAMPLES: [Example; 3] = [
+    Example {
+        name: "maze",
+        description: "Draws a random maze, any key draws a new one",
+        rom: include_bytes!("../roms/maze.ch8"),
+        source: include_str!("../roms/maze.8o"),
+    },
+    Example {
+        name: "bounce",
+        description: "A ball bouncing around the screen",
+        rom: include_bytes!("../roms/bounce.ch8"),
+        source: include_str!("../roms/bounce.8o"),
+    },
+    Example {
+        name: "keypad",
+        description: "Shows the pressed keys, to try out the keyboard",
+        rom: include_bytes!("../roms/keypad.ch8"),
+        source: include_str!("../roms/keypad.8o"),
+    },
+];
+
+/// An example by its name, like `maze`.
+pub fn named(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,144 @@
+//! The hex digit sprites `FX29` points I to. Interpreters shipped different shapes, which are visible in many games,
+//! so the historical ones are bundled. Font files hold the 5 bytes of each digit from `0` to `F`, 80 bytes in total.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The font is stored in the memory reserved for the interpreter, at this address.
+pub const FONT_ADDR: usize = 0x50;
+/// Number of bytes of a font: 16 digits with 5 rows each.
+pub const FONT_SIZE: usize = 16 * DIGIT_HEIGHT;
+/// Rows of a digit sprite.
+pub const DIGIT_HEIGHT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Font {
+    sprites: [u8; FONT_SIZE],
+}
+
+impl Font {
+    /// The font of the COSMAC VIP, where CHIP-8 originated.
+    pub const VIP: Self = Self { sprites: [
+        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+        0x60, 0x20, 0x20, 0x20, 0x70, // 1
+        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+        0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+        0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+        0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+        0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+    ] };
+
+    /// The narrow font of the ETI-660.
+    pub const ETI_660: Self = Self { sprites: [
+        0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+        0x20, 0x20, 0x20, 0x20, 0x20, // 1
+        0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+        0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+        0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+        0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+        0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+        0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+        0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+        0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+        0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+        0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+        0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+        0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+        0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+        0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+    ] };
+
+    /// The narrow font of the DREAM 6800.
+    pub const DREAM_6800: Self = Self { sprites: [
+        0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+        0x40, 0x40, 0x40, 0x40, 0x40, // 1
+        0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+        0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+        0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+        0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+        0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+        0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+        0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+        0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+        0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+        0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+        0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+        0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+        0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+        0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+    ] };
+
+    /// The font of Octo and of most modern interpreters, originally from CHIP-48. The default.
+    pub const OCTO: Self = Self { sprites: [
+        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+        0x20, 0x60, 0x20, 0x20, 0x70, // 1
+        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+    ] };
+
+    /// Names of the bundled fonts, as accepted by [`Font::named`].
+    pub const NAMES: [&'static str; 4] = ["vip", "eti-660", "dream-6800", "octo"];
+
+    /// A bundled font by its name, like `eti-660`.
+    pub fn named(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" => Some(Self::VIP),
+            "eti-660" => Some(Self::ETI_660),
+            "dream-6800" => Some(Self::DREAM_6800),
+            "octo" => Some(Self::OCTO),
+            _ => None,
+        }
+    }
+
+    /// A font from its 80 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut sprites = [0; FONT_SIZE];
+        if bytes.len() != FONT_SIZE {
+            return None;
+        }
+        sprites.copy_from_slice(bytes);
+        Some(Self { sprites })
+    }
+
+    /// Loads a font file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes).ok_or_else(|| {
+            let message = format!("A font has {} bytes, but the file has {}", FONT_SIZE, bytes.len());
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })
+    }
+
+    /// The 5 rows of every digit from `0` to `F`.
+    pub fn sprites(&self) -> &[u8; FONT_SIZE] {
+        &self.sprites
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::OCTO
+    }
+}
@@ -0,0 +1,88 @@
+use std::ops::ControlFlow;
+use crate::{Chip8, Chip8Error};
+
+/// Callbacks invoked by [`Chip8::run_with_hooks`] while the emulator is running. All methods do nothing by
+/// default, so implementors only need to override the ones they are interested in. Returning
+/// [`ControlFlow::Break`] stops the emulator.
+pub trait Hooks {
+    /// Called after the instruction `opcode` located at `pc` was executed.
+    fn after_instruction(&mut self, _chip8: &mut Chip8, _pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Called after each frame, i.e. after the display was rendered and the timers were decremented.
+    fn after_frame(&mut self, _chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Called when the program jumped to itself at `pc`, which stops the emulator.
+    fn halted(&mut self, _chip8: &mut Chip8, _pc: usize) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+impl Hooks for () {}
+
+impl<H: Hooks + ?Sized> Hooks for Box<H> {
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: usize, opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        (**self).after_instruction(chip8, pc, opcode)
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        (**self).after_frame(chip8)
+    }
+
+    fn halted(&mut self, chip8: &mut Chip8, pc: usize) -> Result<(), Chip8Error> {
+        (**self).halted(chip8, pc)
+    }
+}
+
+impl<H: Hooks + ?Sized> Hooks for &mut H {
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: usize, opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        (**self).after_instruction(chip8, pc, opcode)
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        (**self).after_frame(chip8)
+    }
+
+    fn halted(&mut self, chip8: &mut Chip8, pc: usize) -> Result<(), Chip8Error> {
+        (**self).halted(chip8, pc)
+    }
+}
+
+/// Calls the hooks in order. If one of them breaks, the remaining ones are not called.
+impl<H: Hooks> Hooks for Vec<H> {
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: usize, opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        for hook in self {
+            if hook.after_instruction(chip8, pc, opcode)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        for hook in self {
+            if hook.after_frame(chip8)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn halted(&mut self, chip8: &mut Chip8, pc: usize) -> Result<(), Chip8Error> {
+        for hook in self {
+            hook.halted(chip8, pc)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! HTTP API to control a headless emulator instance:
+//!
+//! * `POST /rom` with the ROM as body loads it and resets the machine.
+//! * `POST /step?n=N` executes `N` instructions (default 1). The timers tick at 60 Hz of emulated time, i.e.
+//!   once per instructions-per-frame instructions.
+//! * `GET /display.png?scale=S` returns the display as PNG, scaled by `S` (default 8).
+//! * `POST /keys/K/down` and `POST /keys/K/up` press and release the hex key `K`.
+//! * `GET /state` returns the registers as JSON.
+//! * `POST /resume` runs the machine at 60 frames per second until `POST /pause`, an error or the program halts.
+//! * `GET /` shows a dashboard with the display, a keypad that can be played on touch screens, the registers and
+//!   buttons to pause, resume and step, if enabled with [`HttpApi::set_dashboard`].
+
+use std::io::Read;
+use std::net::ToSocketAddrs;
+use std::ops::ControlFlow;
+use std::time::Instant;
+use tiny_http::{Header, Method, Request, Response, Server};
+use crate::chip8::{FRAME_DURATION, MAX_PROGRAM_SIZE};
+use crate::image::{self, Palette};
+use crate::{Chip8, RunStatus};
+
+/// Web page that polls the API to show the state of the machine.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+pub struct HttpApi {
+    server: Server,
+    chip8: Chip8,
+    /// Whether frames are executed between requests.
+    running: bool,
+    /// Why the machine stopped running on its own, i.e. the error or the halt.
+    stop_reason: Option<String>,
+    dashboard: bool,
+}
+
+impl HttpApi {
+    pub fn bind(addr: impl ToSocketAddrs, chip8: Chip8) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self { server: Server::http(addr)?, chip8, running: false, stop_reason: None, dashboard: false })
+    }
+
+    /// Serves the dashboard at `/`, so that the instance can be inspected from a browser.
+    pub fn set_dashboard(&mut self, dashboard: bool) {
+        self.dashboard = dashboard;
+    }
+
+    /// Handles requests until the server fails. While running, frames are executed between requests.
+    pub fn serve(&mut self) {
+        let mut next_frame = Instant::now();
+        loop {
+            let mut request = match self.running {
+                true => match self.server.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+                    Ok(Some(request)) => request,
+                    Ok(None) => {
+                        self.run_frame();
+                        // Don't catch up on frames missed while handling requests
+                        next_frame = (next_frame + FRAME_DURATION).max(Instant::now());
+                        continue;
+                    },
+                    Err(_) => return,
+                },
+                false => match self.server.recv() {
+                    Ok(request) => {
+                        next_frame = Instant::now();
+                        request
+                    },
+                    Err(_) => return,
+                },
+            };
+            let response = self.handle(&mut request);
+            // The client may have gone away, which is fine
+            let _ = request.respond(response);
+        }
+    }
+
+    /// Executes a frame and stops running if the program failed or halted.
+    fn run_frame(&mut self) {
+        let stop_reason = match self.chip8.run_frame_with_hooks(&mut ()) {
+            Ok(ControlFlow::Continue(())) => return,
+            Ok(ControlFlow::Break(RunStatus::Halted { pc })) => format!("The program halted at {:#05X}", pc),
+            // Nothing else stops the frame without hooks
+            Ok(ControlFlow::Break(_)) => return,
+            Err(err) => err.to_string(),
+        };
+        self.running = false;
+        self.stop_reason = Some(stop_reason);
+    }
+
+    fn handle(&mut self, request: &mut Request) -> HttpResponse {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (request.method(), segments.as_slice()) {
+            (Method::Post, ["rom"]) => self.load_rom(request),
+            (Method::Post, ["step"]) => self.step(query_param(query, "n").unwrap_or(1)),
+            (Method::Get, ["display.png"]) => {
+                let scale = query_param(query, "scale").unwrap_or(8);
+                let png = image::encode_png(&self.chip8, scale, &Palette::default());
+                Response::from_data(png).with_header(content_type("image/png"))
+            },
+            (Method::Post, ["keys", key, action]) => self.key(key, action),
+            (Method::Get, ["state"]) => json(200, self.state_json()),
+            (Method::Post, ["resume"]) => {
+                self.running = true;
+                self.stop_reason = None;
+                json(200, self.state_json())
+            },
+            (Method::Post, ["pause"]) => {
+                self.running = false;
+                json(200, self.state_json())
+            },
+            (Method::Get, [""]) if self.dashboard => {
+                Response::from_string(DASHBOARD).with_header(content_type("text/html; charset=utf-8"))
+            },
+            _ => error(404, "Not found"),
+        }
+    }
+
+    fn load_rom(&mut self, request: &mut Request) -> HttpResponse {
+        let mut rom = Vec::new();
+        if let Err(err) = request.as_reader().take(MAX_PROGRAM_SIZE as u64 + 1).read_to_end(&mut rom) {
+            return error(400, &err.to_string());
+        }
+        if rom.len() > MAX_PROGRAM_SIZE {
+            return error(413, &format!("ROM is larger than {} bytes", MAX_PROGRAM_SIZE));
+        }
+        self.chip8 = Chip8::new(&rom);
+        self.stop_reason = None;
+        json(200, format!(r#"{{"loaded":{}}}"#, rom.len()))
+    }
+
+    fn step(&mut self, n: usize) -> HttpResponse {
+        for executed in 0..n {
+            if let Err(err) = self.chip8.step() {
+                return json(409, format!(r#"{{"executed":{},"error":{:?}}}"#, executed, err.to_string()));
+            }
+        }
+        json(200, format!(r#"{{"executed":{},"pc":{}}}"#, n, self.chip8.pc))
+    }
+
+    fn key(&mut self, key: &str, action: &str) -> HttpResponse {
+        let key = match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => key,
+            _ => return error(400, "Key must be a hex digit"),
+        };
+        match action {
+            "down" => self.chip8.set_key_state(key, true),
+            "up" => self.chip8.set_key_state(key, false),
+            _ => return error(404, "Not found"),
+        }
+        json(200, format!(r#"{{"keypad":{}}}"#, self.chip8.keypad()))
+    }
+
+    fn state_json(&self) -> String {
+        let registers: Vec<String> = self.chip8.registers.iter().map(u8::to_string).collect();
+        let stop_reason = match &self.stop_reason {
+            Some(stop_reason) => format!("{:?}", stop_reason),
+            None => String::from("null"),
+        };
+        format!(
+            r#"{{"pc":{},"i":{},"v":[{}],"sp":{},"dt":{},"st":{},"keypad":{},"running":{},"stopped":{}}}"#,
+            self.chip8.pc, self.chip8.address_register, registers.join(","), self.chip8.stack_pointer,
+            self.chip8.delay_timer, self.chip8.sound_timer, self.chip8.keypad, self.running, stop_reason
+        )
+    }
+}
+
+fn query_param(query: &str, name: &str) -> Option<usize> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn content_type(mime: &str) -> Header {
+    Header::from_bytes("Content-Type", mime).expect("Content types are valid headers")
+}
+
+fn json(status: u16, body: String) -> HttpResponse {
+    Response::from_string(body).with_status_code(status).with_header(content_type("application/json"))
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    json(status, format!(r#"{{"error":{:?}}}"#, message))
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::Chip8;
+
+/// Colors used to render the display into images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Color of set pixels as RGB.
+    pub foreground: [u8; 3],
+    /// Color of unset pixels as RGB.
+    pub background: [u8; 3],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self { foreground: [0xFF, 0xFF, 0xFF], background: [0x00, 0x00, 0x00] }
+    }
+}
+
+impl Palette {
+    /// The color between the background (brightness 0) and the foreground (brightness 255).
+    pub fn blend(&self, brightness: u8) -> [u8; 3] {
+        let brightness = brightness as u32;
+        let channel = |i: usize| {
+            let (foreground, background) = (self.foreground[i] as u32, self.background[i] as u32);
+            ((foreground * brightness + background * (255 - brightness)) / 255) as u8
+        };
+        [channel(0), channel(1), channel(2)]
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses a palette like `#FFCC00,#996600` (foreground, background).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_color = |color: &str| -> Result<[u8; 3], String> {
+            let hex = color.trim().trim_start_matches('#');
+            let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)
+                .ok_or_else(|| format!("Invalid color {:?}, expected something like #FFCC00", color))?;
+            Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+        };
+        let (foreground, background) = s.split_once(',')
+            .ok_or_else(|| format!("Invalid palette {:?}, expected foreground,background", s))?;
+        Ok(Self { foreground: parse_color(foreground)?, background: parse_color(background)? })
+    }
+}
+
+/// Renders the display as shown (see [`Chip8::pixel_brightness`]) as RGB image, where every Chip-8 pixel becomes a
+/// `scale` x `scale` square. Returns the width, height and pixel data.
+pub fn render_rgb(chip8: &Chip8, scale: usize, palette: &Palette) -> (usize, usize, Vec<u8>) {
+    let scale = scale.max(1);
+    let (width, height) = (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| palette.blend(chip8.pixel_brightness(x / scale, y / scale)))
+        .collect();
+    (width, height, pixels)
+}
+
+/// Encodes the display as PNG, where every Chip-8 pixel becomes a `scale` x `scale` square.
+pub fn encode_png(chip8: &Chip8, scale: usize, palette: &Palette) -> Vec<u8> {
+    let (width, height, pixels) = render_rgb(chip8, scale, palette);
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Writing to a Vec can't fail
+    let mut writer = encoder.write_header().expect("Can't write PNG header");
+    writer.write_image_data(&pixels).expect("Can't write PNG data");
+    writer.finish().expect("Can't finish PNG");
+    png
+}
+
+/// Writes the display as PNG to `path`.
+pub fn screenshot(chip8: &Chip8, path: impl AsRef<Path>, scale: usize, palette: &Palette) -> io::Result<()> {
+    fs::write(path, encode_png(chip8, scale, palette))
+}
@@ -0,0 +1,90 @@
+//! Renders the display as PNG images with the inline image protocols of modern terminals: the
+//! [kitty graphics protocol](https://sw.kovidgoyal.net/kitty/graphics-protocol/), which is also supported by e.g.
+//! WezTerm and Ghostty, and the [inline images of iTerm2](https://iterm2.com/documentation-images.html).
+
+use std::env;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crate::hooks::Hooks;
+use crate::image::{self, Palette};
+use crate::{Chip8, Chip8Error};
+
+/// The kitty graphics protocol transmits at most this many bytes of base64 per escape sequence.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm2,
+}
+
+/// Detects the protocol supported by the terminal from the environment, i.e. from the terminfo name in `TERM` and
+/// from the variables set by the terminals. Terminal multiplexers like tmux hide the terminal and aren't supported.
+pub fn detect() -> Option<Protocol> {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    match (var("TERM").as_str(), var("TERM_PROGRAM").as_str()) {
+        ("xterm-kitty" | "xterm-ghostty", _) | (_, "WezTerm") => Some(Protocol::Kitty),
+        _ if env::var_os("KITTY_WINDOW_ID").is_some() => Some(Protocol::Kitty),
+        (_, "iTerm.app") => Some(Protocol::Iterm2),
+        _ => None,
+    }
+}
+
+/// Encodes `png` as escape sequences that draw it at the cursor, without moving the cursor. With the kitty protocol,
+/// every image replaces the previous one.
+pub fn encode(protocol: Protocol, png: &[u8]) -> String {
+    let data = STANDARD.encode(png);
+    match protocol {
+        Protocol::Kitty => {
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+            let mut output = String::new();
+            for (index, chunk) in chunks.iter().enumerate() {
+                // Only the first chunk has the control data. m=1 means more chunks follow.
+                let control = match index {
+                    // PNG (f=100) transmitted and displayed (a=T) as image 1 at placement 1, without responses
+                    // (q=2) and without moving the cursor (C=1)
+                    0 => "f=100,a=T,i=1,p=1,q=2,C=1,",
+                    _ => "",
+                };
+                let more = (index + 1 < chunks.len()) as u8;
+                let chunk = std::str::from_utf8(chunk).expect("Base64 is ASCII");
+                output.push_str(&format!("\x1b_G{}m={};{}\x1b\\", control, more, chunk));
+            }
+            output
+        },
+        // iTerm2 moves the cursor below the image, so it's restored afterwards
+        Protocol::Iterm2 => format!("\x1b7\x1b]1337;File=inline=1;size={}:{}\x07\x1b8", png.len(), data),
+    }
+}
+
+/// Draws the display as PNG image whenever it changed. Disable the character art with [`Chip8::set_text_output`],
+/// so that both don't overwrite each other.
+#[derive(Debug, Clone)]
+pub struct InlineImageRenderer {
+    protocol: Protocol,
+    scale: usize,
+    palette: Palette,
+}
+
+impl InlineImageRenderer {
+    /// Every Chip-8 pixel becomes a `scale` x `scale` square.
+    pub fn new(protocol: Protocol, scale: usize, palette: Palette) -> Self {
+        Self { protocol, scale: scale.max(1), palette }
+    }
+}
+
+impl Hooks for InlineImageRenderer {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        if chip8.dirty_rows == 0 {
+            return Ok(ControlFlow::Continue(()));
+        }
+        let png = image::encode_png(chip8, self.scale, &self.palette);
+        let mut stdout = io::stdout();
+        write!(stdout, "{}", encode(self.protocol, &png))
+            .and_then(|()| stdout.flush())
+            .map_err(|err| Chip8Error::Hook(format!("Can't draw inline image: {}", err)))?;
+        Ok(ControlFlow::Continue(()))
+    }
+}
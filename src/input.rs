@@ -0,0 +1,179 @@
+//! Key presses from the host, independent of how the frontend learns about them. Some libraries report the state of
+//! the keyboard when asked (polling, e.g. SDL), others report every press and release as it happens (events, e.g.
+//! crossterm). Both implement [`Input`], and [`EventInput`] and [`PolledInput`] convert between the two, so that
+//! [`InputHook`] feeds any of them into the interpreter.
+
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+/// A key of the hex keypad was pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Which keys of the hex keypad are held down. Bit `n` is set if key `n` is pressed, like [`Chip8::keypad`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeypadState(pub u16);
+
+impl KeypadState {
+    pub fn is_pressed(self, key: u8) -> bool {
+        key < 16 && (self.0 >> key) & 1 == 1
+    }
+
+    /// Presses or releases `key`. Keys beyond `F` are ignored.
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        let mask = 1u16.checked_shl(key as u32).unwrap_or(0);
+        match pressed {
+            true => self.0 |= mask,
+            false => self.0 &= !mask,
+        }
+    }
+
+    pub fn apply(&mut self, event: KeyEvent) {
+        self.set(event.key, event.pressed);
+    }
+
+    /// The events that turn this state into `new`, ordered by key.
+    pub fn changes(self, new: KeypadState) -> impl Iterator<Item = KeyEvent> {
+        (0..16u8)
+            .filter(move |&key| self.is_pressed(key) != new.is_pressed(key))
+            .map(move |key| KeyEvent { key, pressed: new.is_pressed(key) })
+    }
+}
+
+/// A source of key presses, which can be asked for the held keys, for the key presses and releases, or both.
+pub trait Input {
+    /// The keys held down right now.
+    fn poll(&mut self) -> KeypadState;
+
+    /// The oldest key change not taken yet. Sources that can only be polled return `None`, wrap them in
+    /// [`PolledInput`] to get events.
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        None
+    }
+}
+
+/// Polls the state from the closure, e.g. by asking SDL for the keyboard state.
+impl<F: FnMut() -> KeypadState> Input for F {
+    fn poll(&mut self) -> KeypadState {
+        self()
+    }
+}
+
+/// Event-based frontends push their key events into it. It can be polled like the keyboard state of SDL, and hands
+/// out the pushed events in order.
+#[derive(Debug, Default, Clone)]
+pub struct EventInput {
+    /// The state after all events taken so far.
+    state: KeypadState,
+    queue: VecDeque<KeyEvent>,
+}
+
+impl EventInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a key press or release. Keys beyond `F` are ignored.
+    pub fn push(&mut self, event: KeyEvent) {
+        if event.key < 16 {
+            self.queue.push_back(event);
+        }
+    }
+}
+
+impl Input for EventInput {
+    /// The state after all pushed events, which are taken by this.
+    fn poll(&mut self) -> KeypadState {
+        while self.next_event().is_some() {}
+        self.state
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        let event = self.queue.pop_front()?;
+        self.state.apply(event);
+        Some(event)
+    }
+}
+
+/// Turns a source that can only be polled into events, by comparing each polled state with the previous one.
+/// Presses and releases between two polls are lost, like in the source.
+#[derive(Debug, Default, Clone)]
+pub struct PolledInput<I> {
+    source: I,
+    /// The state the events taken so far lead to.
+    state: KeypadState,
+    /// Changes found by the last poll, which weren't taken yet.
+    pending: VecDeque<KeyEvent>,
+}
+
+impl<I: Input> PolledInput<I> {
+    pub fn new(source: I) -> Self {
+        Self { source, state: KeypadState::default(), pending: VecDeque::new() }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.source
+    }
+}
+
+impl<I: Input> Input for PolledInput<I> {
+    fn poll(&mut self) -> KeypadState {
+        let state = self.source.poll();
+        let known = self.pending.iter().fold(self.state, |mut known, &event| {
+            known.apply(event);
+            known
+        });
+        self.pending.extend(known.changes(state));
+        state
+    }
+
+    fn next_event(&mut self) -> Option<KeyEvent> {
+        if self.pending.is_empty() {
+            self.poll();
+        }
+        let event = self.pending.pop_front()?;
+        self.state.apply(event);
+        Some(event)
+    }
+}
+
+/// Feeds an [`Input`] into the interpreter at the end of every frame: first every event, so that even short presses
+/// reach `FX0A`, then the polled state.
+#[derive(Debug, Default, Clone)]
+pub struct InputHook<I> {
+    input: I,
+}
+
+impl<I: Input> InputHook<I> {
+    pub fn new(input: I) -> Self {
+        Self { input }
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Applies the key changes to `chip8`. Call it before the first frame, afterwards the hook does.
+    pub fn apply(&mut self, chip8: &mut Chip8) {
+        while let Some(event) = self.input.next_event() {
+            chip8.set_key_state(event.key, event.pressed);
+        }
+        chip8.set_keypad(self.input.poll().0);
+    }
+}
+
+impl<I: Input> Hooks for InputHook<I> {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.apply(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
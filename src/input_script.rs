@@ -0,0 +1,87 @@
+//! Key presses from a plain-text script, to reproduce game scenarios in tests and headless runs. Every line presses or
+//! releases a hex key before the frame with the given number (counted from 0), e.g.
+//!
+//! ```text
+//! frame 120: press 5
+//! frame 130: release 5
+//! ```
+//!
+//! Empty lines and `#` comments are ignored. The lines don't have to be ordered by frame.
+
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::Path;
+use crate::hooks::Hooks;
+use crate::replay::KeyEvent;
+use crate::{Chip8, Chip8Error};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    /// Ordered by frame.
+    events: Vec<KeyEvent>,
+    /// Index of the next event to apply.
+    next: usize,
+}
+
+impl InputScript {
+    /// Loads an input script file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parses the contents of an input script file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Line {}: Expected a key change like `frame 120: press 5`", i + 1);
+            let (frame, action) = line.strip_prefix("frame").and_then(|rest| rest.split_once(':')).ok_or_else(invalid)?;
+            let frame = frame.trim().parse().map_err(|_| invalid())?;
+            let (pressed, key) = match action.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["press", key] => (true, *key),
+                ["release", key] => (false, *key),
+                _ => return Err(invalid()),
+            };
+            let key = u8::from_str_radix(key, 16).ok().filter(|&key| key < 16)
+                .ok_or_else(|| format!("Line {}: Invalid key {:?}, expected a hex digit", i + 1, key))?;
+            events.push(KeyEvent { frame, key, pressed });
+        }
+        // Stable, so that changes in the same frame keep their order
+        events.sort_by_key(|event| event.frame);
+        Ok(Self { events, next: 0 })
+    }
+
+    /// The key changes, ordered by frame.
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    /// Presses and releases the keys for the next frame of `chip8`, i.e. for the number of frames executed so far.
+    /// Call it before every frame, or use the script as hook.
+    pub fn apply(&mut self, chip8: &mut Chip8) {
+        let frames = chip8.metrics().frames;
+        while let Some(event) = self.events.get(self.next).filter(|event| event.frame <= frames) {
+            chip8.set_key_state(event.key, event.pressed);
+            self.next += 1;
+        }
+    }
+
+    /// Whether all key changes were applied.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
+
+/// Applies the key changes at the end of every frame. The changes before the first frame have to be applied with
+/// [`InputScript::apply`].
+impl Hooks for InputScript {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.apply(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
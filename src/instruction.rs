@@ -0,0 +1,253 @@
+//! Decoded instructions, shared by the tools that analyze ROMs like the [`crate::disassembler`], the
+//! [`crate::lint`]er and the [`crate::cfg`]. `x` and `y` are register numbers, `nn` is a byte, `n` a nibble and
+//! `addr` a 12 bit address.
+
+use std::fmt;
+use thiserror::Error;
+use crate::PROGRAM_START;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `0NNN` - `SYS addr`, a machine routine of the original interpreter.
+    Sys { addr: u16 },
+    /// `00E0` - `CLS`.
+    Cls,
+    /// `00EE` - `RET`.
+    Ret,
+    /// `1NNN` - `JP addr`.
+    Jump { addr: u16 },
+    /// `2NNN` - `CALL addr`.
+    Call { addr: u16 },
+    /// `3XNN` - `SE vx, nn`.
+    SkipEqByte { x: u8, nn: u8 },
+    /// `4XNN` - `SNE vx, nn`.
+    SkipNeByte { x: u8, nn: u8 },
+    /// `5XY0` - `SE vx, vy`.
+    SkipEqReg { x: u8, y: u8 },
+    /// `6XNN` - `LD vx, nn`.
+    LoadByte { x: u8, nn: u8 },
+    /// `7XNN` - `ADD vx, nn`.
+    AddByte { x: u8, nn: u8 },
+    /// `8XY0` - `LD vx, vy`.
+    LoadReg { x: u8, y: u8 },
+    /// `8XY1` - `OR vx, vy`.
+    Or { x: u8, y: u8 },
+    /// `8XY2` - `AND vx, vy`.
+    And { x: u8, y: u8 },
+    /// `8XY3` - `XOR vx, vy`.
+    Xor { x: u8, y: u8 },
+    /// `8XY4` - `ADD vx, vy`.
+    AddReg { x: u8, y: u8 },
+    /// `8XY5` - `SUB vx, vy`.
+    Sub { x: u8, y: u8 },
+    /// `8XY6` - `SHR vx, vy`.
+    Shr { x: u8, y: u8 },
+    /// `8XY7` - `SUBN vx, vy`.
+    Subn { x: u8, y: u8 },
+    /// `8XYE` - `SHL vx, vy`.
+    Shl { x: u8, y: u8 },
+    /// `9XY0` - `SNE vx, vy`.
+    SkipNeReg { x: u8, y: u8 },
+    /// `ANNN` - `LD I, addr`.
+    LoadI { addr: u16 },
+    /// `BNNN` - `JP V0, addr`.
+    JumpV0 { addr: u16 },
+    /// `CXNN` - `RND vx, nn`.
+    Random { x: u8, nn: u8 },
+    /// `DXYN` - `DRW vx, vy, n`.
+    Draw { x: u8, y: u8, n: u8 },
+    /// `EX9E` - `SKP vx`.
+    SkipKey { x: u8 },
+    /// `EXA1` - `SKNP vx`.
+    SkipNotKey { x: u8 },
+    /// `FX07` - `LD vx, DT`.
+    LoadDelay { x: u8 },
+    /// `FX0A` - `LD vx, K`.
+    WaitKey { x: u8 },
+    /// `FX15` - `LD DT, vx`.
+    SetDelay { x: u8 },
+    /// `FX18` - `LD ST, vx`.
+    SetSound { x: u8 },
+    /// `FX1E` - `ADD I, vx`.
+    AddI { x: u8 },
+    /// `FX29` - `LD F, vx`.
+    LoadFont { x: u8 },
+    /// `FX33` - `LD B, vx`.
+    Bcd { x: u8 },
+    /// `FX55` - `LD [I], vx`.
+    Store { x: u8 },
+    /// `FX65` - `LD vx, [I]`.
+    Load { x: u8 },
+}
+
+/// An opcode that isn't an instruction, most likely data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Illegal instruction {0:04X}")]
+pub struct IllegalOpcode(pub u16);
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Result<Self, IllegalOpcode> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let addr = opcode & 0x0FFF;
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Self::Cls,
+                0x00EE => Self::Ret,
+                _ if nn == 0x00 => Self::Sys { addr },
+                _ => return Err(IllegalOpcode(opcode)),
+            },
+            0x1000 => Self::Jump { addr },
+            0x2000 => Self::Call { addr },
+            0x3000 => Self::SkipEqByte { x, nn },
+            0x4000 => Self::SkipNeByte { x, nn },
+            0x5000 if n == 0 => Self::SkipEqReg { x, y },
+            0x6000 => Self::LoadByte { x, nn },
+            0x7000 => Self::AddByte { x, nn },
+            0x8000 => match n {
+                0x0 => Self::LoadReg { x, y },
+                0x1 => Self::Or { x, y },
+                0x2 => Self::And { x, y },
+                0x3 => Self::Xor { x, y },
+                0x4 => Self::AddReg { x, y },
+                0x5 => Self::Sub { x, y },
+                0x6 => Self::Shr { x, y },
+                0x7 => Self::Subn { x, y },
+                0xE => Self::Shl { x, y },
+                _ => return Err(IllegalOpcode(opcode)),
+            },
+            0x9000 if n == 0 => Self::SkipNeReg { x, y },
+            0xA000 => Self::LoadI { addr },
+            0xB000 => Self::JumpV0 { addr },
+            0xC000 => Self::Random { x, nn },
+            0xD000 => Self::Draw { x, y, n },
+            0xE000 => match nn {
+                0x9E => Self::SkipKey { x },
+                0xA1 => Self::SkipNotKey { x },
+                _ => return Err(IllegalOpcode(opcode)),
+            },
+            0xF000 => match nn {
+                0x07 => Self::LoadDelay { x },
+                0x0A => Self::WaitKey { x },
+                0x15 => Self::SetDelay { x },
+                0x18 => Self::SetSound { x },
+                0x1E => Self::AddI { x },
+                0x29 => Self::LoadFont { x },
+                0x33 => Self::Bcd { x },
+                0x55 => Self::Store { x },
+                0x65 => Self::Load { x },
+                _ => return Err(IllegalOpcode(opcode)),
+            },
+            _ => return Err(IllegalOpcode(opcode)),
+        };
+        Ok(instruction)
+    }
+
+    /// Encodes the instruction, the inverse of [`Instruction::decode`]. Operands are masked to their size.
+    pub fn opcode(self) -> u16 {
+        let xy = |prefix: u16, x: u8, y: u8, n: u16| prefix | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xnn = |prefix: u16, x: u8, nn: u8| prefix | (x as u16 & 0xF) << 8 | nn as u16;
+        match self {
+            Self::Sys { addr } => addr & 0x0FFF,
+            Self::Cls => 0x00E0,
+            Self::Ret => 0x00EE,
+            Self::Jump { addr } => 0x1000 | addr & 0x0FFF,
+            Self::Call { addr } => 0x2000 | addr & 0x0FFF,
+            Self::SkipEqByte { x, nn } => xnn(0x3000, x, nn),
+            Self::SkipNeByte { x, nn } => xnn(0x4000, x, nn),
+            Self::SkipEqReg { x, y } => xy(0x5000, x, y, 0x0),
+            Self::LoadByte { x, nn } => xnn(0x6000, x, nn),
+            Self::AddByte { x, nn } => xnn(0x7000, x, nn),
+            Self::LoadReg { x, y } => xy(0x8000, x, y, 0x0),
+            Self::Or { x, y } => xy(0x8000, x, y, 0x1),
+            Self::And { x, y } => xy(0x8000, x, y, 0x2),
+            Self::Xor { x, y } => xy(0x8000, x, y, 0x3),
+            Self::AddReg { x, y } => xy(0x8000, x, y, 0x4),
+            Self::Sub { x, y } => xy(0x8000, x, y, 0x5),
+            Self::Shr { x, y } => xy(0x8000, x, y, 0x6),
+            Self::Subn { x, y } => xy(0x8000, x, y, 0x7),
+            Self::Shl { x, y } => xy(0x8000, x, y, 0xE),
+            Self::SkipNeReg { x, y } => xy(0x9000, x, y, 0x0),
+            Self::LoadI { addr } => 0xA000 | addr & 0x0FFF,
+            Self::JumpV0 { addr } => 0xB000 | addr & 0x0FFF,
+            Self::Random { x, nn } => xnn(0xC000, x, nn),
+            Self::Draw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            Self::SkipKey { x } => xnn(0xE000, x, 0x9E),
+            Self::SkipNotKey { x } => xnn(0xE000, x, 0xA1),
+            Self::LoadDelay { x } => xnn(0xF000, x, 0x07),
+            Self::WaitKey { x } => xnn(0xF000, x, 0x0A),
+            Self::SetDelay { x } => xnn(0xF000, x, 0x15),
+            Self::SetSound { x } => xnn(0xF000, x, 0x18),
+            Self::AddI { x } => xnn(0xF000, x, 0x1E),
+            Self::LoadFont { x } => xnn(0xF000, x, 0x29),
+            Self::Bcd { x } => xnn(0xF000, x, 0x33),
+            Self::Store { x } => xnn(0xF000, x, 0x55),
+            Self::Load { x } => xnn(0xF000, x, 0x65),
+        }
+    }
+
+    /// Decodes the instruction at `addr` of `rom`, which is loaded at [`PROGRAM_START`]. Returns the opcode and the
+    /// instruction, or `None` if there are no two bytes at `addr`. Unlike with [`Instruction::iter_rom`], `addr` may
+    /// be odd, like the target of a jump.
+    pub fn fetch(rom: &[u8], addr: usize) -> Option<(u16, Result<Instruction, IllegalOpcode>)> {
+        let offset = addr.checked_sub(PROGRAM_START)?;
+        let bytes = rom.get(offset..offset + 2)?;
+        let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+        Some((opcode, Self::decode(opcode)))
+    }
+
+    /// Decodes `rom` two bytes at a time from the start, regardless of whether it's code or data. Yields the
+    /// address (with the ROM loaded at [`PROGRAM_START`]), the opcode and the instruction. A trailing odd byte is
+    /// left out. Use [`crate::disassembler::Disassembly`] to only decode the reachable instructions.
+    pub fn iter_rom(rom: &[u8]) -> impl Iterator<Item = (usize, u16, Result<Instruction, IllegalOpcode>)> + '_ {
+        (PROGRAM_START..PROGRAM_START + rom.len()).step_by(2).map_while(move |addr| {
+            let (opcode, instruction) = Self::fetch(rom, addr)?;
+            Some((addr, opcode, instruction))
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Writes the mnemonic, e.g. `LD V1, 0x05`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Sys { addr } => write!(f, "SYS {:#05X}", addr),
+            Self::Cls => write!(f, "CLS"),
+            Self::Ret => write!(f, "RET"),
+            Self::Jump { addr } => write!(f, "JP {:#05X}", addr),
+            Self::Call { addr } => write!(f, "CALL {:#05X}", addr),
+            Self::SkipEqByte { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Self::SkipNeByte { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Self::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::LoadByte { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Self::AddByte { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Self::LoadReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Self::Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Self::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::LoadI { addr } => write!(f, "LD I, {:#05X}", addr),
+            Self::JumpV0 { addr } => write!(f, "JP V0, {:#05X}", addr),
+            Self::Random { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Self::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Self::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Self::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Self::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Self::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Self::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Self::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Self::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Self::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Self::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Self::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Self::Load { x } => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Which host key presses which key of the hex keypad. By default, the 4x4 keypad is laid onto the left-hand side of
+//! a QWERTY keyboard:
+//!
+//! ```text
+//! 1 2 3 C      1 2 3 4
+//! 4 5 6 D  ->  Q W E R
+//! 7 8 9 E      A S D F
+//! A 0 B F      Z X C V
+//! ```
+//!
+//! On other keyboard layouts, the same physical keys have other characters, e.g. `A Z E R` on AZERTY. The bundled
+//! [`Keymap::LAYOUTS`] keep the physical 4x4 grid, and [`layout_for_locale`] guesses the layout.
+
+/// Arrangement of the keys on the original hex keypad, row by row.
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keymap {
+    /// The host key of every key of the keypad, lowercase.
+    chars: [char; 16],
+}
+
+impl Keymap {
+    /// The default mapping shown above.
+    pub const QWERTY: Self = Self::from_rows([
+        ['1', '2', '3', '4'],
+        ['q', 'w', 'e', 'r'],
+        ['a', 's', 'd', 'f'],
+        ['z', 'x', 'c', 'v'],
+    ]);
+    /// French AZERTY, where the digits need `Shift`, so the characters without it are used.
+    pub const AZERTY: Self = Self::from_rows([
+        ['&', 'é', '"', '\''],
+        ['a', 'z', 'e', 'r'],
+        ['q', 's', 'd', 'f'],
+        ['w', 'x', 'c', 'v'],
+    ]);
+    /// German QWERTZ, where `Y` and `Z` are swapped.
+    pub const QWERTZ: Self = Self::from_rows([
+        ['1', '2', '3', '4'],
+        ['q', 'w', 'e', 'r'],
+        ['a', 's', 'd', 'f'],
+        ['y', 'x', 'c', 'v'],
+    ]);
+    pub const DVORAK: Self = Self::from_rows([
+        ['1', '2', '3', '4'],
+        ['\'', ',', '.', 'p'],
+        ['a', 'o', 'e', 'u'],
+        [';', 'q', 'j', 'k'],
+    ]);
+
+    /// Names of the bundled layouts, as accepted by [`Keymap::layout`].
+    pub const LAYOUTS: [&'static str; 4] = ["qwerty", "azerty", "qwertz", "dvorak"];
+
+    /// The characters of the 4x4 keys on the left of the keyboard, row by row, pressing the keys of
+    /// [`KEYPAD_LAYOUT`].
+    const fn from_rows(rows: [[char; 4]; 4]) -> Self {
+        let mut chars = ['\0'; 16];
+        let mut i = 0;
+        while i < 16 {
+            chars[KEYPAD_LAYOUT[i / 4][i % 4] as usize] = rows[i / 4][i % 4];
+            i += 1;
+        }
+        Self { chars }
+    }
+
+    /// The default mapping for a keyboard layout by its name, like `azerty`.
+    pub fn layout(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" => Some(Self::QWERTY),
+            "azerty" => Some(Self::AZERTY),
+            "qwertz" => Some(Self::QWERTZ),
+            "dvorak" => Some(Self::DVORAK),
+            _ => None,
+        }
+    }
+
+    /// The key of the keypad pressed by the host key `c`, ignoring case.
+    pub fn key_for_char(&self, c: char) -> Option<u8> {
+        let c = c.to_ascii_lowercase();
+        self.chars.iter().position(|&mapped| mapped == c).map(|key| key as u8)
+    }
+
+    /// The host key pressing `key` of the keypad.
+    pub fn char_for_key(&self, key: u8) -> char {
+        self.chars[key as usize & 0xF]
+    }
+
+    /// Lets the host key `c` press `key`. The key that `c` pressed before gets the old host key of `key`, so that
+    /// every host key stays assigned to one key. Whitespace and control characters are rejected.
+    pub fn set(&mut self, key: u8, c: char) -> Result<(), String> {
+        if c.is_whitespace() || c.is_control() {
+            return Err(format!("{:?} can't be used, only letters, digits and punctuation", c));
+        }
+        let c = c.to_ascii_lowercase();
+        let key = key as usize & 0xF;
+        if let Some(previous) = self.key_for_char(c) {
+            self.chars[previous as usize] = self.chars[key];
+        }
+        self.chars[key] = c;
+        Ok(())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::QWERTY
+    }
+}
+
+/// Guesses the keyboard layout from a locale like `de_DE.UTF-8`, as in `$LANG`. Dvorak can't be told from the
+/// locale, and other locales are assumed to use QWERTY.
+pub fn layout_for_locale(locale: &str) -> &'static str {
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let (language, country) = locale.split_once('_').unwrap_or((locale, ""));
+    match (language, country) {
+        // French in Canada and Switzerland uses QWERTY and QWERTZ
+        ("fr", "CA") => "qwerty",
+        ("fr", "CH") => "qwertz",
+        ("fr", _) | ("br", _) => "azerty",
+        ("de", _) | ("cs", _) | ("sk", _) | ("hu", _) | ("sl", _) | ("hr", _) | ("bs", _) | ("sq", _) => "qwertz",
+        _ => "qwerty",
+    }
+}
@@ -0,0 +1,69 @@
+//! A [Chip-8](https://en.wikipedia.org/wiki/CHIP-8) interpreter.
+
+pub mod assembler;
+#[cfg(feature = "async")]
+pub mod async_driver;
+pub mod audio;
+pub mod batch;
+pub mod bench;
+mod builder;
+pub mod bus;
+pub mod cfg;
+pub mod cheats;
+mod chip8;
+pub mod compat;
+pub mod compare;
+pub mod config;
+pub mod console;
+pub mod crash;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod debugger;
+pub mod disassembler;
+mod display;
+pub mod emu;
+pub mod examples;
+pub mod font;
+pub mod hooks;
+#[cfg(feature = "http")]
+pub mod http_api;
+pub mod image;
+pub mod inline_image;
+pub mod input;
+pub mod input_script;
+pub mod instruction;
+pub mod keymap;
+pub mod lint;
+pub mod memcheck;
+mod metrics;
+pub mod multi;
+pub mod netplay;
+pub mod profile;
+mod quirks;
+pub mod recording;
+pub mod replay;
+#[cfg(feature = "websocket")]
+pub mod remote;
+pub mod rewind;
+pub mod rom;
+#[cfg(feature = "lua")]
+pub mod script;
+pub mod serial;
+pub mod sixel;
+pub mod snapshot;
+#[cfg(feature = "websocket")]
+pub mod stream;
+pub mod symbols;
+pub mod terminal;
+pub mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+pub use crate::chip8::{
+    Chip8, Chip8Error, RunPolicy, RunStatus, StepEvent, StepEvents, DEFAULT_STACK_DEPTH, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+    MAX_PROGRAM_SIZE, PROGRAM_START,
+};
+pub use crate::builder::Chip8Builder;
+pub use crate::display::{Framebuffer, Rect};
+pub use crate::metrics::Metrics;
+pub use crate::quirks::Quirks;
@@ -0,0 +1,193 @@
+//! Static analysis of ROMs, finding bugs without running them. Like the [`crate::disassembler`], the linter follows
+//! the control flow from the entry point, but it reports what it can't follow instead of treating it as data:
+//!
+//! * illegal instructions and machine routines (`SYS`) that are reachable
+//! * execution running past the end of the program
+//! * jumps and calls to odd addresses or outside of the program
+//! * `RET` without a call, recursion and calls nested deeper than the stack
+//! * `FX33` and `FX55` writing below the program, where the interpreter keeps the font
+//!
+//! The value of `I` is only known after `ANNN` on the same path, so not every write below the program is found.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use crate::instruction::Instruction;
+use crate::{DEFAULT_STACK_DEPTH, PROGRAM_START};
+
+/// A problem found by the linter.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Warning {
+    /// Address of the instruction causing the problem.
+    pub addr: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05X}: {}", self.addr, self.message)
+    }
+}
+
+/// What a subroutine (or the main program) does, as far as it matters for the stack.
+#[derive(Debug, Default)]
+struct Subroutine {
+    /// Address of the `CALL` instruction and the called subroutine.
+    calls: Vec<(usize, usize)>,
+    /// Addresses of reachable `RET` instructions.
+    returns: Vec<usize>,
+}
+
+struct Linter<'a> {
+    rom: &'a [u8],
+    warnings: BTreeSet<Warning>,
+}
+
+/// Analyzes `rom`, which is loaded at [`PROGRAM_START`], and returns the warnings ordered by address.
+pub fn lint(rom: &[u8]) -> Vec<Warning> {
+    let mut linter = Linter { rom, warnings: BTreeSet::new() };
+    let mut subroutines = BTreeMap::new();
+    let mut pending = vec![PROGRAM_START];
+    while let Some(entry) = pending.pop() {
+        if subroutines.contains_key(&entry) {
+            continue;
+        }
+        let subroutine = linter.walk(entry);
+        pending.extend(subroutine.calls.iter().map(|&(_, target)| target));
+        subroutines.insert(entry, subroutine);
+    }
+
+    for &addr in &subroutines[&PROGRAM_START].returns {
+        linter.warn(addr, "Returns without a subroutine call, which underflows the stack");
+    }
+    let mut depths = BTreeMap::new();
+    let depth = linter.call_depth(&subroutines, PROGRAM_START, &mut vec![PROGRAM_START], &mut depths);
+    if depth > DEFAULT_STACK_DEPTH as usize {
+        // Point at the call that starts the deepest chain
+        let (site, _) = subroutines[&PROGRAM_START].calls.iter()
+            .find(|&&(_, target)| 1 + depths.get(&target).copied().unwrap_or(0) == depth)
+            .copied()
+            .unwrap_or((PROGRAM_START, PROGRAM_START));
+        linter.warn(site, format!(
+            "Subroutine calls nest {} levels deep, but the stack only holds {}",
+            depth, DEFAULT_STACK_DEPTH,
+        ));
+    }
+    linter.warnings.into_iter().collect()
+}
+
+impl Linter<'_> {
+    /// Follows the control flow from `entry` up to the `RET` instructions. Calls aren't followed, but recorded.
+    fn walk(&mut self, entry: usize) -> Subroutine {
+        let mut subroutine = Subroutine::default();
+        let mut visited = BTreeSet::new();
+        // Address of the next instruction and the value of I, if it's known
+        let mut pending = vec![(entry, None)];
+        while let Some((addr, i)) = pending.pop() {
+            if !visited.insert(addr) {
+                continue;
+            }
+            let instruction = match Instruction::fetch(self.rom, addr) {
+                Some((_, Ok(instruction))) => instruction,
+                Some((_, Err(err))) => {
+                    self.warn(addr, format!("{} is reachable", err));
+                    continue;
+                },
+                None => {
+                    self.warn(addr, "Execution runs past the end of the program");
+                    continue;
+                },
+            };
+            let next = addr + 2;
+            match instruction {
+                Instruction::Ret => subroutine.returns.push(addr),
+                Instruction::Sys { .. } => {
+                    self.warn(addr, "Machine routines (SYS) are not supported by the interpreter");
+                },
+                Instruction::Jump { addr: target } => {
+                    if self.check_target(addr, target as usize, "Jump") {
+                        pending.push((target as usize, i));
+                    }
+                },
+                Instruction::Call { addr: target } => {
+                    if self.check_target(addr, target as usize, "Call") {
+                        subroutine.calls.push((addr, target as usize));
+                    }
+                    // The subroutine may change I
+                    pending.push((next, None));
+                },
+                Instruction::SkipEqByte { .. }
+                | Instruction::SkipNeByte { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKey { .. }
+                | Instruction::SkipNotKey { .. } => pending.extend([(next, i), (addr + 4, i)]),
+                Instruction::LoadI { addr: target } => pending.push((next, Some(target as usize))),
+                // The target of `BNNN` depends on V0, so it can't be followed
+                Instruction::JumpV0 { .. } => {},
+                _ => {
+                    let writes = matches!(instruction, Instruction::Bcd { .. } | Instruction::Store { .. });
+                    if let Some(i) = i.filter(|&i| writes && i < PROGRAM_START) {
+                        self.warn(addr, format!("Writes to {:#05X}, below the program where the font is stored", i));
+                    }
+                    // Whether `FX55` and `FX65` increment I depends on the quirks
+                    let i = match instruction {
+                        Instruction::AddI { .. }
+                        | Instruction::LoadFont { .. }
+                        | Instruction::Store { .. }
+                        | Instruction::Load { .. } => None,
+                        _ => i,
+                    };
+                    pending.push((next, i));
+                },
+            }
+        }
+        subroutine
+    }
+
+    /// Checks that the jump or call at `addr` goes to an instruction of the program. Returns whether the target
+    /// can be followed.
+    fn check_target(&mut self, addr: usize, target: usize, kind: &str) -> bool {
+        if target % 2 == 1 {
+            self.warn(addr, format!("{} to odd address {:#05X}", kind, target));
+        }
+        if !(PROGRAM_START..PROGRAM_START + self.rom.len()).contains(&target) {
+            self.warn(addr, format!("{} to {:#05X}, outside of the program", kind, target));
+            return false;
+        }
+        true
+    }
+
+    /// Returns how deep the subroutine calls starting at `entry` nest, and warns about recursion. `chain` are the
+    /// subroutines currently being called, `depths` caches the depths of analyzed subroutines.
+    fn call_depth(
+        &mut self,
+        subroutines: &BTreeMap<usize, Subroutine>,
+        entry: usize,
+        chain: &mut Vec<usize>,
+        depths: &mut BTreeMap<usize, usize>,
+    ) -> usize {
+        let mut depth = 0;
+        for &(site, target) in &subroutines[&entry].calls {
+            if chain.contains(&target) {
+                self.warn(site, format!("Recursive call to {:#05X}, which may overflow the stack", target));
+                continue;
+            }
+            let target_depth = match depths.get(&target) {
+                Some(&target_depth) => target_depth,
+                None => {
+                    chain.push(target);
+                    let target_depth = self.call_depth(subroutines, target, chain, depths);
+                    chain.pop();
+                    depths.insert(target, target_depth);
+                    target_depth
+                },
+            };
+            depth = depth.max(1 + target_depth);
+        }
+        depth
+    }
+
+    fn warn(&mut self, addr: usize, message: impl Into<String>) {
+        self.warnings.insert(Warning { addr, message: message.into() });
+    }
+}
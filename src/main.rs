@@ -1,601 +1,1194 @@
-use std::fs::File;
-use std::io::Read;
+use std::env;
 use std::error::Error;
-use std::thread;
-use std::time::Duration;
-use thiserror::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chip8::{batch, bench, cfg, compare, compat, lint, rom, serial, Chip8, Chip8Error, Quirks, RunPolicy, RunStatus};
+use chip8::{DISPLAY_HEIGHT, PROGRAM_START};
+use chip8::assembler::{self, Assembly};
+use chip8::audio::{self, Tone};
+use chip8::cheats::CheatTable;
+use chip8::config::Config;
+use chip8::crash::CrashReport;
+use chip8::console;
+use chip8::debugger::{Command, Debugger, Location, RegisterDump};
+use chip8::disassembler::{Disassembly, FormatOptions, Syntax};
+use chip8::examples::{self, Example};
+use chip8::font::Font;
+use chip8::hooks::Hooks;
+use chip8::image::Palette;
+use chip8::input_script::InputScript;
+use chip8::keymap::{self, Keymap};
+use chip8::inline_image::{self, InlineImageRenderer, Protocol};
+use chip8::multi::MultiInstance;
+use chip8::netplay::Netplay;
+use chip8::profile::CallProfiler;
+use chip8::symbols::Symbols;
+use chip8::recording::{GifRecorder, VideoRecorder, WavRecorder};
+use chip8::rom::RomId;
+use chip8::replay::{Replay, ReplayPlayer, ReplayRecorder};
+use chip8::rewind::Rewind;
+use chip8::serial::SerialConsole;
+use chip8::sixel::SixelRenderer;
+use chip8::snapshot::Snapshot;
+use chip8::terminal::{self, TerminalBell, TerminalInput, VisualBell};
+use chip8::trace::{JsonTracer, MemoryTracer, ReferenceTrace, TraceFilter};
+
+const USAGE: &str = "\
+Usage: chip8 <command> [options]
+
+Commands:
+  run <rom>      Run a ROM (the default if no command is given, e.g. `chip8 game.ch8`), or a built-in example with
+                 `--example maze`. Without a ROM, the examples are offered.
+  disasm <rom>   Disassemble a ROM
+  asm <source>   Assemble Octo source into a ROM
+  debug <rom>    Debug a ROM on the console (`--tui` for full screen), or a core dump with `debug --core FILE`
+  info <rom>     Show what is known about a ROM
+  bench <rom>    Measure the speed of the interpreter
+  compare <rom>  Run a ROM with two quirk configurations and find where they diverge
+  multi <rom>... Run several ROMs side by side, `Tab` switches the keyboard between them
+  batch <dir>    Run every ROM in a directory headless and report errors
+  lint <rom>     Find bugs in a ROM without running it
+  cfg <rom>      Print the control-flow graph of a ROM in DOT format
+  diff <a> <b>   Compare two snapshots
+  keys           Choose the host keys of the hex keypad (`--layout azerty` for a layout, `--reset` for the default)
+  help           Show this message
+";
 
-static SPRITE_FOR_CHARS: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0x10, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-
-/// Things to mention:
-/// * vx means register number x.
-/// * nn is a constant number (called `number_in`) supplied in the opcode.
-#[derive(Debug, PartialEq, Eq)]
-struct Chip8 {
-    mem: [u8; 4096],
-    /// Registers (V) called V0, V1, ..., V9, VA, VB, ..., VF (hex number of the register is appended).
-    registers: [u8; 16],
-    /// 16 bit address register (I).
-    address_register: u16,
-    /// Program counter (PC).
-    pc: usize,
-
-    stack: [usize; 12],
-    stack_pointer: u8,
-
-    /// The display as a bit array. Access like `display[y][x]`.
-    display: [[u8; 8]; 32],
-    /// Current key pressed by the user.
-    current_key: u8,
-
-    delay_timer: u8,
-    sound_timer: u8,
-
-    refresh_display: bool,
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, subcommand_args) = match args.split_first() {
+        Some((command, subcommand_args)) => (command.as_str(), subcommand_args.to_vec()),
+        // Started without arguments, e.g. by `cargo run`, there's a menu of the examples
+        None if io::stdin().is_terminal() => return run_rom(args.into_iter()),
+        None => {
+            print!("{}", USAGE);
+            return Ok(());
+        },
+    };
+    let mut subcommand_args = subcommand_args.into_iter();
+    match command {
+        "run" => run_rom(subcommand_args),
+        "disasm" => run_disasm(subcommand_args),
+        "asm" => run_asm(subcommand_args),
+        "debug" => run_debug(subcommand_args),
+        "info" => run_info(subcommand_args),
+        "bench" => run_bench(subcommand_args),
+        "multi" => run_multi(subcommand_args),
+        "compare" => run_compare(subcommand_args),
+        "batch" => run_batch(subcommand_args),
+        "lint" => run_lint(subcommand_args),
+        "cfg" => run_cfg(subcommand_args),
+        "keys" => run_keys(subcommand_args),
+        "diff" => {
+            let first = subcommand_args.next().ok_or("diff requires two snapshots")?;
+            let second = subcommand_args.next().ok_or("diff requires two snapshots")?;
+            let load = |path: &str| Snapshot::load(path).map_err(|err| format!("Can't read {}: {}", path, err));
+            println!("{}", load(&first)?.diff(&load(&second)?));
+            Ok(())
+        },
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            Ok(())
+        },
+        // Running is the default, so the command can be left out
+        _ => run_rom(args.into_iter()),
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Error)]
-enum Chip8Error {
-    #[error("Encountered illegal instruction {opcode:#X} at PC={pc}")]
-    IllegalInstruction {
-        opcode: u16,
-        pc: usize
-    },
-
-    #[error("Stack overflow")]
-    StackOverflow,
-
-    #[error("Machine routine nr.{0} called, but is not implemented")]
-    UnknownMachineRoutine(u16),
-}
+/// `chip8 run <rom> [options]`: Runs the ROM in the terminal. `-` reads the ROM from stdin.
+fn run_rom(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut example = None;
+    let mut profile_exec = false;
+    let mut check_uninit = false;
+    let mut write_protection = None;
+    let mut hot_spots = None;
+    let mut flamegraph = None;
+    let mut trace_json = None;
+    let mut trace_filter = None;
+    let mut reference_trace = None;
+    let mut trace_memory = None;
+    let mut trace_memory_range = None;
+    let mut script = None;
+    let mut seed = None;
+    let mut host = None;
+    let mut connect = None;
+    let mut debug_ws = None;
+    let mut stream = None;
+    let mut http = None;
+    let mut dashboard = false;
+    let mut palette = Palette::default();
+    let mut record_gif = None;
+    let mut gif_scale = 4;
+    let mut gif_frame_skip = 0;
+    let mut record_video = None;
+    let mut video_scale = 8;
+    let mut record_wav = None;
+    let mut tone = Tone::default();
+    let mut sample_rate = None;
+    let mut volume = None;
+    let mut record_replay = None;
+    let mut replay = None;
+    let mut input_script = None;
+    let mut cheats = None;
+    let mut font = None;
+    let mut layout = None;
+    let mut load_addr = None;
+    let mut entry = None;
+    let mut segments = Vec::new();
+    let mut reduce_flicker = None;
+    let mut phosphor = None;
+    let mut renderer = None;
+    let mut renderer_scale = 6;
+    let mut rom_db = None;
+    let mut platform = None;
+    let mut quirk_overrides = Vec::new();
+    let mut rom_sha1 = None;
+    let mut symbols_path = None;
+    let mut serial_addr = None;
+    let mut bell = false;
+    let mut visual_bell = false;
+    let mut show_keypad = false;
+    let mut show_stats = false;
+    let mut ipf = None;
+    let mut run_policy = None;
+    let mut keep_going = false;
+    let mut stack_depth = None;
+    let mut core_dump = false;
+    let mut save_state = None;
+    let mut time_travel = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile-exec" => profile_exec = true,
+            "--check-uninit" => check_uninit = true,
+            "--write-protection" => {
+                write_protection = Some(args.next().ok_or("--write-protection requires off, warn or error")?.parse()?)
+            },
+            "--flamegraph" => flamegraph = Some(args.next().ok_or("--flamegraph requires a file")?),
+            "--hot-spots" => {
+                hot_spots = Some(args.next().ok_or("--hot-spots requires a number of instructions")?.parse()?)
+            },
+            "--trace-json" => trace_json = Some(args.next().ok_or("--trace-json requires a file")?),
+            "--trace-only" => {
+                let list = args.next().ok_or("--trace-only requires a list like draw,call")?;
+                trace_filter.get_or_insert_with(TraceFilter::new).set_only(&list)?
+            },
+            "--trace-range" => {
+                let range = args.next().ok_or("--trace-range requires a range like 0x200..0x300")?;
+                trace_filter.get_or_insert_with(TraceFilter::new).set_range(parse_range(&range)?);
+            },
+            "--trace-memory" => {
+                trace_memory = Some(args.next().ok_or("--trace-memory requires a file, or - for stderr")?)
+            },
+            "--trace-memory-range" => {
+                let range = args.next().ok_or("--trace-memory-range requires a range like 0x300..0x310")?;
+                trace_memory_range = Some(parse_range(&range)?);
+            },
+            "--reference-trace" => {
+                reference_trace = Some(args.next().ok_or("--reference-trace requires a file")?)
+            },
+            "--script" => script = Some(args.next().ok_or("--script requires a file")?),
+            "--seed" => seed = Some(args.next().ok_or("--seed requires a number")?.parse::<u64>()?),
+            "--host" => host = Some(args.next().ok_or("--host requires an address")?),
+            "--connect" => connect = Some(args.next().ok_or("--connect requires an address")?),
+            "--debug-ws" => debug_ws = Some(args.next().ok_or("--debug-ws requires an address")?),
+            "--stream" => stream = Some(args.next().ok_or("--stream requires an address")?),
+            "--http" => http = Some(args.next().ok_or("--http requires an address")?),
+            "--dashboard" => dashboard = true,
+            "--palette" => palette = args.next().ok_or("--palette requires colors like #FFFFFF,#000000")?.parse()?,
+            "--record-gif" => record_gif = Some(args.next().ok_or("--record-gif requires a file")?),
+            "--gif-scale" => gif_scale = args.next().ok_or("--gif-scale requires a number")?.parse()?,
+            "--gif-frame-skip" => gif_frame_skip = args.next().ok_or("--gif-frame-skip requires a number")?.parse()?,
+            "--record-video" => record_video = Some(args.next().ok_or("--record-video requires a file")?),
+            "--video-scale" => video_scale = args.next().ok_or("--video-scale requires a number")?.parse()?,
+            "--renderer" => {
+                renderer = Some(args.next().ok_or("--renderer requires auto, text, sixel, kitty or iterm2")?)
+            },
+            "--renderer-scale" => {
+                renderer_scale = args.next().ok_or("--renderer-scale requires a number")?.parse()?
+            },
+            "--record-wav" => record_wav = Some(args.next().ok_or("--record-wav requires a file")?),
+            "--beep-frequency" => {
+                tone.frequency = args.next().ok_or("--beep-frequency requires a frequency in Hz")?.parse()?
+            },
+            "--beep-waveform" => {
+                tone.waveform = args.next().ok_or("--beep-waveform requires square, triangle or sine")?.parse()?
+            },
+            "--beep-volume" => tone.volume = parse_percent(&args.next().ok_or("--beep-volume requires a percentage")?)?,
+            "--beep-duty" => tone.duty_cycle = parse_percent(&args.next().ok_or("--beep-duty requires a percentage")?)?,
+            "--volume" => volume = Some(parse_percent(&args.next().ok_or("--volume requires a percentage")?)?),
+            "--sample-rate" => {
+                sample_rate = Some(args.next().ok_or("--sample-rate requires a sample rate in Hz")?.parse::<u32>()?)
+            },
+            "--record-replay" => record_replay = Some(args.next().ok_or("--record-replay requires a file")?),
+            "--replay" => replay = Some(args.next().ok_or("--replay requires a file")?),
+            "--input-script" => input_script = Some(args.next().ok_or("--input-script requires a file")?),
+            "--cheats" => cheats = Some(args.next().ok_or("--cheats requires a cheat table file")?),
+            "--reduce-flicker" => {
+                reduce_flicker = Some(args.next().ok_or("--reduce-flicker requires a number of frames")?.parse()?);
+            },
+            "--phosphor" => phosphor = Some(args.next().ok_or("--phosphor requires a number of frames")?.parse()?),
+            "--layout" => layout = Some(args.next().ok_or("--layout requires a keyboard layout like azerty")?),
+            "--font" => font = Some(args.next().ok_or("--font requires a font file or a name like vip")?),
+            "--rom-db" => rom_db = Some(args.next().ok_or("--rom-db requires the programs.json of the database")?),
+            "--platform" => platform = Some(args.next().ok_or("--platform requires a platform like originalChip8")?),
+            "--quirk" => quirk_overrides.push(args.next().ok_or("--quirk requires a quirk like vblank=false")?),
+            "--rom-sha1" => rom_sha1 = Some(args.next().ok_or("--rom-sha1 requires a SHA-1 hash")?),
+            "--symbols" => symbols_path = Some(args.next().ok_or("--symbols requires a file")?),
+            "--load-addr" => {
+                load_addr = Some(parse_addr(&args.next().ok_or("--load-addr requires an address like 0x600")?)?)
+            },
+            "--example" => example = Some(args.next().ok_or("--example requires a name like maze")?),
+            "--load" => segments.push(args.next().ok_or("--load requires a file and an address like data.bin@0x300")?),
+            "--entry" => entry = Some(parse_addr(&args.next().ok_or("--entry requires an address like 0x600")?)?),
+            "--serial" => {
+                let addr = args.next()
+                    .ok_or_else(|| format!("--serial requires an address like {:#X}", serial::DEFAULT_ADDR))?;
+                let addr = parse_addr(&addr)?;
+                // The console wraps the flat memory
+                if addr >= chip8::bus::MEMORY_SIZE {
+                    return Err(format!("The serial address {:#X} is beyond the memory", addr).into());
+                }
+                serial_addr = Some(addr);
+            },
+            "--bell" => bell = true,
+            "--visual-bell" => visual_bell = true,
+            "--keypad" => show_keypad = true,
+            "--stats" => show_stats = true,
+            "--keep-going" => keep_going = true,
+            "--core-dump" => core_dump = true,
+            "--time-travel" => time_travel = true,
+            "--save-state" => save_state = Some(args.next().ok_or("--save-state requires a file")?),
+            "--stack-depth" => {
+                stack_depth = Some(args.next().ok_or("--stack-depth requires a number up to 255")?.parse()?)
+            },
+            "--ipf" => ipf = Some(args.next().ok_or("--ipf requires a number of instructions per frame")?.parse()?),
+            "--run-policy" => {
+                let policy = args.next()
+                    .ok_or("--run-policy requires forever, until-halt or a number of instructions")?;
+                run_policy = Some(policy.parse::<RunPolicy>()?);
+            },
+            _ => file_path = Some(arg),
+        }
+    }
 
-impl Chip8 {
-    pub fn new(program: &[u8]) -> Self {
-        let mut chip8 = Self {
-            mem: [0; 4096],
-            registers: Default::default(),
-            address_register: 0,
-            pc: 512,
-            stack: Default::default(),
-            stack_pointer: 0,
-            display: [[0; 8]; 32],
-            current_key: 0,
-            delay_timer: 0,
-            sound_timer: 0,
-            refresh_display: true,
+    // A headless instance can get its ROM over HTTP later on. Otherwise, an example runs if no ROM is given.
+    let rom = match (&file_path, example, &http) {
+        (Some(file_path), _, _) => {
+            Some(rom::load(file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?)
+        },
+        (None, Some(name), _) => {
+            let names: Vec<_> = examples::EXAMPLES.iter().map(|example| example.name).collect();
+            let example = examples::named(&name)
+                .ok_or_else(|| format!("Unknown example {}, expected one of {}", name, names.join(", ")))?;
+            Some(example.load())
+        },
+        (None, None, Some(_)) => None,
+        (None, None, None) => Some(choose_example()?.load()),
+    };
+    let program = rom.as_ref().map(|rom| rom.bytes.clone()).unwrap_or_default();
+    // Symbols come from the assembler, unless a symbol file is given. Only the debuggers use them.
+    let assembly = rom.and_then(|rom| rom.assembly);
+    let symbols = match symbols_path {
+        Some(symbols_path) => Symbols::load(symbols_path)?,
+        None => assembly.as_ref().map(Assembly::symbols).unwrap_or_default(),
+    };
+    if let Some(rom_sha1) = rom_sha1 {
+        rom::verify_sha1(&program, &rom_sha1).map_err(|err| err.to_string())?;
+    }
+    // If the ROM was piped in, the keyboard is still available through the terminal
+    let rom_from_stdin = file_path.as_deref() == Some("-");
+    let mut chip8 = match serial_addr {
+        // Input can only come from stdin if it's neither used for the keyboard nor for the ROM
+        Some(addr) if !io::stdin().is_terminal() && !rom_from_stdin => {
+            Chip8::with_bus(&[], SerialConsole::stdio([0; chip8::bus::MEMORY_SIZE], addr))
+        },
+        Some(addr) => {
+            let (_, no_input) = mpsc::channel();
+            Chip8::with_bus(&[], SerialConsole::new([0; chip8::bus::MEMORY_SIZE], addr, no_input, io::stderr()))
+        },
+        None => Chip8::new(&[]),
+    };
+    let load_addr = load_addr.unwrap_or(PROGRAM_START);
+    chip8.load(load_addr, &program)?;
+    for segment in &segments {
+        let (path, addr) = parse_segment(segment)?;
+        let bytes = fs::read(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        chip8.load(addr, &bytes)?;
+    }
+    chip8.set_entry(entry.unwrap_or(load_addr));
+    let rom_id = RomId::of(&program);
+    if !program.is_empty() {
+        println!("ROM: {}", rom_id);
+    }
+    if let Some(profile) = compat::lookup_sha1(&rom_id.sha1) {
+        println!("Detected {} ({}, {} instructions per frame)", profile.title, profile.platform, profile.tickrate);
+        profile.apply(&mut chip8);
+    }
+    match rom_db {
+        #[cfg(feature = "database")]
+        Some(rom_db) => match chip8::database::RomDatabase::load(rom_db)?.lookup_sha1(&rom_id.sha1) {
+            Some(info) => {
+                println!("{}", info);
+                info.apply(&mut chip8);
+            },
+            None => println!("ROM not found in the database"),
+        },
+        #[cfg(not(feature = "database"))]
+        Some(_) => return Err("--rom-db requires building with the `database` feature".into()),
+        None => {},
+    }
+    // Explicit settings override the detected ones
+    if let Some(platform) = &platform {
+        let quirks = Quirks::for_platform(platform).ok_or_else(|| format!("Unknown platform {}", platform))?;
+        chip8.set_quirks(quirks);
+    }
+    for quirk in quirk_overrides {
+        apply_quirk_override(&mut chip8, &quirk)?;
+    }
+    if let Some(stack_depth) = stack_depth {
+        chip8.set_stack_depth(stack_depth);
+    }
+    if let Some(font) = font {
+        chip8.set_font(&load_font(&font)?);
+    }
+    if let Some(frames) = reduce_flicker {
+        chip8.set_flicker_reduction(frames);
+    }
+    if let Some(frames) = phosphor {
+        chip8.set_phosphor_decay(frames);
+    }
+    let config_path = Config::default_path();
+    let config = match &config_path {
+        Some(path) => Config::load(path).map_err(|err| format!("Can't read config {}: {}", path.display(), err))?,
+        None => Config::default(),
+    };
+    let mut mixer = config.mixer;
+    if let Some(volume) = volume {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err("The volume has to be between 0 and 100%".into());
+        }
+        mixer.set_volume(volume);
+    }
+    chip8.set_mixer(mixer);
+    if let Some(ipf) = ipf {
+        chip8.set_instructions_per_frame(ipf);
+    }
+    if let Some(run_policy) = run_policy {
+        chip8.set_run_policy(run_policy);
+    }
+    if let Some(seed) = seed {
+        chip8.set_seed(seed);
+    }
+    // Graphics replace the character art. Inline images are used automatically where the terminal supports them.
+    let renderer: Option<Box<dyn Hooks>> = match renderer.as_deref() {
+        None | Some("auto") => inline_image::detect()
+            .filter(|_| io::stdout().is_terminal())
+            .map(|protocol| Box::new(InlineImageRenderer::new(protocol, renderer_scale, palette)) as Box<dyn Hooks>),
+        Some("text") => None,
+        Some("sixel") => Some(Box::new(SixelRenderer::new(renderer_scale, palette))),
+        Some("kitty") => Some(Box::new(InlineImageRenderer::new(Protocol::Kitty, renderer_scale, palette))),
+        Some("iterm2") => Some(Box::new(InlineImageRenderer::new(Protocol::Iterm2, renderer_scale, palette))),
+        Some(other) => {
+            return Err(format!("Unknown renderer {}, expected auto, text, sixel, kitty or iterm2", other).into())
+        },
+    };
+    if renderer.is_some() {
+        chip8.set_text_output(false);
+    }
+    match (bell, visual_bell) {
+        (true, true) => return Err("--bell and --visual-bell can't be combined".into()),
+        (true, false) => chip8.set_beeper(TerminalBell),
+        (false, true) => chip8.set_beeper(VisualBell::new()),
+        (false, false) => {},
+    }
+    if check_uninit {
+        chip8.enable_memory_check();
+    }
+    if let Some(write_protection) = write_protection {
+        chip8.set_write_protection(write_protection);
+    }
+    if profile_exec || hot_spots.is_some() {
+        chip8.enable_profiling();
+    }
+    match (trace_json, trace_filter) {
+        (Some(trace_path), trace_filter) => {
+            let mut tracer = JsonTracer::create(trace_path)?;
+            if let Some(trace_filter) = trace_filter {
+                tracer.set_filter(trace_filter);
+            }
+            chip8.set_tracer(tracer);
+        },
+        (None, Some(_)) => return Err("--trace-only and --trace-range require --trace-json".into()),
+        (None, None) => {},
+    }
+    if trace_memory.is_none() && trace_memory_range.is_some() {
+        return Err("--trace-memory-range requires --trace-memory".into());
+    }
+    if let Some(trace_path) = trace_memory {
+        // The terminal shows the display on stdout, but stderr can be redirected
+        let mut tracer = match trace_path.as_str() {
+            "-" => MemoryTracer::new(io::stderr()),
+            _ => MemoryTracer::create(&trace_path).map_err(|err| format!("Can't write {}: {}", trace_path, err))?,
         };
-
-        // Copy sprites to memory
-        chip8.mem[0x50..=0x9F].copy_from_slice(&SPRITE_FOR_CHARS);
-
-        // Copy program to memory starting by memory address 512
-        chip8.mem[512..512+program.len()].copy_from_slice(program);
-        chip8
+        if let Some(range) = trace_memory_range {
+            tracer.set_range(range);
+        }
+        chip8.set_memory_tracer(tracer);
     }
-
-    /// Loads an opcode from memory by fetching two bytes and combing them in big-endian fashion.
-    fn load_opcode(&self) -> u16 {
-        // Instructions are stored in big endian, so the most significant byte is placed at the byte with the lowest
-        // address.
-        let upper = self.mem[self.pc];
-        let lower = self.mem[self.pc + 1];
-        let opcode = u16::from_be_bytes([upper, lower]);
-        opcode
+    if let Some(reference_path) = reference_trace {
+        let reference = ReferenceTrace::open(&reference_path)
+            .map_err(|err| format!("Can't read {}: {}", reference_path, err))?;
+        chip8.set_reference_trace(reference);
     }
 
-    fn get_program_mem_mut(&mut self) -> &mut [u8] {
-        // The first 512 bytes belong to the interpreter
-        &mut self.mem[512..]
+    match http {
+        #[cfg(feature = "http")]
+        Some(addr) => {
+            let mut http_api = chip8::http_api::HttpApi::bind(&addr, chip8).map_err(|err| err.to_string())?;
+            http_api.set_dashboard(dashboard);
+            println!("Serving the HTTP API on {}", addr);
+            if dashboard {
+                println!("Dashboard at http://{}/", addr);
+            }
+            http_api.serve();
+            return Ok(());
+        },
+        #[cfg(not(feature = "http"))]
+        Some(_) => return Err("--http requires building with the `http` feature".into()),
+        None if dashboard => return Err("--dashboard requires --http".into()),
+        None => {},
     }
 
-    fn print_display(&self) {
-        for row in self.display {
-            for mut cell in row {
-                for bit in 0..8 { // Loop through each bit of the byte
-                    // Extract each bit. Get most significant bit first
-                    let pixel = (cell >> (7 - bit)) & 1 == 1;
-                    match pixel {
-                        true => print!("█"),
-                        false => print!(" "),
-                    }
-                }
+    // Connect before anything else, because the client adopts the seed of the host
+    let netplay = match (host, connect) {
+        (Some(addr), _) => {
+            println!("Waiting for the other player on {}", addr);
+            Some(Netplay::host(addr, &program, chip8.seed())?)
+        },
+        (None, Some(addr)) => {
+            let (netplay, seed) = Netplay::connect(addr, &program)?;
+            chip8.set_seed(seed);
+            Some(netplay)
+        },
+        (None, None) => None,
+    };
+    // The replay decides about the settings and keys, but the ROM has to be the recorded one
+    let replay_player = match replay {
+        Some(replay_path) => {
+            let replay = Replay::load(&replay_path).map_err(|err| format!("Can't read {}: {}", replay_path, err))?;
+            if !replay.matches_rom(&program) {
+                return Err(format!("{} was recorded with a different ROM", replay_path).into());
             }
-            println!();
+            replay.apply(&mut chip8);
+            Some(ReplayPlayer::new(&replay))
+        },
+        None => None,
+    };
+
+    let mut gif_recorder = record_gif.as_ref().map(|_| GifRecorder::new(gif_scale, gif_frame_skip));
+    let mut video_recorder = match record_video {
+        Some(video_path) => Some(VideoRecorder::start(video_path, video_scale, palette)?),
+        None => None,
+    };
+    tone.validate(audio::SAMPLE_RATE)?;
+    if sample_rate == Some(0) {
+        return Err("The sample rate can't be 0".into());
+    }
+    if let Some(video_recorder) = &mut video_recorder {
+        video_recorder.set_tone(tone);
+        if let Some(sample_rate) = sample_rate {
+            video_recorder.set_sample_rate(sample_rate);
         }
-        // Go up to the beginning of the display with ansi escape code
-        print!("{}", "\x1b[F".repeat(self.display.len()));
     }
-
-    fn run(&mut self) -> Result<(), Chip8Error> {
-        for _ in 0..10000 {
-            self.exec_instruction()?;
-            self.print_display();
-            self.sound_timer = self.sound_timer.saturating_sub(1);
-            self.delay_timer = self.delay_timer.saturating_sub(1);
-            thread::sleep(Duration::from_secs_f64(1.0 / 60.0)); // Run at 60Hz
+    let mut wav_recorder = record_wav.as_ref().map(|_| {
+        let mut wav_recorder = WavRecorder::new();
+        wav_recorder.set_tone(tone);
+        if let Some(sample_rate) = sample_rate {
+            wav_recorder.set_sample_rate(sample_rate);
         }
-        Ok(())
+        wav_recorder
+    });
+    if time_travel && keep_going {
+        return Err("--time-travel can't be combined with --keep-going, because recovering isn't recorded".into());
     }
-
-    fn exec_instruction(&mut self) -> Result<(), Chip8Error> {
-        self.refresh_display = false;
-
-        let opcode = self.load_opcode();
-        self.pc += 2;
-
-        // Match on the most significant hex digit in the opcode
-        match (opcode & 0xF000) >> 12 {
-            // Opcode starts with 0. Now match on the 2 least significant hex digits
-            0x0 => match opcode & 0x00FF {
-                0x00 => self.call_machine_routine(opcode),
-                0xE0 => self.clear_display(),
-                0xEE => self.subroutine_return(),
-                _ => Err(Chip8Error::IllegalInstruction { opcode, pc: self.pc }),
-            },
-            0x1 => self.jump(opcode),
-            0x2 => self.call_subroutine(opcode),
-            0x3 => self.skip_if_vx_eq_nn(opcode),
-            0x4 => self.skip_if_vx_ne_nn(opcode),
-            0x5 => self.skip_if_vx_eq_vy(opcode),
-            0x6 => self.set_vx_to_n(opcode),
-            0x7 => self.add_n_to_vx(opcode),
-            // Opcode starts with 8. Now match on the east significant hex digits
-            0x8 => match opcode & 0x000F {
-                0x0 => self.set_vx_to_vy(opcode),
-                0x1 => self.set_vx_to_vx_bitor_vy(opcode),
-                0x2 => self.set_vx_to_vx_bitand_vy(opcode),
-                0x3 => self.set_vx_to_vx_xor_vy(opcode),
-                0x4 => self.add_vy_to_vx(opcode),
-                0x5 => self.subtract_vy_from_vx(opcode),
-                0x6 => self.right_shift_vx(opcode),
-                0x7 => self.set_vx_to_vy_minus_vx(opcode),
-                0xE => self.left_shift_vx(opcode),
-                _ => Err(Chip8Error::IllegalInstruction { opcode, pc: self.pc })
-            },
-            0x9 => self.skip_if_vx_ne_vy(opcode),
-            0xA => self.set_i_addr_to_n(opcode),
-            0xB => self.jump_to_n_plus_v0(opcode),
-            0xC => self.set_to_vx_rand_bitand_n(opcode),
-            0xD => self.draw_sprite_at_coordinates_vx_vy_with_height_n(opcode),
-            // Opcode starts with E. Now match on the 2 least significant hex digits
-            0xE => match opcode & 0x00FF {
-                0x9E => self.skip_if_key_in_vk_pressed(opcode),
-                0xA1 => self.skip_if_key_in_vk_not_pressed(opcode),
-                _ => Err(Chip8Error::IllegalInstruction { opcode, pc: self.pc })
+    let mut rewind = time_travel.then(|| Rewind::unlimited(&chip8));
+    let mut call_profiler = flamegraph.as_ref().map(|_| CallProfiler::new());
+    let mut replay_recorder = record_replay.as_ref().map(|_| {
+        let mut replay_recorder = ReplayRecorder::new(&program, &chip8);
+        let detected = || compat::lookup_sha1(&rom_id.sha1).map(|profile| profile.platform.to_string());
+        replay_recorder.set_platform(platform.clone().or_else(detected).unwrap_or_default());
+        replay_recorder
+    });
+
+    let mut hooks: Vec<Box<dyn Hooks + '_>> = Vec::new();
+    // Draw before the terminal input, so that its overlays end up on top of the image
+    if let Some(renderer) = renderer {
+        hooks.push(renderer);
+    }
+    if io::stdin().is_terminal() || (rom_from_stdin && io::stdout().is_terminal()) {
+        let mut terminal_input = TerminalInput::new()?;
+        terminal_input.set_palette(palette);
+        let keymap = match &layout {
+            Some(layout) => keyboard_layout(layout)?,
+            None => config.keymap.unwrap_or_else(detected_keymap),
+        };
+        terminal_input.set_keymap(keymap);
+        terminal_input.set_show_keypad(show_keypad);
+        terminal_input.set_show_stats(show_stats);
+        hooks.push(Box::new(terminal_input));
+    }
+    match script {
+        #[cfg(feature = "lua")]
+        Some(script_path) => hooks.push(Box::new(chip8::script::LuaScript::load(script_path)?)),
+        #[cfg(not(feature = "lua"))]
+        Some(_) => return Err("--script requires building with the `lua` feature".into()),
+        None => {},
+    }
+    if let Some(input_path) = input_script {
+        let mut input_script = InputScript::load(&input_path)
+            .map_err(|err| format!("Can't read {}: {}", input_path, err))?;
+        input_script.apply(&mut chip8);
+        hooks.push(Box::new(input_script));
+    }
+    if let Some(cheats_path) = cheats {
+        let cheats = CheatTable::load(&cheats_path).map_err(|err| format!("Can't read {}: {}", cheats_path, err))?;
+        hooks.push(Box::new(cheats));
+    }
+    match debug_ws {
+        #[cfg(feature = "websocket")]
+        Some(addr) => {
+            let mut remote_debugger = chip8::remote::RemoteDebugger::bind(addr)?;
+            remote_debugger.debugger_mut().set_symbols(symbols.clone());
+            if let Some(assembly) = &assembly {
+                remote_debugger.debugger_mut().set_source_map(assembly.source_map.clone());
             }
-            0xF => match opcode & 0x00FF {
-                0x07 => self.set_vx_to_delay_timer(opcode),
-                0x0A => self.wait_for_key_press_and_store_in_vx(opcode),
-                0x15 => self.set_delay_timer_to_vx(opcode),
-                0x18 => self.set_sound_timer_to_vx(opcode),
-                0x1E => self.add_vx_to_i(opcode),
-                0x29 => self.set_i_to_sprite_addr(opcode),
-                0x33 => self.store_bcd_in_mem(opcode),
-                0x55 => self.store_v0_to_vx_in_mem(opcode),
-                0x65 => self.load_v0_to_vx_from_mem(opcode),
-                _ => Err(Chip8Error::IllegalInstruction { opcode, pc: self.pc })
-            },
-            _ => Err(Chip8Error::IllegalInstruction { opcode, pc: self.pc }),
-        }
+            hooks.push(Box::new(remote_debugger));
+        },
+        #[cfg(not(feature = "websocket"))]
+        Some(_) => return Err("--debug-ws requires building with the `websocket` feature".into()),
+        None => {},
     }
-
-    fn wait_for_key_press_and_store_in_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        self.registers[vx] = line.as_bytes()[0];
-        Ok(())
+    match stream {
+        #[cfg(feature = "websocket")]
+        Some(addr) => hooks.push(Box::new(chip8::stream::DisplayStream::bind(addr)?)),
+        #[cfg(not(feature = "websocket"))]
+        Some(_) => return Err("--stream requires building with the `websocket` feature".into()),
+        None => {},
     }
-
-    fn set_delay_timer_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        self.delay_timer = self.registers[vx];
-        Ok(())
+    if let Some(gif_recorder) = &mut gif_recorder {
+        hooks.push(Box::new(gif_recorder));
     }
-
-    fn set_sound_timer_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        self.sound_timer = self.registers[vx];
-        Ok(())
+    if let Some(video_recorder) = &mut video_recorder {
+        hooks.push(Box::new(video_recorder));
     }
-
-    fn set_i_to_sprite_addr(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        let sprite_addr = self.registers[vx] as usize * 5;
-        self.address_register = sprite_addr as u16;
-        Ok(())
+    if let Some(wav_recorder) = &mut wav_recorder {
+        hooks.push(Box::new(wav_recorder));
     }
-
-    fn store_bcd_in_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        let vx_val = self.registers[vx];
-        let hundreds = vx_val / 100;
-        let tens = (vx_val % 100) / 10;
-        let ones = vx_val % 10;
-        self.mem[self.address_register as usize] = hundreds;
-        self.mem[self.address_register as usize + 1] = tens;
-        self.mem[self.address_register as usize + 2] = ones;
-        Ok(())
+    if let Some(rewind) = &mut rewind {
+        hooks.push(Box::new(rewind));
     }
-
-    fn load_v0_to_vx_from_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        for i in 0..=vx {
-            self.registers[i] = self.mem[self.address_register as usize + i];
-        }
-        Ok(())
+    if let Some(call_profiler) = &mut call_profiler {
+        hooks.push(Box::new(call_profiler));
+    }
+    // Netplay comes last, so that it sends the keys pressed by the player and by the script
+    if let Some(netplay) = netplay {
+        hooks.push(Box::new(netplay));
+    }
+    // Replays come after everything that changes keys
+    if let Some(replay_player) = replay_player {
+        hooks.push(Box::new(replay_player));
+    }
+    if let Some(replay_recorder) = &mut replay_recorder {
+        hooks.push(Box::new(replay_recorder));
     }
 
-    fn store_v0_to_vx_in_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        for i in 0..=vx {
-            self.mem[self.address_register as usize + i] = self.registers[i];
+    let result = loop {
+        match chip8.run_with_hooks(&mut hooks) {
+            Err(err) if keep_going && err.is_recoverable() => {
+                if !recover(&mut chip8, &err)? {
+                    break Ok(RunStatus::Stopped);
+                }
+            },
+            result => break result,
         }
-        Ok(())
+    };
+    // Restore the terminal before printing anything else
+    drop(hooks);
+    match result {
+        Ok(RunStatus::Halted { pc }) => println!("The program halted at {:#05X}", pc),
+        Ok(RunStatus::InstructionLimit) => println!("Stopped after {} instructions", chip8.metrics().instructions),
+        Ok(_) => {},
+        Err(err) => {
+            println!("{}", CrashReport::new(&chip8, err));
+            if core_dump {
+                let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+                let dump_path = format!("chip8-{}.core", secs);
+                Snapshot::of(&chip8).save(&dump_path)?;
+                println!("Wrote the machine state to {}", dump_path);
+            }
+        },
     }
-
-    /// Call machine routine. Opcode: `0NNN` - `SYS addr`.
-    fn call_machine_routine(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let machine_routine_nr = opcode & 0x0FFF;
-        Err(Chip8Error::UnknownMachineRoutine(machine_routine_nr))?;
-        Ok(())
+    if let (Some(gif_recorder), Some(gif_path)) = (gif_recorder, record_gif) {
+        gif_recorder.finish(gif_path, &palette)?;
     }
-
-    /// Clears the display, i.e. sets all bytes to zero. Opcode: `00E0` - `CLS`.
-    fn clear_display(&mut self) -> Result<(), Chip8Error> {
-        self.display = Default::default();
-        self.refresh_display = true;
-        Ok(())
+    if let Some(video_recorder) = video_recorder {
+        video_recorder.finish()?;
     }
-
-    /// Return from subroutine. Opcode: `00EE` - `RET`.
-    fn subroutine_return(&mut self) -> Result<(), Chip8Error> {
-        self.pc = self.stack[self.stack_pointer as usize] as usize;
-        self.stack_pointer -= 1;
-        Ok(())
+    if let (Some(wav_recorder), Some(wav_path)) = (wav_recorder, record_wav) {
+        wav_recorder.finish(wav_path)?;
     }
-
-    /// Set the program counter to NNN. Opcode: `1NNN` - `JP addr`.
-    fn jump(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let jump_addr = opcode & 0x0FFF;
-        self.pc = jump_addr as usize;
-        Ok(())
+    if let (Some(replay_recorder), Some(replay_path)) = (replay_recorder, record_replay) {
+        replay_recorder.replay().save(replay_path)?;
     }
-
-    /// Call subroutine. Opcode: `2NNN` - `CALL addr`.
-    fn call_subroutine(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        self.stack_pointer += 1;
-        let stack_frame = self.stack.get_mut(self.stack_pointer as usize).ok_or(Chip8Error::StackOverflow)?;
-        *stack_frame = self.pc;
-        let subroutine_mem_addr = opcode & 0x0FFF;
-        self.pc = subroutine_mem_addr as usize;
-        Ok(())
+    if let Some(save_state) = save_state {
+        Snapshot::of(&chip8).save(save_state)?;
     }
-
-    /// Skip next instruction if vx (register) == nn (constant in). Opcode: `3XNN` - `SE vx, byte`.
-    fn skip_if_vx_eq_nn(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let register_number = (opcode & 0x0F00) >> 8;
-        let register_value = self.registers[register_number as usize];
-        let number_in = opcode & 0x00FF;
-        if register_value == number_in as u8 {
+    // Keep the volume chosen with --volume or muted with the hotkey for the next run
+    if let Some(path) = config_path.filter(|_| *chip8.mixer() != config.mixer) {
+        let mut config = config;
+        config.mixer = *chip8.mixer();
+        config.save(&path).map_err(|err| format!("Can't write config {}: {}", path.display(), err))?;
+    }
+    if let (Some(call_profiler), Some(flamegraph)) = (call_profiler, flamegraph) {
+        fs::write(flamegraph, call_profiler.folded(&symbols))?;
+    }
+    if let Some(rewind) = rewind {
+        println!("Recorded {} instructions, use `seek` and `step-back` to travel back", rewind.end());
+        let mut debugger = Debugger::new();
+        debugger.set_symbols(symbols);
+        if let Some(assembly) = assembly {
+            debugger.set_source_map(assembly.source_map);
         }
-        Ok(())
+        console::time_travel(&mut chip8, &mut debugger, rewind, &[])?;
     }
-
-    /// Skip next instruction if vx (register) != nn (constant in). Opcode: `4XNN` - `SNE vx, byte`.
-    fn skip_if_vx_ne_nn(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let register_number = (opcode & 0x0F00) >> 8;
-        let register_value = self.registers[register_number as usize];
-        let number_in = opcode & 0x00FF;
-        if register_value != number_in as u8 {
+    if let Some(profiler) = chip8.profiler() {
+        if profile_exec {
+            print!("{}", profiler);
+        }
+        if let Some(n) = hot_spots {
+            print!("{}", profiler.hot_spots(n));
         }
-        Ok(())
     }
-
-    /// Skip next instruction if vx (register) == vy (register). Opcode: `5XY0` - `SE vx, vy`.
-    fn skip_if_vx_eq_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        // Get number of the registers vx and vy
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy_number = (opcode & 0x00F0) >> 4;
-        // Get their values
-        let vx_value = self.registers[vx as usize];
-        let vy_value = self.registers[vy_number as usize];
-        if vx_value == vy_value {
+    if let Some(memory_check) = chip8.memory_check() {
+        for read in memory_check.uninitialized_reads() {
+            println!("Warning: {}", read);
         }
-        Ok(())
     }
-
-    /// vx = n., i.e. put value nn into register vx. Opcode: `6XNN` - `LD vx, byte`.
-    fn set_vx_to_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let number_in = opcode & 0x00FF;
-        self.registers[vx as usize] = number_in as u8;
-        Ok(())
+    for write in chip8.protected_writes() {
+        println!("Warning: {} (allow it with --write-protection off)", write);
     }
+    Ok(())
+}
 
-    /// vx += n, i.e. adds the constant n to register vx. Opcode: `7XNN` - `ADD vx, byte`.
-    fn add_n_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let number_in = opcode & 0x00FF;
-        self.registers[vx as usize] += number_in as u8;
-        Ok(())
+/// `chip8 asm <source> [-o FILE] [--write-symbols FILE]`: Assembles Octo source into a ROM, written to the file or
+/// next to the source with the extension `.ch8`.
+fn run_asm(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut source_path = None;
+    let mut output = None;
+    let mut write_symbols = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(args.next().ok_or("-o requires a file")?),
+            "--write-symbols" => write_symbols = Some(args.next().ok_or("--write-symbols requires a file")?),
+            _ => source_path = Some(arg),
+        }
     }
-
-    /// vx = vy, i.e. sets register vx to the value of register vy. Opcode: `8XY0` - `LD vx, vy`.
-    fn set_vx_to_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy_number = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] = self.registers[vy_number as usize];
-        Ok(())
+    let source_path = source_path.ok_or("asm requires a source file")?;
+    let source = fs::read_to_string(&source_path).map_err(|err| format!("Can't read {}: {}", source_path, err))?;
+    let assembly = assembler::assemble(&source).map_err(|err| format!("{}: {}", source_path, err))?;
+    let output = output.unwrap_or_else(|| {
+        let stem = source_path.strip_suffix(".8o").unwrap_or(&source_path);
+        format!("{}.ch8", stem)
+    });
+    fs::write(&output, &assembly.rom).map_err(|err| format!("Can't write {}: {}", output, err))?;
+    if let Some(write_symbols) = write_symbols {
+        fs::write(write_symbols, assembly.symbols().to_string())?;
     }
+    println!("Wrote {} bytes to {}", assembly.rom.len(), output);
+    Ok(())
+}
 
-    /// vx |= vy, i.e. sets register vx to vx bitwise or vy. Opcode: `8XY1` - `OR vx, vy`.
-    fn set_vx_to_vx_bitor_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] |= self.registers[vy as usize];
-        Ok(())
+/// `chip8 debug <rom> [--symbols FILE] [--tui] [--debug-script FILE] [--cheats FILE]` debugs the ROM on the console,
+/// or full screen with `--tui`. The console first executes the debugger commands of the `--debug-script`, one per
+/// line. The addresses of the `--cheats` table stay frozen while debugging.
+/// `chip8 debug --core FILE [rom]` inspects a core dump written by `run --core-dump`, where the ROM only provides the
+/// symbols.
+fn run_debug(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut core = None;
+    let mut symbols_path = None;
+    let mut tui = false;
+    let mut script_path = None;
+    let mut cheats_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--core" => core = Some(args.next().ok_or("--core requires a core dump file")?),
+            "--debug-script" => script_path = Some(args.next().ok_or("--debug-script requires a file")?),
+            "--cheats" => cheats_path = Some(args.next().ok_or("--cheats requires a cheat table file")?),
+            "--tui" => tui = true,
+            "--symbols" => symbols_path = Some(args.next().ok_or("--symbols requires a file")?),
+            _ => file_path = Some(arg),
+        }
     }
-
-    /// vx &= vy, i.e. sets register vx to vx bitwise and vy. Opcode: `8XY2` - `AND vx, vy`.
-    fn set_vx_to_vx_bitand_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] &= self.registers[vy as usize];
-        Ok(())
+    let rom = match &file_path {
+        Some(file_path) => Some(rom::load(file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?),
+        None if core.is_some() => None,
+        None => return Err("debug requires a ROM or a core dump".into()),
+    };
+    let program = rom.as_ref().map(|rom| rom.bytes.clone()).unwrap_or_default();
+    let assembly = rom.and_then(|rom| rom.assembly);
+    let symbols = match symbols_path {
+        Some(symbols_path) => Symbols::load(symbols_path)?,
+        None => assembly.as_ref().map(Assembly::symbols).unwrap_or_default(),
+    };
+    if tui && script_path.is_some() {
+        return Err("--debug-script only works on the console, not with --tui".into());
     }
-
-    /// vx ^= vy, i.e. sets register vx to vx xor vy. Opcode: `8XY3` - `XOR vx, vy`.
-    fn set_vx_to_vx_xor_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] ^= self.registers[vy as usize];
-        Ok(())
+    let script: Vec<String> = match script_path {
+        Some(script_path) => {
+            let script = fs::read_to_string(&script_path)
+                .map_err(|err| format!("Can't read {}: {}", script_path, err))?;
+            script.lines().map(String::from).collect()
+        },
+        None => Vec::new(),
+    };
+    let mut debugger = Debugger::new();
+    debugger.set_symbols(symbols);
+    if let Some(cheats_path) = cheats_path {
+        let cheats = CheatTable::load(&cheats_path).map_err(|err| format!("Can't read {}: {}", cheats_path, err))?;
+        debugger.set_cheats(cheats);
     }
-
-    /// vx += vy, i.e. sets register vx to vx plus vy. Opcode: `8XY4` - `ADD vx, vy`.
-    fn add_vy_to_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-
-        self.registers[vx as usize] += self.registers[vy as usize];
-        Ok(())
+    if let Some(assembly) = assembly {
+        debugger.set_source_map(assembly.source_map);
     }
-
-    /// vx -= vy, i.e. sets register vx to vx minus vy. Opcode: `8XY5` - `SUB vx, vy`.
-    fn subtract_vy_from_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] -= self.registers[vy as usize];
-        Ok(())
+    if let Some(core) = core {
+        let mut chip8 = Chip8::new(&[]);
+        let snapshot = Snapshot::load(&core).map_err(|err| format!("Can't read {}: {}", core, err))?;
+        snapshot.restore(&mut chip8);
+        println!("{}", console::format_reply(&debugger.execute(&chip8, Command::Regs), snapshot.pc));
+        // The last executed instruction is the one that failed
+        let fault_pc = snapshot.history.last().map_or(snapshot.pc, |&(pc, _)| pc);
+        println!("Last executed instruction at {:#05X}:", fault_pc);
+        let disasm = Command::Disasm { addr: Some(Location::Addr(fault_pc)), count: 4 };
+        println!("{}", console::format_reply(&debugger.execute(&chip8, disasm), snapshot.pc));
+        console::post_mortem(&chip8, &mut debugger, &script)?;
+        return Ok(());
     }
-
-    /// vx >>= 1, i.e. stores the least significant bit of VX in VF and shift the register VX one to the right.
-    /// Opcode: `8XY6` - `SHR vx`. `Y` is a don't care.
-    fn right_shift_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.registers[0xF] = self.registers[vx as usize] & 0b1;
-        self.registers[vx as usize] >>= 1;
-        Ok(())
+    let mut chip8 = Chip8::new(&program);
+    if let Some(profile) = compat::lookup(&program) {
+        profile.apply(&mut chip8);
     }
-
-    /// vx = vy - vx, i.e. sets register vx to vx minus vy. Opcode: `8XY7` - `SUBN vx, vy`.
-    fn set_vx_to_vy_minus_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy = (opcode & 0x00F0) >> 4;
-        self.registers[vx as usize] = self.registers[vy as usize] - self.registers[vx as usize];
-        Ok(())
+    if tui {
+        #[cfg(feature = "tui")]
+        chip8::tui::run(&mut chip8, &mut debugger)?;
+        #[cfg(not(feature = "tui"))]
+        return Err("--tui requires building with the `tui` feature".into());
+    } else {
+        console::debug(&mut chip8, &mut debugger, &script)?;
     }
+    Ok(())
+}
 
-    /// vx <<= 1, i.e. stores the most significant bit of VX in VF and shift the register VX one to the left.
-    /// Opcode: `8XYE` - `SHL vx`. `Y` is a don't care.
-    fn left_shift_vx(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.registers[0xF] = (self.registers[vx as usize] & 0x80) >> 7;
-        self.registers[vx as usize] <<= 1;
-        Ok(())
+/// `chip8 info <rom>`: Prints the size, hash and known settings of the ROM, and how much of it is reachable code.
+fn run_info(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let file_path = args.next().ok_or("info requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let rom_id = RomId::of(&rom.bytes);
+    println!("Size: {} bytes", rom_id.size);
+    println!("SHA-1: {}", rom_id.sha1);
+    println!("CRC32: {:08x}", rom_id.crc32);
+    match compat::lookup_sha1(&rom_id.sha1) {
+        Some(profile) => println!(
+            "Known as {} ({}, {} instructions per frame)",
+            profile.title, profile.platform, profile.tickrate,
+        ),
+        None => println!("Not in the compatibility table"),
+    }
+    let disassembly = Disassembly::analyze(&rom.bytes);
+    let code_bytes = disassembly.instructions().count() * 2;
+    println!("Reachable code: {} bytes, data: {} bytes", code_bytes, rom.bytes.len().saturating_sub(code_bytes));
+    let warnings = lint::lint(&rom.bytes).len();
+    if warnings > 0 {
+        println!("{} warnings, see `chip8 lint {}`", warnings, file_path);
     }
+    Ok(())
+}
 
-    /// Skip next instruction if vx (register) != vy (register). Opcode: `9XY0` - `SNE vx, vy`.
-    fn skip_if_vx_ne_vy(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        // Get number of the registers vx and vy
-        let vx = (opcode & 0x0F00) >> 8;
-        let vy_number = (opcode & 0x00F0) >> 4;
-        // Get their values
-        let vx_value = self.registers[vx as usize];
-        let vy_value = self.registers[vy_number as usize];
-        if vx_value != vy_value {
+/// `chip8 bench <rom> [--seconds N] [--ipf N]`: Runs the ROM headless as fast as possible and prints the throughput.
+fn run_bench(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut seconds = 5.0;
+    let mut ipf = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seconds" => seconds = args.next().ok_or("--seconds requires a number")?.parse()?,
+            "--ipf" => ipf = Some(args.next().ok_or("--ipf requires a number of instructions per frame")?.parse()?),
+            _ => file_path = Some(arg),
         }
-        Ok(())
     }
-
-    /// I = n, i.e. sets the I address register to the number n. Opcode: `ANNN` - `LD I, addr`.
-    fn set_i_addr_to_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let n = opcode & 0x0FFF;
-        self.address_register = n;
-        Ok(())
+    let file_path = file_path.ok_or("bench requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let mut chip8 = Chip8::new(&rom.bytes);
+    // Fixed seed, so that runs are comparable
+    chip8.set_seed(0);
+    if let Some(profile) = compat::lookup(&rom.bytes) {
+        profile.apply(&mut chip8);
     }
-
-    /// I = V0 + n, i.e. sets the I address register to register V0 plus n. Opcode: `BNNN` - `JP V0, addr`.
-    fn jump_to_n_plus_v0(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let n = opcode & 0x0FFF;
-        self.pc = (self.registers[0] as u16 + n) as usize;
-        Ok(())
+    if let Some(ipf) = ipf {
+        chip8.set_instructions_per_frame(ipf);
     }
+    println!("{}", bench::run(&mut chip8, Duration::from_secs_f64(seconds)));
+    Ok(())
+}
 
-    /// `vx = rand()`, i.e. sets `vx` to a random number combined with a bitwise or with n to limit the maximum value.
-    /// Opcode: `CXNN` - `RND vx, byte`
-    fn set_to_vx_rand_bitand_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let n = opcode & 0x00FF;
-        let rand = self.pc + self.current_key as usize + self.stack_pointer as usize;
-        self.registers[vx as usize] = (rand as u8) & n as u8;
-        Ok(())
+/// `chip8 multi <rom>... [--ipf N]`: Runs the ROMs side by side in the terminal. The same ROM may be given several
+/// times, e.g. to compare quirks of the detected profile with the defaults.
+fn run_multi(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_paths = Vec::new();
+    let mut ipf = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ipf" => ipf = Some(args.next().ok_or("--ipf requires a number of instructions per frame")?.parse()?),
+            _ => file_paths.push(arg),
+        }
     }
+    if file_paths.is_empty() {
+        return Err("multi requires at least one ROM".into());
+    }
+    let mut multi = MultiInstance::new();
+    for file_path in file_paths {
+        let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+        let mut chip8 = Chip8::new(&rom.bytes);
+        if let Some(profile) = compat::lookup(&rom.bytes) {
+            profile.apply(&mut chip8);
+        }
+        if let Some(ipf) = ipf {
+            chip8.set_instructions_per_frame(ipf);
+        }
+        multi.add(file_path, chip8);
+    }
+    multi.run()?;
+    Ok(())
+}
 
-    /// Draws a sprite at the coordinates (vx, vy), so the numbers stored in the registers vx and vy, with height n
-    /// and width 8. The data is fetched from the memory address stored in the register I. Register vf is set to 1 if
-    /// any screen pixels are flipped from set to unset to allow for collision detection.
-    fn draw_sprite_at_coordinates_vx_vy_with_height_n(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let height = (opcode & 0x000F) as usize;
-        let register_vx = (opcode & 0x0F00) >> 8;
-        let register_vy = (opcode & 0x00F0) >> 4;
-        // Coordinates
-        let x = self.registers[register_vx as usize] as usize % 64;
-        let y = self.registers[register_vy as usize] as usize % 32;
-
-        // Reset collision flag
-        self.registers[0xF] = 0;
-
-        for row in 0..height {
-            let sprite = self.mem[self.address_register as usize + row];
-            for col in 0..8 {
-                let pixel_from_u8 = |byte: u8, bit: usize| (byte >> (7 - bit)) & 0b1 == 1;
-                let merge_pixel_into_u8 = |byte: u8, bit: usize, pixel: bool| {
-                    let pixel = pixel as u8;
-                    let mask = 0b1 << (7 - bit);
-                    // First unset the specify bit, then set it to the pixel value
-                    (byte & !mask) | (pixel << (7 - bit))
-                };
+/// `chip8 compare <rom> [--first PLATFORM] [--second PLATFORM] [--first-quirk NAME=BOOL] [--second-quirk NAME=BOOL]
+/// [--frames N] [--seed N] [--ipf N] [--press KEY@START..END]`: Runs the ROM with two quirk configurations in lockstep
+/// and reports the first frame where they diverge. Both configurations start with the detected quirks. Fails on a
+/// divergence.
+fn run_compare(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut platforms = [None, None];
+    let mut quirk_overrides = [Vec::new(), Vec::new()];
+    let mut frames = 600;
+    let mut seed = 0;
+    let mut ipf = None;
+    // Key, first frame and end frame (exclusive) of every key press
+    let mut presses: Vec<(u8, Range<usize>)> = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--first" => platforms[0] = Some(args.next().ok_or("--first requires a platform like originalChip8")?),
+            "--second" => platforms[1] = Some(args.next().ok_or("--second requires a platform like originalChip8")?),
+            "--first-quirk" => {
+                quirk_overrides[0].push(args.next().ok_or("--first-quirk requires a quirk like vblank=false")?)
+            },
+            "--second-quirk" => {
+                quirk_overrides[1].push(args.next().ok_or("--second-quirk requires a quirk like vblank=false")?)
+            },
+            "--frames" => frames = args.next().ok_or("--frames requires a number")?.parse()?,
+            "--seed" => seed = args.next().ok_or("--seed requires a number")?.parse()?,
+            "--ipf" => ipf = Some(args.next().ok_or("--ipf requires a number of instructions per frame")?.parse()?),
+            "--press" => {
+                let press = args.next().ok_or("--press requires a key and frames like 5@60..90")?;
+                let (key, frames) = press.split_once('@')
+                    .ok_or_else(|| format!("Expected a key and frames like 5@60..90, got {}", press))?;
+                let key = u8::from_str_radix(key, 16).ok().filter(|&key| key < 16)
+                    .ok_or_else(|| format!("Invalid key {:?}, expected a hex digit", key))?;
+                presses.push((key, parse_range(frames)?));
+            },
+            _ => file_path = Some(arg),
+        }
+    }
+    let file_path = file_path.ok_or("compare requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let mut machines = Vec::new();
+    for (platform, quirk_overrides) in platforms.iter().zip(&quirk_overrides) {
+        let mut chip8 = Chip8::new(&rom.bytes);
+        if let Some(profile) = compat::lookup(&rom.bytes) {
+            profile.apply(&mut chip8);
+        }
+        if let Some(platform) = platform {
+            let quirks = Quirks::for_platform(platform).ok_or_else(|| format!("Unknown platform {}", platform))?;
+            chip8.set_quirks(quirks);
+        }
+        for quirk in quirk_overrides {
+            apply_quirk_override(&mut chip8, quirk)?;
+        }
+        if let Some(ipf) = ipf {
+            chip8.set_instructions_per_frame(ipf);
+        }
+        machines.push(chip8);
+    }
+    let (first, second) = machines.split_at_mut(1);
+    let (first, second) = (&mut first[0], &mut second[0]);
+    match first.quirks().differences(second.quirks()).as_slice() {
+        [] => println!("The configurations have the same quirks"),
+        differences => println!("Differing quirks: {}", differences.join(", ")),
+    }
+    let outcome = compare::run(first, second, frames, seed, |frame| {
+        presses.iter()
+            .filter(|(_, frames)| frames.contains(&(frame as usize)))
+            .fold(0, |keypad, (key, _)| keypad | 1 << key)
+    });
+    println!("{}", outcome);
+    match outcome {
+        compare::Outcome::Diverged { .. } => Err("The configurations diverged".into()),
+        compare::Outcome::Failed { first, second, .. } if first != second => Err("The configurations diverged".into()),
+        _ => Ok(()),
+    }
+}
 
-                let local_x = ((x + col) % 64) / 8;
-                let local_y = (y + row) % 32;
+/// Sets a quirk given like `vblank=false`.
+fn apply_quirk_override(chip8: &mut Chip8, quirk: &str) -> Result<(), Box<dyn Error>> {
+    let mut quirks = *chip8.quirks();
+    let set = quirk.split_once('=')
+        .and_then(|(name, enabled)| Some(quirks.set(name, enabled.parse().ok()?)))
+        .unwrap_or(false);
+    if !set {
+        return Err(format!("Invalid quirk {:?}, expected something like vblank=false", quirk).into());
+    }
+    chip8.set_quirks(quirks);
+    Ok(())
+}
 
-                let sprite = pixel_from_u8(sprite, col);
+/// The default keys of a keyboard layout by name.
+fn keyboard_layout(layout: &str) -> Result<Keymap, Box<dyn Error>> {
+    Keymap::layout(layout).ok_or_else(|| {
+        format!("Unknown keyboard layout {}, expected one of {}", layout, Keymap::LAYOUTS.join(", ")).into()
+    })
+}
 
-                let old_display_pixel = pixel_from_u8(self.display[local_y][local_x], (x + col) % 8);
-                let new_display_pixel = old_display_pixel ^ sprite;
+/// The default keys of the keyboard layout guessed from the locale.
+fn detected_keymap() -> Keymap {
+    let locale = ["LC_ALL", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_default();
+    Keymap::layout(keymap::layout_for_locale(&locale)).unwrap_or_default()
+}
 
-                self.display[local_y][local_x] = merge_pixel_into_u8(self.display[local_y][local_x], (x + col) % 8, new_display_pixel);
+/// A bundled font by name, or a font file.
+fn load_font(font: &str) -> Result<Font, Box<dyn Error>> {
+    match Font::named(font) {
+        Some(font) => Ok(font),
+        None => Ok(Font::load(font).map_err(|err| format!("Can't read font {}: {}", font, err))?),
+    }
+}
 
-                let flip_from_set_to_unset = old_display_pixel && !new_display_pixel;
-                self.registers[0xF] |= flip_from_set_to_unset as u8;
-            }
+/// `chip8 batch <dir> [--frames N]`: Runs every ROM in the directory headless and reports how each run ended. Fails if
+/// any ROM stopped with an error.
+fn run_batch(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut dir = None;
+    let mut frames = 600;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => frames = args.next().ok_or("--frames requires a number")?.parse()?,
+            _ => dir = Some(arg),
         }
-
-        self.refresh_display = true;
-        Ok(())
     }
+    let dir = dir.ok_or("batch requires a directory of ROMs")?;
+    let results = batch::run_dir(&dir, frames).map_err(|err| format!("Can't read {}: {}", dir, err))?;
+    for result in &results {
+        println!("{}", result);
+    }
+    let summary = batch::Summary::of(&results);
+    println!("{}", summary);
+    if summary.errors > 0 {
+        return Err(format!("{} ROMs failed", summary.errors).into());
+    }
+    Ok(())
+}
 
-    /// Skips the next instruction if the key stored in vx is pressed. Opcode: `EX9E` - `SKP vx`.
-    fn skip_if_key_in_vk_pressed(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        if self.current_key == self.registers[vx as usize] {
+/// `chip8 keys [--reset] [--layout NAME]`: Asks for the host key of every key of the hex keypad and saves them in the
+/// config file. `--layout` saves the default keys of a keyboard layout instead, `--reset` goes back to the keys of
+/// the layout guessed from the locale.
+fn run_keys(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut reset = false;
+    let mut layout = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--reset" => reset = true,
+            "--layout" => layout = Some(args.next().ok_or("--layout requires a keyboard layout like azerty")?),
+            other => return Err(format!("Unknown option {}", other).into()),
         }
-        Ok(())
     }
-
-    /// Skips the next instruction if the key stored in vx is not pressed. Opcode: `EX9E` - `SKNP vx`.
-    fn skip_if_key_in_vk_not_pressed(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        if self.current_key != self.registers[vx as usize] {
+    let path = Config::default_path().ok_or("Can't find the config directory, set XDG_CONFIG_HOME or HOME")?;
+    let mut config = Config::load(&path).map_err(|err| format!("Can't read config {}: {}", path.display(), err))?;
+    if reset {
+        config.keymap = None;
+    } else if let Some(layout) = layout {
+        config.keymap = Some(keyboard_layout(&layout)?);
+    } else {
+        println!("Press the host key for every key of the hex keypad, Enter keeps it, Esc cancels");
+        match terminal::remap_keys(&config.keymap.unwrap_or_else(detected_keymap))? {
+            Some(keymap) => config.keymap = Some(keymap),
+            None => {
+                println!("Cancelled, the keys weren't changed");
+                return Ok(());
+            },
         }
-        Ok(())
     }
+    config.save(&path).map_err(|err| format!("Can't write config {}: {}", path.display(), err))?;
+    println!("Saved the keys to {}", path.display());
+    Ok(())
+}
 
-    /// `vx = get_delay_timer()`, i.e. sets register `vx` to the value of the delay time. Opcode: `CXNN` - `RND vx,
-    /// byte`.
-    fn set_vx_to_delay_timer(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.registers[vx as usize] = self.delay_timer;
-        Ok(())
+/// `chip8 lint <rom>`: Prints the warnings of the static analysis. Fails if there are any.
+fn run_lint(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let file_path = args.next().ok_or("lint requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let warnings = lint::lint(&rom.bytes);
+    for warning in &warnings {
+        println!("{}", warning);
     }
-
-    /// `vx = get_key()`, i.e. waits for a user input and writes that key into register `vx`. Opcode: `FX0A` - `LD
-    /// vx, key`.
-    fn set_vx_to_get_key_blocking(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let key = 0; // TODO
-        self.registers[vx as usize] = key;
-        Ok(())
+    if !warnings.is_empty() {
+        return Err(format!("{} warnings", warnings.len()).into());
     }
+    Ok(())
+}
 
-    /// `delay_timer = vx`, i.e. sets the delay timer to the value of the register `vx`. Opcode: `FX15` - `LD DT, vx`.
-    fn set_delay_timer(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.delay_timer = self.registers[vx as usize];
-        Ok(())
+/// `chip8 disasm <rom> [-o FILE] [--no-addresses] [--no-bytes] [--lowercase] [--octo] [--symbols FILE]`: Writes the
+/// disassembly to the file or stdout. With `--octo`, the output can be assembled again.
+fn run_disasm(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut output = None;
+    let mut options = FormatOptions::default();
+    let mut symbols_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(args.next().ok_or("-o requires a file")?),
+            "--no-addresses" => options.addresses = false,
+            "--no-bytes" => options.bytes = false,
+            "--lowercase" => options.lowercase = true,
+            "--octo" => options.syntax = Syntax::Octo,
+            "--symbols" => symbols_path = Some(args.next().ok_or("--symbols requires a file")?),
+            _ => file_path = Some(arg),
+        }
     }
-
-    /// `sound_timer = vx`, i.e. sets the sound timer to the value of the register `vx`. Opcode: `FX18` - `LD ST, vx`.
-    fn set_sound_timer(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.sound_timer = self.registers[vx as usize];
-        Ok(())
+    let file_path = file_path.ok_or("disasm requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let mut disassembly = Disassembly::analyze(&rom.bytes);
+    match (symbols_path, &rom.assembly) {
+        (Some(symbols_path), _) => disassembly.set_symbols(&Symbols::load(symbols_path)?),
+        (None, Some(assembly)) => disassembly.set_symbols(&assembly.symbols()),
+        (None, None) => {},
     }
-
-    /// `I += vx`, i.e. adds the register `vx` to the address register `I`. Opcode: `FX1E` - `ADD I, vx`.
-    fn add_vx_to_i(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.address_register += self.registers[vx as usize] as u16;
-        Ok(())
+    let text = disassembly.format(&options);
+    match output {
+        Some(output) => fs::write(&output, text).map_err(|err| format!("Can't write {}: {}", output, err))?,
+        None => print!("{}", text),
     }
+    Ok(())
+}
 
-    // `I = sprite_addr[vx]`, i.e. sets the address register `I` to the address of the sprite for the char in `vx`.
-    // Opcode: `FX1E` - `LD F, vx`.
-    fn set_addr_register_to_char(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        self.address_register = vx * 5; // Each char uses 5 bytes of memory
-        Ok(())
+/// `chip8 cfg <rom>`: Prints the control-flow graph in the DOT format of Graphviz.
+fn run_cfg(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let file_path = args.next().ok_or("cfg requires a ROM")?;
+    let rom = rom::load(&file_path).map_err(|err| format!("Can't read {}: {}", file_path, err))?;
+    let mut disassembly = Disassembly::analyze(&rom.bytes);
+    if let Some(assembly) = &rom.assembly {
+        disassembly.set_symbols(&assembly.symbols());
     }
+    print!("{}", cfg::ControlFlowGraph::new(&disassembly).to_dot());
+    Ok(())
+}
 
-    /// Writes the binary-coded decimal representation of `vx` with the most significant of the three bcd digits at
-    /// the address `I`, the middle at `I + 1`, the least significant bit at `I + 2`. Opcode: `FX33` - `LD B, vx`.
-    fn write_bcd_of_vx_at_i(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = (opcode & 0x0F00) >> 8;
-        let vx_value: u8 = self.registers[vx as usize];
-        self.mem[self.address_register as usize] = vx_value / 100; // Most significant bit
-        self.mem[(self.address_register + 1) as usize] = (vx_value / 10) % 10;
-        self.mem[(self.address_register + 2) as usize] = vx_value % 10; // Least significant bit
-        Ok(())
+/// Asks the user what to do after a recoverable error. Returns whether to continue with the next instruction.
+fn recover(chip8: &mut Chip8, err: &Chip8Error) -> io::Result<bool> {
+    // The keyboard input puts the terminal into raw mode, which doesn't echo what the user types
+    let raw_mode = crossterm::terminal::is_raw_mode_enabled()?;
+    crossterm::terminal::disable_raw_mode()?;
+    // Move below the display, which is printed from the top left
+    print!("{}", "\n".repeat(DISPLAY_HEIGHT));
+    println!("Error: {}", err);
+    let resume = loop {
+        print!("[s]kip the instruction and continue, show [r]egisters or [q]uit? ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            break false;
+        }
+        match answer.trim() {
+            "s" | "skip" => break true,
+            "r" | "registers" => println!("{}", RegisterDump::of(chip8)),
+            "q" | "quit" => break false,
+            _ => {},
+        }
+    };
+    if raw_mode {
+        crossterm::terminal::enable_raw_mode()?;
     }
+    chip8.request_redraw();
+    Ok(resume)
+}
 
-    /// `reg_dump(vx, &I)`, i.e. writes the value of the registers `v0` to `vx` to memory starting at address `I`.
-    /// Opcode: `FX55` -`LD [I], vx`.
-    fn dump_registers_to_mem(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        for vi in 0..=vx {
-            self.mem[self.address_register as usize + vi] = self.registers[vi];
-        }
-        Ok(())
+/// Parses a range of addresses like `0x200..0x300`, without the end. Addresses are parsed like in [`parse_addr`].
+fn parse_range(range: &str) -> Result<Range<usize>, Box<dyn Error>> {
+    let (start, end) = range.split_once("..")
+        .ok_or_else(|| format!("Expected a range like 0x200..0x300, got {}", range))?;
+    Ok(parse_addr(start)?..parse_addr(end)?)
+}
+
+/// Parses an address, which is hex with `0x` prefix or decimal.
+fn parse_addr(addr: &str) -> Result<usize, Box<dyn Error>> {
+    match addr.strip_prefix("0x") {
+        Some(hex) => Ok(usize::from_str_radix(hex, 16)?),
+        None => Ok(addr.parse()?),
     }
+}
 
-    /// `reg_load(vx, &I)`, i.e. writes the value of memory starting at address `I` to the registers `v0` to `vx`.
-    /// Opcode: `FX65` - `LD vx, [I]`.
-    fn load_registers_from_memory(&mut self, opcode: u16) -> Result<(), Chip8Error> {
-        let vx = ((opcode & 0x0F00) >> 8) as usize;
-        for vi in 0..=vx {
-            self.registers[vi] = self.mem[self.address_register as usize + vi];
+/// Asks on the terminal which of the examples to run.
+fn choose_example() -> Result<&'static Example, Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        return Err("No ROM given, run `chip8 help` for the usage".into());
+    }
+    println!("No ROM given, choose an example:");
+    for (i, example) in examples::EXAMPLES.iter().enumerate() {
+        println!("  {}. {:<8} {}", i + 1, example.name, example.description);
+    }
+    loop {
+        print!("Number or name [1]: ");
+        io::stdout().flush()?;
+        let mut choice = String::new();
+        if io::stdin().read_line(&mut choice)? == 0 {
+            return Err("No example chosen".into());
+        }
+        let choice = choice.trim();
+        let example = match choice.parse::<usize>() {
+            _ if choice.is_empty() => examples::EXAMPLES.first(),
+            Ok(number) => number.checked_sub(1).and_then(|i| examples::EXAMPLES.get(i)),
+            Err(_) => examples::named(choice),
+        };
+        match example {
+            Some(example) => return Ok(example),
+            None => println!("There's no example {}", choice),
         }
-        Ok(())
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let file_path = "src/PONG";
-    let mut file = File::open(file_path).expect("Can't open program file");
-    let mut program = Vec::new();
-    file.read_to_end(&mut program).expect("Can't read program from file");
-    let mut chip8 = Chip8::new(&program);
-    if let Err(err) = chip8.run() {
-        println!("Error: {}", err);
-    }
-    Ok(())
+/// Parses a memory segment like `data.bin@0x300` into the file and the address it's loaded at.
+fn parse_segment(segment: &str) -> Result<(&str, usize), Box<dyn Error>> {
+    let (path, addr) = segment.rsplit_once('@')
+        .ok_or_else(|| format!("Expected a file and an address like data.bin@0x300, got {}", segment))?;
+    Ok((path, parse_addr(addr)?))
+}
+
+/// Parses a percentage like `25` or `25%` into a fraction like 0.25.
+fn parse_percent(percent: &str) -> Result<f64, Box<dyn Error>> {
+    let percent: f64 = percent.trim_end_matches('%').parse()
+        .map_err(|_| format!("Expected a percentage like 25%, got {}", percent))?;
+    Ok(percent / 100.0)
 }
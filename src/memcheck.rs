@@ -0,0 +1,120 @@
+//! Finds instructions reading memory that was never written, which is a common bug in homebrew ROMs: a variable used
+//! before it's set, or a table read from the wrong address. Memory counts as initialized once it's written, by
+//! loading the ROM and the font, by an instruction, or from outside like by a cheat. Enable the check with
+//! [`crate::Chip8::enable_memory_check`].
+//!
+//! Writes to the memory of the interpreter below [`PROGRAM_START`], which holds the font, are almost always bugs
+//! too. They are reported unless [`WriteProtection::Off`] is set, see [`crate::Chip8::set_write_protection`].
+
+use std::fmt;
+use std::str::FromStr;
+use crate::chip8::PROGRAM_START;
+
+/// An instruction read memory that was never written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitializedRead {
+    /// Address of the instruction.
+    pub pc: usize,
+    pub addr: usize,
+}
+
+impl fmt::Display for UninitializedRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Read of uninitialized memory at {:#05X} at PC={:#05X}", self.addr, self.pc)
+    }
+}
+
+/// Remembers which bytes of memory were written, and the reads of the other ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryCheck {
+    initialized: Vec<bool>,
+    /// Whether a read of each address was reported already, so that loops don't repeat the same report.
+    reported: Vec<bool>,
+    reads: Vec<UninitializedRead>,
+}
+
+impl MemoryCheck {
+    /// Starts with all `size` bytes of memory uninitialized.
+    pub fn new(size: usize) -> Self {
+        Self { initialized: vec![false; size], reported: vec![false; size], reads: Vec::new() }
+    }
+
+    /// Marks `addr` as written. Addresses outside of memory are ignored.
+    pub fn write(&mut self, addr: usize) {
+        if let Some(initialized) = self.initialized.get_mut(addr) {
+            *initialized = true;
+        }
+    }
+
+    /// Reports the read of `addr` by the instruction at `pc` if `addr` was never written. Only the first read of
+    /// every address is reported.
+    pub fn read(&mut self, pc: usize, addr: usize) {
+        if self.is_initialized(addr) || self.reported.get(addr).copied().unwrap_or(true) {
+            return;
+        }
+        self.reported[addr] = true;
+        self.reads.push(UninitializedRead { pc, addr });
+    }
+
+    pub fn is_initialized(&self, addr: usize) -> bool {
+        self.initialized.get(addr).copied().unwrap_or(false)
+    }
+
+    /// Marks the whole memory as uninitialized again, e.g. after a reset cleared it. The reports are kept.
+    pub fn clear(&mut self) {
+        self.initialized.iter_mut().for_each(|initialized| *initialized = false);
+    }
+
+    /// The reported reads in the order they happened.
+    pub fn uninitialized_reads(&self) -> &[UninitializedRead] {
+        &self.reads
+    }
+}
+
+/// What happens when an instruction writes to the memory below [`PROGRAM_START`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WriteProtection {
+    /// Allows the writes, for ROMs that use the low memory on purpose.
+    Off,
+    /// Allows the writes, but reports them with [`crate::Chip8::protected_writes`].
+    #[default]
+    Warn,
+    /// Stops with [`crate::Chip8Error::ProtectedWrite`].
+    Error,
+}
+
+impl WriteProtection {
+    /// Whether an instruction writing to `addr` is reported.
+    pub fn protects(self, addr: usize) -> bool {
+        self != Self::Off && addr < PROGRAM_START
+    }
+}
+
+impl FromStr for WriteProtection {
+    type Err = String;
+
+    /// Parses `off`, `warn` or `error`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("Invalid write protection {:?}, expected off, warn or error", s)),
+        }
+    }
+}
+
+/// An instruction wrote to the memory below [`PROGRAM_START`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtectedWrite {
+    /// Address of the instruction.
+    pub pc: usize,
+    pub addr: usize,
+    pub value: u8,
+}
+
+impl fmt::Display for ProtectedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Write of {:#04X} to the interpreter memory at {:#05X} at PC={:#05X}", self.value, self.addr, self.pc)
+    }
+}
@@ -0,0 +1,13 @@
+/// Counters describing what the emulator has done so far. Embedders can read them via [`crate::Chip8::metrics`]
+/// instead of instrumenting the core themselves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of successfully executed instructions.
+    pub instructions: u64,
+    /// Number of frames rendered to the display.
+    pub frames: u64,
+    /// Number of executed `DXYN` (draw sprite) instructions.
+    pub draw_calls: u64,
+    /// Number of `DXYN` instructions that flipped at least one pixel from set to unset.
+    pub collisions: u64,
+}
@@ -0,0 +1,215 @@
+//! Runs several independent machines side by side in one terminal, e.g. to compare revisions of a ROM or for demo
+//! walls. The displays are tiled left to right and top to bottom. The keyboard controls the focused machine, whose
+//! title is highlighted. `Tab` moves the focus to the next machine, `Esc` or `Ctrl+C` quits.
+
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use crate::chip8::FRAME_DURATION;
+use crate::display;
+use crate::terminal::{key_for_char, HOLD_FRAMES};
+use crate::{Chip8, Framebuffer, RunStatus, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Columns between two tiles.
+const TILE_GAP: usize = 2;
+/// Rows of a tile: the title and the display.
+const TILE_HEIGHT: usize = DISPLAY_HEIGHT + 1;
+
+struct Instance {
+    name: String,
+    chip8: Chip8,
+    /// Why the machine stopped, i.e. the error or the halt. Stopped machines don't execute anymore.
+    stopped: Option<String>,
+    /// The display when the tile was last drawn, or `None` if the tile has to be drawn in the next frame.
+    drawn: Option<Framebuffer>,
+}
+
+/// Machines running side by side, of which one receives the input.
+pub struct MultiInstance {
+    instances: Vec<Instance>,
+    focused: usize,
+    /// Frames left until each key of the focused machine counts as released.
+    held: [u8; 16],
+}
+
+impl MultiInstance {
+    pub fn new() -> Self {
+        Self { instances: Vec::new(), focused: 0, held: [0; 16] }
+    }
+
+    /// Adds a machine, whose tile is titled `name`.
+    pub fn add(&mut self, name: impl Into<String>, chip8: Chip8) {
+        self.instances.push(Instance { name: name.into(), chip8, stopped: None, drawn: None });
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// The machine at `index` in the order they were added.
+    pub fn chip8(&self, index: usize) -> &Chip8 {
+        &self.instances[index].chip8
+    }
+
+    /// Why the machine at `index` stopped, if it did.
+    pub fn stopped(&self, index: usize) -> Option<&str> {
+        self.instances[index].stopped.as_deref()
+    }
+
+    /// Index of the machine that receives the input.
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    /// Moves the focus to the next machine. Keys held on the previously focused machine are released.
+    pub fn focus_next(&mut self) {
+        if self.instances.is_empty() {
+            return;
+        }
+        self.instances[self.focused].chip8.set_keypad(0);
+        self.held = [0; 16];
+        self.instances[self.focused].drawn = None;
+        self.focused = (self.focused + 1) % self.instances.len();
+        self.instances[self.focused].drawn = None;
+    }
+
+    /// Executes a frame on every machine that hasn't stopped.
+    pub fn run_frame(&mut self) {
+        for instance in self.instances.iter_mut().filter(|instance| instance.stopped.is_none()) {
+            instance.stopped = match instance.chip8.run_frame_with_hooks(&mut ()) {
+                Ok(ControlFlow::Break(RunStatus::Halted { pc })) => Some(format!("halted at {:#05X}", pc)),
+                // Nothing else stops the frame without hooks
+                Ok(_) => None,
+                Err(err) => Some(err.to_string()),
+            };
+            if instance.stopped.is_some() {
+                instance.drawn = None;
+            }
+        }
+    }
+
+    /// Runs all machines at 60 frames per second in the terminal until `Esc` or `Ctrl+C` is pressed.
+    pub fn run(&mut self) -> io::Result<()> {
+        let _raw_mode = RawMode::enable()?;
+        print!("\x1b[2J");
+        let mut next_frame = Instant::now();
+        loop {
+            if self.handle_input()?.is_break() {
+                break;
+            }
+            self.run_frame();
+            self.draw()?;
+            next_frame += FRAME_DURATION;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(remaining) => thread::sleep(remaining),
+                None => next_frame = Instant::now(),
+            }
+        }
+        // Leave the cursor below the tiles
+        let rows = self.instances.len().div_ceil(self.tiles_per_row());
+        print!("\x1b[{};1H", rows * TILE_HEIGHT + 1);
+        io::stdout().flush()
+    }
+
+    /// Reads the pending key events and presses the keys on the focused machine.
+    fn handle_input(&mut self) -> io::Result<ControlFlow<()>> {
+        for frames in &mut self.held {
+            *frames = frames.saturating_sub(1);
+        }
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key_event) = event::read()? {
+                if self.handle_key(key_event).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+        }
+        let keypad = self.held.iter().enumerate()
+            .filter(|(_, &frames)| frames > 0)
+            .fold(0, |keypad, (key, _)| keypad | 1 << key);
+        if let Some(instance) = self.instances.get_mut(self.focused) {
+            instance.chip8.set_keypad(keypad);
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent) -> ControlFlow<()> {
+        let is_ctrl_c = key_event.code == KeyCode::Char('c') && key_event.modifiers.contains(KeyModifiers::CONTROL);
+        match key_event.code {
+            KeyCode::Esc => return ControlFlow::Break(()),
+            _ if is_ctrl_c => return ControlFlow::Break(()),
+            KeyCode::Tab if key_event.kind == KeyEventKind::Press => self.focus_next(),
+            KeyCode::Char(c) => {
+                if let Some(key) = key_for_char(c) {
+                    self.held[key as usize] = match key_event.kind {
+                        KeyEventKind::Release => 0,
+                        _ => HOLD_FRAMES,
+                    };
+                }
+            },
+            _ => {},
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Number of tiles that fit next to each other into the terminal.
+    fn tiles_per_row(&self) -> usize {
+        let columns = terminal::size().map_or(80, |(columns, _)| columns as usize);
+        (columns / (DISPLAY_WIDTH + TILE_GAP)).max(1)
+    }
+
+    /// Draws the tiles whose display or title changed since they were last drawn.
+    fn draw(&mut self) -> io::Result<()> {
+        let tiles_per_row = self.tiles_per_row();
+        let mut output = String::new();
+        for (index, instance) in self.instances.iter_mut().enumerate() {
+            if instance.drawn.as_ref() == Some(instance.chip8.framebuffer()) {
+                continue;
+            }
+            // Terminal rows and columns start at 1
+            let top = index / tiles_per_row * TILE_HEIGHT + 1;
+            let left = index % tiles_per_row * (DISPLAY_WIDTH + TILE_GAP) + 1;
+            let title = match &instance.stopped {
+                Some(stopped) => format!("{} ({})", instance.name, stopped),
+                None => instance.name.clone(),
+            };
+            let title = format!("{:<width$.width$}", title, width = DISPLAY_WIDTH);
+            let style = if index == self.focused { "\x1b[7m" } else { "" };
+            output.push_str(&format!("\x1b[{};{}H{}{}\x1b[0m", top, left, style, title));
+            for (y, &row) in instance.chip8.framebuffer().rows().iter().enumerate() {
+                output.push_str(&format!("\x1b[{};{}H{}", top + 1 + y, left, display::row_art(row)));
+            }
+            instance.drawn = Some(*instance.chip8.framebuffer());
+        }
+        print!("{}", output);
+        io::stdout().flush()
+    }
+}
+
+impl Default for MultiInstance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Puts the terminal into raw mode for as long as this value lives.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
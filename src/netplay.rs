@@ -0,0 +1,125 @@
+//! Two-player netplay over TCP. Both instances run in lockstep: after every frame, each side sends its local
+//! keypad state and waits for the one of its peer, then both continue with the combined keypad. As long as both
+//! instances run the same ROM with the same seed, they stay in sync.
+//!
+//! Protocol (all numbers big endian):
+//! * Handshake, sent by both sides: `"C8NP"`, version (u8), seed (u64), ROM hash (u64). The client adopts the
+//!   seed of the host.
+//! * Every frame, sent by both sides: frame number (u32), keypad (u16).
+
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::ControlFlow;
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+const MAGIC: &[u8; 4] = b"C8NP";
+const VERSION: u8 = 1;
+
+/// A connection to the other player.
+pub struct Netplay {
+    stream: TcpStream,
+    frame: u32,
+    /// Keys held by the local player, which are sent to the peer.
+    local_keypad: u16,
+    /// Keys of both players, as last set on the interpreter.
+    combined_keypad: u16,
+}
+
+impl Netplay {
+    /// Waits on `addr` for the other player to connect. Both players will use `seed` for the random number
+    /// generator.
+    pub fn host(addr: impl ToSocketAddrs, rom: &[u8], seed: u64) -> io::Result<Self> {
+        Self::accept(&TcpListener::bind(addr)?, rom, seed)
+    }
+
+    /// Like [`Netplay::host`], but waits for the other player on an already bound `listener`.
+    pub fn accept(listener: &TcpListener, rom: &[u8], seed: u64) -> io::Result<Self> {
+        let (mut stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        write_handshake(&mut stream, seed, rom_hash(rom))?;
+        read_handshake(&mut stream, rom_hash(rom))?;
+        Ok(Self::new(stream))
+    }
+
+    /// Connects to the player hosting on `addr`. Returns the connection and the seed chosen by the host.
+    pub fn connect(addr: impl ToSocketAddrs, rom: &[u8]) -> io::Result<(Self, u64)> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let seed = read_handshake(&mut stream, rom_hash(rom))?;
+        write_handshake(&mut stream, seed, rom_hash(rom))?;
+        Ok((Self::new(stream), seed))
+    }
+
+    fn new(stream: TcpStream) -> Self {
+        Self { stream, frame: 0, local_keypad: 0, combined_keypad: 0 }
+    }
+
+    /// Sends the local keypad state for the current frame and returns the one of the peer.
+    fn exchange(&mut self, keypad: u16) -> io::Result<u16> {
+        let mut message = [0; 6];
+        message[..4].copy_from_slice(&self.frame.to_be_bytes());
+        message[4..].copy_from_slice(&keypad.to_be_bytes());
+        self.stream.write_all(&message)?;
+
+        self.stream.read_exact(&mut message)?;
+        let peer_frame = u32::from_be_bytes([message[0], message[1], message[2], message[3]]);
+        if peer_frame != self.frame {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Peer is at frame {}, but we are at frame {}", peer_frame, self.frame),
+            ));
+        }
+        self.frame = self.frame.wrapping_add(1);
+        Ok(u16::from_be_bytes([message[4], message[5]]))
+    }
+}
+
+impl Hooks for Netplay {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        // Only the keys changed since the combined keypad was set come from the local player, the others would echo
+        // the keys of the peer back
+        let pressed = chip8.keypad() & !self.combined_keypad;
+        let released = self.combined_keypad & !chip8.keypad();
+        self.local_keypad = (self.local_keypad | pressed) & !released;
+        let peer_keypad = self.exchange(self.local_keypad)
+            .map_err(|err| Chip8Error::Hook(format!("Netplay: {}", err)))?;
+        self.combined_keypad = self.local_keypad | peer_keypad;
+        chip8.set_keypad(self.combined_keypad);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+fn write_handshake(stream: &mut TcpStream, seed: u64, rom_hash: u64) -> io::Result<()> {
+    let mut message = Vec::with_capacity(21);
+    message.extend_from_slice(MAGIC);
+    message.push(VERSION);
+    message.extend_from_slice(&seed.to_be_bytes());
+    message.extend_from_slice(&rom_hash.to_be_bytes());
+    stream.write_all(&message)
+}
+
+/// Reads the handshake of the peer, checks that it runs the same ROM and returns its seed.
+fn read_handshake(stream: &mut TcpStream, rom_hash: u64) -> io::Result<u64> {
+    let mut message = [0; 21];
+    stream.read_exact(&mut message)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    if &message[..4] != MAGIC {
+        return Err(invalid("Peer is not a chip8 netplay instance"));
+    }
+    if message[4] != VERSION {
+        return Err(invalid("Peer uses a different netplay protocol version"));
+    }
+    let seed = u64::from_be_bytes(message[5..13].try_into().unwrap());
+    let peer_rom_hash = u64::from_be_bytes(message[13..21].try_into().unwrap());
+    if peer_rom_hash != rom_hash {
+        return Err(invalid("Peer runs a different ROM"));
+    }
+    Ok(seed)
+}
+
+/// FNV-1a hash of the ROM, used to check that both players run the same program.
+fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use crate::disassembler::mnemonic;
+use crate::hooks::Hooks;
+use crate::symbols::Symbols;
+use crate::{Chip8, Chip8Error};
+
+/// Number of program counters listed in the per-PC section of the report.
+const TOP_PCS: usize = 20;
+
+/// Execution count and accumulated wall time of a group of executed instructions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExecStats {
+    pub count: u64,
+    pub time: Duration,
+}
+
+impl ExecStats {
+    fn add(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.time += elapsed;
+    }
+}
+
+/// Collects execution statistics per opcode class (like `8XY4`) and per program counter.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Profiler {
+    by_class: HashMap<&'static str, ExecStats>,
+    by_pc: HashMap<usize, ExecStats>,
+    /// The opcode last executed at each program counter.
+    opcodes: HashMap<usize, u16>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `opcode` located at `pc` was executed and took `elapsed` wall time.
+    pub fn record(&mut self, pc: usize, opcode: u16, elapsed: Duration) {
+        self.by_class.entry(opcode_class(opcode)).or_default().add(elapsed);
+        self.by_pc.entry(pc).or_default().add(elapsed);
+        self.opcodes.insert(pc, opcode);
+    }
+
+    /// The `n` most executed instructions, like the hot loops of the program.
+    pub fn hot_spots(&self, n: usize) -> HotSpots {
+        let mut spots: Vec<HotSpot> = self.by_pc.iter()
+            .map(|(&pc, stats)| HotSpot { pc, opcode: self.opcodes[&pc], count: stats.count })
+            .collect();
+        spots.sort_by(|a, b| b.count.cmp(&a.count).then(a.pc.cmp(&b.pc)));
+        spots.truncate(n);
+        HotSpots { total: self.total().count, spots }
+    }
+
+    /// Statistics per opcode class, sorted by accumulated time (most expensive first).
+    pub fn classes(&self) -> Vec<(&'static str, ExecStats)> {
+        let mut classes: Vec<_> = self.by_class.iter().map(|(&class, &stats)| (class, stats)).collect();
+        classes.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        classes
+    }
+
+    /// Statistics per program counter, sorted by accumulated time (most expensive first).
+    pub fn pcs(&self) -> Vec<(usize, ExecStats)> {
+        let mut pcs: Vec<_> = self.by_pc.iter().map(|(&pc, &stats)| (pc, stats)).collect();
+        pcs.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(&b.0)));
+        pcs
+    }
+
+    fn total(&self) -> ExecStats {
+        self.by_class.values().fold(ExecStats::default(), |acc, stats| ExecStats {
+            count: acc.count + stats.count,
+            time: acc.time + stats.time,
+        })
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        // Avoid dividing by zero if nothing was executed
+        let total_nanos = total.time.as_nanos().max(1) as f64;
+        let percent = |stats: &ExecStats| stats.time.as_nanos() as f64 / total_nanos * 100.0;
+        let avg_nanos = |stats: &ExecStats| stats.time.as_nanos() / stats.count.max(1) as u128;
+
+        writeln!(f, "Executed {} instructions in {:?}", total.count, total.time)?;
+        writeln!(f)?;
+        writeln!(f, "{:<6} {:>12} {:>14} {:>10} {:>7}", "class", "count", "time", "avg (ns)", "time %")?;
+        for (class, stats) in self.classes() {
+            writeln!(
+                f, "{:<6} {:>12} {:>14} {:>10} {:>6.2}%",
+                class, stats.count, format!("{:?}", stats.time), avg_nanos(&stats), percent(&stats)
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<6} {:>12} {:>14} {:>10} {:>7}", "pc", "count", "time", "avg (ns)", "time %")?;
+        for (pc, stats) in self.pcs().into_iter().take(TOP_PCS) {
+            writeln!(
+                f, "{:<#6X} {:>12} {:>14} {:>10} {:>6.2}%",
+                pc, stats.count, format!("{:?}", stats.time), avg_nanos(&stats), percent(&stats)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An instruction and how often it was executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotSpot {
+    pub pc: usize,
+    /// The opcode last executed at `pc`, which differs from earlier ones in self-modifying code.
+    pub opcode: u16,
+    pub count: u64,
+}
+
+/// The most executed instructions, see [`Profiler::hot_spots`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotSpots {
+    /// Number of all executed instructions.
+    pub total: u64,
+    /// Sorted by execution count (most executed first).
+    pub spots: Vec<HotSpot>,
+}
+
+/// Formats the hot spots as a table with the disassembly of the instructions.
+impl fmt::Display for HotSpots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<6} {:>12} {:>7}  {:<6} instruction", "pc", "count", "count %", "opcode")?;
+        for spot in &self.spots {
+            let percent = spot.count as f64 / self.total.max(1) as f64 * 100.0;
+            let instruction = mnemonic(spot.opcode).unwrap_or_else(|| String::from("(illegal)"));
+            writeln!(
+                f, "{:<#6X} {:>12} {:>6.2}%  {:04X}   {}",
+                spot.pc, spot.count, percent, spot.opcode, instruction
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the instructions executed in each subroutine, separately for every chain of calls leading to it. The counts
+/// are exported as folded stacks, which tools like inferno or flamegraph.pl turn into flame graphs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CallProfiler {
+    /// Entry addresses of the called subroutines, outermost first.
+    frames: Vec<usize>,
+    /// Executed instructions per call stack.
+    counts: HashMap<Vec<usize>, u64>,
+}
+
+impl CallProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `opcode` was executed in the current subroutine. `CALL` counts for the caller and `RET` for the
+    /// subroutine that returns.
+    pub fn record(&mut self, opcode: u16) {
+        *self.counts.entry(self.frames.clone()).or_default() += 1;
+        if opcode & 0xF000 == 0x2000 {
+            self.frames.push((opcode & 0x0FFF) as usize);
+        } else if opcode == 0x00EE {
+            self.frames.pop();
+        }
+    }
+
+    /// One line per call stack like `main;update;draw_ball 42`, with the number of instructions executed in the
+    /// innermost subroutine. Subroutines are named by `symbols` or by their address.
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let mut lines: Vec<String> = self.counts.iter()
+            .map(|(frames, count)| {
+                let names = frames.iter().map(|&addr| match symbols.name(addr) {
+                    Some(name) => name.to_string(),
+                    None => format!("{:#05X}", addr),
+                });
+                let stack: Vec<String> = std::iter::once(String::from("main")).chain(names).collect();
+                format!("{} {}", stack.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+impl Hooks for CallProfiler {
+    fn after_instruction(&mut self, _chip8: &mut Chip8, _pc: usize, opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.record(opcode);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Returns the opcode pattern (like `8XY4`) the opcode belongs to, or `????` for illegal opcodes.
+pub fn opcode_class(opcode: u16) -> &'static str {
+    match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode & 0x00FF {
+            0xE0 => "00E0",
+            0xEE => "00EE",
+            0x00 => "0NNN",
+            _ => "????",
+        },
+        0x1 => "1NNN",
+        0x2 => "2NNN",
+        0x3 => "3XNN",
+        0x4 => "4XNN",
+        0x5 => "5XY0",
+        0x6 => "6XNN",
+        0x7 => "7XNN",
+        0x8 => match opcode & 0x000F {
+            0x0 => "8XY0",
+            0x1 => "8XY1",
+            0x2 => "8XY2",
+            0x3 => "8XY3",
+            0x4 => "8XY4",
+            0x5 => "8XY5",
+            0x6 => "8XY6",
+            0x7 => "8XY7",
+            0xE => "8XYE",
+            _ => "????",
+        },
+        0x9 => "9XY0",
+        0xA => "ANNN",
+        0xB => "BNNN",
+        0xC => "CXNN",
+        0xD => "DXYN",
+        0xE => match opcode & 0x00FF {
+            0x9E => "EX9E",
+            0xA1 => "EXA1",
+            _ => "????",
+        },
+        0xF => match opcode & 0x00FF {
+            0x07 => "FX07",
+            0x0A => "FX0A",
+            0x15 => "FX15",
+            0x18 => "FX18",
+            0x1E => "FX1E",
+            0x29 => "FX29",
+            0x33 => "FX33",
+            0x55 => "FX55",
+            0x65 => "FX65",
+            _ => "????",
+        },
+        _ => "????",
+    }
+}
@@ -0,0 +1,140 @@
+/// Behaviors that differ between Chip-8 implementations. Programs written for one platform often rely on the
+/// behavior of that platform, so they need the right quirks to run correctly. The names follow the
+/// [CHIP-8 database](https://github.com/chip-8/chip-8-database).
+///
+/// The default matches the behavior this interpreter always had.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6` and `8XYE` shift `vx` in place and ignore `vy`, instead of shifting `vy` into `vx`.
+    pub shift: bool,
+    /// `FX55` and `FX65` increment `I` by `X` instead of `X + 1`. Only relevant if `I` isn't left unchanged.
+    pub memory_increment_by_x: bool,
+    /// `FX55` and `FX65` leave `I` unchanged instead of incrementing it.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped.
+    pub wrap: bool,
+    /// `BNNN` jumps to `XNN + vx` instead of `NNN + v0`.
+    pub jump: bool,
+    /// `DXYN` waits for the next frame, so at most one sprite is drawn per frame.
+    pub vblank: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset `vf` to zero.
+    pub logic: bool,
+    /// `I` wraps around at the end of memory, instead of raising [`crate::Chip8Error::MemoryOutOfBounds`] when it
+    /// points beyond memory. This one isn't in the database, its name for [`Quirks::set`] is `iWrap`.
+    pub i_wrap: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+            i_wrap: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// The quirks of a platform, named like in the CHIP-8 database (e.g. `originalChip8` or `superchip`).
+    pub fn for_platform(platform: &str) -> Option<Self> {
+        let quirks = match platform {
+            "originalChip8" | "hybridVIP" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+                i_wrap: false,
+            },
+            "modernChip8" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: false,
+                logic: false,
+                i_wrap: false,
+            },
+            "chip48" | "superchip1" => Self {
+                shift: true,
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+                i_wrap: false,
+            },
+            "superchip" => Self {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+                i_wrap: false,
+            },
+            "xochip" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: true,
+                jump: false,
+                vblank: false,
+                logic: false,
+                i_wrap: false,
+            },
+            _ => return None,
+        };
+        Some(quirks)
+    }
+
+    /// Sets the quirk called `name` (like in the CHIP-8 database, e.g. `memoryIncrementByX`). Returns `false` if
+    /// there is no such quirk.
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            "iWrap" => &mut self.i_wrap,
+            _ => return false,
+        };
+        *quirk = enabled;
+        true
+    }
+
+    /// Names of the quirks that differ from `other`, in the order of the fields.
+    pub fn differences(&self, other: &Quirks) -> Vec<&'static str> {
+        self.named().iter().zip(other.named().iter())
+            .filter(|((_, enabled), (_, other_enabled))| enabled != other_enabled)
+            .map(|((name, _), _)| *name)
+            .collect()
+    }
+
+    /// The quirks with their names for [`Quirks::set`].
+    fn named(&self) -> [(&'static str, bool); 8] {
+        [
+            ("shift", self.shift),
+            ("memoryIncrementByX", self.memory_increment_by_x),
+            ("memoryLeaveIUnchanged", self.memory_leave_i_unchanged),
+            ("wrap", self.wrap),
+            ("jump", self.jump),
+            ("vblank", self.vblank),
+            ("logic", self.logic),
+            ("iWrap", self.i_wrap),
+        ]
+    }
+}
@@ -0,0 +1,259 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use crate::audio::{self, Resampler, Synth, Tone, SAMPLE_RATE};
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::hooks::Hooks;
+use crate::image::{self, Palette};
+use crate::{Chip8, Chip8Error};
+
+/// Frames per second the emulator renders at, used to compute the delays between recorded frames.
+const FPS: u64 = 60;
+
+/// A captured display, one byte (0 or 1) per pixel.
+struct GifFrame {
+    /// Number of the frame in which the display was captured.
+    frame: u64,
+    pixels: Vec<u8>,
+}
+
+/// Records the display while the emulator runs and encodes it as animated GIF when [`GifRecorder::finish`] is
+/// called. Frames are only captured when the display changed, the time in between is encoded as frame delay.
+pub struct GifRecorder {
+    scale: usize,
+    frame_skip: u64,
+    frames: Vec<GifFrame>,
+    /// Number of frames rendered since recording started.
+    frame: u64,
+    /// Whether the display changed since the last captured frame.
+    dirty: bool,
+}
+
+impl GifRecorder {
+    /// Every Chip-8 pixel becomes a `scale` x `scale` square. After a frame was captured, the next `frame_skip`
+    /// frames are not captured, which keeps the GIF small for ROMs that redraw constantly.
+    pub fn new(scale: usize, frame_skip: u64) -> Self {
+        Self { scale: scale.max(1), frame_skip, frames: Vec::new(), frame: 0, dirty: true }
+    }
+
+    fn capture(&mut self, chip8: &Chip8) {
+        let pixels = (0..DISPLAY_HEIGHT)
+            .flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| chip8.shown_framebuffer().pixel(x, y) as u8)
+            .collect();
+        self.frames.push(GifFrame { frame: self.frame, pixels });
+    }
+
+    /// Encodes the recorded frames as GIF looping forever and writes it to `path`.
+    pub fn finish(&self, path: impl AsRef<Path>, palette: &Palette) -> io::Result<()> {
+        let (width, height) = (DISPLAY_WIDTH * self.scale, DISPLAY_HEIGHT * self.scale);
+        let global_palette = [palette.background, palette.foreground].concat();
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &global_palette)
+            .map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+
+        // Delays are in hundredths of a second. Converting absolute frame numbers avoids accumulating rounding errors.
+        let centis = |frame: u64| frame * 100 / FPS;
+        for (i, recorded) in self.frames.iter().enumerate() {
+            let next_frame = self.frames.get(i + 1).map_or(self.frame, |next| next.frame);
+            let pixels: Vec<u8> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| recorded.pixels[y / self.scale * DISPLAY_WIDTH + x / self.scale])
+                .collect();
+            let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+            frame.delay = (centis(next_frame) - centis(recorded.frame)).min(u16::MAX as u64) as u16;
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+impl Hooks for GifRecorder {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.dirty |= chip8.dirty_rows != 0;
+        let skipped = self.frames.last().is_some_and(|last| self.frame - last.frame <= self.frame_skip);
+        if self.dirty && !skipped {
+            self.capture(chip8);
+            self.dirty = false;
+        }
+        self.frame += 1;
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Records the display and the beeper into a video by piping raw frames into an external `ffmpeg` process. The
+/// container and codecs are chosen by ffmpeg from the extension of the output file, e.g. `.mp4` or `.webm`.
+///
+/// The video is encoded while the emulator runs. The audio is collected in memory and muxed into the video in a
+/// second ffmpeg run by [`VideoRecorder::finish`].
+pub struct VideoRecorder {
+    path: PathBuf,
+    /// Path of the video without audio, which is written while recording.
+    video_path: PathBuf,
+    ffmpeg: Child,
+    stdin: BufWriter<ChildStdin>,
+    scale: usize,
+    palette: Palette,
+    audio: AudioTrack,
+}
+
+impl VideoRecorder {
+    /// Starts ffmpeg to record a video to `path`. Every Chip-8 pixel becomes a `scale` x `scale` square.
+    pub fn start(path: impl AsRef<Path>, scale: usize, palette: Palette) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let video_path = sibling_path(&path, "video");
+        let scale = scale.max(1);
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale)])
+            .args(["-r", &FPS.to_string(), "-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(&video_path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("Can't start ffmpeg: {}", err)))?;
+        let stdin = BufWriter::new(ffmpeg.stdin.take().expect("Stdin of ffmpeg is piped"));
+        Ok(Self {
+            path,
+            video_path,
+            ffmpeg,
+            stdin,
+            scale,
+            palette,
+            audio: AudioTrack::new(),
+        })
+    }
+
+    /// Sets how the beeper sounds in the video, [`Tone::default`] if not set.
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.audio.synth.set_tone(tone);
+    }
+
+    /// Sets the sample rate of the audio in the video, [`SAMPLE_RATE`] if not set. Only call it before recording.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.audio.resampler = Resampler::new(SAMPLE_RATE, sample_rate);
+    }
+
+    /// Finishes encoding the video and adds the recorded audio to it.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stdin.flush()?;
+        drop(self.stdin);
+        check_ffmpeg(self.ffmpeg.wait()?)?;
+
+        let audio_path = sibling_path(&self.path, "audio").with_extension("wav");
+        self.audio.write_wav(&audio_path)?;
+        let status = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(&self.video_path)
+            .arg("-i")
+            .arg(&audio_path)
+            .args(["-c:v", "copy", "-shortest"])
+            .arg(&self.path)
+            .status();
+        // Clean up the intermediate files, even if muxing failed
+        let _ = fs::remove_file(&self.video_path);
+        let _ = fs::remove_file(&audio_path);
+        check_ffmpeg(status?)
+    }
+}
+
+impl Hooks for VideoRecorder {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        let (_, _, pixels) = image::render_rgb(chip8, self.scale, &self.palette);
+        self.stdin.write_all(&pixels)
+            .map_err(|err| Chip8Error::Hook(format!("Can't write video frame to ffmpeg: {}", err)))?;
+        self.audio.frame(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Records the beeper and writes it as WAV file when [`WavRecorder::finish`] is called.
+#[derive(Debug)]
+pub struct WavRecorder {
+    audio: AudioTrack,
+}
+
+impl WavRecorder {
+    pub fn new() -> Self {
+        Self { audio: AudioTrack::new() }
+    }
+
+    /// Sets how the beeper sounds in the recording, [`Tone::default`] if not set.
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.audio.synth.set_tone(tone);
+    }
+
+    /// Sets the sample rate of the WAV file, [`SAMPLE_RATE`] if not set. Only call it before recording.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.audio.resampler = Resampler::new(SAMPLE_RATE, sample_rate);
+    }
+
+    /// Writes the recorded audio to `path`.
+    pub fn finish(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.audio.write_wav(path)
+    }
+}
+
+impl Default for WavRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hooks for WavRecorder {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.audio.frame(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// The beeper generated frame by frame and converted to the sample rate of the recording.
+#[derive(Debug)]
+struct AudioTrack {
+    synth: Synth,
+    resampler: Resampler,
+    /// Samples of the current frame at [`SAMPLE_RATE`].
+    frame_samples: Vec<i16>,
+    /// Samples at the rate of the resampler's output.
+    samples: Vec<i16>,
+}
+
+impl AudioTrack {
+    fn new() -> Self {
+        Self {
+            synth: Synth::new(SAMPLE_RATE, Tone::default()),
+            resampler: Resampler::new(SAMPLE_RATE, SAMPLE_RATE),
+            frame_samples: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn frame(&mut self, chip8: &Chip8) {
+        self.frame_samples.clear();
+        self.synth.frame(chip8.sound_timer > 0, FPS as u32, &mut self.frame_samples);
+        chip8.mixer().apply(&mut self.frame_samples);
+        self.resampler.process(&self.frame_samples, &mut self.samples);
+    }
+
+    fn write_wav(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        audio::write_wav(path, self.resampler.output_rate(), &self.samples)
+    }
+}
+
+/// Returns `path` with `.suffix` inserted before the extension, e.g. `out.video.mp4` for `out.mp4`.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}.{}.{}", stem, suffix, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}.{}", stem, suffix)),
+    }
+}
+
+fn check_ffmpeg(status: std::process::ExitStatus) -> io::Result<()> {
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("ffmpeg failed with {}", status))),
+    }
+}
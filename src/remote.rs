@@ -0,0 +1,167 @@
+//! Remote debugging over a WebSocket. Clients send [`Command`]s as JSON text messages, e.g.
+//! `{"cmd":"break","addr":512}`, `{"cmd":"break","addr":"main_loop"}` (with symbols) or
+//! `{"cmd":"mem","addr":512,"len":16}`, and receive a [`Reply`] for each of them, e.g.
+//! `{"reply":"memory","addr":512,"bytes":[...]}`. For programs assembled from source, breakpoints can be set by
+//! line with `{"cmd":"break","addr":{"line":42}}`. When execution pauses, the server additionally sends
+//! `{"event":"paused","reason":"breakpoint","pc":512,"line":42,"watches":[...]}`, where `line` is `null` without
+//! source and `watches` are the values of the expressions added with `{"cmd":"watch","expr":"[I+2]"}`. When the
+//! program jumps to itself, which stops the emulator, the server sends `{"event":"halted","pc":512}`.
+
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::ControlFlow;
+use tungstenite::{Message, WebSocket};
+use crate::debugger::{Command, Debugger, PauseReason, Reply, WatchValue};
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+/// Serves the [`Debugger`] to one WebSocket client at a time.
+pub struct RemoteDebugger {
+    listener: TcpListener,
+    client: Option<WebSocket<TcpStream>>,
+    debugger: Debugger,
+}
+
+#[derive(serde::Serialize)]
+struct PausedEvent {
+    event: &'static str,
+    reason: PauseReason,
+    pc: usize,
+    /// Source line of the instruction at `pc`.
+    line: Option<usize>,
+    watches: Vec<WatchValue>,
+}
+
+#[derive(serde::Serialize)]
+struct HaltedEvent {
+    event: &'static str,
+    pc: usize,
+}
+
+impl RemoteDebugger {
+    /// Listens for debugger clients on `addr`. The emulator keeps running until a client attaches.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, client: None, debugger: Debugger::new() })
+    }
+
+    /// Accepts a waiting client, if there is one and no other client is attached.
+    fn accept(&mut self, blocking: bool) -> Result<(), Chip8Error> {
+        if self.client.is_some() {
+            return Ok(());
+        }
+        self.listener.set_nonblocking(!blocking).map_err(hook_error)?;
+        let stream = match self.listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(err) => return Err(hook_error(err)),
+        };
+        stream.set_nonblocking(false).map_err(hook_error)?;
+        stream.set_nodelay(true).map_err(hook_error)?;
+        // A failed handshake is the client's problem, so just wait for the next one
+        if let Ok(client) = tungstenite::accept(stream) {
+            self.client = Some(client);
+        }
+        Ok(())
+    }
+
+    /// Reads the next command from the client. Returns `None` if no message is available without blocking or the
+    /// client disconnected.
+    fn receive(&mut self, blocking: bool) -> Option<Command> {
+        let client = self.client.as_mut()?;
+        if client.get_mut().set_nonblocking(!blocking).is_err() {
+            self.client = None;
+            return None;
+        }
+        loop {
+            match client.read() {
+                Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                    Ok(command) => return Some(command),
+                    Err(err) => {
+                        let reply = Reply::Error { message: format!("Invalid command: {}", err) };
+                        self.send(&reply);
+                        return None;
+                    },
+                },
+                // Pings are answered by tungstenite itself
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => return None,
+                Err(_) => {
+                    self.client = None;
+                    return None;
+                },
+            }
+        }
+    }
+
+    fn send(&mut self, message: &impl serde::Serialize) {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return,
+        };
+        let json = serde_json::to_string(message).expect("Debugger messages are always serializable");
+        // Sending happens in blocking mode, so that messages are never dropped
+        let sent = client.get_mut().set_nonblocking(false).is_ok() && client.send(Message::text(json)).is_ok();
+        if !sent {
+            self.client = None;
+        }
+    }
+
+    /// The debugger serving the client, e.g. to set symbols.
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    fn execute(&mut self, chip8: &Chip8, command: Command) {
+        let reply = self.debugger.execute(chip8, command);
+        self.send(&reply);
+    }
+
+    /// Handles commands until the client steps or continues. Waits for a client if none is attached.
+    fn pause(&mut self, chip8: &Chip8, reason: PauseReason) -> Result<(), Chip8Error> {
+        let line = self.debugger.source_line(chip8.pc).map(|(line, _)| line);
+        let watches = self.debugger.watch_values(chip8);
+        let event = PausedEvent { event: "paused", reason, pc: chip8.pc, line, watches };
+        self.send(&event);
+        while self.debugger.is_paused() {
+            if self.client.is_none() {
+                self.accept(true)?;
+                self.send(&event);
+            }
+            if let Some(command) = self.receive(true) {
+                self.execute(chip8, command);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Hooks for RemoteDebugger {
+    fn after_instruction(&mut self, chip8: &mut Chip8, _pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.debugger.apply_freezes(chip8);
+        if let Some(reason) = self.debugger.check(chip8) {
+            self.pause(chip8, reason)?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.accept(false)?;
+        while let Some(command) = self.receive(false) {
+            self.execute(chip8, command);
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn halted(&mut self, _chip8: &mut Chip8, pc: usize) -> Result<(), Chip8Error> {
+        self.send(&HaltedEvent { event: "halted", pc });
+        Ok(())
+    }
+}
+
+fn hook_error(err: io::Error) -> Chip8Error {
+    Chip8Error::Hook(format!("Remote debugger: {}", err))
+}
@@ -0,0 +1,291 @@
+//! Replays of a whole run: the configuration of the machine and every key press and release. Together with the ROM,
+//! a replay reproduces the run exactly, because the machine is deterministic for a given seed.
+//!
+//! File format `.c8r` (all numbers big endian): `"C8RP"`, version (u8), header length (u32), header, number of
+//! events (u32), events. The header is the SHA-1 hash of the ROM (20 bytes), instructions per frame (u32), quirks
+//! (u16, see below), seed (u64), platform name length (u8) and the platform name as UTF-8 (empty if unknown). Every
+//! event is its kind (u8), payload length (u8) and payload. Key events (kind 1) have the frame (u64), the key (u8)
+//! and whether the key was pressed (1) or released (0) as payload.
+//!
+//! The quirks are bit flags from the least significant bit: shift, memoryIncrementByX, memoryLeaveIUnchanged, wrap,
+//! jump, vblank, logic and iWrap. Unknown bits are ignored.
+//!
+//! Readers reject other versions. Within a version, new header fields are only appended and new kinds of events may
+//! be added, which readers skip by their lengths, so that older readers can still play newer replays.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use sha1_smol::Sha1;
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error, Quirks};
+
+const MAGIC: &[u8; 4] = b"C8RP";
+const VERSION: u8 = 1;
+const KEY_EVENT: u8 = 1;
+
+/// A key of the hex keypad was pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Number of frames executed before the key changed, i.e. the key changes before the frame with this number
+    /// (counted from 0).
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub rom_sha1: [u8; 20],
+    pub instructions_per_frame: u32,
+    pub quirks: Quirks,
+    pub seed: u64,
+    /// Platform of the ROM, like in the CHIP-8 database, or empty if unknown.
+    pub platform: String,
+    /// Ordered by frame.
+    pub events: Vec<KeyEvent>,
+}
+
+impl Replay {
+    /// Starts a replay of `rom`, which was just loaded into `chip8`, without events yet.
+    pub fn new(rom: &[u8], chip8: &Chip8) -> Self {
+        Self {
+            rom_sha1: Sha1::from(rom).digest().bytes(),
+            instructions_per_frame: chip8.instructions_per_frame(),
+            quirks: *chip8.quirks(),
+            seed: chip8.seed(),
+            platform: String::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Whether the replay was recorded with `rom`.
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        Sha1::from(rom).digest().bytes() == self.rom_sha1
+    }
+
+    /// Sets up `chip8` like when the replay was recorded, including the keys changed before the first frame. The ROM
+    /// has to be loaded already.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        chip8.set_instructions_per_frame(self.instructions_per_frame);
+        chip8.set_quirks(self.quirks);
+        chip8.set_seed(self.seed);
+        for event in self.events.iter().take_while(|event| event.frame == 0) {
+            chip8.set_key_state(event.key, event.pressed);
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the replay to a file at `path`, truncating it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let platform = self.platform.as_bytes();
+        let platform = &platform[..platform.len().min(u8::MAX as usize)];
+        let mut header = Vec::new();
+        header.extend_from_slice(&self.rom_sha1);
+        header.extend_from_slice(&self.instructions_per_frame.to_be_bytes());
+        header.extend_from_slice(&quirk_bits(&self.quirks).to_be_bytes());
+        header.extend_from_slice(&self.seed.to_be_bytes());
+        header.push(platform.len() as u8);
+        header.extend_from_slice(platform);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(header.len() as u32).to_be_bytes())?;
+        out.write_all(&header)?;
+        out.write_all(&(self.events.len() as u32).to_be_bytes())?;
+        for event in &self.events {
+            out.write_all(&[KEY_EVENT, 10])?;
+            out.write_all(&event.frame.to_be_bytes())?;
+            out.write_all(&[event.key, event.pressed as u8])?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a replay"));
+        }
+        if read_bytes(input, 1)?[0] != VERSION {
+            return Err(invalid("Unsupported replay version"));
+        }
+        let header_len = read_u32(input)? as usize;
+        let header = read_bytes(input, header_len)?;
+        // Fields appended by newer writers come after the known ones and are ignored
+        let mut header = header.as_slice();
+        let mut rom_sha1 = [0; 20];
+        header.read_exact(&mut rom_sha1)?;
+        let instructions_per_frame = read_u32(&mut header)?;
+        let quirks = quirks_from_bits(read_u16(&mut header)?);
+        let seed = read_u64(&mut header)?;
+        let platform_len = read_bytes(&mut header, 1)?[0] as usize;
+        let platform = String::from_utf8(read_bytes(&mut header, platform_len)?)
+            .map_err(|_| invalid("Platform name isn't UTF-8"))?;
+
+        let event_count = read_u32(input)?;
+        let mut events = Vec::new();
+        for _ in 0..event_count {
+            let kind_and_len = read_bytes(input, 2)?;
+            let payload = read_bytes(input, kind_and_len[1] as usize)?;
+            if kind_and_len[0] != KEY_EVENT {
+                continue;
+            }
+            if payload.len() < 10 {
+                return Err(invalid("Key event is too short"));
+            }
+            let frame = read_u64(&mut &payload[..8])?;
+            let key = payload[8];
+            if key > 0xF {
+                return Err(invalid("Key event for a key beyond F"));
+            }
+            events.push(KeyEvent { frame, key, pressed: payload[9] != 0 });
+        }
+        Ok(Self { rom_sha1, instructions_per_frame, quirks, seed, platform, events })
+    }
+}
+
+/// Records the key presses and releases of a running [`Chip8`] into a [`Replay`]. Should come after all hooks that
+/// change keys, so that it sees the keys the next frame is executed with.
+#[derive(Debug)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    keypad: u16,
+}
+
+impl ReplayRecorder {
+    /// Starts recording `chip8`, into which `rom` was just loaded. The keys held right now count as pressed before the
+    /// first frame.
+    pub fn new(rom: &[u8], chip8: &Chip8) -> Self {
+        let mut replay = Replay::new(rom, chip8);
+        replay.events.extend(changed_keys(0, chip8.keypad(), 0));
+        Self { replay, keypad: chip8.keypad() }
+    }
+
+    /// Sets the platform stored in the replay, like in the CHIP-8 database.
+    pub fn set_platform(&mut self, platform: impl Into<String>) {
+        self.replay.platform = platform.into();
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn into_replay(self) -> Replay {
+        self.replay
+    }
+}
+
+impl Hooks for ReplayRecorder {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        let keypad = chip8.keypad();
+        self.replay.events.extend(changed_keys(self.keypad, keypad, chip8.metrics().frames));
+        self.keypad = keypad;
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Plays the key events of a [`Replay`] set up with [`Replay::apply`]. Should come after all hooks that change keys,
+/// so that the replay decides about the keys.
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    events: Vec<KeyEvent>,
+    /// Index of the next event to play.
+    next: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: &Replay) -> Self {
+        // The events before the first frame were played by `Replay::apply`
+        let next = replay.events.iter().take_while(|event| event.frame == 0).count();
+        Self { events: replay.events.clone(), next }
+    }
+
+    /// Whether all events were played.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
+
+impl Hooks for ReplayPlayer {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        let frames = chip8.metrics().frames;
+        while let Some(event) = self.events.get(self.next).filter(|event| event.frame <= frames) {
+            chip8.set_key_state(event.key, event.pressed);
+            self.next += 1;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Key events for the keys that differ between `old` and `new`.
+fn changed_keys(old: u16, new: u16, frame: u64) -> impl Iterator<Item = KeyEvent> {
+    (0..16u8)
+        .filter(move |&key| (old ^ new) >> key & 1 == 1)
+        .map(move |key| KeyEvent { frame, key, pressed: new >> key & 1 == 1 })
+}
+
+fn quirk_bits(quirks: &Quirks) -> u16 {
+    [
+        quirks.shift,
+        quirks.memory_increment_by_x,
+        quirks.memory_leave_i_unchanged,
+        quirks.wrap,
+        quirks.jump,
+        quirks.vblank,
+        quirks.logic,
+        quirks.i_wrap,
+    ].iter().enumerate().fold(0, |bits, (bit, &enabled)| bits | (enabled as u16) << bit)
+}
+
+fn quirks_from_bits(bits: u16) -> Quirks {
+    let enabled = |bit: u16| (bits >> bit) & 1 == 1;
+    Quirks {
+        shift: enabled(0),
+        memory_increment_by_x: enabled(1),
+        memory_leave_i_unchanged: enabled(2),
+        wrap: enabled(3),
+        jump: enabled(4),
+        vblank: enabled(5),
+        logic: enabled(6),
+        i_wrap: enabled(7),
+    }
+}
+
+fn read_bytes(input: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    input.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
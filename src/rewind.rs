@@ -0,0 +1,204 @@
+//! Reverse execution and time travel for debuggers. The state of the machine is tiny, so instead of recording every
+//! change, a [`Rewind`] takes a [`Snapshot`] every few instructions. Going to an instruction restores the last
+//! snapshot before it and executes the instructions up to it again. Key presses and the timer ticks at the end of
+//! frames are logged, because they are the only changes that don't come from instructions. Changes by scripts or by
+//! recovering from errors aren't recorded.
+
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use crate::hooks::Hooks;
+use crate::snapshot::Snapshot;
+use crate::{Chip8, Chip8Error};
+
+/// Number of instructions between checkpoints. At most this many instructions are executed again to go back.
+const CHECKPOINT_INTERVAL: u64 = 100;
+/// Number of checkpoints kept by [`Rewind::new`]. Older ones are dropped, which limits how far back is possible.
+const MAX_CHECKPOINTS: usize = 1024;
+
+#[derive(Debug)]
+struct Checkpoint {
+    /// Number of instructions executed before the snapshot was taken.
+    position: u64,
+    snapshot: Snapshot,
+    /// Not part of snapshots, but decides when the timers tick.
+    cycles_since_tick: u32,
+}
+
+/// A change of the machine state that doesn't come from an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    /// The keypad changed before the instruction at the position of the event.
+    Keypad(u16),
+    /// The timers ticked at the end of a frame, after the instruction at the position of the event.
+    TimerTick,
+}
+
+/// Records the execution of a [`Chip8`] to go back to earlier instructions. Has to see every executed instruction and
+/// the end of every frame, either as hook or by calling [`Rewind::record`] after every instruction.
+#[derive(Debug)]
+pub struct Rewind {
+    /// Ordered by position.
+    checkpoints: VecDeque<Checkpoint>,
+    /// Maximum number of checkpoints, or `None` to record everything.
+    max_checkpoints: Option<usize>,
+    /// Positions and events in the order they happened.
+    events: VecDeque<(u64, Event)>,
+    /// Number of executed instructions.
+    position: u64,
+    /// Position of the last recorded instruction, which is after `position` after going back.
+    end: u64,
+    /// Keypad state of the last executed instruction.
+    keypad: u16,
+    /// Value after the last executed instruction, to notice timer ticks at the end of frames.
+    cycles_since_tick: u32,
+}
+
+impl Rewind {
+    /// Starts recording at the current state of `chip8`. Only the last few seconds of emulated time are kept.
+    pub fn new(chip8: &Chip8) -> Self {
+        Self::with_max_checkpoints(chip8, Some(MAX_CHECKPOINTS))
+    }
+
+    /// Starts recording a whole session at the current state of `chip8`. Memory usage grows by about 50 bytes per
+    /// executed instruction.
+    pub fn unlimited(chip8: &Chip8) -> Self {
+        Self::with_max_checkpoints(chip8, None)
+    }
+
+    fn with_max_checkpoints(chip8: &Chip8, max_checkpoints: Option<usize>) -> Self {
+        let mut rewind = Self {
+            checkpoints: VecDeque::new(),
+            max_checkpoints,
+            events: VecDeque::new(),
+            position: 0,
+            end: 0,
+            keypad: chip8.keypad,
+            cycles_since_tick: chip8.cycles_since_tick,
+        };
+        rewind.checkpoint(chip8);
+        rewind
+    }
+
+    /// Number of instructions executed since the recording started.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The oldest position that can be gone back to.
+    pub fn start(&self) -> u64 {
+        self.checkpoints.front().map_or(0, |checkpoint| checkpoint.position)
+    }
+
+    /// The newest recorded position, which can be gone forward to after going back.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Records that `chip8` executed an instruction. Everything recorded after the current position, i.e. before
+    /// going back, is dropped, because the execution may take a different path now.
+    pub fn record(&mut self, chip8: &Chip8) {
+        self.position += 1;
+        let position = self.position;
+        while self.checkpoints.back().is_some_and(|checkpoint| checkpoint.position >= position) {
+            self.checkpoints.pop_back();
+        }
+        while self.events.back().is_some_and(|&(event_position, _)| event_position >= position) {
+            self.events.pop_back();
+        }
+        self.end = position;
+        if chip8.keypad != self.keypad {
+            self.keypad = chip8.keypad;
+            self.events.push_back((position, Event::Keypad(chip8.keypad)));
+        }
+        self.cycles_since_tick = chip8.cycles_since_tick;
+        let last_checkpoint = self.checkpoints.back().map_or(0, |checkpoint| checkpoint.position);
+        if position - last_checkpoint >= CHECKPOINT_INTERVAL {
+            self.checkpoint(chip8);
+        }
+    }
+
+    /// Records the end of a frame, where the timers may tick.
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        if chip8.cycles_since_tick != self.cycles_since_tick {
+            self.cycles_since_tick = chip8.cycles_since_tick;
+            self.events.push_back((self.position, Event::TimerTick));
+        }
+    }
+
+    /// Puts `chip8` back into the state before the last executed instruction. Returns `false` if the recording
+    /// doesn't go back that far.
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> Result<bool, Chip8Error> {
+        match self.position.checked_sub(1) {
+            Some(position) => self.seek(chip8, position),
+            None => Ok(false),
+        }
+    }
+
+    /// Puts `chip8` into the state after `position` instructions, between [`Rewind::start`] and [`Rewind::end`], by
+    /// executing them again from the last checkpoint before. Returns `false` if `position` wasn't recorded. Replayed
+    /// instructions are profiled and traced again, if enabled.
+    pub fn seek(&mut self, chip8: &mut Chip8, position: u64) -> Result<bool, Chip8Error> {
+        if !(self.start()..=self.end).contains(&position) {
+            return Ok(false);
+        }
+        let checkpoint = self.checkpoints.iter()
+            .rev()
+            .find(|checkpoint| checkpoint.position <= position)
+            .expect("Positions after the start have a checkpoint before them");
+        checkpoint.snapshot.restore(chip8);
+        chip8.cycles_since_tick = checkpoint.cycles_since_tick;
+        // Checkpoints are taken after the instruction, but before the end of the frame
+        let mut events = self.events.iter()
+            .skip_while(|&&(event_position, event)| {
+                event_position < checkpoint.position
+                    || (event_position == checkpoint.position && matches!(event, Event::Keypad(_)))
+            })
+            .peekable();
+        for n in checkpoint.position..=position {
+            if n > checkpoint.position {
+                if let Some(&(_, Event::Keypad(keypad))) = events.next_if(|&&(event_position, event)| {
+                    event_position == n && matches!(event, Event::Keypad(_))
+                }) {
+                    chip8.set_keypad(keypad);
+                }
+                chip8.step()?;
+            }
+            if events.next_if(|&&(event_position, event)| event_position == n && event == Event::TimerTick).is_some() {
+                chip8.tick_timers();
+            }
+        }
+        self.position = position;
+        self.keypad = chip8.keypad;
+        self.cycles_since_tick = chip8.cycles_since_tick;
+        Ok(true)
+    }
+
+    fn checkpoint(&mut self, chip8: &Chip8) {
+        self.checkpoints.push_back(Checkpoint {
+            position: self.position,
+            snapshot: Snapshot::of(chip8),
+            cycles_since_tick: chip8.cycles_since_tick,
+        });
+        if self.max_checkpoints.is_some_and(|max_checkpoints| self.checkpoints.len() > max_checkpoints) {
+            self.checkpoints.pop_front();
+            let oldest = self.checkpoints[0].position;
+            while self.events.front().is_some_and(|&(event_position, _)| event_position < oldest) {
+                self.events.pop_front();
+            }
+        }
+    }
+}
+
+impl Hooks for Rewind {
+    fn after_instruction(&mut self, chip8: &mut Chip8, _pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.record(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.record_frame(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
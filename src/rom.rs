@@ -0,0 +1,178 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use flate2::read::{DeflateDecoder, GzDecoder};
+use sha1_smol::Sha1;
+use crate::assembler::{self, Assembly};
+use crate::MAX_PROGRAM_SIZE;
+
+/// A loaded program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    pub bytes: Vec<u8>,
+    /// The result of the assembler, if the ROM was assembled from source.
+    pub assembly: Option<Assembly>,
+}
+
+/// What identifies a ROM in ROM databases and collections: its size and hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomId {
+    pub size: usize,
+    /// See [`sha1`].
+    pub sha1: String,
+    pub crc32: u32,
+}
+
+impl RomId {
+    pub fn of(rom: &[u8]) -> Self {
+        Self { size: rom.len(), sha1: sha1(rom), crc32: crc32fast::hash(rom) }
+    }
+}
+
+impl fmt::Display for RomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes, SHA-1 {}, CRC32 {:08x}", self.size, self.sha1, self.crc32)
+    }
+}
+
+/// Reads the ROM at `path`, or from stdin if `path` is `-`. `http://` and `https://` URLs are downloaded if the
+/// `url` feature is enabled. Octo source files (`.8o`) are assembled. Archives are unpacked: `.gz` files hold the
+/// ROM, of `.zip` files the first `.ch8` file is loaded.
+pub fn load(path: &str) -> io::Result<Rom> {
+    let mut reader: Box<dyn Read> = match path {
+        "-" => Box::new(io::stdin().lock()),
+        _ if is_url(path) => download(path)?,
+        _ => Box::new(File::open(path)?),
+    };
+    if has_extension(path, ".zip") {
+        let mut archive = Vec::new();
+        reader.read_to_end(&mut archive)?;
+        return Ok(Rom { bytes: unzip_rom(&archive)?, assembly: None });
+    }
+    // The name inside of the gzip file decides e.g. whether it's assembled
+    let path = match has_extension(path, ".gz") {
+        true => {
+            reader = Box::new(GzDecoder::new(reader));
+            &path[..path.len() - ".gz".len()]
+        },
+        false => path,
+    };
+    if path.ends_with(".8o") {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let assembly = assembler::assemble(&source)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        return Ok(Rom { bytes: assembly.rom.clone(), assembly: Some(assembly) });
+    }
+    Ok(Rom { bytes: read_limited(reader)?, assembly: None })
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+#[cfg(feature = "url")]
+fn download(url: &str) -> io::Result<Box<dyn Read>> {
+    let response = ureq::get(url).call().map_err(io::Error::other)?;
+    Ok(Box::new(response.into_body().into_reader()))
+}
+
+#[cfg(not(feature = "url"))]
+fn download(_url: &str) -> io::Result<Box<dyn Read>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Loading ROMs from URLs requires building with the `url` feature"))
+}
+
+/// Whether `path` ends with `extension` like `.zip`, ignoring case.
+fn has_extension(path: &str, extension: &str) -> bool {
+    path.len() >= extension.len()
+        && path.is_char_boundary(path.len() - extension.len())
+        && path[path.len() - extension.len()..].eq_ignore_ascii_case(extension)
+}
+
+/// Unpacks the first `.ch8` file of a zip archive. Only uncompressed and deflated files are supported, which is what
+/// zip tools write.
+fn unzip_rom(archive: &[u8]) -> io::Result<Vec<u8>> {
+    // The end of central directory record is at the end, followed by a comment of up to 64 KiB
+    let end = (0..archive.len().saturating_sub(21)).rev()
+        .take(0x10000)
+        .find(|&offset| le32(archive, offset).ok() == Some(0x0605_4B50))
+        .ok_or_else(|| invalid_zip("The end of the central directory is missing"))?;
+    let mut entry = le32(archive, end + 16)?;
+    for _ in 0..le16(archive, end + 10)? {
+        if le32(archive, entry)? != 0x0201_4B50 {
+            return Err(invalid_zip("Broken central directory"));
+        }
+        let name_len = le16(archive, entry + 28)?;
+        let name = archive.get(entry + 46..entry + 46 + name_len).ok_or_else(|| invalid_zip("Truncated"))?;
+        let name = String::from_utf8_lossy(name);
+        if !has_extension(&name, ".ch8") {
+            entry += 46 + name_len + le16(archive, entry + 30)? + le16(archive, entry + 32)?;
+            continue;
+        }
+        let header = le32(archive, entry + 42)?;
+        if le32(archive, header)? != 0x0403_4B50 {
+            return Err(invalid_zip("Broken file header"));
+        }
+        let start = header + 30 + le16(archive, header + 26)? + le16(archive, header + 28)?;
+        let data = archive.get(start..start + le32(archive, entry + 20)?).ok_or_else(|| invalid_zip("Truncated"))?;
+        return match le16(archive, entry + 10)? {
+            0 => read_limited(data),
+            8 => read_limited(DeflateDecoder::new(data)),
+            method => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is compressed with method {}, only deflate is supported", name, method),
+            )),
+        };
+    }
+    Err(invalid_zip("There's no .ch8 file in it"))
+}
+
+/// The little-endian `u16` at `offset` of a zip archive.
+fn le16(archive: &[u8], offset: usize) -> io::Result<usize> {
+    match archive.get(offset..offset + 2) {
+        Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize),
+        None => Err(invalid_zip("Truncated")),
+    }
+}
+
+/// The little-endian `u32` at `offset` of a zip archive.
+fn le32(archive: &[u8], offset: usize) -> io::Result<usize> {
+    match archive.get(offset..offset + 4) {
+        Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize),
+        None => Err(invalid_zip("Truncated")),
+    }
+}
+
+fn invalid_zip(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid zip archive: {}", message))
+}
+
+/// Checks that the SHA-1 hash of `rom` is `expected`, so that a downloaded ROM is the one that was intended.
+pub fn verify_sha1(rom: &[u8], expected: &str) -> io::Result<()> {
+    let actual = sha1(rom);
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ROM has SHA-1 hash {}, but {} was expected", actual, expected),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a ROM from `reader`, failing if it doesn't fit into memory.
+fn read_limited(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut rom = Vec::new();
+    reader.take(MAX_PROGRAM_SIZE as u64 + 1).read_to_end(&mut rom)?;
+    if rom.len() > MAX_PROGRAM_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ROM is larger than {} bytes", MAX_PROGRAM_SIZE),
+        ));
+    }
+    Ok(rom)
+}
+
+/// SHA-1 hash of the ROM as lowercase hex string, which is how ROM databases identify programs.
+pub fn sha1(rom: &[u8]) -> String {
+    Sha1::from(rom).digest().to_string()
+}
@@ -0,0 +1,103 @@
+//! Lua scripting hooks. A script may define the global functions `on_frame()` and `on_instruction(pc, opcode)`,
+//! which are called while the emulator runs. Inside them, the global table `chip8` gives access to the machine:
+//!
+//! * `chip8.read(addr)` / `chip8.write(addr, value)` read and write a byte of memory.
+//! * `chip8.reg(n)` / `chip8.set_reg(n, value)` read and write register `vn`.
+//! * `chip8.i()` / `chip8.set_i(value)` read and write the address register.
+//! * `chip8.pc()` returns the program counter.
+//! * `chip8.press(key)` / `chip8.release(key)` inject key presses.
+
+use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::path::Path;
+use mlua::{Function, IntoLuaMulti, Lua};
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+pub struct LuaScript {
+    lua: Lua,
+}
+
+impl LuaScript {
+    /// Loads and executes the script at `path`, which should define the callback functions.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Chip8Error> {
+        let source = std::fs::read_to_string(path).map_err(|err| Chip8Error::Hook(err.to_string()))?;
+        Self::from_source(&source)
+    }
+
+    pub fn from_source(source: &str) -> Result<Self, Chip8Error> {
+        let lua = Lua::new();
+        lua.load(source).exec().map_err(lua_error)?;
+        Ok(Self { lua })
+    }
+
+    /// Calls the global Lua function `name` with `args` if the script defined it. While the function runs, the
+    /// `chip8` table is bound to `chip8`.
+    fn call(&self, chip8: &mut Chip8, name: &str, args: impl IntoLuaMulti) -> Result<(), Chip8Error> {
+        let globals = self.lua.globals();
+        let callback: Option<Function> = globals.get(name).map_err(lua_error)?;
+        let callback = match callback {
+            Some(callback) => callback,
+            None => return Ok(()),
+        };
+
+        let chip8 = RefCell::new(chip8);
+        self.lua.scope(|scope| {
+            let api = self.lua.create_table()?;
+            api.set("read", scope.create_function(|_, addr: usize| {
+                let chip8 = chip8.borrow();
+                Ok((addr < chip8.bus.size()).then(|| chip8.bus.peek(addr)))
+            })?)?;
+            api.set("write", scope.create_function(|_, (addr, value): (usize, u8)| {
+                let mut chip8 = chip8.borrow_mut();
+                if addr < chip8.bus.size() {
+                    chip8.poke(addr, value);
+                }
+                Ok(())
+            })?)?;
+            api.set("reg", scope.create_function(|_, n: usize| {
+                Ok(chip8.borrow().registers.get(n).copied())
+            })?)?;
+            api.set("set_reg", scope.create_function(|_, (n, value): (usize, u8)| {
+                if let Some(register) = chip8.borrow_mut().registers.get_mut(n) {
+                    *register = value;
+                }
+                Ok(())
+            })?)?;
+            api.set("i", scope.create_function(|_, ()| Ok(chip8.borrow().address_register))?)?;
+            api.set("set_i", scope.create_function(|_, value: u16| {
+                chip8.borrow_mut().address_register = value;
+                Ok(())
+            })?)?;
+            api.set("pc", scope.create_function(|_, ()| Ok(chip8.borrow().pc))?)?;
+            api.set("press", scope.create_function(|_, key: u8| {
+                chip8.borrow_mut().set_key_state(key, true);
+                Ok(())
+            })?)?;
+            api.set("release", scope.create_function(|_, key: u8| {
+                chip8.borrow_mut().set_key_state(key, false);
+                Ok(())
+            })?)?;
+            globals.set("chip8", api)?;
+            callback.call::<()>(args)
+        }).map_err(lua_error)
+    }
+}
+
+impl Hooks for LuaScript {
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: usize, opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.call(chip8, "on_instruction", (pc, opcode))?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.call(chip8, "on_frame", ())?;
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+fn lua_error(err: mlua::Error) -> Chip8Error {
+    Chip8Error::Hook(err.to_string())
+}
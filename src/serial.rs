@@ -0,0 +1,86 @@
+//! A serial console mapped to one address, so ROMs can do text I/O for testing and teaching. Writing a byte to the
+//! address (e.g. with `FX55` and `X = 0`) sends it to the host, reading it (`FX65`) returns the next byte of input or
+//! zero if there is none.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use crate::bus::Bus;
+
+/// Address of the console unless another one is given, the last byte of memory.
+pub const DEFAULT_ADDR: usize = 0xFFF;
+
+/// A [`Bus`] that maps the console to `addr` and passes all other accesses to the wrapped bus.
+pub struct SerialConsole<B> {
+    inner: B,
+    addr: usize,
+    input: Receiver<u8>,
+    output: Box<dyn Write>,
+}
+
+impl<B> SerialConsole<B> {
+    /// Maps the console to `addr` of `inner`. Reads consume bytes from `input`, writes go to `output`.
+    pub fn new(inner: B, addr: usize, input: Receiver<u8>, output: impl Write + 'static) -> Self {
+        Self { inner, addr, input, output: Box::new(output) }
+    }
+
+    /// Maps the console to `addr` of `inner`, reading input from stdin in the background and writing output to
+    /// stderr, so it doesn't mix with the display.
+    pub fn stdio(inner: B, addr: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                match byte {
+                    Ok(byte) if sender.send(byte).is_ok() => {},
+                    _ => break,
+                }
+            }
+        });
+        Self::new(inner, addr, receiver, io::stderr())
+    }
+}
+
+impl<B: fmt::Debug> fmt::Debug for SerialConsole<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SerialConsole").field("inner", &self.inner).field("addr", &self.addr).finish_non_exhaustive()
+    }
+}
+
+impl<B: Bus> Bus for SerialConsole<B> {
+    /// The console has no readable state, so peeking at it always returns zero.
+    fn peek(&self, addr: usize) -> u8 {
+        match addr == self.addr {
+            true => 0,
+            false => self.inner.peek(addr),
+        }
+    }
+
+    fn read(&mut self, addr: usize) -> u8 {
+        match addr == self.addr {
+            true => self.input.try_recv().unwrap_or(0),
+            false => self.inner.read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        if addr != self.addr {
+            return self.inner.write(addr, value);
+        }
+        // The console is best effort, a ROM can't do anything about a closed output anyway
+        let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+    }
+
+    fn memory(&self) -> &[u8] {
+        self.inner.memory()
+    }
+
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    /// Clears the wrapped memory without sending anything to the console.
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+}
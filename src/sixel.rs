@@ -0,0 +1,95 @@
+//! Renders the display as [sixel](https://en.wikipedia.org/wiki/Sixel) graphics for terminals that support it, e.g.
+//! xterm, foot and mlterm. Unlike the character art, the pixels stay square and can be scaled up.
+
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use crate::hooks::Hooks;
+use crate::image::Palette;
+use crate::{Chip8, Chip8Error, Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Every sixel character encodes a column of this many pixels.
+const BAND_HEIGHT: usize = 6;
+
+/// Encodes `display` as sixel image, where every Chip-8 pixel becomes a `scale` x `scale` square. The image starts
+/// at the cursor.
+pub fn encode(display: &Framebuffer, scale: usize, palette: &Palette) -> String {
+    let scale = scale.max(1);
+    let (width, height) = (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+    // Pixel aspect ratio 1:1, and the background is drawn explicitly
+    let mut output = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for (index, color) in [palette.background, palette.foreground].iter().enumerate() {
+        let [r, g, b] = color.map(|channel| channel as u32 * 100 / 255);
+        output.push_str(&format!("#{};2;{};{};{}", index, r, g, b));
+    }
+    for band in 0..height.div_ceil(BAND_HEIGHT) {
+        for (index, set) in [false, true].iter().copied().enumerate() {
+            output.push_str(&format!("#{}", index));
+            let sixels = (0..width).map(|x| {
+                let bits = (0..BAND_HEIGHT)
+                    .map(|dy| band * BAND_HEIGHT + dy)
+                    .enumerate()
+                    .filter(|&(_, y)| y < height && display.pixel(x / scale, y / scale) == set)
+                    .fold(0, |bits, (dy, _)| bits | 1 << dy);
+                (b'?' + bits) as char
+            });
+            push_run_length_encoded(&mut output, sixels);
+            // Go back to the start of the band for the next color
+            output.push('$');
+        }
+        output.push('-');
+    }
+    output.push_str("\x1b\\");
+    output
+}
+
+/// Appends `sixels`, where repeated characters are replaced by `!<count><character>`.
+fn push_run_length_encoded(output: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let push_run = |output: &mut String, sixel: char, count: usize| match count {
+        1..=3 => output.extend(std::iter::repeat_n(sixel, count)),
+        _ => output.push_str(&format!("!{}{}", count, sixel)),
+    };
+    for sixel in sixels {
+        run = match run {
+            Some((current, count)) if current == sixel => Some((current, count + 1)),
+            Some((current, count)) => {
+                push_run(output, current, count);
+                Some((sixel, 1))
+            },
+            None => Some((sixel, 1)),
+        };
+    }
+    if let Some((sixel, count)) = run {
+        push_run(output, sixel, count);
+    }
+}
+
+/// Draws the display as sixel image whenever it changed. Disable the character art with
+/// [`Chip8::set_text_output`], so that both don't overwrite each other.
+#[derive(Debug, Clone)]
+pub struct SixelRenderer {
+    scale: usize,
+    palette: Palette,
+}
+
+impl SixelRenderer {
+    /// Every Chip-8 pixel becomes a `scale` x `scale` square.
+    pub fn new(scale: usize, palette: Palette) -> Self {
+        Self { scale: scale.max(1), palette }
+    }
+}
+
+impl Hooks for SixelRenderer {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        if chip8.dirty_rows == 0 {
+            return Ok(ControlFlow::Continue(()));
+        }
+        // Keep the cursor at the top left of the display, like the character art does
+        let image = encode(chip8.shown_framebuffer(), self.scale, &self.palette);
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b7{}\x1b8", image)
+            .and_then(|()| stdout.flush())
+            .map_err(|err| Chip8Error::Hook(format!("Can't draw sixel image: {}", err)))?;
+        Ok(ControlFlow::Continue(()))
+    }
+}
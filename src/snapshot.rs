@@ -0,0 +1,292 @@
+//! Snapshots of the whole machine state, e.g. for core dumps after a crash. Snapshots can be restored into a
+//! [`Chip8`] and saved to files.
+//!
+//! File format (all numbers big endian): `"C8SS"`, version (u8), PC (u16), I (u16), V0 to VF (16 bytes), delay
+//! timer (u8), sound timer (u8), keypad (u16), seed (u64), random number generator state (u64), stack depth (u8),
+//! stack pointer (u8), stack (u16 per entry, `depth + 1` entries), memory size (u32), memory, display (256 bytes),
+//! number of recent instructions (u8), recent instructions (PC as u16 and opcode as u16 each).
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use crate::display::{self, Framebuffer, Rect};
+use crate::{Chip8, DISPLAY_HEIGHT};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u8 = 1;
+
+/// The state of a [`Chip8`]. Configuration like the quirks or the speed isn't part of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: usize,
+    /// Address register (I).
+    pub i: u16,
+    pub registers: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keypad: u16,
+    pub seed: u64,
+    pub rng_state: u64,
+    /// Return addresses in `stack[1..=stack_pointer]`. The length is the stack depth plus one.
+    pub stack: Vec<usize>,
+    pub stack_pointer: u8,
+    pub memory: Vec<u8>,
+    /// The display, formatted as block characters with `{}`.
+    pub display: Framebuffer,
+    /// PC and opcode of the last executed instructions, oldest first.
+    pub history: Vec<(usize, u16)>,
+}
+
+impl Snapshot {
+    /// Captures the current state of `chip8`.
+    pub fn of(chip8: &Chip8) -> Self {
+        Self {
+            pc: chip8.pc,
+            i: chip8.address_register,
+            registers: chip8.registers,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            keypad: chip8.keypad,
+            seed: chip8.seed,
+            rng_state: chip8.rng_state,
+            stack: chip8.stack.clone(),
+            stack_pointer: chip8.stack_pointer,
+            memory: chip8.memory().to_vec(),
+            display: chip8.display,
+            history: chip8.history.iter().copied().collect(),
+        }
+    }
+
+    /// Puts `chip8` into the captured state. Memory is written through the bus of `chip8`, as far as it's large
+    /// enough.
+    pub fn restore(&self, chip8: &mut Chip8) {
+        // Sets the PC and stops waiting for a key, which FX0A starts again if the PC is at it
+        chip8.set_pc(self.pc);
+        chip8.address_register = self.i;
+        chip8.registers = self.registers;
+        chip8.delay_timer = self.delay_timer;
+        chip8.sound_timer = self.sound_timer;
+        chip8.keypad = self.keypad;
+        chip8.seed = self.seed;
+        chip8.rng_state = self.rng_state;
+        chip8.stack = self.stack.clone();
+        chip8.stack_pointer = self.stack_pointer;
+        for (addr, &byte) in self.memory.iter().enumerate().take(chip8.bus.size()) {
+            chip8.poke(addr, byte);
+        }
+        chip8.display = self.display;
+        chip8.history = self.history.iter().copied().collect();
+        chip8.request_redraw();
+    }
+
+    /// FNV-1a hash of the snapshot in the file format, which is equal for equal snapshots.
+    pub fn hash(&self) -> u64 {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes).expect("Writing to a Vec never fails");
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+
+    /// Compares this snapshot with `other`, e.g. to find where two quirk configurations diverge.
+    pub fn diff(&self, other: &Snapshot) -> SnapshotDiff {
+        let mut values = vec![
+            ("PC", self.pc as u64, other.pc as u64),
+            ("I", self.i as u64, other.i as u64),
+        ];
+        let names = ["V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF"];
+        for ((name, &left), &right) in names.iter().zip(&self.registers).zip(&other.registers) {
+            values.push((name, left as u64, right as u64));
+        }
+        values.extend([
+            ("DT", self.delay_timer as u64, other.delay_timer as u64),
+            ("ST", self.sound_timer as u64, other.sound_timer as u64),
+            ("SP", self.stack_pointer as u64, other.stack_pointer as u64),
+            ("keypad", self.keypad as u64, other.keypad as u64),
+            ("RNG state", self.rng_state, other.rng_state),
+        ]);
+        let mut values: Vec<(String, u64, u64)> = values.into_iter()
+            .filter(|(_, left, right)| left != right)
+            .map(|(name, left, right)| (name.to_string(), left, right))
+            .collect();
+        // Only the active part of the stack matters
+        let active_stack = |snapshot: &Snapshot| snapshot.stack[1..=snapshot.stack_pointer as usize].to_vec();
+        let (stack, other_stack) = (active_stack(self), active_stack(other));
+        for n in 0..stack.len().max(other_stack.len()) {
+            let (left, right) = (stack.get(n).copied().unwrap_or(0), other_stack.get(n).copied().unwrap_or(0));
+            if left != right {
+                values.push((format!("stack[{}]", n + 1), left as u64, right as u64));
+            }
+        }
+
+        // Runs of differing bytes, where bytes beyond the end of the smaller memory always differ
+        let mut memory: Vec<Range<usize>> = Vec::new();
+        for addr in 0..self.memory.len().max(other.memory.len()) {
+            if self.memory.get(addr) == other.memory.get(addr) {
+                continue;
+            }
+            match memory.last_mut() {
+                Some(range) if range.end == addr => range.end += 1,
+                _ => memory.push(addr..addr + 1),
+            }
+        }
+
+        SnapshotDiff { values, memory, display: display::dirty_rects(&self.display, &other.display) }
+    }
+
+    /// Reads a snapshot file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the snapshot to a file at `path`, truncating it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(self.pc as u16).to_be_bytes())?;
+        out.write_all(&self.i.to_be_bytes())?;
+        out.write_all(&self.registers)?;
+        out.write_all(&[self.delay_timer, self.sound_timer])?;
+        out.write_all(&self.keypad.to_be_bytes())?;
+        out.write_all(&self.seed.to_be_bytes())?;
+        out.write_all(&self.rng_state.to_be_bytes())?;
+        out.write_all(&[(self.stack.len() - 1) as u8, self.stack_pointer])?;
+        for &addr in &self.stack {
+            out.write_all(&(addr as u16).to_be_bytes())?;
+        }
+        out.write_all(&(self.memory.len() as u32).to_be_bytes())?;
+        out.write_all(&self.memory)?;
+        for row in self.display.rows() {
+            out.write_all(&row.to_be_bytes())?;
+        }
+        out.write_all(&[self.history.len() as u8])?;
+        for &(pc, opcode) in &self.history {
+            out.write_all(&(pc as u16).to_be_bytes())?;
+            out.write_all(&opcode.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a snapshot"));
+        }
+        if read_u8(input)? != VERSION {
+            return Err(invalid("Unsupported snapshot version"));
+        }
+        let pc = read_u16(input)? as usize;
+        let i = read_u16(input)?;
+        let mut registers = [0; 16];
+        input.read_exact(&mut registers)?;
+        let delay_timer = read_u8(input)?;
+        let sound_timer = read_u8(input)?;
+        let keypad = read_u16(input)?;
+        let seed = read_u64(input)?;
+        let rng_state = read_u64(input)?;
+        let stack_depth = read_u8(input)?;
+        let stack_pointer = read_u8(input)?;
+        if stack_pointer > stack_depth {
+            return Err(invalid("Stack pointer is beyond the stack"));
+        }
+        let stack = (0..=stack_depth).map(|_| Ok(read_u16(input)? as usize)).collect::<io::Result<_>>()?;
+        let memory_size = read_u32(input)? as usize;
+        let mut memory = Vec::new();
+        input.by_ref().take(memory_size as u64).read_to_end(&mut memory)?;
+        if memory.len() != memory_size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut rows = [0; DISPLAY_HEIGHT];
+        for row in &mut rows {
+            *row = read_u64(input)?;
+        }
+        let display = Framebuffer::from_rows(rows);
+        let history_len = read_u8(input)?;
+        let history = (0..history_len)
+            .map(|_| Ok((read_u16(input)? as usize, read_u16(input)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            pc,
+            i,
+            registers,
+            delay_timer,
+            sound_timer,
+            keypad,
+            seed,
+            rng_state,
+            stack,
+            stack_pointer,
+            memory,
+            display,
+            history,
+        })
+    }
+}
+
+/// The differences between two snapshots, see [`Snapshot::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Registers and other values that differ, with their value in the first and the second snapshot.
+    pub values: Vec<(String, u64, u64)>,
+    /// Address ranges of differing memory.
+    pub memory: Vec<Range<usize>>,
+    /// Regions of the display that differ.
+    pub display: Vec<Rect>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.memory.is_empty() && self.display.is_empty()
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "The snapshots are equal");
+        }
+        let mut lines = Vec::new();
+        for (name, left, right) in &self.values {
+            lines.push(format!("{}: {:#X} != {:#X}", name, left, right));
+        }
+        for range in &self.memory {
+            lines.push(format!("Memory {:#05X}..{:#05X} ({} bytes)", range.start, range.end, range.len()));
+        }
+        for rect in &self.display {
+            lines.push(format!("Display at ({}, {}), {}x{} pixels", rect.x, rect.y, rect.width, rect.height));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
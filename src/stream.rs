@@ -0,0 +1,83 @@
+//! Streams the display over a WebSocket, so that viewers can watch a ROM running on another machine. Every message
+//! is binary and holds changed rows of the display, each as row index (1 byte) followed by the 64 pixels of the row
+//! (8 bytes, big-endian, most significant bit leftmost). New viewers first receive all rows, then only the rows that
+//! changed in a frame. Messages from viewers are ignored.
+
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::ControlFlow;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+use crate::hooks::Hooks;
+use crate::{Chip8, Chip8Error};
+
+/// Viewers that don't take a frame within this time are disconnected, instead of slowing down the emulation.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Sends the display to any number of WebSocket viewers after every frame that changed it.
+pub struct DisplayStream {
+    listener: TcpListener,
+    /// Viewers that already received the whole display.
+    viewers: Vec<WebSocket<TcpStream>>,
+    /// Viewers that still have to receive the whole display.
+    new_viewers: Vec<WebSocket<TcpStream>>,
+}
+
+impl DisplayStream {
+    /// Listens for viewers on `addr`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, viewers: Vec::new(), new_viewers: Vec::new() })
+    }
+
+    /// Accepts all waiting viewers.
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            stream.set_nonblocking(false)?;
+            stream.set_nodelay(true)?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            // A failed handshake is the viewer's problem, so just wait for the next one
+            if let Ok(viewer) = tungstenite::accept(stream) {
+                self.new_viewers.push(viewer);
+            }
+        }
+    }
+}
+
+/// Encodes the rows of the display whose bit is set in `rows`.
+fn encode_rows(chip8: &Chip8, rows: u32) -> Vec<u8> {
+    let mut message = Vec::new();
+    for (y, row) in chip8.shown_framebuffer().rows().iter().enumerate() {
+        if (rows >> y) & 1 == 1 {
+            message.push(y as u8);
+            message.extend_from_slice(&row.to_be_bytes());
+        }
+    }
+    message
+}
+
+impl Hooks for DisplayStream {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.accept().map_err(|err| Chip8Error::Hook(format!("Display stream: {}", err)))?;
+        if chip8.dirty_rows != 0 && !self.viewers.is_empty() {
+            let message = Message::binary(encode_rows(chip8, chip8.dirty_rows));
+            // Disconnected and slow viewers are dropped
+            self.viewers.retain_mut(|viewer| viewer.send(message.clone()).is_ok());
+        }
+        if !self.new_viewers.is_empty() {
+            let message = Message::binary(encode_rows(chip8, u32::MAX));
+            for mut viewer in self.new_viewers.drain(..) {
+                if viewer.send(message.clone()).is_ok() {
+                    self.viewers.push(viewer);
+                }
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
@@ -0,0 +1,85 @@
+//! Names for addresses, used by the disassembler and the debugger. Symbol files (`.sym`) contain one symbol per
+//! line, the hex address followed by the name, e.g. `0x242 main_loop`. Empty lines and `#` comments are ignored.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Symbols {
+    by_addr: BTreeMap<usize, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a symbol file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parses the contents of a symbol file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Line {}: Expected an address and a name like `0x242 main_loop`", i + 1);
+            let (addr, name) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let digits = addr.strip_prefix("0x").or_else(|| addr.strip_prefix("0X")).unwrap_or(addr);
+            let addr = usize::from_str_radix(digits, 16).map_err(|_| invalid())?;
+            symbols.insert(addr, name.trim());
+        }
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, addr: usize, name: impl Into<String>) {
+        self.by_addr.insert(addr, name.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_addr.is_empty()
+    }
+
+    /// The name of the symbol at exactly `addr`.
+    pub fn name(&self, addr: usize) -> Option<&str> {
+        self.by_addr.get(&addr).map(String::as_str)
+    }
+
+    /// The address of the symbol called `name`.
+    pub fn addr(&self, name: &str) -> Option<usize> {
+        self.by_addr.iter().find(|(_, symbol)| *symbol == name).map(|(&addr, _)| addr)
+    }
+
+    /// Describes `addr` relative to the closest symbol at or before it, e.g. `main_loop+0x4`. Falls back to the hex
+    /// address if there is no such symbol.
+    pub fn describe(&self, addr: usize) -> String {
+        match self.by_addr.range(..=addr).next_back() {
+            Some((&symbol_addr, name)) if symbol_addr == addr => name.clone(),
+            Some((&symbol_addr, name)) => format!("{}+{:#X}", name, addr - symbol_addr),
+            None => format!("{:#05X}", addr),
+        }
+    }
+
+    /// All symbols, ordered by address.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.by_addr.iter().map(|(&addr, name)| (addr, name.as_str()))
+    }
+}
+
+/// Formats the symbols as symbol file.
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, name) in self.iter() {
+            writeln!(f, "{:#05X} {}", addr, name)?;
+        }
+        Ok(())
+    }
+}
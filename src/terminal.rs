@@ -0,0 +1,334 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{execute, terminal};
+use crate::audio::Beeper;
+use crate::hooks::Hooks;
+use crate::image::{self, Palette};
+use crate::keymap::{Keymap, KEYPAD_LAYOUT};
+use crate::{Chip8, Chip8Error, DISPLAY_WIDTH};
+
+/// Number of frames a key stays pressed after a key press, for terminals that don't report key releases.
+pub(crate) const HOLD_FRAMES: u8 = 10;
+/// Writes a screenshot of the display to the current directory.
+const SCREENSHOT_KEY: KeyCode = KeyCode::F(12);
+/// Every Chip-8 pixel becomes a square of this size in screenshots.
+const SCREENSHOT_SCALE: usize = 8;
+/// Speed up or slow down the emulation by about 10%.
+const FASTER_KEY: char = '+';
+const SLOWER_KEY: char = '-';
+/// Mutes or unmutes the sound.
+const MUTE_KEY: char = 'm';
+/// Restarts the program, keeping the memory (soft reset) or reloading it (hard reset).
+const SOFT_RESET_KEY: KeyCode = KeyCode::F(5);
+const HARD_RESET_KEY: KeyCode = KeyCode::F(6);
+/// Row of the stats overlay right of the display. The keypad overlay is drawn below it.
+const STATS_ROW: usize = 0;
+const KEYPAD_ROW: usize = 2;
+
+/// Maps a host key to a Chip-8 key with the default [`Keymap`], which lays the 4x4 hex keypad onto the left-hand
+/// side of a QWERTY keyboard.
+pub fn key_for_char(c: char) -> Option<u8> {
+    Keymap::QWERTY.key_for_char(c)
+}
+
+/// Reads the keyboard from the terminal, which is put into raw mode for as long as this value lives. `Esc` or
+/// `Ctrl+C` stops the emulator, `F12` saves a screenshot, `+` and `-` change the instructions per frame, `m` mutes,
+/// `F5` restarts the program keeping the memory and `F6` restarts it from scratch.
+pub struct TerminalInput {
+    /// Frames left until each key counts as released.
+    held: [u8; 16],
+    /// Whether the terminal reports key releases, in which case keys are held until they are released.
+    reports_releases: bool,
+    /// Colors used for screenshots.
+    palette: Palette,
+    keymap: Keymap,
+    screenshot_requested: bool,
+    /// Number of steps to speed up (positive) or slow down (negative) the emulation at the end of the frame.
+    speed_change: i32,
+    mute_toggle_requested: bool,
+    soft_reset_requested: bool,
+    hard_reset_requested: bool,
+    show_keypad: bool,
+    /// Pressed and polled keys when the keypad overlay was last drawn, to only redraw it when they change.
+    drawn_keypad: Option<(u16, u16)>,
+    show_stats: bool,
+}
+
+impl TerminalInput {
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        Ok(Self {
+            held: [0; 16],
+            reports_releases,
+            palette: Palette::default(),
+            keymap: Keymap::default(),
+            screenshot_requested: false,
+            speed_change: 0,
+            mute_toggle_requested: false,
+            soft_reset_requested: false,
+            hard_reset_requested: false,
+            show_keypad: false,
+            drawn_keypad: None,
+            show_stats: false,
+        })
+    }
+
+    /// Sets the colors used for screenshots.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Sets the host keys of the hex keypad, [`Keymap::QWERTY`] by default.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Shows the keypad next to the display. Pressed keys are inverted, keys the program checks are underlined.
+    pub fn set_show_keypad(&mut self, show_keypad: bool) {
+        self.show_keypad = show_keypad;
+    }
+
+    /// Shows the instructions per frame and the number of frames next to the display.
+    pub fn set_show_stats(&mut self, show_stats: bool) {
+        self.show_stats = show_stats;
+    }
+
+    /// Draws the keypad overlay right of the display, if it changed since it was last drawn.
+    fn draw_keypad(&mut self, chip8: &Chip8) {
+        let state = (chip8.keypad(), chip8.polled_keys());
+        if self.drawn_keypad == Some(state) {
+            return;
+        }
+        self.drawn_keypad = Some(state);
+        let lines: Vec<String> = KEYPAD_LAYOUT.iter()
+            .map(|row| row.iter().map(|&key| {
+                let style = match (chip8.is_key_pressed(key), (chip8.polled_keys() >> key) & 1 == 1) {
+                    (true, _) => "\x1b[7m",
+                    (false, true) => "\x1b[4m",
+                    (false, false) => "",
+                };
+                format!(" {}{:X}\x1b[0m", style, key)
+            }).collect())
+            .collect();
+        print_right_of_display(KEYPAD_ROW, &lines);
+    }
+
+    fn draw_stats(&self, chip8: &Chip8) {
+        let mut stats = format!(" IPF {} (+/-)  Frame {}", chip8.instructions_per_frame(), chip8.metrics().frames);
+        if chip8.mixer().is_muted() {
+            stats.push_str("  Muted (m)");
+        }
+        print_right_of_display(STATS_ROW, &[stats]);
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent) -> ControlFlow<()> {
+        let is_ctrl_c = key_event.code == KeyCode::Char('c') && key_event.modifiers.contains(KeyModifiers::CONTROL);
+        if key_event.code == KeyCode::Esc || is_ctrl_c {
+            return ControlFlow::Break(());
+        }
+        if key_event.code == SCREENSHOT_KEY && key_event.kind == KeyEventKind::Press {
+            self.screenshot_requested = true;
+        }
+        if key_event.kind == KeyEventKind::Press {
+            match key_event.code {
+                KeyCode::Char(FASTER_KEY) => self.speed_change += 1,
+                KeyCode::Char(SLOWER_KEY) => self.speed_change -= 1,
+                KeyCode::Char(MUTE_KEY) => self.mute_toggle_requested = true,
+                SOFT_RESET_KEY => self.soft_reset_requested = true,
+                HARD_RESET_KEY => self.hard_reset_requested = true,
+                _ => {},
+            }
+        }
+        if let KeyCode::Char(c) = key_event.code {
+            if let Some(key) = self.keymap.key_for_char(c) {
+                self.held[key as usize] = match key_event.kind {
+                    KeyEventKind::Release => 0,
+                    _ if self.reports_releases => u8::MAX,
+                    _ => HOLD_FRAMES,
+                };
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// The keys currently held down as bitmask, see [`Chip8::keypad`].
+    fn keypad(&self) -> u16 {
+        self.held.iter().enumerate()
+            .filter(|(_, &frames)| frames > 0)
+            .fold(0, |keypad, (key, _)| keypad | 1 << key)
+    }
+}
+
+impl Hooks for TerminalInput {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        if !self.reports_releases {
+            for frames in &mut self.held {
+                *frames = frames.saturating_sub(1);
+            }
+        }
+        while event::poll(Duration::ZERO).map_err(|err| Chip8Error::Hook(err.to_string()))? {
+            if let Event::Key(key_event) = event::read().map_err(|err| Chip8Error::Hook(err.to_string()))? {
+                if self.handle_key(key_event).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+        }
+        chip8.set_keypad(self.keypad());
+        for _ in 0..self.speed_change.unsigned_abs() {
+            let ipf = chip8.instructions_per_frame();
+            let step = (ipf / 10).max(1);
+            let ipf = if self.speed_change > 0 { ipf.saturating_add(step) } else { ipf - step };
+            chip8.set_instructions_per_frame(ipf);
+        }
+        self.speed_change = 0;
+        if std::mem::take(&mut self.mute_toggle_requested) {
+            let mut mixer = *chip8.mixer();
+            mixer.toggle_mute();
+            chip8.set_mixer(mixer);
+        }
+        // A hard reset includes everything a soft reset does
+        let soft_reset = std::mem::take(&mut self.soft_reset_requested);
+        if std::mem::take(&mut self.hard_reset_requested) {
+            chip8.reset();
+        } else if soft_reset {
+            chip8.soft_reset();
+        }
+        if self.show_stats {
+            self.draw_stats(chip8);
+        }
+        if self.show_keypad {
+            self.draw_keypad(chip8);
+        }
+        if std::mem::take(&mut self.screenshot_requested) {
+            let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis());
+            image::screenshot(chip8, format!("screenshot-{}.png", millis), SCREENSHOT_SCALE, &self.palette)
+                .map_err(|err| Chip8Error::Hook(format!("Can't write screenshot: {}", err)))?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Asks for the host key of every key of the hex keypad, row by row like on the keypad. `Enter` keeps the current
+/// key and `Esc` cancels, which returns `None`. Keys used by [`TerminalInput`] itself can't be chosen.
+pub fn remap_keys(keymap: &Keymap) -> io::Result<Option<Keymap>> {
+    let mut keymap = *keymap;
+    terminal::enable_raw_mode()?;
+    let result = (|| -> io::Result<Option<Keymap>> {
+        for key in KEYPAD_LAYOUT.iter().flatten().copied() {
+            print!("Key {:X} (now {}): ", key, keymap.char_for_key(key).to_ascii_uppercase());
+            io::stdout().flush()?;
+            loop {
+                let key_event = match event::read()? {
+                    Event::Key(key_event) if key_event.kind != KeyEventKind::Release => key_event,
+                    _ => continue,
+                };
+                let is_ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+                match key_event.code {
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char('c') if is_ctrl => return Ok(None),
+                    // Enter arrives as Ctrl+J in some terminals
+                    KeyCode::Char('j') if is_ctrl => break,
+                    KeyCode::Char(_) if is_ctrl => {},
+                    KeyCode::Enter => break,
+                    KeyCode::Char(c) if [FASTER_KEY, SLOWER_KEY, MUTE_KEY].contains(&c.to_ascii_lowercase()) => {
+                        print!("\x1b7 {} is used by the emulator\x1b8", c);
+                    },
+                    KeyCode::Char(c) => match keymap.set(key, c) {
+                        Ok(()) => break,
+                        Err(err) => print!("\x1b7 {}\x1b8", err),
+                    },
+                    _ => {},
+                }
+                io::stdout().flush()?;
+            }
+            print!("{}\x1b[K\r\n", keymap.char_for_key(key).to_ascii_uppercase());
+        }
+        Ok(Some(keymap))
+    })();
+    let _ = terminal::disable_raw_mode();
+    println!();
+    result
+}
+
+/// Prints `lines` right of the display, starting at `row` of the display. The cursor has to be at the top left of
+/// the display, where it is put back afterwards.
+fn print_right_of_display(row: usize, lines: &[String]) {
+    let mut output = String::from("\x1b7");
+    if row > 0 {
+        output.push_str(&format!("\x1b[{}B", row));
+    }
+    for line in lines {
+        // Clear the rest of the line, in case the previous line was longer
+        output.push_str(&format!("\x1b[{}C{}\x1b[K\r\n", DISPLAY_WIDTH + 1, line));
+    }
+    output.push_str("\x1b8");
+    print!("{}", output);
+    let _ = io::stdout().flush();
+}
+
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = terminal::disable_raw_mode();
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Rings the terminal bell whenever the sound starts.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalBell;
+
+impl Beeper for TerminalBell {
+    fn set_active(&mut self, active: bool) {
+        if active {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+/// Shows the sound by inverting the colors of the terminal while it plays, for when it can't be heard, e.g. without
+/// audio or for deaf players. Games that signal events with beeps stay playable.
+#[derive(Debug, Default)]
+pub struct VisualBell {
+    active: bool,
+}
+
+impl VisualBell {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Beeper for VisualBell {
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+        // Reverse video mode of the VT100 (DECSCNM)
+        print!("{}", if active { "\x1b[?5h" } else { "\x1b[?5l" });
+        let _ = io::stdout().flush();
+    }
+
+    fn is_audible(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for VisualBell {
+    fn drop(&mut self) {
+        // Don't leave the terminal inverted if the emulator stops during a beep
+        if self.active {
+            self.set_active(false);
+        }
+    }
+}
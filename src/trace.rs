@@ -0,0 +1,327 @@
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use crate::profile::opcode_class;
+
+/// Names for groups of opcode classes, for [`TraceFilter::set_only`].
+const GROUPS: [(&str, &[&str]); 11] = [
+    ("draw", &["00E0", "DXYN"]),
+    ("call", &["2NNN"]),
+    ("return", &["00EE"]),
+    ("jump", &["1NNN", "BNNN"]),
+    ("skip", &["3XNN", "4XNN", "5XY0", "9XY0", "EX9E", "EXA1"]),
+    ("register", &["6XNN", "7XNN", "8XY0", "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE"]),
+    ("memory", &["ANNN", "FX1E", "FX29", "FX33", "FX55", "FX65"]),
+    ("timer", &["FX07", "FX15", "FX18"]),
+    ("key", &["EX9E", "EXA1", "FX0A"]),
+    ("random", &["CXNN"]),
+    ("sys", &["0NNN"]),
+];
+
+/// The machine state right after an instruction was executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Address of the executed instruction.
+    pub pc: usize,
+    pub opcode: u16,
+    /// Registers V0 to VF after the instruction was executed.
+    pub registers: [u8; 16],
+    /// Address register (I).
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceEntry {
+    /// Formats the entry as a single line of JSON (without the trailing newline), e.g.
+    /// `{"pc":512,"opcode":24581,"v":[5,0,...],"i":0,"dt":0,"st":0}`.
+    pub fn to_json(&self) -> String {
+        let registers: Vec<String> = self.registers.iter().map(u8::to_string).collect();
+        format!(
+            r#"{{"pc":{},"opcode":{},"v":[{}],"i":{},"dt":{},"st":{}}}"#,
+            self.pc, self.opcode, registers.join(","), self.i, self.delay_timer, self.sound_timer
+        )
+    }
+
+    /// Parses a line written by [`TraceEntry::to_json`]. The order of the keys and whitespace between the tokens
+    /// don't matter, and keys written by other emulators are ignored as long as their values are numbers, strings or
+    /// arrays of numbers.
+    pub fn from_json(line: &str) -> Result<Self, String> {
+        let body = line.trim().strip_prefix('{').and_then(|line| line.strip_suffix('}'))
+            .ok_or("Expected a JSON object")?;
+        let mut fields = HashMap::new();
+        let mut rest = body.trim();
+        while !rest.is_empty() {
+            let (key, value) = rest.split_once(':').ok_or("Expected a key and a value")?;
+            let value = value.trim_start();
+            let end = match value.as_bytes().first() {
+                Some(b'[') => value.find(']').ok_or("Unterminated array")? + 1,
+                Some(b'"') => string_len(value).ok_or("Unterminated string")?,
+                _ => value.find(',').unwrap_or(value.len()),
+            };
+            fields.insert(key.trim().trim_matches('"'), value[..end].trim());
+            let next = value[end..].trim_start();
+            rest = match next.strip_prefix(',') {
+                Some(next) => next.trim_start(),
+                None if next.is_empty() => next,
+                None => return Err(format!("Expected a comma before {}", next)),
+            };
+        }
+
+        let registers: Vec<u8> = field::<String>(&fields, "v")?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(|register| register.trim().parse().map_err(|_| format!("Invalid register value {:?}", register)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            pc: field(&fields, "pc")?,
+            opcode: field(&fields, "opcode")?,
+            registers: registers.try_into().map_err(|_| "Expected 16 registers")?,
+            i: field(&fields, "i")?,
+            delay_timer: field(&fields, "dt")?,
+            sound_timer: field(&fields, "st")?,
+        })
+    }
+}
+
+/// Length of the JSON string at the start of `value`, including the quotes, or `None` if it doesn't end.
+fn string_len(value: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i + 1),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    let value = fields.get(key).ok_or_else(|| format!("Missing {:?}", key))?;
+    value.parse().map_err(|_| format!("Invalid value {} for {:?}", value, key))
+}
+
+/// Writes one JSON line per executed instruction, so traces can be diffed against other emulators.
+pub struct JsonTracer {
+    out: Box<dyn Write>,
+    filter: TraceFilter,
+}
+
+impl JsonTracer {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Self { out: Box::new(out), filter: TraceFilter::default() }
+    }
+
+    /// Creates a tracer writing to the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Only writes the instructions matching `filter`.
+    pub fn set_filter(&mut self, filter: TraceFilter) {
+        self.filter = filter;
+    }
+
+    pub fn trace(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        if !self.filter.matches(entry.pc, entry.opcode) {
+            return Ok(());
+        }
+        writeln!(self.out, "{}", entry.to_json())
+    }
+}
+
+/// A read or write of memory by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// Address of the instruction.
+    pub pc: usize,
+    pub opcode: u16,
+    pub addr: usize,
+    /// The value read or written.
+    pub value: u8,
+    pub write: bool,
+}
+
+impl MemoryAccess {
+    /// Formats the access as a single line of JSON (without the trailing newline), e.g.
+    /// `{"pc":514,"opcode":61797,"access":"read","addr":768,"value":0}`.
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"pc":{},"opcode":{},"access":"{}","addr":{},"value":{}}}"#,
+            self.pc, self.opcode, if self.write { "write" } else { "read" }, self.addr, self.value
+        )
+    }
+}
+
+/// Writes one JSON line per memory access of the instructions, to find out which instruction corrupts data.
+pub struct MemoryTracer {
+    out: Box<dyn Write>,
+    /// Addresses whose accesses are traced, or `None` for all.
+    range: Option<Range<usize>>,
+}
+
+impl MemoryTracer {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Self { out: Box::new(out), range: None }
+    }
+
+    /// Creates a tracer writing to the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Only traces the accesses of addresses in `range`, e.g. the variable that gets corrupted.
+    pub fn set_range(&mut self, range: Range<usize>) {
+        self.range = Some(range);
+    }
+
+    pub fn trace(&mut self, access: &MemoryAccess) -> io::Result<()> {
+        if self.range.as_ref().is_some_and(|range| !range.contains(&access.addr)) {
+            return Ok(());
+        }
+        writeln!(self.out, "{}", access.to_json())
+    }
+}
+
+impl fmt::Debug for MemoryTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryTracer").field("range", &self.range).finish_non_exhaustive()
+    }
+}
+
+/// Decides which instructions are traced, to keep traces of long runs focused. By default, everything is traced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Opcode classes like `8XY4` to trace, or `None` for all.
+    classes: Option<BTreeSet<&'static str>>,
+    /// Addresses of the instructions to trace, or `None` for all.
+    range: Option<Range<usize>>,
+}
+
+impl TraceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only traces the instructions in the comma separated list of groups (`draw`, `call`, `return`, `jump`, `skip`,
+    /// `register`, `memory`, `timer`, `key`, `random` and `sys`) or opcode classes (like `8XY4`).
+    pub fn set_only(&mut self, list: &str) -> Result<(), String> {
+        let mut classes = BTreeSet::new();
+        for name in list.split(',').map(str::trim) {
+            if let Some((_, group)) = GROUPS.iter().find(|(group, _)| group.eq_ignore_ascii_case(name)) {
+                classes.extend(group.iter().copied());
+                continue;
+            }
+            // Every opcode class is in a group
+            let class = GROUPS.iter().flat_map(|(_, group)| group.iter().copied())
+                .find(|class| class.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("Unknown instruction group or opcode class {}", name))?;
+            classes.insert(class);
+        }
+        self.classes = Some(classes);
+        Ok(())
+    }
+
+    /// Only traces the instructions located in `range`.
+    pub fn set_range(&mut self, range: Range<usize>) {
+        self.range = Some(range);
+    }
+
+    /// Whether the instruction `opcode` located at `pc` is traced.
+    pub fn matches(&self, pc: usize, opcode: u16) -> bool {
+        self.range.as_ref().is_none_or(|range| range.contains(&pc))
+            && self.classes.as_ref().is_none_or(|classes| classes.contains(opcode_class(opcode)))
+    }
+}
+
+impl fmt::Debug for JsonTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonTracer").finish_non_exhaustive()
+    }
+}
+
+/// A trace of a known good run, e.g. written by another emulator or a prior version with `--trace-json`. Comparing
+/// it with the state after every instruction finds the first instruction that behaves differently.
+pub struct ReferenceTrace {
+    lines: Lines<Box<dyn BufRead>>,
+    /// Number of the last read line, starting at 1.
+    line: usize,
+}
+
+impl ReferenceTrace {
+    pub fn new(input: impl BufRead + 'static) -> Self {
+        Self { lines: (Box::new(input) as Box<dyn BufRead>).lines(), line: 0 }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+
+    /// Compares `actual` with the next entry of the reference. Returns the differences if there are any. Once the
+    /// reference ends, everything matches.
+    pub fn check(&mut self, actual: &TraceEntry) -> io::Result<Option<Divergence>> {
+        let line = match self.lines.next() {
+            Some(line) => line?,
+            None => return Ok(None),
+        };
+        self.line += 1;
+        let expected = TraceEntry::from_json(&line).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Line {} of the reference trace: {}", self.line, err))
+        })?;
+        match expected == *actual {
+            true => Ok(None),
+            false => Ok(Some(Divergence { line: self.line, expected, actual: *actual })),
+        }
+    }
+}
+
+impl fmt::Debug for ReferenceTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReferenceTrace").field("line", &self.line).finish_non_exhaustive()
+    }
+}
+
+/// The first instruction after which the state differs from the [`ReferenceTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Line of the reference trace, starting at 1.
+    pub line: usize,
+    pub expected: TraceEntry,
+    pub actual: TraceEntry,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (expected, actual) = (&self.expected, &self.actual);
+        write!(f, "Diverged from line {} of the reference trace", self.line)?;
+        if expected.pc != actual.pc || expected.opcode != actual.opcode {
+            // Comparing the registers makes no sense if different instructions were executed
+            return write!(
+                f,
+                ": expected {:04X} at {:#05X}, executed {:04X} at {:#05X}",
+                expected.opcode, expected.pc, actual.opcode, actual.pc,
+            );
+        }
+        write!(f, " after {:04X} at {:#05X}:", actual.opcode, actual.pc)?;
+        let mut values = vec![
+            (String::from("I"), expected.i, actual.i),
+            (String::from("DT"), expected.delay_timer as u16, actual.delay_timer as u16),
+            (String::from("ST"), expected.sound_timer as u16, actual.sound_timer as u16),
+        ];
+        for (n, (&expected, &actual)) in expected.registers.iter().zip(&actual.registers).enumerate() {
+            values.push((format!("V{:X}", n), expected as u16, actual as u16));
+        }
+        for (name, expected, actual) in values.into_iter().filter(|(_, expected, actual)| expected != actual) {
+            write!(f, "\n  {}: expected {:#X}, got {:#X}", name, expected, actual)?;
+        }
+        Ok(())
+    }
+}
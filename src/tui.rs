@@ -0,0 +1,420 @@
+//! Full-screen terminal debugger with panes for the display, disassembly, registers, stack and memory, so ROMs can be
+//! debugged over SSH without a GUI. Keys while paused:
+//!
+//! * `s` steps, `r` steps back, `c` continues until the next breakpoint or error
+//! * `Up`/`Down` move the cursor in the disassembly, `b` toggles a breakpoint at the cursor, `g` moves the cursor
+//!   back to the PC
+//! * `PageUp`/`PageDown` scroll the memory, `i` shows the memory at I, `m` switches between the hex dump and a bitmap
+//!   of the memory with one pixel per byte, where brighter pixels are larger values, recently executed bytes are green
+//!   and the instruction at the PC is red, to spot sprite tables and self-modifying code
+//! * `q` or `Esc` quits
+//!
+//! While running, the keypad is on the keyboard like in the terminal frontend (see
+//! [`crate::terminal::key_for_char`]) and `Esc` pauses.
+
+use std::io;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use crate::debugger::{Command, Debugger, PauseReason, RegisterDump, Reply};
+use crate::hooks::Hooks;
+use crate::rewind::Rewind;
+use crate::terminal::key_for_char;
+use crate::{Chip8, Chip8Error, RunStatus, StepEvent, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// The timers tick and the display is redrawn at 60 Hz while running.
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Number of frames a key stays pressed, because terminals usually don't report key releases.
+const HOLD_FRAMES: u8 = 10;
+/// Bytes per line of the memory pane.
+const MEMORY_COLUMNS: usize = 16;
+/// Bytes per pixel row of the memory bitmap. Every line shows two pixel rows.
+const BITMAP_COLUMNS: usize = 64;
+/// Frames an executed byte stays highlighted in the memory bitmap, fading out.
+const EXECUTED_FRAMES: u8 = 15;
+
+/// Records the execution and pauses the emulation when the debugger hits a breakpoint or watchpoint.
+struct BreakpointHook<'a> {
+    debugger: &'a mut Debugger,
+    rewind: &'a mut Rewind,
+    /// Frames left to highlight each byte as executed.
+    executed: &'a mut [u8],
+    /// Why the emulation paused.
+    reason: Option<PauseReason>,
+}
+
+impl Hooks for BreakpointHook<'_> {
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        mark_executed(self.executed, pc);
+        self.debugger.apply_freezes(chip8);
+        self.rewind.record(chip8);
+        self.reason = self.debugger.check(chip8);
+        match self.reason {
+            Some(_) => Ok(ControlFlow::Break(())),
+            None => Ok(ControlFlow::Continue(())),
+        }
+    }
+
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        self.rewind.after_frame(chip8)
+    }
+}
+
+struct Tui<'a> {
+    chip8: &'a mut Chip8,
+    debugger: &'a mut Debugger,
+    rewind: Rewind,
+    running: bool,
+    /// Address of the selected instruction in the disassembly.
+    cursor: usize,
+    /// First address shown in the memory pane.
+    memory_addr: usize,
+    /// Show the memory as bitmap instead of as hex dump.
+    memory_bitmap: bool,
+    /// Frames left to highlight each byte as executed in the memory bitmap.
+    executed: Vec<u8>,
+    /// Frames left until each key counts as released.
+    held: [u8; 16],
+    /// Shown in the status line, e.g. the last error.
+    message: String,
+}
+
+/// Debugs `chip8` in a full-screen terminal interface until the user quits. The emulation starts paused.
+pub fn run(chip8: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let cursor = chip8.pc;
+    let rewind = Rewind::new(chip8);
+    let executed = vec![0; chip8.bus().size()];
+    let mut tui = Tui {
+        chip8,
+        debugger,
+        rewind,
+        running: false,
+        cursor,
+        memory_addr: 0,
+        memory_bitmap: false,
+        executed,
+        held: [0; 16],
+        message: String::from("Paused"),
+    };
+    let result = tui.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Tui<'_> {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut next_frame = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if !self.running {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release && self.handle_paused_key(key).is_break() {
+                        return Ok(());
+                    }
+                }
+                next_frame = Instant::now();
+                continue;
+            }
+            // Handle input until the next frame is due
+            while let Some(remaining) = next_frame.checked_duration_since(Instant::now()) {
+                if !event::poll(remaining)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release && self.handle_running_key(key).is_break() {
+                        return Ok(());
+                    }
+                }
+            }
+            next_frame += FRAME_DURATION;
+            if self.running {
+                self.run_frame();
+            }
+        }
+    }
+
+    fn handle_paused_key(&mut self, key: KeyEvent) -> ControlFlow<()> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return ControlFlow::Break(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return ControlFlow::Break(()),
+            KeyCode::Char('s') => {
+                let pc = self.chip8.pc;
+                self.message = match self.chip8.step() {
+                    Ok(events) => {
+                        mark_executed(&mut self.executed, pc);
+                        self.debugger.apply_freezes(self.chip8);
+                        self.rewind.record(self.chip8);
+                        match events.contains(StepEvent::WaitingForKey) {
+                            true => String::from("Paused, waiting for a key press"),
+                            false => String::from("Paused"),
+                        }
+                    },
+                    Err(err) => err.to_string(),
+                };
+                self.cursor = self.chip8.pc;
+            },
+            KeyCode::Char('r') => {
+                self.message = match self.rewind.step_back(self.chip8) {
+                    Ok(true) => String::from("Paused"),
+                    Ok(false) => String::from("Reached the start of the recording"),
+                    Err(err) => err.to_string(),
+                };
+                self.cursor = self.chip8.pc;
+            },
+            KeyCode::Char('c') => {
+                self.running = true;
+                self.message = String::from("Running, Esc pauses");
+            },
+            KeyCode::Char('b') => {
+                let addr = self.cursor.into();
+                let command = match self.debugger.breakpoints().any(|breakpoint| breakpoint == self.cursor) {
+                    true => Command::Delete { addr },
+                    false => Command::Break { addr },
+                };
+                self.debugger.execute(self.chip8, command);
+            },
+            KeyCode::Char('g') => self.cursor = self.chip8.pc,
+            KeyCode::Char('i') => {
+                self.memory_addr = self.chip8.address_register as usize / MEMORY_COLUMNS * MEMORY_COLUMNS
+            },
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(2),
+            KeyCode::Down => self.cursor = (self.cursor + 2).min(self.chip8.bus().size() - 2),
+            KeyCode::Char('m') => self.memory_bitmap = !self.memory_bitmap,
+            KeyCode::PageUp => self.memory_addr = self.memory_addr.saturating_sub(self.memory_page()),
+            KeyCode::PageDown => {
+                let last_line = self.chip8.bus().size() - MEMORY_COLUMNS;
+                self.memory_addr = (self.memory_addr + self.memory_page()).min(last_line);
+            },
+            _ => {},
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn handle_running_key(&mut self, key: KeyEvent) -> ControlFlow<()> {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return ControlFlow::Break(()),
+            KeyCode::Esc => self.pause(String::from("Paused")),
+            KeyCode::Char(c) => {
+                if let Some(key) = key_for_char(c) {
+                    self.held[key as usize] = HOLD_FRAMES;
+                    self.chip8.set_key_state(key, true);
+                }
+            },
+            _ => {},
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn run_frame(&mut self) {
+        for key in 0..16 {
+            if self.held[key] > 0 {
+                self.held[key] -= 1;
+                if self.held[key] == 0 {
+                    self.chip8.set_key_state(key as u8, false);
+                }
+            }
+        }
+        for frames in &mut self.executed {
+            *frames = frames.saturating_sub(1);
+        }
+        let mut hook = BreakpointHook {
+            debugger: self.debugger,
+            rewind: &mut self.rewind,
+            executed: &mut self.executed,
+            reason: None,
+        };
+        let result = self.chip8.run_frame_with_hooks(&mut hook);
+        match result {
+            Ok(ControlFlow::Continue(())) => {},
+            Ok(ControlFlow::Break(RunStatus::Halted { pc })) => self.pause(format!("Halted at {:#05X}", pc)),
+            Ok(ControlFlow::Break(_)) => {
+                let message = match (hook.reason, self.chip8.history.back()) {
+                    (Some(PauseReason::Watchpoint), Some(&(pc, _))) => {
+                        format!("I={:#05X}, set by the instruction at {:#05X}", self.chip8.address_register, pc)
+                    },
+                    _ => format!("Breakpoint at {:#05X}", self.chip8.pc),
+                };
+                self.pause(message);
+            },
+            Err(err) => self.pause(err.to_string()),
+        }
+    }
+
+    fn pause(&mut self, message: String) {
+        self.running = false;
+        self.message = message;
+        self.cursor = self.chip8.pc;
+        // Keys pressed while running would stay pressed forever otherwise
+        self.held = [0; 16];
+        self.chip8.set_keypad(0);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Length(DISPLAY_WIDTH as u16 + 2), Constraint::Min(0)])
+            .areas(main);
+        let [display, disassembly] = Layout::vertical([
+            Constraint::Length(DISPLAY_HEIGHT as u16 / 2 + 2),
+            Constraint::Min(0),
+        ]).areas(left);
+        let [registers, stack, memory] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Length(self.chip8.stack_depth() as u16 / 2 + 3),
+            Constraint::Min(0),
+        ]).areas(right);
+
+        frame.render_widget(Paragraph::new(self.display_lines()).block(Block::bordered().title("Display")), display);
+        frame.render_widget(
+            Paragraph::new(self.disassembly_lines(disassembly)).block(Block::bordered().title("Disassembly")),
+            disassembly,
+        );
+        let registers_text = RegisterDump::of(self.chip8).to_string();
+        // The stack has its own pane
+        let registers_lines: Vec<Line> = registers_text.lines().take(2).map(Line::from).collect();
+        frame.render_widget(Paragraph::new(registers_lines).block(Block::bordered().title("Registers")), registers);
+        frame.render_widget(Paragraph::new(self.stack_lines()).block(Block::bordered().title("Stack")), stack);
+        let memory_lines = match self.memory_bitmap {
+            true => self.memory_bitmap_lines(memory),
+            false => self.memory_lines(memory),
+        };
+        frame.render_widget(Paragraph::new(memory_lines).block(Block::bordered().title("Memory")), memory);
+        let help = match self.running {
+            true => "Esc pause",
+            false => "s step  r step back  c continue  b breakpoint  g go to PC  i memory at I  m memory view  q quit",
+        };
+        let line = Line::from(vec![Span::from(&self.message).bold(), Span::from("  "), Span::from(help)]);
+        frame.render_widget(line, status);
+    }
+
+    /// The display with two pixel rows per line, using half blocks.
+    fn display_lines(&self) -> Vec<Line<'static>> {
+        (0..DISPLAY_HEIGHT / 2)
+            .map(|row| {
+                let text: String = (0..DISPLAY_WIDTH)
+                    .map(|x| match (self.chip8.pixel(x, 2 * row), self.chip8.pixel(x, 2 * row + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect();
+                Line::from(text)
+            })
+            .collect()
+    }
+
+    /// Instructions around the cursor. Breakpoints are marked with `*`, the PC with `>`.
+    fn disassembly_lines(&mut self, area: Rect) -> Vec<Line<'static>> {
+        let rows = area.height.saturating_sub(2) as usize;
+        let start = self.cursor.saturating_sub(rows / 2 * 2);
+        let reply = self.debugger.execute(self.chip8, Command::Disasm { addr: Some(start.into()), count: rows });
+        let instructions = match reply {
+            Reply::Disassembly { instructions } => instructions,
+            _ => Vec::new(),
+        };
+        let breakpoints: Vec<usize> = self.debugger.breakpoints().collect();
+        instructions.iter()
+            .map(|instruction| {
+                let breakpoint = if breakpoints.contains(&instruction.addr) { '*' } else { ' ' };
+                let pc = if instruction.addr == self.chip8.pc { '>' } else { ' ' };
+                let mnemonic = instruction.mnemonic.as_deref().unwrap_or("(illegal)");
+                let text = format!(
+                    "{}{} {:#05X}: {:04X}    {}",
+                    breakpoint, pc, instruction.addr, instruction.opcode, mnemonic,
+                );
+                let style = match (instruction.addr == self.cursor, instruction.addr == self.chip8.pc) {
+                    (true, _) => Style::new().reversed(),
+                    (false, true) => Style::new().bold(),
+                    (false, false) => Style::new(),
+                };
+                Line::styled(text, style)
+            })
+            .collect()
+    }
+
+    /// The call stack with symbol names, innermost frame first.
+    fn stack_lines(&mut self) -> Vec<Line<'static>> {
+        match self.debugger.execute(self.chip8, Command::Backtrace) {
+            Reply::Backtrace { frames } => frames.iter()
+                .enumerate()
+                .map(|(n, frame)| Line::from(format!("#{} {:#05X} {}", n, frame.addr, frame.location)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// A hex dump starting at the scroll position. The byte at I is highlighted.
+    fn memory_lines(&self, area: Rect) -> Vec<Line<'static>> {
+        let rows = area.height.saturating_sub(2) as usize;
+        let i = self.chip8.address_register as usize;
+        (0..rows)
+            .map(|row| self.memory_addr + row * MEMORY_COLUMNS)
+            .take_while(|&addr| addr + MEMORY_COLUMNS <= self.chip8.bus().size())
+            .map(|addr| {
+                let mut spans = vec![Span::from(format!("{:#05X}:", addr))];
+                for byte_addr in addr..addr + MEMORY_COLUMNS {
+                    let text = format!(" {:02X}", self.chip8.bus().peek(byte_addr));
+                    spans.push(if byte_addr == i { Span::from(text).reversed() } else { Span::from(text) });
+                }
+                Line::from(spans)
+            })
+            .collect()
+    }
+    /// Bytes scrolled by `PageUp` and `PageDown`, four lines of the memory pane.
+    fn memory_page(&self) -> usize {
+        match self.memory_bitmap {
+            true => 4 * 2 * BITMAP_COLUMNS,
+            false => 4 * MEMORY_COLUMNS,
+        }
+    }
+
+    /// The memory as bitmap starting at the scroll position, with one pixel per byte and two pixel rows per line,
+    /// using half blocks.
+    fn memory_bitmap_lines(&self, area: Rect) -> Vec<Line<'static>> {
+        let rows = area.height.saturating_sub(2) as usize;
+        let start = self.memory_addr / (2 * BITMAP_COLUMNS) * (2 * BITMAP_COLUMNS);
+        (0..rows)
+            .map(|row| start + row * 2 * BITMAP_COLUMNS)
+            .take_while(|&addr| addr < self.chip8.bus().size())
+            .map(|addr| {
+                let spans: Vec<Span> = (addr..addr + BITMAP_COLUMNS)
+                    .map(|top| {
+                        let style = Style::new().fg(self.byte_color(top)).bg(self.byte_color(top + BITMAP_COLUMNS));
+                        Span::styled("▀", style)
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    /// Gray as bright as the byte at `addr`, green if it was executed recently and red at the PC.
+    fn byte_color(&self, addr: usize) -> Color {
+        if addr >= self.chip8.bus().size() {
+            return Color::Reset;
+        }
+        if addr == self.chip8.pc || addr == self.chip8.pc + 1 {
+            return Color::Rgb(255, 0, 0);
+        }
+        let value = self.chip8.bus().peek(addr);
+        match self.executed[addr] {
+            0 => Color::Rgb(value, value, value),
+            frames => Color::Rgb(value / 2, value.max(60 + 13 * frames), value / 2),
+        }
+    }
+}
+
+/// Highlights the instruction at `pc` as executed.
+fn mark_executed(executed: &mut [u8], pc: usize) {
+    for addr in pc..(pc + 2).min(executed.len()) {
+        executed[addr] = EXECUTED_FRAMES;
+    }
+}
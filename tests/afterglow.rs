@@ -0,0 +1,87 @@
+use chip8::image::{self, Palette};
+use chip8::Chip8;
+
+/// Toggles the top left pixel in every frame, like a sprite that's erased and redrawn.
+const BLINK: [u8; 9] = [
+    0xA2, 0x08, // 0x200: LD I, 0x208
+    0x60, 0x00, // 0x202: LD V0, 0
+    0xD0, 0x01, // 0x204: DRW V0, V0, 1
+    0x12, 0x04, // 0x206: JP 0x204
+    0x80, // 0x208: sprite
+];
+
+/// Runs `frames` frames and returns whether the top left pixel was shown after each of them.
+fn shown_pixels(chip8: &mut Chip8, frames: usize) -> Vec<bool> {
+    (0..frames)
+        .map(|_| {
+            assert!(chip8.run_frame_with_hooks(&mut ()).unwrap().is_continue());
+            chip8.shown_framebuffer().pixel(0, 0)
+        })
+        .collect()
+}
+
+fn blinking_chip8() -> Chip8 {
+    let mut chip8 = Chip8::new(&BLINK);
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    // One draw and the jump back per frame
+    chip8.set_instructions_per_frame(2);
+    chip8
+}
+
+#[test]
+fn shows_display_without_reduction() {
+    let mut chip8 = blinking_chip8();
+    assert_eq!(shown_pixels(&mut chip8, 4), [true, false, true, false]);
+}
+
+#[test]
+fn keeps_blinking_pixel_on() {
+    let mut chip8 = blinking_chip8();
+    chip8.set_flicker_reduction(2);
+    assert_eq!(shown_pixels(&mut chip8, 4), [true, true, true, true]);
+    // The program still sees its own display
+    assert!(!chip8.pixel(0, 0));
+}
+
+#[test]
+fn clears_pixel_after_frames_off() {
+    let mut chip8 = blinking_chip8();
+    chip8.set_flicker_reduction(3);
+    // Drawn and erased
+    assert_eq!(shown_pixels(&mut chip8, 2), [true, true]);
+    // Draw somewhere else from now on, so that the pixel stays off
+    chip8.set_register(0, 8);
+    assert_eq!(shown_pixels(&mut chip8, 3), [true, false, false]);
+}
+
+#[test]
+fn fades_out_with_phosphor_decay() {
+    let mut chip8 = blinking_chip8();
+    chip8.set_phosphor_decay(3);
+    let mut brightness = Vec::new();
+    for frame in 0..6 {
+        if frame == 2 {
+            // Draw somewhere else from now on, so that the pixel stays off
+            chip8.set_register(0, 8);
+        }
+        assert!(chip8.run_frame_with_hooks(&mut ()).unwrap().is_continue());
+        brightness.push(chip8.pixel_brightness(0, 0));
+    }
+    assert_eq!(brightness, [255, 191, 127, 63, 0, 0]);
+    // Pixels that were never set are dark
+    assert_eq!(chip8.pixel_brightness(20, 20), 0);
+}
+
+#[test]
+fn renders_fading_pixels_blended() {
+    let mut chip8 = blinking_chip8();
+    chip8.set_phosphor_decay(1);
+    assert!(chip8.run_frame_with_hooks(&mut ()).unwrap().is_continue());
+    assert!(chip8.run_frame_with_hooks(&mut ()).unwrap().is_continue());
+    let palette = Palette { foreground: [0xFF, 0xCC, 0x00], background: [0x00, 0x00, 0x66] };
+    let (_, _, pixels) = image::render_rgb(&chip8, 1, &palette);
+    assert_eq!(&pixels[..3], &[0x7F, 0x65, 0x33]);
+    assert_eq!(palette.blend(255), palette.foreground);
+    assert_eq!(palette.blend(0), palette.background);
+}
@@ -0,0 +1,37 @@
+//! Assembling Octo source into ROMs.
+
+use chip8::assembler::{assemble, AssembleError};
+
+#[test]
+fn macro_arguments_are_substituted() {
+    let source = "
+        :macro move-right REG AMOUNT { REG += AMOUNT }
+        : main
+            move-right v1 2
+            move-right v3 0x10
+    ";
+    // The program starts with a jump to main, which doesn't come first
+    assert_eq!(assemble(source).expect("The source is valid").rom, [0x12, 0x02, 0x71, 0x02, 0x73, 0x10]);
+}
+
+#[test]
+fn forward_references_are_patched() {
+    let source = "
+        : main
+            :unpack 0xA data
+            jump main
+        : data
+            0xFF
+    ";
+    let assembly = assemble(source).expect("The source is valid");
+    // v0 := 0xA and the high nibble of 0x206, v1 := its low byte
+    assert_eq!(assembly.rom, [0x60, 0xA2, 0x61, 0x06, 0x12, 0x00, 0xFF]);
+    assert_eq!(assembly.labels["data"], 0x206);
+}
+
+#[test]
+fn byte_out_of_range_is_an_error() {
+    let error = AssembleError { line: 3, message: String::from("256 doesn't fit into a byte") };
+    assert_eq!(assemble(": main\n  v0 := 1\n  v1 := 256"), Err(error));
+    assert!(assemble(": main\n  :byte -129").is_err());
+}
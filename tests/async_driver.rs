@@ -0,0 +1,33 @@
+//! The async driver runs ROMs on a tokio runtime and applies key events between frames.
+#![cfg(feature = "async")]
+
+use chip8::async_driver::{Chip8Async, KeyEvent};
+use chip8::{Chip8, RunPolicy, RunStatus};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc;
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    Builder::new_current_thread().enable_time().build().expect("Runtime starts").block_on(future)
+}
+
+#[test]
+fn waits_for_keys() {
+    // Wait for a key, then halt
+    let mut chip8 = Chip8Async::new(Chip8::new(&[0xF0, 0x0A, 0x12, 0x02]));
+    let (keys, receiver) = mpsc::unbounded_channel();
+    chip8.set_keys(receiver);
+    keys.send(KeyEvent { key: 0x5, pressed: true }).expect("The driver receives keys");
+    assert_eq!(block_on(chip8.run()), Ok(RunStatus::Halted { pc: 0x202 }));
+    assert_eq!(chip8.chip8().keypad(), 1 << 0x5);
+}
+
+#[test]
+fn stops_after_instructions() {
+    // Loop forever
+    let mut chip8 = Chip8::new(&[0x70, 0x01, 0x12, 0x00]);
+    chip8.set_instructions_per_frame(7);
+    chip8.set_run_policy(RunPolicy::Instructions(20));
+    let mut chip8 = Chip8Async::new(chip8);
+    assert_eq!(block_on(chip8.run()), Ok(RunStatus::InstructionLimit));
+    assert_eq!(chip8.chip8().metrics().instructions, 20);
+}
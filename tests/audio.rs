@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use chip8::Chip8;
+use chip8::audio::{Beeper, Mixer, Resampler, Synth, Tone, Waveform, SAMPLE_RATE};
+use chip8::terminal::VisualBell;
+
+fn one_frame(tone: Tone) -> Vec<i16> {
+    let mut samples = Vec::new();
+    Synth::new(SAMPLE_RATE, tone).frame(true, 60, &mut samples);
+    samples
+}
+
+#[test]
+fn default_tone_is_quiet_square_wave() {
+    let samples = one_frame(Tone::default());
+    assert_eq!(samples.len(), SAMPLE_RATE as usize / 60);
+    assert!(samples.iter().all(|&sample| sample == i16::MAX / 4 || sample == -(i16::MAX / 4)));
+    // 440 Hz at 44.1 kHz: the first half period of about 50 samples is high
+    assert!(samples[..50].iter().all(|&sample| sample > 0));
+    assert!(samples[51..100].iter().all(|&sample| sample < 0));
+}
+
+#[test]
+fn silence_while_not_beeping() {
+    let mut samples = Vec::new();
+    Synth::new(SAMPLE_RATE, Tone::default()).frame(false, 60, &mut samples);
+    assert!(!samples.is_empty());
+    assert!(samples.iter().all(|&sample| sample == 0));
+}
+
+#[test]
+fn frequency_changes_period() {
+    let tone = Tone { frequency: 882.0, ..Tone::default() };
+    let samples = one_frame(tone);
+    // 882 Hz has a period of 50 samples
+    assert!(samples[..25].iter().all(|&sample| sample > 0));
+    assert!(samples[26..50].iter().all(|&sample| sample < 0));
+    assert!(samples[51..75].iter().all(|&sample| sample > 0));
+}
+
+#[test]
+fn duty_cycle_shortens_high_part() {
+    let tone = Tone { frequency: 882.0, duty_cycle: 0.25, ..Tone::default() };
+    let samples = one_frame(tone);
+    let high = samples[..50].iter().filter(|&&sample| sample > 0).count();
+    assert!((12..=13).contains(&high), "{} high samples", high);
+}
+
+#[test]
+fn volume_scales_amplitude() {
+    let tone = Tone { volume: 1.0, waveform: Waveform::Sine, ..Tone::default() };
+    let peak = one_frame(tone).into_iter().map(|sample| sample.unsigned_abs()).max().unwrap();
+    assert!(peak > i16::MAX as u16 - 10);
+    let silent = Tone { volume: 0.0, ..Tone::default() };
+    assert!(one_frame(silent).iter().all(|&sample| sample == 0));
+}
+
+#[test]
+fn waveforms_are_continuous() {
+    for waveform in [Waveform::Triangle, Waveform::Sine] {
+        let tone = Tone { waveform, volume: 1.0, ..Tone::default() };
+        let samples = one_frame(tone);
+        let max_step = samples.windows(2).map(|pair| (pair[1] as i32 - pair[0] as i32).abs()).max().unwrap();
+        // Far below the jump of a square wave from the top to the bottom
+        assert!(max_step < i16::MAX as i32 / 8, "{:?} jumps by {}", waveform, max_step);
+    }
+}
+
+#[test]
+fn parse_waveform() {
+    assert_eq!("Triangle".parse(), Ok(Waveform::Triangle));
+    assert_eq!("sine".parse(), Ok(Waveform::Sine));
+    assert!("sawtooth".parse::<Waveform>().is_err());
+}
+
+#[test]
+fn validate_tone() {
+    assert!(Tone::default().validate(SAMPLE_RATE).is_ok());
+    assert!(Tone { frequency: 0.0, ..Tone::default() }.validate(SAMPLE_RATE).is_err());
+    assert!(Tone { frequency: 30_000.0, ..Tone::default() }.validate(SAMPLE_RATE).is_err());
+    assert!(Tone { volume: 1.5, ..Tone::default() }.validate(SAMPLE_RATE).is_err());
+    assert!(Tone { duty_cycle: -0.1, ..Tone::default() }.validate(SAMPLE_RATE).is_err());
+}
+
+/// One second of the default beep at [`SAMPLE_RATE`].
+fn one_second() -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut synth = Synth::new(SAMPLE_RATE, Tone::default());
+    for _ in 0..60 {
+        synth.frame(true, 60, &mut samples);
+    }
+    samples
+}
+
+/// Number of times the signal changes from negative to positive.
+fn rising_edges(samples: &[i16]) -> usize {
+    samples.windows(2).filter(|pair| pair[0] < 0 && pair[1] >= 0).count()
+}
+
+#[test]
+fn resampler_keeps_same_rate() {
+    let input = one_second();
+    let mut output = Vec::new();
+    Resampler::new(SAMPLE_RATE, SAMPLE_RATE).process(&input, &mut output);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn resampler_keeps_pitch() {
+    let input = one_second();
+    for rate in [48_000, 22_050, 16_000, 96_000] {
+        let mut output = Vec::new();
+        Resampler::new(SAMPLE_RATE, rate).process(&input, &mut output);
+        // When upsampling, the output after the last input sample waits for the next chunk
+        assert!(output.len().abs_diff(rate as usize) <= 3, "{} samples at {} Hz", output.len(), rate);
+        assert!(rising_edges(&output).abs_diff(440) <= 1, "{} periods at {} Hz", rising_edges(&output), rate);
+    }
+}
+
+#[test]
+fn resampler_interpolates() {
+    let mut output = Vec::new();
+    Resampler::new(1, 2).process(&[0, 100, 200], &mut output);
+    assert_eq!(output, [0, 50, 100, 150, 200]);
+}
+
+#[test]
+fn resampler_chunks_match_whole() {
+    let input = one_second();
+    let mut whole = Vec::new();
+    Resampler::new(SAMPLE_RATE, 48_000).process(&input, &mut whole);
+    let mut chunked = Vec::new();
+    let mut resampler = Resampler::new(SAMPLE_RATE, 48_000);
+    for chunk in input.chunks(735) {
+        resampler.process(chunk, &mut chunked);
+    }
+    assert_eq!(chunked.len(), whole.len());
+    // Positions are accumulated differently, so samples may differ by rounding
+    assert!(chunked.iter().zip(&whole).all(|(a, b)| (*a as i32 - *b as i32).abs() <= 1));
+}
+
+#[test]
+fn mixer_scales_and_mutes() {
+    let mut mixer = Mixer::new();
+    let mut samples = [1000, -1000];
+    mixer.apply(&mut samples);
+    assert_eq!(samples, [1000, -1000]);
+
+    mixer.set_volume(0.5);
+    mixer.apply(&mut samples);
+    assert_eq!(samples, [500, -500]);
+
+    mixer.toggle_mute();
+    assert!(!mixer.is_audible());
+    mixer.apply(&mut samples);
+    assert_eq!(samples, [0, 0]);
+    assert_eq!(mixer.volume(), 0.5);
+
+    mixer.set_volume(7.0);
+    assert_eq!(mixer.volume(), 1.0);
+}
+
+/// Remembers whether it's active.
+#[derive(Debug, Clone, Default)]
+struct SharedBeeper {
+    active: Rc<RefCell<bool>>,
+    visual: bool,
+}
+
+impl Beeper for SharedBeeper {
+    fn set_active(&mut self, active: bool) {
+        *self.active.borrow_mut() = active;
+    }
+
+    fn is_audible(&self) -> bool {
+        !self.visual
+    }
+}
+
+#[test]
+fn muting_silences_beeper() {
+    let beeper = SharedBeeper::default();
+    let mut chip8 = Chip8::new(&[]);
+    chip8.set_beeper(beeper.clone());
+    chip8.set_sound_timer(10);
+    assert!(*beeper.active.borrow());
+
+    let mut mixer = *chip8.mixer();
+    mixer.set_muted(true);
+    chip8.set_mixer(mixer);
+    assert!(!*beeper.active.borrow());
+
+    mixer.set_muted(false);
+    chip8.set_mixer(mixer);
+    assert!(*beeper.active.borrow());
+}
+
+#[test]
+fn muting_keeps_visual_beeper() {
+    assert!(!VisualBell::new().is_audible());
+    let beeper = SharedBeeper { visual: true, ..SharedBeeper::default() };
+    let mut chip8 = Chip8::new(&[]);
+    chip8.set_beeper(beeper.clone());
+    let mut mixer = *chip8.mixer();
+    mixer.set_volume(0.0);
+    mixer.set_muted(true);
+    chip8.set_mixer(mixer);
+
+    chip8.set_sound_timer(10);
+    assert!(*beeper.active.borrow());
+    chip8.set_sound_timer(0);
+    assert!(!*beeper.active.borrow());
+}
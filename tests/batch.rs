@@ -0,0 +1,45 @@
+//! The batch runner runs a directory of ROMs headless and sorts out how each run ended.
+
+use std::fs;
+use chip8::batch::{self, Outcome, Summary};
+use chip8::Chip8Error;
+
+#[test]
+fn runs_every_rom_in_directory() {
+    let dir = std::env::temp_dir().join(format!("chip8-batch-{}", std::process::id()));
+    fs::create_dir_all(dir.join("subdirectory")).unwrap();
+    fs::write(dir.join("a_halts.ch8"), [0x60, 0x01, 0x12, 0x02]).unwrap();
+    fs::write(dir.join("b_illegal.ch8"), [0x60, 0x01, 0xFF, 0xFF]).unwrap();
+    // Counts V0 up forever
+    fs::write(dir.join("c_counts.ch8"), [0x70, 0x01, 0x12, 0x00]).unwrap();
+    fs::write(dir.join("d_invalid.8o"), "v0 := ").unwrap();
+    fs::write(dir.join("subdirectory").join("skipped.ch8"), [0xFF, 0xFF]).unwrap();
+    let results = batch::run_dir(&dir, 10);
+    fs::remove_dir_all(&dir).unwrap();
+    let results = results.expect("The directory is readable");
+
+    let names: Vec<_> = results.iter().map(|result| result.path.file_name().unwrap().to_owned()).collect();
+    assert_eq!(names, ["a_halts.ch8", "b_illegal.ch8", "c_counts.ch8", "d_invalid.8o"]);
+    assert_eq!(results[0].outcome, Outcome::InfiniteLoop { pc: 0x202 });
+    assert_eq!(results[1].outcome, Outcome::Error {
+        error: Chip8Error::IllegalInstruction { opcode: 0xFFFF, pc: 0x204 },
+        pc: 0x202,
+    });
+    assert_eq!(results[2].outcome, Outcome::Completed);
+    assert_eq!((results[2].metrics.frames, results[2].metrics.instructions), (10, 10));
+    assert!(matches!(results[3].outcome, Outcome::InvalidRom(_)));
+
+    let summary = Summary::of(&results);
+    assert_eq!(summary, Summary { completed: 1, errors: 1, infinite_loops: 1, invalid: 1 });
+    assert_eq!(summary.to_string(), "1 ok, 1 errors, 1 infinite loops, 1 invalid");
+    let line = results[0].to_string();
+    assert!(line.ends_with("a_halts.ch8: infinite loop at 0x202 (1 frames, 2 instructions, 0 draw calls)"));
+}
+
+#[test]
+fn runs_rom_with_known_settings() {
+    // The bundled ROMs are in the compatibility table, so maze runs 15 instructions per frame
+    let (outcome, metrics) = batch::run_rom(include_bytes!("../roms/maze.ch8"), 4);
+    assert_eq!(outcome, Outcome::Completed);
+    assert_eq!((metrics.frames, metrics.instructions), (4, 60));
+}
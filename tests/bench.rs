@@ -0,0 +1,43 @@
+//! The benchmark runs ROMs as fast as possible and reports the throughput.
+
+use std::time::Duration;
+use chip8::bench::{self, BenchResult};
+use chip8::{Chip8, Chip8Error};
+
+#[test]
+fn runs_whole_frames_until_time_is_up() {
+    let mut chip8 = Chip8::new(include_bytes!("../roms/bounce.ch8"));
+    chip8.set_instructions_per_frame(10);
+    let result = bench::run(&mut chip8, Duration::from_millis(20));
+    assert!(result.elapsed >= Duration::from_millis(20));
+    assert!(result.frames > 0);
+    assert_eq!(result.instructions, result.frames * 10);
+    assert_eq!(result.error, None);
+
+    // Only what was executed during the benchmark counts
+    let second = bench::run(&mut chip8, Duration::from_millis(1));
+    assert_eq!(second.instructions, second.frames * 10);
+    assert_eq!(chip8.metrics().frames, result.frames + second.frames);
+}
+
+#[test]
+fn stops_at_errors() {
+    let mut chip8 = Chip8::new(&[0x60, 0x01, 0xFF, 0xFF]);
+    let result = bench::run(&mut chip8, Duration::from_secs(10));
+    assert!(result.elapsed < Duration::from_secs(10));
+    assert_eq!(result.instructions, 1);
+    assert_eq!(result.error, Some(Chip8Error::IllegalInstruction { opcode: 0xFFFF, pc: 0x204 }));
+}
+
+#[test]
+fn formats_throughput() {
+    let result = BenchResult { instructions: 1200, frames: 120, elapsed: Duration::from_secs(2), error: None };
+    assert_eq!(result.instructions_per_second(), 600.0);
+    assert_eq!(
+        result.to_string(),
+        "1200 instructions and 120 frames in 2.00 s\n600 instructions/s\n60 frames/s (1.0x real time)",
+    );
+
+    let result = BenchResult { error: Some(Chip8Error::StackOverflow), ..result };
+    assert!(result.to_string().ends_with("(1.0x real time)\nStopped early: Stack overflow"));
+}
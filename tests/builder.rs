@@ -0,0 +1,72 @@
+//! The builder applies all settings, and the same ones as the setters.
+
+use chip8::snapshot::Snapshot;
+use chip8::{Chip8, Quirks, RunPolicy};
+
+#[test]
+fn builder_matches_setters() {
+    let rom = [0xC0, 0xFF, 0x12, 0x00];
+    let quirks = Quirks::for_platform("originalChip8").expect("The platform is known");
+    let mut built = Chip8::builder()
+        .rom(&rom)
+        .quirks(quirks)
+        .instructions_per_frame(15)
+        .seed(42)
+        .stack_depth(4)
+        .run_policy(RunPolicy::Instructions(100))
+        .build();
+    let mut configured = Chip8::new(&rom);
+    configured.set_quirks(quirks);
+    configured.set_instructions_per_frame(15);
+    configured.set_seed(42);
+    configured.set_stack_depth(4);
+    configured.set_run_policy(RunPolicy::Instructions(100));
+
+    assert_eq!(built.quirks(), configured.quirks());
+    assert_eq!(built.instructions_per_frame(), 15);
+    assert_eq!(built.run_policy(), RunPolicy::Instructions(100));
+    for _ in 0..10 {
+        built.step().expect("The program is valid");
+        configured.step().expect("The program is valid");
+    }
+    assert_eq!(Snapshot::of(&built), Snapshot::of(&configured));
+}
+
+#[test]
+fn builder_loads_at_address() {
+    // V0 := 7, then halt, built for 0x600 like ETI-660 programs
+    let rom = [0x60, 0x07, 0x16, 0x02];
+    let mut chip8 = Chip8::builder().rom(&rom).load_addr(0x600).build();
+    assert_eq!(chip8.pc(), 0x600);
+    assert_eq!(chip8.bus().peek(0x200), 0);
+    assert_eq!(chip8.bus().peek(0x603), 0x02);
+    chip8.step().expect("The program is valid");
+    assert_eq!(chip8.register(0), 7);
+
+    let chip8 = Chip8::builder().rom(&rom).load_addr(0x600).entry(0x602).build();
+    assert_eq!(chip8.pc(), 0x602);
+}
+
+#[test]
+fn load_rejects_data_beyond_memory() {
+    let mut chip8 = Chip8::new(&[]);
+    assert!(chip8.load(0xFFE, &[1, 2]).is_ok());
+    assert!(chip8.load(0xFFE, &[1, 2, 3]).is_err());
+    assert!(chip8.load(0x2000, &[]).is_err());
+}
+
+#[test]
+fn builder_loads_segments_over_rom() {
+    let rom = [0x60, 0x07, 0x16, 0x02];
+    let chip8 = Chip8::builder()
+        .rom(&rom)
+        .segment(0x300, &[0xAA, 0xBB])
+        .segment(0x202, &[0x12, 0x00])
+        .segment(0x301, &[0xCC])
+        .build();
+    assert_eq!(chip8.pc(), 0x200);
+    assert_eq!(chip8.bus().peek(0x200), 0x60);
+    assert_eq!(chip8.bus().peek(0x202), 0x12);
+    assert_eq!(chip8.bus().peek(0x300), 0xAA);
+    assert_eq!(chip8.bus().peek(0x301), 0xCC);
+}
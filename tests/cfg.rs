@@ -0,0 +1,68 @@
+//! The control-flow graph splits the reachable instructions into basic blocks connected by their branches.
+
+use chip8::cfg::{ControlFlowGraph, Edge, EdgeKind};
+use chip8::disassembler::Disassembly;
+
+/// Counts V0 up to 5 in a subroutine, then halts. Ends with data that isn't reachable.
+const ROM: [u8; 16] = [
+    0x60, 0x00, // 0x200: V0 := 0
+    0x22, 0x0A, // 0x202: Call 0x20A
+    0x30, 0x05, // 0x204: Skip if V0 == 5
+    0x12, 0x02, // 0x206: Jump back to the call
+    0x12, 0x08, // 0x208: Halt
+    0x70, 0x01, // 0x20A: V0 += 1
+    0x00, 0xEE, // 0x20C: Return
+    0xFF, 0xFF, // 0x20E: Data
+];
+
+fn graph() -> ControlFlowGraph {
+    ControlFlowGraph::new(&Disassembly::analyze(&ROM))
+}
+
+#[test]
+fn splits_blocks_at_jump_targets_and_branches() {
+    let graph = graph();
+    let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+    assert_eq!(starts, [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+    assert_eq!(graph.blocks[&0x20A].instructions, [(0x20A, 0x7001), (0x20C, 0x00EE)]);
+    assert_eq!(graph.blocks[&0x202].label.as_deref(), Some("label_202"));
+    assert_eq!(graph.blocks[&0x20A].label.as_deref(), Some("sub_20A"));
+    assert_eq!(graph.blocks[&0x200].label, None);
+}
+
+#[test]
+fn connects_blocks_by_their_branches() {
+    let graph = graph();
+    let edges = |start: usize| graph.blocks[&start].edges.clone();
+    let edge = |target: usize, kind: EdgeKind| Edge { target, kind };
+    // The block falls through into the jump target
+    assert_eq!(edges(0x200), [edge(0x202, EdgeKind::Next)]);
+    assert_eq!(edges(0x202), [edge(0x20A, EdgeKind::Call), edge(0x204, EdgeKind::Next)]);
+    assert_eq!(edges(0x204), [edge(0x206, EdgeKind::Next), edge(0x208, EdgeKind::Skip)]);
+    assert_eq!(edges(0x206), [edge(0x202, EdgeKind::Jump)]);
+    assert_eq!(edges(0x208), [edge(0x208, EdgeKind::Jump)]);
+    // Where a subroutine returns to isn't known statically
+    assert_eq!(edges(0x20A), []);
+}
+
+#[test]
+fn exports_dot() {
+    let expected = r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b_200 [label="0x200: LD V0, 0x00\l"];
+    b_202 [label="label_202:\l0x202: CALL 0x20A\l"];
+    b_204 [label="0x204: SE V0, 0x05\l"];
+    b_206 [label="0x206: JP 0x202\l"];
+    b_208 [label="label_208:\l0x208: JP 0x208\l"];
+    b_20A [label="sub_20A:\l0x20A: ADD V0, 0x01\l0x20C: RET\l"];
+    b_200 -> b_202;
+    b_202 -> b_20A [style=dashed];
+    b_202 -> b_204;
+    b_204 -> b_206;
+    b_204 -> b_208 [label="skip"];
+    b_206 -> b_202;
+    b_208 -> b_208;
+}
+"#;
+    assert_eq!(graph().to_dot(), expected);
+}
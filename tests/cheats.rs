@@ -0,0 +1,123 @@
+use chip8::cheats::{CheatTable, Every, Freeze, MemorySearch, SearchFilter, SearchMatch};
+use chip8::debugger::{Command, Debugger, Location, Reply};
+use chip8::hooks::Hooks;
+use chip8::Chip8;
+
+/// Decrements the "lives" at 0x300 in every instruction of an endless loop.
+const LOSE_LIVES: [u8; 10] = [
+    0xA3, 0x00, // 0x200: LD I, 0x300
+    0xF0, 0x65, // 0x202: LD V0, [I]
+    0x70, 0xFF, // 0x204: ADD V0, 0xFF
+    0xF0, 0x55, // 0x206: LD [I], V0
+    0x12, 0x02, // 0x208: JP 0x202
+];
+
+#[test]
+fn parse_cheat_table() {
+    let cheats = CheatTable::parse(r#"
+        [[freeze]]
+        name = "Infinite lives"
+        address = 0x300
+        value = 3
+
+        [[freeze]]
+        address = 0x2F0
+        value = 0xFF
+        every = "instruction"
+    "#).unwrap();
+    assert_eq!(cheats.freezes(), &[
+        Freeze { name: String::new(), addr: 0x2F0, value: 0xFF, every: Every::Instruction },
+        Freeze { name: String::from("Infinite lives"), addr: 0x300, value: 3, every: Every::Frame },
+    ]);
+
+    assert!(CheatTable::parse("").unwrap().is_empty());
+    assert!(CheatTable::parse("[[freeze]]\naddress = 0x300\nvalue = 256").is_err());
+    assert!(CheatTable::parse("[[freeze]]\nvalue = 1").is_err());
+    assert!(CheatTable::parse("[[freeze]]\naddress = 0x300\nvalue = 1\nevery = \"second\"").is_err());
+}
+
+#[test]
+fn freeze_after_every_frame() {
+    let mut chip8 = Chip8::new(&LOSE_LIVES);
+    chip8.set_instructions_per_frame(10);
+    let mut cheats = CheatTable::parse("[[freeze]]\naddress = 0x300\nvalue = 3").unwrap();
+    for _ in 0..5 {
+        assert!(chip8.run_frame_with_hooks(&mut cheats).unwrap().is_continue());
+        assert_eq!(chip8.bus().peek(0x300), 3);
+    }
+}
+
+#[test]
+fn freeze_after_every_instruction() {
+    let mut chip8 = Chip8::new(&LOSE_LIVES);
+    let mut cheats = CheatTable::new();
+    cheats.freeze(Freeze { name: String::new(), addr: 0x300, value: 3, every: Every::Instruction });
+    for _ in 0..8 {
+        let pc = chip8.pc();
+        chip8.step().unwrap();
+        assert!(cheats.after_instruction(&mut chip8, pc, 0).unwrap().is_continue());
+        assert_eq!(chip8.bus().peek(0x300), 3);
+    }
+    // Every load sees the frozen value
+    assert_eq!(chip8.register(0), 2);
+}
+
+#[test]
+fn freeze_in_debugger() {
+    let mut chip8 = Chip8::new(&LOSE_LIVES);
+    let mut debugger = Debugger::new();
+    let freeze = Command::Freeze { addr: Location::Addr(0x300), value: 3 };
+    assert_eq!(debugger.execute(&chip8, freeze), Reply::Ok);
+    for _ in 0..4 {
+        chip8.step().unwrap();
+        debugger.apply_freezes(&mut chip8);
+        assert_eq!(chip8.bus().peek(0x300), 3);
+    }
+    let freezes = vec![Freeze { name: String::new(), addr: 0x300, value: 3, every: Every::Instruction }];
+    assert_eq!(debugger.execute(&chip8, Command::Freezes), Reply::Freezes { freezes });
+
+    let unfreeze = || Command::Unfreeze { addr: Location::Addr(0x300) };
+    assert_eq!(debugger.execute(&chip8, unfreeze()), Reply::Ok);
+    assert!(matches!(debugger.execute(&chip8, unfreeze()), Reply::Error { .. }));
+    assert!(matches!(debugger.execute(&chip8, Command::Freeze { addr: Location::Addr(0x10000), value: 1 }),
+        Reply::Error { .. }));
+}
+
+#[test]
+fn search_memory() {
+    let mut chip8 = Chip8::new(&LOSE_LIVES);
+    let mut search = MemorySearch::new();
+    assert!(search.search(&chip8, SearchFilter::Decreased).is_err());
+    assert_eq!(search.search(&chip8, SearchFilter::Any).unwrap().len(), chip8.bus().size());
+
+    // Load, decrement and store the lives once, which wraps around from 0
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    assert!(search.clone().search(&chip8, SearchFilter::Decreased).unwrap().is_empty());
+    let matches = search.search(&chip8, SearchFilter::Increased).unwrap();
+    assert_eq!(matches, &[SearchMatch { addr: 0x300, value: 0xFF }]);
+    assert_eq!(search.search(&chip8, SearchFilter::Unchanged).unwrap().len(), 1);
+    assert!(search.search(&chip8, SearchFilter::Equal(0)).unwrap().is_empty());
+
+    search.reset();
+    assert!(!search.is_started());
+    assert!(search.search(&chip8, SearchFilter::Equal(0xFF)).unwrap().iter().any(|found| found.addr == 0x300));
+}
+
+#[test]
+fn search_in_debugger() {
+    let mut chip8 = Chip8::new(&LOSE_LIVES);
+    let mut debugger = Debugger::new();
+    assert!(matches!(debugger.execute(&chip8, Command::Search { filter: SearchFilter::Equal(0) }),
+        Reply::SearchMatches { .. }));
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    let matches = vec![SearchMatch { addr: 0x300, value: 0xFF }];
+    assert_eq!(debugger.execute(&chip8, Command::Search { filter: SearchFilter::Changed }),
+        Reply::SearchMatches { matches });
+    assert_eq!(debugger.execute(&chip8, Command::SearchReset), Reply::Ok);
+    assert!(matches!(debugger.execute(&chip8, Command::Search { filter: SearchFilter::Changed }),
+        Reply::Error { .. }));
+}
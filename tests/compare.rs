@@ -0,0 +1,30 @@
+use chip8::compare::{self, Outcome};
+use chip8::{Chip8, Quirks};
+
+/// Shifts with `8XY6`, whose result depends on the shift quirk, then halts.
+const SHIFT_ROM: [u8; 8] = [0x60, 0x01, 0x61, 0x04, 0x80, 0x16, 0x12, 0x06];
+
+#[test]
+fn finds_the_frame_where_quirks_diverge() {
+    let mut first = Chip8::builder().rom(&SHIFT_ROM).instructions_per_frame(10).build();
+    let mut second = Chip8::builder()
+        .rom(&SHIFT_ROM)
+        .instructions_per_frame(10)
+        .quirks(Quirks { shift: false, ..Quirks::default() })
+        .build();
+    assert_eq!(first.quirks().differences(second.quirks()), ["shift"]);
+    match compare::run(&mut first, &mut second, 10, 0, |_| 0) {
+        Outcome::Diverged { frame: 0, diff } => {
+            let names: Vec<&str> = diff.values.iter().map(|(name, _, _)| name.as_str()).collect();
+            assert_eq!(names, ["V0", "VF"]);
+        },
+        outcome => panic!("Unexpected outcome {:?}", outcome),
+    }
+}
+
+#[test]
+fn equal_configurations_halt_together() {
+    let mut first = Chip8::builder().rom(&SHIFT_ROM).instructions_per_frame(10).build();
+    let mut second = Chip8::builder().rom(&SHIFT_ROM).instructions_per_frame(10).build();
+    assert_eq!(compare::run(&mut first, &mut second, 10, 0, |_| 0), Outcome::Halted { frame: 0, pc: 0x206 });
+}
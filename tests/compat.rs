@@ -0,0 +1,58 @@
+//! Settings for known ROMs from the bundled compatibility table.
+
+use chip8::compat::{self, Profile};
+use chip8::{Chip8, Quirks};
+
+#[test]
+fn parses_line_with_quirk_overrides() {
+    let line = "0123456789abcdef0123456789abcdef01234567 superchip 30 vblank=true wrap=true # Some Game";
+    let (hash, profile) = compat::parse_line(line).unwrap();
+    assert_eq!(hash, "0123456789abcdef0123456789abcdef01234567");
+    let quirks = Quirks { vblank: true, wrap: true, ..Quirks::for_platform("superchip").unwrap() };
+    assert_eq!(profile, Profile { title: "Some Game", platform: "superchip", tickrate: 30, quirks });
+}
+
+#[test]
+fn parses_line_without_title() {
+    let (_, profile) = compat::parse_line("00ff originalChip8 10").unwrap();
+    assert_eq!(profile.title, "");
+    assert_eq!(profile.quirks, Quirks::for_platform("originalChip8").unwrap());
+}
+
+#[test]
+fn skips_comments_and_invalid_lines() {
+    assert_eq!(compat::parse_line("# 00ff originalChip8 10"), None);
+    assert_eq!(compat::parse_line(""), None);
+    assert_eq!(compat::parse_line("00ff"), None);
+    assert_eq!(compat::parse_line("00ff unknownPlatform 10"), None);
+    assert_eq!(compat::parse_line("00ff originalChip8 fast"), None);
+    assert_eq!(compat::parse_line("00ff originalChip8 10 unknownQuirk=true"), None);
+    assert_eq!(compat::parse_line("00ff originalChip8 10 vblank=yes"), None);
+}
+
+#[test]
+fn finds_bundled_roms() {
+    let maze = include_bytes!("../roms/maze.ch8");
+    let profile = compat::lookup(maze).unwrap();
+    assert_eq!(profile.title, "Maze");
+    assert_eq!(profile.platform, "modernChip8");
+
+    let mut chip8 = Chip8::new(maze);
+    profile.apply(&mut chip8);
+    assert_eq!(chip8.instructions_per_frame(), profile.tickrate);
+    assert_eq!(*chip8.quirks(), profile.quirks);
+
+    assert!(compat::lookup(include_bytes!("../roms/bounce.ch8")).is_some());
+    assert!(compat::lookup(include_bytes!("../roms/keypad.ch8")).is_some());
+}
+
+#[test]
+fn looks_up_hash_case_insensitively() {
+    let profile = compat::lookup_sha1("1ABD5D9C899DCCD5533B3B9D0955C7C25E0FA995").unwrap();
+    assert_eq!(profile.title, "Maze");
+}
+
+#[test]
+fn unknown_rom_has_no_profile() {
+    assert_eq!(compat::lookup(&[0x12, 0x00]), None);
+}
@@ -0,0 +1,58 @@
+use std::env;
+use std::fs;
+use chip8::config::Config;
+use chip8::keymap::Keymap;
+
+#[test]
+fn parse_audio_settings() {
+    let config = Config::parse("[audio]\nvolume = 0.4\nmuted = true\n").unwrap();
+    assert_eq!(config.mixer.volume(), 0.4);
+    assert!(config.mixer.is_muted());
+    assert_eq!(Config::parse("[audio]\nvolume = 1\n").unwrap().mixer.volume(), 1.0);
+}
+
+#[test]
+fn missing_settings_are_defaults() {
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    assert_eq!(Config::parse("[audio]\n").unwrap(), Config::default());
+}
+
+#[test]
+fn reject_invalid_settings() {
+    assert!(Config::parse("[audio]\nvolume = 2.0\n").is_err());
+    assert!(Config::parse("[audio]\nvolume = \"loud\"\n").is_err());
+    assert!(Config::parse("[audio]\nmuted = 1\n").is_err());
+    assert!(Config::parse("audio = 3\n").is_err());
+}
+
+#[test]
+fn parse_keys() {
+    let config = Config::parse("[keys]\n5 = \"I\"\nA = \"y\"\n").unwrap();
+    let keymap = config.keymap.unwrap();
+    assert_eq!(keymap.key_for_char('i'), Some(0x5));
+    assert_eq!(keymap.key_for_char('y'), Some(0xA));
+    assert_eq!(keymap.key_for_char('1'), Some(0x1));
+    assert_eq!(Config::parse("").unwrap().keymap, None);
+
+    assert!(Config::parse("[keys]\nG = \"g\"\n").is_err());
+    assert!(Config::parse("[keys]\n1 = \"ab\"\n").is_err());
+    assert!(Config::parse("[keys]\n1 = \"k\"\n2 = \"k\"\n").is_err());
+}
+
+#[test]
+fn save_and_load() {
+    let path = env::temp_dir().join(format!("chip8-config-test-{}", std::process::id())).join("config.toml");
+    assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+    let mut config = Config::default();
+    config.mixer.set_volume(0.3);
+    config.mixer.set_muted(true);
+    let mut keymap = Keymap::default();
+    keymap.set(0x0, '"').unwrap();
+    keymap.set(0xF, '\\').unwrap();
+    keymap.set(0x1, 'é').unwrap();
+    config.keymap = Some(keymap);
+    config.save(&path).unwrap();
+    assert_eq!(Config::load(&path).unwrap(), config);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
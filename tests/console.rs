@@ -0,0 +1,70 @@
+//! The console debugger inspects core dumps with commands read from stdin.
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use chip8::snapshot::Snapshot;
+use chip8::Chip8;
+
+/// Runs `chip8 debug --core` on the state of a crashed ROM with the `commands` as input, and returns the output. The
+/// core dump is named after the `test`, so that tests can run in parallel.
+fn post_mortem(test: &str, commands: &str) -> String {
+    let rom = [
+        0x60, 0x2A, // V0 := 42
+        0xA3, 0x00, // I := 0x300
+        0xF0, 0x55, // Store V0 at 0x300
+        0x22, 0x0A, // Call 0x20A
+        0x12, 0x08,
+        0xFF, 0xFF, // Illegal
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    for _ in 0..4 {
+        chip8.step().expect("The first instructions are valid");
+    }
+    chip8.step().expect_err("The instruction is illegal");
+    let path = std::env::temp_dir().join(format!("chip8-console-{}-{}.core", std::process::id(), test));
+    Snapshot::of(&chip8).save(&path).expect("The temp dir is writable");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .arg("debug")
+        .arg("--core")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("The debugger starts");
+    child.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+    let output = child.wait_with_output().expect("The debugger ends with the input");
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn shows_state_of_core_dump() {
+    let output = post_mortem("state", "");
+    let expected = "\
+PC=0x20C I=0x300 SP=1 DT=0 ST=0
+V0=2A V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00
+Stack: 0x208
+Last executed instruction at 0x20A:
+   0x20A: FFFF    (illegal)
+=> 0x20C: 0000    SYS 0x000
+";
+    assert!(output.starts_with(expected), "Unexpected output:\n{}", output);
+}
+
+#[test]
+fn inspects_but_doesnt_execute() {
+    let output = post_mortem("inspect", "mem 0x300 2\nbt\nstep\ncontinue\nbogus\nquit\nregs\n");
+    let replies: Vec<&str> = output.split("(chip8) ").skip(1).collect();
+    assert_eq!(replies, [
+        "0x300: 2A 00\n",
+        "#0 0x20C 0x20C\n#1 0x208 0x208\n",
+        "Error: The machine is frozen, it can only be inspected\n",
+        "Error: The machine is frozen, it can only be inspected\n",
+        "Error: Unknown command bogus\n",
+        // Nothing after quit is executed
+        "",
+    ]);
+}
@@ -0,0 +1,45 @@
+//! `chip8 run --core-dump` saves the machine state when the emulation fails.
+
+use std::fs;
+use std::process::{Command, Stdio};
+use chip8::snapshot::Snapshot;
+
+#[test]
+fn writes_state_at_failure() {
+    let dir = std::env::temp_dir().join(format!("chip8-core-dump-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rom = [
+        0x60, 0x2A, // V0 := 42
+        0xA3, 0x00, // I := 0x300
+        0xF0, 0x55, // Store V0 at 0x300
+        0x22, 0x0A, // Call 0x20A
+        0x12, 0x08,
+        0xFF, 0xFF, // Illegal
+    ];
+    fs::write(dir.join("crash.ch8"), rom).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .args(["run", "crash.ch8", "--core-dump"])
+        .current_dir(&dir)
+        // Keep the config of the user out of it
+        .env("HOME", &dir)
+        .stdin(Stdio::null())
+        .output()
+        .expect("The emulator runs");
+    let dumps: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "core"))
+        .collect();
+    let snapshot = dumps.first().map(Snapshot::load);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Error: Encountered illegal instruction 0xFFFF"), "Unexpected output:\n{}", stdout);
+    assert!(stdout.contains("Wrote the machine state to chip8-"));
+    assert_eq!(dumps.len(), 1);
+    let snapshot = snapshot.unwrap().expect("The core dump is readable");
+    assert_eq!(snapshot.pc, 0x20C);
+    assert_eq!((snapshot.i, snapshot.registers[0]), (0x300, 0x2A));
+    assert_eq!(snapshot.memory[0x300], 0x2A);
+    assert_eq!(snapshot.stack[1..=snapshot.stack_pointer as usize], [0x208]);
+    assert_eq!(snapshot.history.last(), Some(&(0x20A, 0xFFFF)));
+}
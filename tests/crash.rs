@@ -0,0 +1,42 @@
+//! Crash reports show the registers, the last instructions and the display when the emulation fails.
+
+use chip8::crash::CrashReport;
+use chip8::{Chip8, Chip8Error};
+
+#[test]
+fn reports_state_at_failure() {
+    let rom = [
+        0x60, 0x2A, // V0 := 42
+        0xA3, 0x00, // I := 0x300
+        0x22, 0x08, // Call 0x208
+        0x12, 0x06,
+        0xFF, 0xFF, // Illegal
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    for _ in 0..3 {
+        chip8.step().expect("The first instructions are valid");
+    }
+    let error = chip8.step().expect_err("The instruction is illegal");
+    let report = CrashReport::new(&chip8, error);
+    assert_eq!(report.error, Chip8Error::IllegalInstruction { opcode: 0xFFFF, pc: 0x20A });
+    assert_eq!(report.registers.stack, [0x206]);
+    assert_eq!(report.history, [(0x200, 0x602A), (0x202, 0xA300), (0x204, 0x2208), (0x208, 0xFFFF)]);
+
+    let expected = "\
+Error: Encountered illegal instruction 0xFFFF at PC=522
+PC=0x20A I=0x300 SP=1 DT=0 ST=0
+V0=2A V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00
+Stack: 0x206
+Last instructions:
+  0x200: 602A    LD V0, 0x2A
+  0x202: A300    LD I, 0x300
+  0x204: 2208    CALL 0x208
+  0x208: FFFF    (illegal)
+Display:
+";
+    let text = report.to_string();
+    assert!(text.starts_with(expected), "Unexpected report:\n{}", text);
+    // The display is empty, one line per row
+    assert_eq!(text[expected.len()..].lines().count(), 32);
+    assert!(text[expected.len()..].chars().all(|c| c == ' ' || c == '\n'));
+}
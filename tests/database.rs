@@ -0,0 +1,79 @@
+//! Looking up ROMs in the CHIP-8 database.
+#![cfg(feature = "database")]
+
+use chip8::database::{RomDatabase, RomInfo};
+use chip8::{Chip8, Quirks};
+
+/// Two programs in the format of `programs.json`, one of them with two ROM versions.
+const PROGRAMS: &str = r#"[
+    {
+        "title": "Pong",
+        "authors": ["Paul Vervalin"],
+        "release": "1990",
+        "roms": {
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA": {
+                "tickrate": 9,
+                "platforms": ["originalChip8"],
+                "quirkyPlatforms": { "originalChip8": { "vblank": false } }
+            },
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb": {
+                "platforms": ["someFuturePlatform"]
+            }
+        }
+    },
+    {
+        "title": "Maze",
+        "description": "Draws a random maze",
+        "roms": { "1abd5d9c899dccd5533b3b9d0955c7c25e0fa995": { "platforms": ["modernChip8"] } }
+    }
+]"#;
+
+#[test]
+fn looks_up_rom_by_hash() {
+    let database = RomDatabase::from_json(PROGRAMS).expect("The JSON is valid");
+    let info = database.lookup_sha1("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").expect("The ROM is in the database");
+    let quirks = Quirks { vblank: false, ..Quirks::for_platform("originalChip8").unwrap() };
+    assert_eq!(info, RomInfo {
+        title: String::from("Pong"),
+        authors: vec![String::from("Paul Vervalin")],
+        release: Some(String::from("1990")),
+        description: None,
+        tickrate: Some(9),
+        quirks: Some(quirks),
+    });
+    assert_eq!(info.to_string(), "Pong by Paul Vervalin (1990)");
+
+    let mut chip8 = Chip8::new(&[]);
+    info.apply(&mut chip8);
+    assert_eq!(chip8.instructions_per_frame(), 9);
+    assert_eq!(*chip8.quirks(), quirks);
+}
+
+#[test]
+fn unknown_platform_has_no_quirks() {
+    let database = RomDatabase::from_json(PROGRAMS).expect("The JSON is valid");
+    let info = database.lookup_sha1("BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB").expect("The ROM is in the database");
+    assert_eq!((info.title.as_str(), info.tickrate, info.quirks), ("Pong", None, None));
+}
+
+#[test]
+fn looks_up_rom_by_content() {
+    let database = RomDatabase::from_json(PROGRAMS).expect("The JSON is valid");
+    let info = database.lookup(include_bytes!("../roms/maze.ch8")).expect("The ROM is in the database");
+    assert_eq!(info.title, "Maze");
+    assert_eq!(info.description.as_deref(), Some("Draws a random maze"));
+    assert_eq!(info.to_string(), "Maze");
+}
+
+#[test]
+fn misses_unknown_rom() {
+    let database = RomDatabase::from_json(PROGRAMS).expect("The JSON is valid");
+    assert_eq!(database.lookup_sha1("cccccccccccccccccccccccccccccccccccccccc"), None);
+    assert_eq!(database.lookup(include_bytes!("../roms/bounce.ch8")), None);
+}
+
+#[test]
+fn rejects_invalid_json() {
+    assert!(RomDatabase::from_json(r#"[{"authors": []}]"#).is_err());
+    assert!(RomDatabase::from_json("{").is_err());
+}
@@ -0,0 +1,115 @@
+//! The debugger core shows watch expressions whenever execution pauses, and pauses at watchpoints on I.
+
+use chip8::console::{format_reply, parse_command};
+use chip8::debugger::{Command, Debugger, PauseReason, Reply, WatchExpr};
+use chip8::Chip8;
+
+/// Counts the byte at 0x300 up forever.
+const COUNT_UP: [u8; 10] = [
+    0xA3, 0x00, // 0x200: LD I, 0x300
+    0xF0, 0x65, // 0x202: LD V0, [I]
+    0x70, 0x01, // 0x204: ADD V0, 0x01
+    0xF0, 0x55, // 0x206: LD [I], V0
+    0x12, 0x02, // 0x208: JP 0x202
+];
+
+fn watches(debugger: &mut Debugger, chip8: &Chip8) -> String {
+    format_reply(&debugger.execute(chip8, Command::Watches), chip8.pc())
+}
+
+#[test]
+fn parses_watch_expressions() {
+    assert_eq!("vA".parse(), Ok(WatchExpr::V(0xA)));
+    assert_eq!("pc".parse(), Ok(WatchExpr::Pc));
+    assert_eq!("[0x300]".parse(), Ok(WatchExpr::Mem(0x300)));
+    assert_eq!("[ i + 2 ]".parse(), Ok(WatchExpr::MemAtI(2)));
+    assert_eq!("[i]".parse(), Ok(WatchExpr::MemAtI(0)));
+    for expr in ["VG", "[0x300", "[I-1]", "X"] {
+        assert!(expr.parse::<WatchExpr>().is_err(), "{} is invalid", expr);
+    }
+    for expr in ["V3", "I", "PC", "SP", "DT", "ST", "[0x300]", "[I]", "[I+2]"] {
+        assert_eq!(expr.parse::<WatchExpr>().map(|expr| expr.to_string()).as_deref(), Ok(expr));
+    }
+}
+
+#[test]
+fn marks_changed_values() {
+    let mut chip8 = Chip8::new(&COUNT_UP);
+    let mut debugger = Debugger::new();
+    assert_eq!(watches(&mut debugger, &chip8), "No watch expressions");
+    for expr in ["watch I", "watch V0", "watch [0x300]", "watch [I+1]"] {
+        let command = parse_command(expr).expect("The command is valid");
+        assert_eq!(debugger.execute(&chip8, command), Reply::Ok);
+    }
+    // Watching an expression twice shows it once
+    debugger.execute(&chip8, Command::Watch { expr: WatchExpr::V(0) });
+
+    // The values when starting to watch count as shown
+    assert_eq!(watches(&mut debugger, &chip8), "  I = 0x000\n  V0 = 0x00\n  [0x300] = 0x00\n  [I+1] = 0x00");
+    for _ in 0..4 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(watches(&mut debugger, &chip8), "* I = 0x300\n* V0 = 0x01\n* [0x300] = 0x01\n  [I+1] = 0x00");
+    chip8.step().expect("The program is valid");
+    assert_eq!(watches(&mut debugger, &chip8), "  I = 0x300\n  V0 = 0x01\n  [0x300] = 0x01\n  [I+1] = 0x00");
+
+    assert_eq!(debugger.execute(&chip8, parse_command("unwatch V0").unwrap()), Reply::Ok);
+    assert_eq!(
+        debugger.execute(&chip8, parse_command("unwatch V0").unwrap()),
+        Reply::Error { message: String::from("Not watching V0") },
+    );
+    assert_eq!(
+        debugger.execute(&chip8, Command::Watch { expr: WatchExpr::Mem(0x1000) }),
+        Reply::Error { message: String::from("Address 0x1000 is out of bounds") },
+    );
+}
+
+#[test]
+fn pauses_when_i_enters_watched_range() {
+    let rom = [
+        0xA2, 0xF0, // 0x200: LD I, 0x2F0
+        0x60, 0x08, // 0x202: LD V0, 0x08
+        0xF0, 0x1E, // 0x204: ADD I, V0
+        0x12, 0x04, // 0x206: JP 0x204
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.execute(&chip8, parse_command("watch-i 0x300..0x310").unwrap()), Reply::Ok);
+    assert_eq!(
+        format_reply(&debugger.execute(&chip8, Command::Breakpoints), chip8.pc()),
+        "I in 0x300..0x310",
+    );
+
+    let mut pauses = Vec::new();
+    for _ in 0..9 {
+        chip8.step().expect("The program is valid");
+        if let Some(reason) = debugger.check(&chip8) {
+            pauses.push((chip8.i(), reason));
+        }
+    }
+    // I passes 0x2F8, 0x300, 0x308 and 0x310, only adding it into the range pauses
+    assert_eq!(pauses, [(0x300, PauseReason::Watchpoint), (0x308, PauseReason::Watchpoint)]);
+    assert!(!debugger.is_paused());
+
+    assert_eq!(
+        debugger.execute(&chip8, Command::WatchI { start: 0x310, end: 0x310 }),
+        Reply::Error { message: String::from("The range 0x310..0x310 is empty") },
+    );
+    assert_eq!(debugger.execute(&chip8, Command::UnwatchI { start: 0x300, end: 0x310 }), Reply::Ok);
+    assert_eq!(format_reply(&debugger.execute(&chip8, Command::Breakpoints), chip8.pc()), "No breakpoints");
+}
+
+#[test]
+fn pauses_when_i_is_set_to_same_watched_address() {
+    // Sets I to 0x300 over and over
+    let mut chip8 = Chip8::new(&[0xA3, 0x00, 0x12, 0x00]);
+    let mut debugger = Debugger::new();
+    debugger.execute(&chip8, Command::WatchI { start: 0x300, end: 0x301 });
+    let reasons: Vec<_> = (0..4)
+        .map(|_| {
+            chip8.step().expect("The program is valid");
+            debugger.check(&chip8)
+        })
+        .collect();
+    assert_eq!(reasons, [Some(PauseReason::Watchpoint), None, Some(PauseReason::Watchpoint), None]);
+}
@@ -0,0 +1,77 @@
+//! The disassembler tells code from data by following the control flow, and its Octo output assembles back into
+//! the same ROM.
+
+use chip8::assembler;
+use chip8::disassembler::{Disassembly, FormatOptions, Syntax};
+use proptest::prelude::*;
+
+fn round_trip(rom: &[u8], options: FormatOptions) -> Vec<u8> {
+    let source = Disassembly::analyze(rom).format(&options);
+    assembler::assemble(&source).unwrap_or_else(|err| panic!("{} in\n{}", err, source)).rom
+}
+
+proptest! {
+    #[test]
+    fn octo_output_reassembles(rom in prop::collection::vec(any::<u8>(), 0..512), addresses: bool, bytes: bool) {
+        let options = FormatOptions { addresses, bytes, syntax: Syntax::Octo, ..FormatOptions::default() };
+        prop_assert_eq!(round_trip(&rom, options), rom);
+    }
+
+    /// Instructions whose jumps, calls and addresses mostly point into the ROM, so that most of it is reachable
+    /// and labeled.
+    #[test]
+    fn octo_output_of_code_reassembles(instructions in prop::collection::vec((0u16..0x10, 0u16..0x1000), 1..256)) {
+        let len = instructions.len() as u16 * 2;
+        let rom: Vec<u8> = instructions.iter()
+            .flat_map(|&(kind, operand)| {
+                let operand = match kind {
+                    0x1 | 0x2 | 0xA | 0xB => 0x200 + operand % len,
+                    _ => operand,
+                };
+                (kind << 12 | operand).to_be_bytes()
+            })
+            .collect();
+        prop_assert_eq!(round_trip(&rom, FormatOptions { syntax: Syntax::Octo, ..FormatOptions::default() }), rom);
+    }
+}
+
+/// Loads a sprite and calls a subroutine, followed by the sprite, which could also be decoded as `SE VC, 0x42`.
+const ROM: [u8; 11] = [
+    0xA2, 0x08, // 0x200: I := sprite
+    0x22, 0x06, // 0x202: Call 0x206
+    0x12, 0x04, // 0x204: Halt
+    0x00, 0xEE, // 0x206: Return
+    0x3C, 0x42, 0x42, // 0x208: Sprite
+];
+
+#[test]
+fn separates_code_from_data() {
+    let disassembly = Disassembly::analyze(&ROM);
+    let code: Vec<usize> = disassembly.instructions().map(|(addr, _, _)| addr).collect();
+    assert_eq!(code, [0x200, 0x202, 0x204, 0x206]);
+    assert!(!disassembly.is_code(0x208));
+    assert_eq!(disassembly.label(0x204), Some("label_204"));
+    assert_eq!(disassembly.label(0x206), Some("sub_206"));
+    assert_eq!(disassembly.label(0x208), Some("data_208"));
+    assert_eq!(disassembly.label(0x200), None);
+}
+
+#[test]
+fn formats_code_and_data() {
+    let expected = "\
+0x200: A208    LD I, data_208
+0x202: 2206    CALL sub_206
+label_204:
+0x204: 1204    JP label_204
+sub_206:
+0x206: 00EE    RET
+data_208:
+0x208: db 0x3C, 0x42, 0x42
+";
+    assert_eq!(Disassembly::analyze(&ROM).to_string(), expected);
+
+    let options = FormatOptions { addresses: false, bytes: false, lowercase: true, ..FormatOptions::default() };
+    let lowercase = Disassembly::analyze(&ROM).format(&options);
+    assert_eq!(lowercase.lines().collect::<Vec<_>>()[..2], ["ld i, data_208", "call sub_206"]);
+    assert!(lowercase.ends_with("data_208:\ndb 0x3c, 0x42, 0x42\n"));
+}
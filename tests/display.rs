@@ -0,0 +1,22 @@
+//! Frontends only upload the regions of the display that changed.
+
+use chip8::{Chip8, Rect};
+
+#[test]
+fn dirty_rects_cover_drawn_sprite_once() {
+    // Draw the digit 0 at (10, 3), twice
+    let rom = [0x60, 0x0A, 0x61, 0x03, 0xF2, 0x29, 0xD0, 0x15, 0xD0, 0x15];
+    let mut chip8 = Chip8::new(&rom);
+    assert!(chip8.take_dirty_rects().is_empty());
+    for _ in 0..4 {
+        chip8.step().expect("The program is valid");
+    }
+    let digit = Rect { x: 10, y: 3, width: 4, height: 5 };
+    assert_eq!(chip8.take_dirty_rects(), [digit]);
+    assert!(chip8.take_dirty_rects().is_empty());
+
+    // Erasing changes the same pixels back
+    chip8.step().expect("The program is valid");
+    assert_eq!(chip8.take_dirty_rects(), [digit]);
+    assert!(chip8.take_dirty_rects().is_empty());
+}
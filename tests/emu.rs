@@ -0,0 +1,61 @@
+//! The emulator thread runs ROMs and answers commands with events.
+
+use std::time::Duration;
+use chip8::emu::{EmuCommand, EmuEvent, Emulator};
+use chip8::{Framebuffer, DISPLAY_HEIGHT, MAX_PROGRAM_SIZE};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn rom(program: &[u16]) -> Vec<u8> {
+    program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+}
+
+fn next_event(emulator: &Emulator) -> EmuEvent {
+    emulator.events().recv_timeout(TIMEOUT).expect("The emulator sends an event")
+}
+
+#[test]
+fn draws_and_halts() {
+    let emulator = Emulator::spawn(|chip8| chip8.set_instructions_per_frame(10));
+    // Draw the sprite behind the program at (0, 0), then halt
+    assert!(emulator.send(EmuCommand::LoadRom(rom(&[0xA206, 0xD005, 0x1204, 0xF090, 0x9090, 0xF000]))));
+    let display = match next_event(&emulator) {
+        EmuEvent::FrameReady { display, dirty_rects } => {
+            assert!(!dirty_rects.is_empty());
+            *display
+        },
+        event => panic!("Expected a frame, got {:?}", event),
+    };
+    let mut expected = [0; DISPLAY_HEIGHT];
+    for (row, byte) in [0xF0u64, 0x90, 0x90, 0x90, 0xF0].iter().enumerate() {
+        expected[row] = byte << 56;
+    }
+    assert_eq!(display, Framebuffer::from_rows(expected));
+    assert_eq!(next_event(&emulator), EmuEvent::Halted { pc: 0x204 });
+
+    assert!(emulator.send(EmuCommand::SaveState));
+    match next_event(&emulator) {
+        EmuEvent::StateSaved(Some(snapshot)) => assert_eq!(snapshot.pc, 0x204),
+        event => panic!("Expected the state, got {:?}", event),
+    }
+
+    // The ROM runs again after a reset
+    assert!(emulator.send(EmuCommand::Reset));
+    let mut event = next_event(&emulator);
+    while let EmuEvent::FrameReady { .. } = event {
+        event = next_event(&emulator);
+    }
+    assert_eq!(event, EmuEvent::Halted { pc: 0x204 });
+}
+
+#[test]
+fn reports_errors() {
+    let emulator = Emulator::spawn(|_| {});
+    assert!(emulator.send(EmuCommand::SaveState));
+    assert_eq!(next_event(&emulator), EmuEvent::StateSaved(None));
+    assert!(emulator.send(EmuCommand::LoadRom(vec![0; MAX_PROGRAM_SIZE + 1])));
+    assert_eq!(next_event(&emulator), EmuEvent::RomTooLarge { size: MAX_PROGRAM_SIZE + 1 });
+    // Returning without a call fails
+    assert!(emulator.send(EmuCommand::LoadRom(rom(&[0x00EE]))));
+    assert!(matches!(next_event(&emulator), EmuEvent::Error(_)));
+}
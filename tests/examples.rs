@@ -0,0 +1,33 @@
+//! The built-in examples are up to date with their sources and run without errors.
+
+use chip8::assembler;
+use chip8::examples::{self, EXAMPLES};
+use chip8::Chip8;
+
+#[test]
+fn examples_match_their_sources() {
+    for example in &EXAMPLES {
+        let assembly = assembler::assemble(example.source)
+            .unwrap_or_else(|err| panic!("{} doesn't assemble: {}", example.name, err));
+        assert_eq!(assembly.rom, example.rom, "Rebuild roms/{}.ch8 with `chip8 asm`", example.name);
+    }
+}
+
+#[test]
+fn examples_run() {
+    for example in &EXAMPLES {
+        let mut chip8 = Chip8::new(example.rom);
+        // Press and release a key, for the examples waiting for one
+        for frame in 0..120 {
+            chip8.set_keypad(if frame == 60 { 1 << 5 } else { 0 });
+            chip8.run_frame().unwrap_or_else(|err| panic!("{} failed: {}", example.name, err));
+        }
+        assert!(chip8.framebuffer().rows().iter().any(|&row| row != 0), "{} draws nothing", example.name);
+    }
+}
+
+#[test]
+fn examples_by_name() {
+    assert_eq!(examples::named("MAZE").map(|example| example.name), Some("maze"));
+    assert!(examples::named("pong").is_none());
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use chip8::font::{Font, FONT_ADDR, FONT_SIZE};
+use chip8::Chip8;
+
+/// Draws the digit in V0 at (0, 0).
+const DRAW_DIGIT: [u8; 4] = [
+    0xF0, 0x29, // LD F, V0
+    0xD0, 0x05, // DRW V0, V0, 5
+];
+
+/// The rows of the top left 8x5 pixels of the display.
+fn drawn_digit(chip8: &Chip8) -> Vec<u8> {
+    (0..5).map(|y| (0..8).fold(0, |row, x| row << 1 | chip8.pixel(x, y) as u8)).collect()
+}
+
+#[test]
+fn font_is_in_memory() {
+    let chip8 = Chip8::new(&[]);
+    let font: Vec<u8> = (FONT_ADDR..FONT_ADDR + FONT_SIZE).map(|addr| chip8.bus().peek(addr)).collect();
+    assert_eq!(font, Font::default().sprites());
+}
+
+#[test]
+fn load_font_points_to_digit() {
+    let mut chip8 = Chip8::new(&[0xF0, 0x29]);
+    chip8.set_register(0, 0x1B);
+    chip8.step().unwrap();
+    // Only the lowest digit counts
+    assert_eq!(chip8.i() as usize, FONT_ADDR + 0xB * 5);
+}
+
+#[test]
+fn draw_with_custom_font() {
+    let mut chip8 = Chip8::new(&DRAW_DIGIT);
+    chip8.set_font(&Font::ETI_660);
+    chip8.set_register(0, 0);
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(drawn_digit(&chip8), &[0xE0, 0xA0, 0xA0, 0xA0, 0xE0]);
+}
+
+#[test]
+fn named_and_loaded_fonts() {
+    for name in Font::NAMES {
+        assert!(Font::named(name).is_some(), "{}", name);
+    }
+    assert_eq!(Font::named("VIP"), Some(Font::VIP));
+    assert_eq!(Font::named("schip"), None);
+
+    let path = std::env::temp_dir().join(format!("chip8-font-{}.bin", std::process::id()));
+    fs::write(&path, Font::DREAM_6800.sprites()).unwrap();
+    assert_eq!(Font::load(&path).unwrap(), Font::DREAM_6800);
+    fs::write(&path, [0; 79]).unwrap();
+    assert!(Font::load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
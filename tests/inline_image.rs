@@ -0,0 +1,19 @@
+use chip8::inline_image::{self, Protocol};
+
+#[test]
+fn splits_kitty_images_into_chunks() {
+    // 3 bytes become 4 characters of base64, so this needs two chunks of up to 4096 characters
+    let png = vec![0; 3102];
+    let output = inline_image::encode(Protocol::Kitty, &png);
+    let chunks: Vec<&str> = output.split_terminator("\x1b\\").collect();
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks[0].starts_with("\x1b_Gf=100,a=T,i=1,p=1,q=2,C=1,m=1;AAAA"));
+    assert_eq!(chunks[0].len(), "\x1b_Gf=100,a=T,i=1,p=1,q=2,C=1,m=1;".len() + 4096);
+    assert_eq!(chunks[1], format!("\x1b_Gm=0;{}", "A".repeat(4136 - 4096)));
+}
+
+#[test]
+fn encodes_iterm2_images_with_their_size() {
+    let output = inline_image::encode(Protocol::Iterm2, b"PNG");
+    assert_eq!(output, "\x1b7\x1b]1337;File=inline=1;size=3:UE5H\x07\x1b8");
+}
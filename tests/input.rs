@@ -0,0 +1,68 @@
+use chip8::Chip8;
+use chip8::hooks::Hooks;
+use chip8::input::{EventInput, Input, InputHook, KeyEvent, KeypadState, PolledInput};
+
+fn press(key: u8) -> KeyEvent {
+    KeyEvent { key, pressed: true }
+}
+
+fn release(key: u8) -> KeyEvent {
+    KeyEvent { key, pressed: false }
+}
+
+#[test]
+fn keypad_state_changes() {
+    let mut state = KeypadState::default();
+    state.set(0x3, true);
+    state.set(0xA, true);
+    state.set(0x10, true);
+    assert_eq!(state, KeypadState(1 << 0x3 | 1 << 0xA));
+    let changes: Vec<_> = state.changes(KeypadState(1 << 0x3 | 1 << 0x1)).collect();
+    assert_eq!(changes, [press(0x1), release(0xA)]);
+}
+
+#[test]
+fn event_input_can_be_polled() {
+    let mut input = EventInput::new();
+    input.push(press(0x5));
+    input.push(press(0x6));
+    input.push(release(0x5));
+    input.push(press(0x20));
+    assert_eq!(input.next_event(), Some(press(0x5)));
+    assert_eq!(input.poll(), KeypadState(1 << 0x6));
+    assert_eq!(input.next_event(), None);
+}
+
+#[test]
+fn polled_input_gives_events() {
+    let mut states = vec![KeypadState(1 << 0x2 | 1 << 0x7), KeypadState(1 << 0x7), KeypadState(0)].into_iter();
+    let mut input = PolledInput::new(move || states.next().unwrap_or_default());
+    assert_eq!(input.next_event(), Some(press(0x2)));
+    assert_eq!(input.next_event(), Some(press(0x7)));
+    assert_eq!(input.next_event(), Some(release(0x2)));
+    assert_eq!(input.next_event(), Some(release(0x7)));
+    assert_eq!(input.next_event(), None);
+}
+
+#[test]
+fn hook_feeds_short_presses_to_wait_for_key() {
+    // Wait for a key in V0
+    let mut chip8 = Chip8::new(&[0xF0, 0x0A]);
+    chip8.step().unwrap();
+    let mut hook = InputHook::new(EventInput::new());
+    hook.input_mut().push(press(0x9));
+    hook.input_mut().push(release(0x9));
+    assert!(hook.after_frame(&mut chip8).unwrap().is_continue());
+    assert_eq!(chip8.keypad(), 0);
+    chip8.step().unwrap();
+    assert_eq!(chip8.waiting_for_key(), None);
+    assert_eq!(chip8.register(0), 0x9);
+}
+
+#[test]
+fn hook_polls_closures() {
+    let mut chip8 = Chip8::new(&[]);
+    let mut hook = InputHook::new(|| KeypadState(1 << 0xE));
+    hook.apply(&mut chip8);
+    assert_eq!(chip8.keypad(), 1 << 0xE);
+}
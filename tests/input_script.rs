@@ -0,0 +1,26 @@
+use chip8::batch::{self, Outcome};
+use chip8::input_script::InputScript;
+use chip8::replay::KeyEvent;
+
+#[test]
+fn parses_key_changes_ordered_by_frame() {
+    let script = InputScript::parse("# Start the game\nframe 130: release 5\n\nframe 120: press 5\n").unwrap();
+    assert_eq!(script.events(), [
+        KeyEvent { frame: 120, key: 5, pressed: true },
+        KeyEvent { frame: 130, key: 5, pressed: false },
+    ]);
+    let error = InputScript::parse("frame 1: hold 5").unwrap_err();
+    assert_eq!(error, "Line 1: Expected a key change like `frame 120: press 5`");
+    assert_eq!(InputScript::parse("frame 1: press G").unwrap_err(), "Line 1: Invalid key \"G\", expected a hex digit");
+}
+
+#[test]
+fn headless_runs_press_keys() {
+    // Waits for a key, then jumps to itself if it was key A
+    let rom = [0xF0, 0x0A, 0x30, 0x0A, 0x12, 0x00, 0x12, 0x06];
+    let mut script = InputScript::parse("frame 3: press A\nframe 5: release A").unwrap();
+    let (outcome, metrics) = batch::run_rom_with_input(&rom, 60, &mut script);
+    assert_eq!(outcome, Outcome::InfiniteLoop { pc: 0x206 });
+    assert!(metrics.frames >= 5);
+    assert!(script.is_finished());
+}
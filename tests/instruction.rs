@@ -0,0 +1,34 @@
+//! Decoding and encoding instructions are inverse to each other, and the interpreter agrees with the decoder.
+
+use chip8::disassembler::mnemonic;
+use chip8::instruction::{IllegalOpcode, Instruction};
+use chip8::{Chip8, Chip8Error, PROGRAM_START};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn decode_encode_round_trip(opcode: u16) {
+        match Instruction::decode(opcode) {
+            Ok(instruction) => prop_assert_eq!(instruction.opcode(), opcode),
+            Err(err) => prop_assert_eq!(err, IllegalOpcode(opcode)),
+        }
+    }
+
+    #[test]
+    fn iter_rom_decodes_every_opcode(rom in prop::collection::vec(any::<u8>(), 0..64)) {
+        let decoded: Vec<_> = Instruction::iter_rom(&rom).collect();
+        prop_assert_eq!(decoded.len(), rom.len() / 2);
+        for (n, (addr, opcode, instruction)) in decoded.into_iter().enumerate() {
+            prop_assert_eq!(addr, PROGRAM_START + n * 2);
+            prop_assert_eq!(opcode, u16::from_be_bytes([rom[n * 2], rom[n * 2 + 1]]));
+            prop_assert_eq!(instruction.ok().map(|instruction| instruction.to_string()), mnemonic(opcode));
+        }
+    }
+
+    #[test]
+    fn executes_exactly_the_decodable_opcodes(opcode: u16) {
+        let mut chip8 = Chip8::new(&opcode.to_be_bytes());
+        let illegal = matches!(chip8.step(), Err(Chip8Error::IllegalInstruction { .. }));
+        prop_assert_eq!(illegal, Instruction::decode(opcode).is_err());
+    }
+}
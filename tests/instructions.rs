@@ -0,0 +1,218 @@
+//! Property-based tests of the instruction semantics with random register values.
+
+use chip8::snapshot::Snapshot;
+use chip8::{Chip8, Chip8Error, Quirks, StepEvent, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use proptest::prelude::*;
+
+/// Address used for memory accesses, well behind the test programs.
+const DATA: u16 = 0x300;
+
+/// Executes every instruction of `program` once and returns the resulting state.
+fn run(program: &[u16]) -> Snapshot {
+    let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip8 = Chip8::new(&rom);
+    for _ in program {
+        chip8.step().expect("Test programs are valid");
+    }
+    Snapshot::of(&chip8)
+}
+
+/// `6XNN` - `LD vx, value`.
+fn load(x: usize, value: u8) -> u16 {
+    0x6000 | (x as u16) << 8 | value as u16
+}
+
+/// `8XYN`, the arithmetic and logic instruction `n`.
+fn alu(x: usize, y: usize, n: u16) -> u16 {
+    0x8000 | (x as u16) << 8 | (y as u16) << 4 | n
+}
+
+/// Two different registers other than VF, which some instructions overwrite with a flag.
+fn two_registers() -> impl Strategy<Value = (usize, usize)> {
+    (0..0xFusize, 0..0xFusize).prop_filter("registers must differ", |(x, y)| x != y)
+}
+
+proptest! {
+    #[test]
+    fn add_sets_carry((x, y) in two_registers(), a: u8, b: u8) {
+        let state = run(&[load(x, a), load(y, b), alu(x, y, 0x4)]);
+        let (sum, carry) = a.overflowing_add(b);
+        prop_assert_eq!(state.registers[x], sum);
+        prop_assert_eq!(state.registers[0xF], carry as u8);
+    }
+
+    #[test]
+    fn sub_clears_vf_on_borrow((x, y) in two_registers(), a: u8, b: u8) {
+        let state = run(&[load(x, a), load(y, b), alu(x, y, 0x5)]);
+        prop_assert_eq!(state.registers[x], a.wrapping_sub(b));
+        prop_assert_eq!(state.registers[0xF], (a >= b) as u8);
+    }
+
+    #[test]
+    fn subn_clears_vf_on_borrow((x, y) in two_registers(), a: u8, b: u8) {
+        let state = run(&[load(x, a), load(y, b), alu(x, y, 0x7)]);
+        prop_assert_eq!(state.registers[x], b.wrapping_sub(a));
+        prop_assert_eq!(state.registers[0xF], (b >= a) as u8);
+    }
+
+    #[test]
+    fn shr_moves_dropped_bit_to_vf((x, y) in two_registers(), a: u8, b: u8) {
+        let state = run(&[load(x, a), load(y, b), alu(x, y, 0x6)]);
+        prop_assert_eq!(state.registers[x], a >> 1);
+        prop_assert_eq!(state.registers[0xF], a & 1);
+    }
+
+    #[test]
+    fn shl_moves_dropped_bit_to_vf((x, y) in two_registers(), a: u8, b: u8) {
+        let state = run(&[load(x, a), load(y, b), alu(x, y, 0xE)]);
+        prop_assert_eq!(state.registers[x], a << 1);
+        prop_assert_eq!(state.registers[0xF], a >> 7);
+    }
+
+    #[test]
+    fn add_constant_wraps_without_flag(x in 0..0xFusize, a: u8, b: u8, flag: u8) {
+        let state = run(&[load(0xF, flag), load(x, a), 0x7000 | (x as u16) << 8 | b as u16]);
+        prop_assert_eq!(state.registers[x], a.wrapping_add(b));
+        prop_assert_eq!(state.registers[0xF], flag);
+    }
+
+    #[test]
+    fn bcd_digits_recombine(x in 0..0x10usize, a: u8) {
+        let state = run(&[0xA000 | DATA, load(x, a), 0xF033 | (x as u16) << 8]);
+        let digits = &state.memory[DATA as usize..DATA as usize + 3];
+        prop_assert!(digits.iter().all(|&digit| digit < 10));
+        prop_assert_eq!(digits[0] as u32 * 100 + digits[1] as u32 * 10 + digits[2] as u32, a as u32);
+    }
+
+    #[test]
+    fn store_and_load_registers_round_trip(values in prop::collection::vec(any::<u8>(), 1..=16)) {
+        let last = values.len() - 1;
+        let mut program: Vec<u16> = values.iter().enumerate().map(|(n, &value)| load(n, value)).collect();
+        program.push(0xA000 | DATA);
+        program.push(0xF055 | (last as u16) << 8);
+        program.extend((0..=last).map(|n| load(n, 0)));
+        program.push(0xF065 | (last as u16) << 8);
+        let state = run(&program);
+        prop_assert_eq!(&state.memory[DATA as usize..=DATA as usize + last], &values[..]);
+        prop_assert_eq!(&state.registers[..=last], &values[..]);
+    }
+
+    #[test]
+    fn draw_matches_pixel_by_pixel(x: u8, y: u8, sprite in prop::collection::vec(any::<u8>(), 1..16), wrap: bool) {
+        // Draws the sprite stored behind the program twice, which erases it again
+        let program = [load(0, x), load(1, y), 0xA20A, 0xD010 | sprite.len() as u16, 0xD010 | sprite.len() as u16];
+        let rom: Vec<u8> = program.iter()
+            .flat_map(|opcode| opcode.to_be_bytes())
+            .chain(sprite.iter().copied())
+            .collect();
+        let mut chip8 = Chip8::new(&rom);
+        chip8.set_quirks(Quirks { wrap, ..*chip8.quirks() });
+        for _ in 0..4 {
+            chip8.step().expect("Test programs are valid");
+        }
+        let mut expected = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        for (row, byte) in sprite.iter().enumerate() {
+            for col in 0..8 {
+                let (px, py) = (x as usize % DISPLAY_WIDTH + col, y as usize % DISPLAY_HEIGHT + row);
+                if (byte >> (7 - col)) & 1 == 1 && (wrap || (px < DISPLAY_WIDTH && py < DISPLAY_HEIGHT)) {
+                    expected[py % DISPLAY_HEIGHT][px % DISPLAY_WIDTH] = true;
+                }
+            }
+        }
+        for (py, row) in expected.iter().enumerate() {
+            for (px, &pixel) in row.iter().enumerate() {
+                prop_assert_eq!(chip8.pixel(px, py), pixel, "Pixel ({}, {})", px, py);
+            }
+        }
+        prop_assert_eq!(chip8.register(0xF), 0);
+        chip8.step().expect("Test programs are valid");
+        prop_assert!((0..DISPLAY_HEIGHT).all(|py| (0..DISPLAY_WIDTH).all(|px| !chip8.pixel(px, py))));
+        let drawn = expected.iter().flatten().any(|&pixel| pixel);
+        prop_assert_eq!(chip8.register(0xF), drawn as u8);
+    }
+
+    #[test]
+    fn step_reports_events(x: u8, sprite: u8, sound: u8, keypad: u16) {
+        // Draw, start the sound, then wait for a key
+        let program = [load(0, x), load(1, sound), 0xA20C, 0xD001, 0xF118, 0xF20A];
+        let mut rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        rom.push(sprite);
+        let mut chip8 = Chip8::new(&rom);
+        chip8.set_instructions_per_frame(100);
+        for _ in 0..3 {
+            prop_assert!(chip8.step().expect("Test programs are valid").is_empty());
+        }
+        let events = chip8.step().expect("Test programs are valid");
+        prop_assert_eq!(events.contains(StepEvent::DisplayUpdated), sprite != 0);
+        prop_assert!(events.iter().all(|event| event == StepEvent::DisplayUpdated));
+        let events = chip8.step().expect("Test programs are valid");
+        prop_assert_eq!(events.contains(StepEvent::SoundStarted), sound != 0);
+        chip8.set_keypad(keypad);
+        let events = chip8.step().expect("Test programs are valid");
+        prop_assert_eq!(events.contains(StepEvent::WaitingForKey), keypad == 0);
+    }
+}
+
+#[test]
+fn framebuffer_formats_as_art() {
+    // Draw the sprite behind the program at (2, 1)
+    let rom = [0x60, 0x02, 0x61, 0x01, 0xA2, 0x0A, 0xD0, 0x12, 0x00, 0x00, 0b1010_0000, 0b0110_0000];
+    let mut chip8 = Chip8::new(&rom);
+    for _ in 0..4 {
+        chip8.step().expect("The program is valid");
+    }
+    let art = chip8.framebuffer().to_string();
+    let lines: Vec<&str> = art.lines().map(str::trim_end).collect();
+    assert_eq!(lines.len(), DISPLAY_HEIGHT);
+    assert_eq!(lines[..4], ["", "  █ █", "   ██", ""]);
+}
+
+#[test]
+fn wait_for_key_resumes_on_key_press() {
+    // Wait for a key in V3, then set V0
+    let mut chip8 = Chip8::new(&[0xF3, 0x0A, 0x60, 0x01]);
+    assert!(chip8.step().unwrap().contains(StepEvent::WaitingForKey));
+    assert_eq!(chip8.waiting_for_key(), Some(3));
+    assert_eq!(chip8.pc(), 0x200);
+    assert!(chip8.step().unwrap().contains(StepEvent::WaitingForKey));
+
+    // A press is taken even if the key is released before the next instruction
+    chip8.set_key_state(0xB, true);
+    chip8.set_key_state(0xB, false);
+    assert!(!chip8.step().unwrap().contains(StepEvent::WaitingForKey));
+    assert_eq!(chip8.waiting_for_key(), None);
+    assert_eq!(chip8.register(3), 0xB);
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.step().unwrap();
+    assert_eq!(chip8.register(0), 1);
+}
+
+#[test]
+fn wait_for_key_takes_held_key() {
+    let mut chip8 = Chip8::new(&[0xF3, 0x0A]);
+    chip8.set_keypad(1 << 0xC);
+    assert!(!chip8.step().unwrap().contains(StepEvent::WaitingForKey));
+    assert_eq!(chip8.waiting_for_key(), None);
+    assert_eq!(chip8.register(3), 0xC);
+}
+
+#[test]
+fn add_to_i_wraps_with_i_wrap_quirk() {
+    // I := 0xFFE, V0 := 3, I += V0
+    let rom = [0xAF, 0xFE, 0x60, 0x03, 0xF0, 0x1E];
+    let mut chip8 = Chip8::new(&rom);
+    let mut quirks = Quirks::default();
+    assert!(quirks.set("iWrap", true));
+    chip8.set_quirks(quirks);
+    for _ in 0..3 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.i(), 0x001);
+
+    let mut chip8 = Chip8::new(&rom);
+    chip8.set_quirks(Quirks { i_wrap: false, ..Quirks::default() });
+    chip8.step().expect("The program is valid");
+    chip8.step().expect("The program is valid");
+    assert!(matches!(chip8.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1001, pc: 0x204 })));
+    assert_eq!(chip8.i(), 0xFFE);
+}
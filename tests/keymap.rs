@@ -0,0 +1,57 @@
+use chip8::keymap::{layout_for_locale, Keymap};
+
+#[test]
+fn default_is_left_side_of_qwerty() {
+    let keymap = Keymap::default();
+    assert_eq!(keymap.key_for_char('1'), Some(0x1));
+    assert_eq!(keymap.key_for_char('4'), Some(0xC));
+    assert_eq!(keymap.key_for_char('X'), Some(0x0));
+    assert_eq!(keymap.key_for_char('v'), Some(0xF));
+    assert_eq!(keymap.key_for_char('p'), None);
+    assert_eq!(keymap.char_for_key(0xA), 'z');
+}
+
+#[test]
+fn set_swaps_taken_keys() {
+    let mut keymap = Keymap::default();
+    keymap.set(0x5, 'I').unwrap();
+    assert_eq!(keymap.key_for_char('i'), Some(0x5));
+    assert_eq!(keymap.key_for_char('w'), None);
+
+    // `q` pressed 4, which gets the old host key of 5
+    keymap.set(0x5, 'q').unwrap();
+    assert_eq!(keymap.key_for_char('q'), Some(0x5));
+    assert_eq!(keymap.key_for_char('i'), Some(0x4));
+
+    assert!(keymap.set(0x5, ' ').is_err());
+    assert!(keymap.set(0x5, '\n').is_err());
+}
+
+#[test]
+fn layouts_keep_physical_grid() {
+    let azerty = Keymap::layout("AZERTY").unwrap();
+    // The key below 1 on the keypad is the top left letter
+    assert_eq!(azerty.key_for_char('a'), Some(0x4));
+    assert_eq!(azerty.key_for_char('&'), Some(0x1));
+    assert_eq!(azerty.key_for_char('w'), Some(0xA));
+    assert_eq!(Keymap::layout("qwertz").unwrap().key_for_char('y'), Some(0xA));
+    assert_eq!(Keymap::layout("dvorak").unwrap().key_for_char('o'), Some(0x8));
+    assert_eq!(Keymap::layout("colemak"), None);
+    for name in Keymap::LAYOUTS {
+        let keymap = Keymap::layout(name).unwrap();
+        let mut chars: Vec<char> = (0..16).map(|key| keymap.char_for_key(key)).collect();
+        chars.sort_unstable();
+        chars.dedup();
+        assert_eq!(chars.len(), 16, "{} maps a host key twice", name);
+    }
+}
+
+#[test]
+fn guess_layout_from_locale() {
+    assert_eq!(layout_for_locale("fr_FR.UTF-8"), "azerty");
+    assert_eq!(layout_for_locale("fr_CA.UTF-8"), "qwerty");
+    assert_eq!(layout_for_locale("de_AT"), "qwertz");
+    assert_eq!(layout_for_locale("en_US.UTF-8"), "qwerty");
+    assert_eq!(layout_for_locale("C"), "qwerty");
+    assert_eq!(layout_for_locale(""), "qwerty");
+}
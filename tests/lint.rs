@@ -0,0 +1,36 @@
+//! The linter reports bugs of ROMs without running them.
+
+use chip8::lint::{lint, Warning};
+
+fn messages(rom: &[u8]) -> Vec<String> {
+    lint(rom).iter().map(Warning::to_string).collect()
+}
+
+#[test]
+fn clean_program_has_no_warnings() {
+    // Call a subroutine that returns, then halt
+    assert!(messages(&[0x22, 0x04, 0x12, 0x02, 0x60, 0x01, 0x00, 0xEE]).is_empty());
+}
+
+#[test]
+fn reports_unbalanced_stack() {
+    assert_eq!(messages(&[0x00, 0xEE]), ["0x200: Returns without a subroutine call, which underflows the stack"]);
+    assert_eq!(messages(&[0x22, 0x00])[0], "0x200: Recursive call to 0x200, which may overflow the stack");
+}
+
+#[test]
+fn reports_bad_control_flow() {
+    assert_eq!(messages(&[0x60, 0x01]), ["0x202: Execution runs past the end of the program"]);
+    let jump = messages(&[0x13, 0x01]);
+    assert_eq!(jump, ["0x200: Jump to 0x301, outside of the program", "0x200: Jump to odd address 0x301"]);
+    assert_eq!(messages(&[0xFF, 0xFF]), ["0x200: Illegal instruction FFFF is reachable"]);
+}
+
+#[test]
+fn reports_writes_to_font() {
+    // I := 0x010, store V0 there, halt
+    let warnings = lint(&[0xA0, 0x10, 0xF0, 0x55, 0x12, 0x04]);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].addr, 0x202);
+    assert_eq!(warnings[0].message, "Writes to 0x010, below the program where the font is stored");
+}
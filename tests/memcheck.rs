@@ -0,0 +1,128 @@
+//! The memory check reports reads of memory that was never written, and the write protection writes to the memory of
+//! the interpreter.
+
+use chip8::memcheck::{MemoryCheck, ProtectedWrite, UninitializedRead, WriteProtection};
+use chip8::{Chip8, Chip8Error, Quirks};
+
+#[test]
+fn reports_first_read_of_uninitialized_memory() {
+    let rom = [
+        0xA3, 0x00, // I := 0x300
+        0xF1, 0x65, // Load V0 and V1 from 0x300, which was never written
+        0xA3, 0x10, // I := 0x310
+        0xF0, 0x55, // Store V0 at 0x310
+        0xF0, 0x65, // Load it again
+        0xA2, 0x00, // I := 0x200, the ROM
+        0xF0, 0x65,
+        0xF0, 0x29, // The font digit of V0
+        0xD0, 0x05,
+        0x12, 0x00, // Do it all again
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    chip8.enable_memory_check();
+    for _ in 0..20 {
+        chip8.step().expect("The program is valid");
+    }
+    let expected = [UninitializedRead { pc: 0x202, addr: 0x300 }, UninitializedRead { pc: 0x202, addr: 0x301 }];
+    let memory_check = chip8.memory_check().expect("The check is enabled");
+    assert_eq!(memory_check.uninitialized_reads(), expected);
+    assert!(memory_check.is_initialized(0x310));
+    assert_eq!(expected[0].to_string(), "Read of uninitialized memory at 0x300 at PC=0x202");
+
+    // After a reset, the memory the program wrote is uninitialized again
+    chip8.reset();
+    let memory_check = chip8.memory_check().expect("The check stays enabled");
+    assert!(!memory_check.is_initialized(0x310));
+    assert!(memory_check.is_initialized(0x200));
+    assert_eq!(memory_check.uninitialized_reads().len(), 2);
+}
+
+#[test]
+fn loaded_data_is_initialized() {
+    let mut chip8 = Chip8::new(&[0xA3, 0x00, 0xF0, 0x65]);
+    chip8.load(0x300, &[0]).expect("The data fits");
+    chip8.enable_memory_check();
+    chip8.step().expect("The program is valid");
+    chip8.step().expect("The program is valid");
+    assert!(chip8.memory_check().expect("The check is enabled").uninitialized_reads().is_empty());
+
+    let mut memory_check = MemoryCheck::new(4);
+    memory_check.read(0x200, 4);
+    memory_check.write(4);
+    assert!(memory_check.uninitialized_reads().is_empty());
+}
+
+/// Overwrites the font digit 0 with 0xFF, twice.
+const FONT_WRITE: [u8; 10] = [0x60, 0xFF, 0xA0, 0x50, 0xF0, 0x55, 0xF0, 0x55, 0x12, 0x08];
+
+#[test]
+fn warns_about_writes_to_interpreter_memory() {
+    let mut chip8 = Chip8::new(&FONT_WRITE);
+    assert_eq!(chip8.write_protection(), WriteProtection::Warn);
+    for _ in 0..5 {
+        chip8.step().expect("Writes are only reported");
+    }
+    assert_eq!(chip8.bus().peek(0x50), 0xFF);
+    assert_eq!(chip8.protected_writes(), [ProtectedWrite { pc: 0x204, addr: 0x50, value: 0xFF }]);
+
+    let mut chip8 = Chip8::new(&FONT_WRITE);
+    chip8.set_write_protection(WriteProtection::Off);
+    for _ in 0..5 {
+        chip8.step().expect("Writes are allowed");
+    }
+    assert!(chip8.protected_writes().is_empty());
+}
+
+#[test]
+fn write_protection_can_stop_the_program() {
+    let mut chip8 = Chip8::new(&FONT_WRITE);
+    chip8.set_write_protection("error".parse().expect("The name is valid"));
+    chip8.step().expect("The program is valid");
+    chip8.step().expect("The program is valid");
+    let err = chip8.step().expect_err("The write is protected");
+    assert_eq!(err, Chip8Error::ProtectedWrite { addr: 0x50, pc: 0x204 });
+    assert!(err.is_recoverable());
+    assert_eq!(chip8.bus().peek(0x50), 0xF0);
+    assert!("sometimes".parse::<WriteProtection>().is_err());
+}
+
+#[test]
+fn clipped_sprite_rows_are_not_read() {
+    let rom = [
+        0x61, 0x1E, // V1 := 30
+        0xA3, 0x00, // I := 0x300
+        0xD0, 0x18, // Draw 8 rows at (V0, V1), of which only 2 are visible
+        0xAF, 0xFE, // I := 0xFFE, 2 bytes before the end of memory
+        0xD0, 0x18,
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    chip8.set_quirks(Quirks { wrap: false, ..Quirks::default() });
+    chip8.load(0x300, &[0xFF, 0xFF]).expect("The data fits");
+    chip8.enable_memory_check();
+    for _ in 0..3 {
+        chip8.step().expect("The program is valid");
+    }
+    assert!(chip8.memory_check().expect("The check is enabled").uninitialized_reads().is_empty());
+    for _ in 0..2 {
+        chip8.step().expect("The clipped rows beyond memory aren't read");
+    }
+}
+
+#[test]
+fn write_memory_is_checked_like_instructions() {
+    let mut chip8 = Chip8::new(&[0x12, 0x00]);
+    chip8.enable_memory_check();
+    chip8.write_memory(0x300, &[1, 2, 3]).expect("The memory isn't protected");
+    assert_eq!(chip8.memory()[0x300..0x303], [1, 2, 3]);
+    assert!(chip8.memory_check().expect("The check is enabled").is_initialized(0x302));
+
+    chip8.write_memory(0x50, &[0xFF]).expect("Writes are only reported");
+    assert_eq!(chip8.protected_writes(), [ProtectedWrite { pc: 0x200, addr: 0x50, value: 0xFF }]);
+
+    chip8.set_write_protection(WriteProtection::Error);
+    assert_eq!(chip8.write_memory(0x51, &[0xFF]), Err(Chip8Error::ProtectedWrite { addr: 0x51, pc: 0x200 }));
+    assert_eq!(chip8.memory()[0x51], 0x90);
+    let err = chip8.write_memory(0xFFF, &[1, 2]).expect_err("The second byte is beyond memory");
+    assert_eq!(err, Chip8Error::MemoryOutOfBounds { addr: 0x1000, pc: 0x200 });
+    assert_eq!(chip8.memory()[0xFFF], 1);
+}
@@ -0,0 +1,20 @@
+use chip8::multi::MultiInstance;
+use chip8::Chip8;
+
+#[test]
+fn runs_instances_independently() {
+    let mut multi = MultiInstance::new();
+    // Jumps to itself
+    multi.add("halts", Chip8::new(&[0x12, 0x00]));
+    // Waits for a key, then halts
+    multi.add("waits", Chip8::new(&[0xF0, 0x0A, 0x12, 0x02]));
+    multi.run_frame();
+    assert_eq!(multi.stopped(0), Some("halted at 0x200"));
+    assert_eq!(multi.stopped(1), None);
+    assert_eq!(multi.chip8(1).pc(), 0x200);
+
+    multi.focus_next();
+    assert_eq!(multi.focused(), 1);
+    multi.focus_next();
+    assert_eq!(multi.focused(), 0);
+}
@@ -0,0 +1,50 @@
+//! Two netplay instances connected over the loopback interface.
+
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use chip8::hooks::Hooks;
+use chip8::netplay::Netplay;
+use chip8::Chip8;
+
+const ROM: [u8; 2] = [0x12, 0x00];
+
+/// Starts a host on a free port, which accepts one player with `rom` in the background.
+fn host(rom: &'static [u8]) -> (String, thread::JoinHandle<io::Result<Netplay>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Can bind to the loopback interface");
+    let addr = listener.local_addr().unwrap().to_string();
+    (addr, thread::spawn(move || Netplay::accept(&listener, rom, 42)))
+}
+
+#[test]
+fn handshake_rejects_different_rom() {
+    let (addr, host) = host(&ROM);
+    assert!(Netplay::connect(&addr, &[0x12, 0x02]).is_err());
+    assert!(host.join().unwrap().is_err());
+}
+
+#[test]
+fn exchanges_keypads_without_echo() {
+    let (addr, host) = host(&ROM);
+    let (mut client, seed) = Netplay::connect(&addr, &ROM).expect("The ROMs match");
+    assert_eq!(seed, 42);
+    let host = thread::spawn(move || {
+        let mut netplay = host.join().unwrap().expect("The ROMs match");
+        let mut chip8 = Chip8::new(&ROM);
+        chip8.set_keypad(1 << 1);
+        assert!(netplay.after_frame(&mut chip8).unwrap().is_continue());
+        let first = chip8.keypad();
+        chip8.set_key_state(1, false);
+        assert!(netplay.after_frame(&mut chip8).unwrap().is_continue());
+        (first, chip8.keypad())
+    });
+
+    let mut chip8 = Chip8::new(&ROM);
+    chip8.set_keypad(1 << 2);
+    assert!(client.after_frame(&mut chip8).unwrap().is_continue());
+    assert_eq!(chip8.keypad(), 1 << 1 | 1 << 2);
+    // The client doesn't change its keys, so it must not send key 1 of the host back
+    assert!(client.after_frame(&mut chip8).unwrap().is_continue());
+    assert_eq!(chip8.keypad(), 1 << 2);
+    assert_eq!(host.join().unwrap(), (1 << 1 | 1 << 2, 1 << 2));
+}
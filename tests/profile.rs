@@ -0,0 +1,86 @@
+//! The profilers count executed instructions per opcode class, per program counter and per call stack.
+
+use std::ops::ControlFlow;
+use std::time::Duration;
+use chip8::profile::{CallProfiler, ExecStats, HotSpot, HotSpots, Profiler};
+use chip8::symbols::Symbols;
+use chip8::{Chip8, RunStatus};
+
+fn stats(count: u64, micros: u64) -> ExecStats {
+    ExecStats { count, time: Duration::from_micros(micros) }
+}
+
+#[test]
+fn record_groups_by_class_and_pc() {
+    let mut profiler = Profiler::new();
+    profiler.record(0x200, 0x6001, Duration::from_micros(1));
+    profiler.record(0x202, 0x8124, Duration::from_micros(5));
+    profiler.record(0x204, 0x6102, Duration::from_micros(2));
+    profiler.record(0x202, 0x8124, Duration::from_micros(5));
+
+    // Most expensive first
+    assert_eq!(profiler.classes(), [("8XY4", stats(2, 10)), ("6XNN", stats(2, 3))]);
+    assert_eq!(profiler.pcs(), [(0x202, stats(2, 10)), (0x204, stats(1, 2)), (0x200, stats(1, 1))]);
+}
+
+#[test]
+fn profiling_counts_executed_instructions() {
+    // Jump back until V0 is 3, then loop at 0x208
+    let rom = [0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0x12, 0x08, 0x12, 0x08];
+    let mut chip8 = Chip8::new(&rom);
+    chip8.enable_profiling();
+    for _ in 0..10 {
+        chip8.step().expect("The program is valid");
+    }
+    let profiler = chip8.profiler().expect("Profiling is enabled");
+    let mut counts: Vec<_> = profiler.classes().into_iter().map(|(class, stats)| (class, stats.count)).collect();
+    counts.sort();
+    assert_eq!(counts, [("1NNN", 4), ("3XNN", 3), ("7XNN", 3)]);
+    let mut counts: Vec<_> = profiler.pcs().into_iter().map(|(pc, stats)| (pc, stats.count)).collect();
+    counts.sort();
+    assert_eq!(counts, [(0x200, 3), (0x202, 3), (0x204, 2), (0x206, 1), (0x208, 1)]);
+}
+
+#[test]
+fn lists_hot_spots() {
+    // Jump back until V0 is 3, then loop at 0x208
+    let rom = [0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0x12, 0x08, 0x12, 0x08];
+    let mut chip8 = Chip8::new(&rom);
+    chip8.enable_profiling();
+    for _ in 0..10 {
+        chip8.step().expect("The program is valid");
+    }
+    let hot_spots = chip8.profiler().expect("Profiling is enabled").hot_spots(2);
+    // Equally hot instructions are ordered by address
+    assert_eq!(hot_spots, HotSpots { total: 10, spots: vec![
+        HotSpot { pc: 0x200, opcode: 0x7001, count: 3 },
+        HotSpot { pc: 0x202, opcode: 0x3003, count: 3 },
+    ] });
+    assert_eq!(hot_spots.to_string(), concat!(
+        "pc            count count %  opcode instruction\n",
+        "0x200             3  30.00%  7001   ADD V0, 0x01\n",
+        "0x202             3  30.00%  3003   SE V0, 0x03\n",
+    ));
+}
+
+#[test]
+fn folds_call_stacks() {
+    let rom = [
+        0x22, 0x06, // 0x200: Call 0x206
+        0x22, 0x0C, // 0x202: Call 0x20C
+        0x12, 0x04, // 0x204: Halt
+        0x60, 0x01, // 0x206: V0 := 1
+        0x22, 0x0C, // 0x208: Call 0x20C
+        0x00, 0xEE, // 0x20A: Return
+        0x70, 0x01, // 0x20C: V0 += 1
+        0x00, 0xEE, // 0x20E: Return
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    let mut call_profiler = CallProfiler::new();
+    chip8.set_instructions_per_frame(20);
+    let status = chip8.run_frame_with_hooks(&mut call_profiler).expect("The program is valid");
+    assert_eq!(status, ControlFlow::Break(RunStatus::Halted { pc: 0x204 }));
+    let mut symbols = Symbols::new();
+    symbols.insert(0x206, "update");
+    assert_eq!(call_profiler.folded(&symbols), "main 3\nmain;0x20C 2\nmain;update 3\nmain;update;0x20C 2\n");
+}
@@ -0,0 +1,73 @@
+use std::ops::ControlFlow;
+use chip8::hooks::Hooks;
+use chip8::replay::{KeyEvent, Replay, ReplayPlayer, ReplayRecorder};
+use chip8::snapshot::Snapshot;
+use chip8::{Chip8, Chip8Error};
+
+/// Waits for a key and stores it in V0, over and over again.
+const WAIT_KEY_ROM: [u8; 4] = [0xF0, 0x0A, 0x12, 0x00];
+
+/// Presses key 5 at the end of frame 3 and releases it at the end of frame 5.
+struct Player;
+
+impl Hooks for Player {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        match chip8.metrics().frames {
+            3 => chip8.set_key_state(5, true),
+            5 => chip8.set_key_state(5, false),
+            _ => {},
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+#[test]
+fn plays_back_recorded_keys() {
+    let mut chip8 = Chip8::builder().rom(&WAIT_KEY_ROM).seed(7).build();
+    let mut recorder = ReplayRecorder::new(&WAIT_KEY_ROM, &chip8);
+    let mut hooks: Vec<Box<dyn Hooks + '_>> = vec![Box::new(Player), Box::new(&mut recorder)];
+    for _ in 0..10 {
+        assert!(chip8.run_frame_with_hooks(&mut hooks).unwrap().is_continue());
+    }
+    drop(hooks);
+    let replay = recorder.into_replay();
+    assert_eq!(replay.events, [
+        KeyEvent { frame: 3, key: 5, pressed: true },
+        KeyEvent { frame: 5, key: 5, pressed: false },
+    ]);
+    assert_eq!(chip8.registers()[0], 5);
+
+    let mut bytes = Vec::new();
+    replay.write_to(&mut bytes).unwrap();
+    let replay = Replay::read_from(&mut bytes.as_slice()).unwrap();
+    assert!(replay.matches_rom(&WAIT_KEY_ROM));
+    let mut replayed = Chip8::new(&WAIT_KEY_ROM);
+    replay.apply(&mut replayed);
+    let mut player = ReplayPlayer::new(&replay);
+    for _ in 0..10 {
+        assert!(replayed.run_frame_with_hooks(&mut player).unwrap().is_continue());
+    }
+    assert!(player.is_finished());
+    assert_eq!(Snapshot::of(&replayed), Snapshot::of(&chip8));
+}
+
+#[test]
+fn skips_unknown_header_fields_and_events() {
+    let mut replay = Replay::new(&WAIT_KEY_ROM, &Chip8::new(&WAIT_KEY_ROM));
+    replay.platform = String::from("originalChip8");
+    replay.events.push(KeyEvent { frame: 1, key: 0xA, pressed: true });
+    let mut bytes = Vec::new();
+    replay.write_to(&mut bytes).unwrap();
+
+    // A newer writer appended a header field and added a kind of event before the key event
+    let header_len = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) as usize;
+    let header_end = 9 + header_len;
+    let mut newer = bytes[..5].to_vec();
+    newer.extend_from_slice(&(header_len as u32 + 3).to_be_bytes());
+    newer.extend_from_slice(&bytes[9..header_end]);
+    newer.extend_from_slice(&[1, 2, 3]);
+    newer.extend_from_slice(&2u32.to_be_bytes());
+    newer.extend_from_slice(&[0x42, 1, 0xFF]);
+    newer.extend_from_slice(&bytes[header_end + 4..]);
+    assert_eq!(Replay::read_from(&mut newer.as_slice()).unwrap(), replay);
+}
@@ -0,0 +1,117 @@
+//! Resetting restarts the loaded program without loading it again.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::mpsc;
+use chip8::bus::MEMORY_SIZE;
+use chip8::serial::SerialConsole;
+use chip8::snapshot::Snapshot;
+use chip8::Chip8;
+
+/// Changes everything a reset restores: registers, I, the font, RAM, the display, the timers and the stack.
+const ROM: [u8; 24] = [
+    0xC0, 0xFF, // V0 := random
+    0x61, 0x05, // V1 := 5
+    0xA0, 0x50, // I := 0x50, the font
+    0xF1, 0x55, // Overwrite the font with V0 and V1
+    0xD0, 0x15, // Draw at (V0, V1)
+    0xF1, 0x18, // ST := V1
+    0xF1, 0x15, // DT := V1
+    0x22, 0x12, // Call 0x212
+    0x12, 0x10, // Not reached
+    0xA2, 0x20, // I := 0x220
+    0xF0, 0x55, // Store V0 at 0x220
+    0x12, 0x16, // Halt
+];
+
+#[test]
+fn reset_restores_loaded_state() {
+    let mut chip8 = Chip8::builder().rom(&ROM).seed(42).build();
+    let loaded = Snapshot::of(&chip8);
+    for _ in 0..12 {
+        chip8.step().expect("The program is valid");
+    }
+    let random = chip8.register(0);
+    assert_ne!(Snapshot::of(&chip8), loaded);
+
+    chip8.reset();
+    assert_eq!(Snapshot::of(&chip8), loaded);
+    assert_eq!(chip8.sound_timer(), 0);
+    // The random numbers repeat from the seed
+    chip8.step().expect("The program is valid");
+    assert_eq!(chip8.register(0), random);
+}
+
+#[test]
+fn reset_keeps_entry_and_loaded_data() {
+    // Increments the byte at 0x300, which is loaded separately
+    let rom = [0xA3, 0x00, 0xF0, 0x65, 0x70, 0x01, 0xF0, 0x55, 0x16, 0x08];
+    let mut chip8 = Chip8::builder().rom(&[0x00, 0x00]).segment(0x600, &rom).entry(0x600).build();
+    chip8.load(0x300, &[41]).expect("The data fits");
+    for _ in 0..5 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.bus().peek(0x300), 42);
+
+    chip8.reset();
+    assert_eq!(chip8.pc(), 0x600);
+    assert_eq!(chip8.entry(), 0x600);
+    assert_eq!(chip8.bus().peek(0x300), 41);
+}
+
+#[test]
+fn soft_reset_keeps_memory() {
+    // Counts the restarts in V0 and at 0x300, then draws and calls a subroutine that doesn't return
+    let rom = [0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0xD0, 0x01, 0x22, 0x0A, 0x12, 0x0A];
+    let mut chip8 = Chip8::new(&rom);
+    for _ in 0..6 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.stack(), [0x20A]);
+
+    chip8.soft_reset();
+    assert_eq!(chip8.pc(), 0x200);
+    assert!(chip8.stack().is_empty());
+    assert!(chip8.framebuffer().rows().iter().all(|&row| row == 0));
+    for _ in 0..3 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.bus().peek(0x300), 2);
+
+    chip8.reset();
+    assert_eq!(chip8.register(0), 0);
+    assert_eq!(chip8.bus().peek(0x300), 0);
+}
+
+/// Console output shared with the test.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn reset_doesnt_write_to_serial_console() {
+    // Write 'A' to the console at 0x300
+    let rom = [0x60, b'A', 0xA3, 0x00, 0xF0, 0x55];
+    let output = Output::default();
+    let (_input, receiver) = mpsc::channel();
+    let console = SerialConsole::new([0; MEMORY_SIZE], 0x300, receiver, output.clone());
+    let mut chip8 = Chip8::with_bus(&rom, console);
+    for _ in 0..3 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(*output.0.borrow(), b"A");
+
+    chip8.reset();
+    assert_eq!(*output.0.borrow(), b"A");
+    assert_eq!(chip8.bus().peek(0x200), 0x60);
+}
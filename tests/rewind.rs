@@ -0,0 +1,133 @@
+//! Stepping back and seeking with a [`Rewind`] restores exactly the states the machine went through, also across key
+//! presses and frames.
+
+use std::ops::ControlFlow;
+use chip8::hooks::Hooks;
+use chip8::rewind::Rewind;
+use chip8::snapshot::Snapshot;
+use chip8::{Chip8, Chip8Error, Quirks};
+use proptest::prelude::*;
+
+/// Records the execution and the state after every instruction.
+struct Recorder {
+    rewind: Rewind,
+    states: Vec<Snapshot>,
+}
+
+impl Hooks for Recorder {
+    fn after_instruction(&mut self, chip8: &mut Chip8, _pc: usize, _opcode: u16)
+        -> Result<ControlFlow<()>, Chip8Error>
+    {
+        self.rewind.record(chip8);
+        self.states.push(Snapshot::of(chip8));
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// The timers may tick after the last instruction of the frame.
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        let _ = self.rewind.after_frame(chip8)?;
+        *self.states.last_mut().expect("Frames execute instructions") = Snapshot::of(chip8);
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Programs of valid instructions with jumps and calls into the program, so that they run for a while.
+fn program() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec((0u16..0x10, 0u16..0x1000), 1..128).prop_map(|instructions| {
+        let len = instructions.len() as u16;
+        instructions.iter()
+            .flat_map(|&(kind, operand)| {
+                let opcode = match kind {
+                    0x0 => [0x00E0, 0x00EE][operand as usize % 2],
+                    0x1 | 0x2 => kind << 12 | (0x200 + operand % len * 2),
+                    0x5 | 0x9 => kind << 12 | operand & 0xFF0,
+                    0x8 => {
+                        let n = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE][operand as usize % 9];
+                        0x8000 | operand & 0xFF0 | n
+                    },
+                    0xB => 0xB200 + operand % len * 2,
+                    0xE => 0xE000 | operand & 0xF00 | [0x9E, 0xA1][operand as usize % 2],
+                    0xF => {
+                        let nn = [0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65][operand as usize % 9];
+                        0xF000 | operand & 0xF00 | nn
+                    },
+                    _ => kind << 12 | operand,
+                };
+                opcode.to_be_bytes()
+            })
+            .collect()
+    })
+}
+
+/// Runs `rom` for a frame per entry of `keypads`, with the keys pressed during the frame, until it fails.
+fn run_frames(rom: &[u8], keypads: &[u16], vblank: bool) -> (Chip8, Recorder) {
+    let mut chip8 = Chip8::new(rom);
+    chip8.set_quirks(Quirks { vblank, ..*chip8.quirks() });
+    chip8.set_instructions_per_frame(10);
+    let start = Snapshot::of(&chip8);
+    let mut recorder = Recorder { rewind: Rewind::unlimited(&chip8), states: vec![start] };
+    for &keypad in keypads {
+        chip8.set_keypad(keypad);
+        if chip8.run_frame_with_hooks(&mut recorder).is_err() {
+            break;
+        }
+    }
+    (chip8, recorder)
+}
+
+/// Steps back through all recorded states and compares them. The current state isn't compared, because a failed
+/// instruction leaves the machine in a state that isn't recorded.
+fn step_back_all(chip8: &mut Chip8, recorder: &mut Recorder) -> Result<(), TestCaseError> {
+    recorder.states.pop();
+    while let Some(expected) = recorder.states.pop() {
+        prop_assert!(recorder.rewind.step_back(chip8).expect("Replayed instructions succeeded before"));
+        prop_assert_eq!(&Snapshot::of(chip8), &expected);
+    }
+    prop_assert!(!recorder.rewind.step_back(chip8).expect("Nothing is replayed at the start"));
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn step_back_restores_states(rom in program(), keys in prop::collection::vec(any::<(u8, u16)>(), 0..16)) {
+        let mut chip8 = Chip8::new(&rom);
+        let start = Snapshot::of(&chip8);
+        let mut recorder = Recorder { rewind: Rewind::new(&chip8), states: vec![start] };
+        // Press keys every few instructions, until the random program fails
+        'run: for (steps, keypad) in keys {
+            chip8.set_keypad(keypad);
+            for _ in 0..steps % 32 {
+                if chip8.step().is_err() {
+                    break 'run;
+                }
+                recorder.rewind.record(&chip8);
+                recorder.states.push(Snapshot::of(&chip8));
+            }
+        }
+        step_back_all(&mut chip8, &mut recorder)?;
+    }
+
+    #[test]
+    fn step_back_across_frames(rom in program(), keypads in prop::collection::vec(any::<u16>(), 0..16), vblank: bool) {
+        let (mut chip8, mut recorder) = run_frames(&rom, &keypads, vblank);
+        step_back_all(&mut chip8, &mut recorder)?;
+    }
+
+    #[test]
+    fn seek_back_and_forth(
+        rom in program(),
+        keypads in prop::collection::vec(any::<u16>(), 0..16),
+        vblank: bool,
+        targets in prop::collection::vec(any::<prop::sample::Index>(), 1..8),
+    ) {
+        let (mut chip8, mut recorder) = run_frames(&rom, &keypads, vblank);
+        prop_assert_eq!(recorder.rewind.end() as usize, recorder.states.len() - 1);
+        for target in targets {
+            let position = target.index(recorder.states.len());
+            let seeked = recorder.rewind.seek(&mut chip8, position as u64);
+            prop_assert!(seeked.expect("Replayed instructions succeeded before"));
+            prop_assert_eq!(&Snapshot::of(&chip8), &recorder.states[position]);
+        }
+        prop_assert!(!recorder.rewind.seek(&mut chip8, recorder.states.len() as u64).expect("Nothing is replayed"));
+    }
+}
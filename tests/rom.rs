@@ -0,0 +1,118 @@
+//! Loading ROMs from compressed archives, and identifying them.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use chip8::rom::{self, RomId};
+
+const ROM: [u8; 6] = [0x60, 0x07, 0xA2, 0x04, 0x12, 0x04];
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chip8-rom-{}-{}", std::process::id(), name))
+}
+
+/// A zip archive with the files, deflated if `deflate` is set. The CRCs are left out, as they aren't checked.
+fn zip(files: &[(&str, &[u8])], deflate: bool) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in files {
+        let stored = match deflate {
+            true => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            },
+            false => data.to_vec(),
+        };
+        let method: u16 = if deflate { 8 } else { 0 };
+        let offset = archive.len() as u32;
+        let sizes = [(stored.len() as u32).to_le_bytes(), (data.len() as u32).to_le_bytes()].concat();
+        archive.extend_from_slice(&0x0403_4B50u32.to_le_bytes());
+        archive.extend_from_slice(&[20, 0, 0, 0]);
+        archive.extend_from_slice(&method.to_le_bytes());
+        archive.extend_from_slice(&[0; 8]);
+        archive.extend_from_slice(&sizes);
+        archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&[0, 0]);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&stored);
+
+        directory.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+        directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+        directory.extend_from_slice(&method.to_le_bytes());
+        directory.extend_from_slice(&[0; 8]);
+        directory.extend_from_slice(&sizes);
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 4]);
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&directory_offset.to_le_bytes());
+    archive.extend_from_slice(&[0, 0]);
+    archive
+}
+
+#[test]
+fn loads_first_rom_of_zip() {
+    for deflate in [false, true] {
+        let path = temp_path(&format!("{}.ZIP", deflate));
+        let files: [(&str, &[u8]); 3] = [("readme.txt", b"Not a ROM"), ("games/pong.ch8", &ROM), ("b.ch8", &[0; 2])];
+        fs::write(&path, zip(&files, deflate)).unwrap();
+        let loaded = rom::load(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.expect("The archive holds a ROM").bytes, ROM);
+    }
+}
+
+#[test]
+fn rejects_zip_without_rom() {
+    let path = temp_path("empty.zip");
+    fs::write(&path, zip(&[("readme.txt", b"Not a ROM")], true)).unwrap();
+    let loaded = rom::load(&path.to_string_lossy());
+    fs::remove_file(&path).unwrap();
+    assert!(loaded.is_err());
+
+    fs::write(&path, b"Not a zip archive").unwrap();
+    let loaded = rom::load(&path.to_string_lossy());
+    fs::remove_file(&path).unwrap();
+    assert!(loaded.is_err());
+}
+
+#[test]
+fn loads_gzip() {
+    let path = temp_path("pong.ch8.gz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&ROM).unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+    let loaded = rom::load(&path.to_string_lossy());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.expect("The file is a gzipped ROM").bytes, ROM);
+
+    // Sources are assembled after unpacking
+    let path = temp_path("halt.8o.gz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b": main loop again").unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+    let loaded = rom::load(&path.to_string_lossy());
+    fs::remove_file(&path).unwrap();
+    assert!(loaded.expect("The file is a gzipped source").assembly.is_some());
+}
+
+#[test]
+fn identifies_rom() {
+    let id = RomId::of(b"123456789");
+    assert_eq!(id.size, 9);
+    assert_eq!(id.sha1, "f7c3bc1d808e04732adf679965ccc34ca7ae3441");
+    // The check value of CRC-32
+    assert_eq!(id.crc32, 0xCBF43926);
+    assert_eq!(id.to_string(), "9 bytes, SHA-1 f7c3bc1d808e04732adf679965ccc34ca7ae3441, CRC32 cbf43926");
+}
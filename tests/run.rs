@@ -0,0 +1,65 @@
+//! Running until the [`RunPolicy`] says to stop.
+
+use std::ops::ControlFlow;
+use chip8::hooks::Hooks;
+use chip8::{Chip8, Chip8Error, RunPolicy, RunStatus};
+
+/// Increments V0 forever.
+const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+/// Stops the emulation after a number of frames.
+struct StopAfter(u64);
+
+impl Hooks for StopAfter {
+    fn after_frame(&mut self, chip8: &mut Chip8) -> Result<ControlFlow<()>, Chip8Error> {
+        match chip8.metrics().frames >= self.0 {
+            true => Ok(ControlFlow::Break(())),
+            false => Ok(ControlFlow::Continue(())),
+        }
+    }
+}
+
+#[test]
+fn jump_to_itself_halts() {
+    // V0 := 1, then jump to the jump
+    let mut chip8 = Chip8::new(&[0x60, 0x01, 0x12, 0x02]);
+    chip8.set_text_output(false);
+    assert_eq!(chip8.run_policy(), RunPolicy::UntilHalt);
+    assert_eq!(chip8.run(), Ok(RunStatus::Halted { pc: 0x202 }));
+    assert_eq!(chip8.register(0), 1);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn instruction_limit_stops_at_exact_count() {
+    let mut chip8 = Chip8::new(&COUNTER);
+    chip8.set_text_output(false);
+    // The limit ends the second frame early
+    chip8.set_instructions_per_frame(7);
+    chip8.set_run_policy(RunPolicy::Instructions(11));
+    assert_eq!(chip8.run(), Ok(RunStatus::InstructionLimit));
+    assert_eq!(chip8.metrics().instructions, 11);
+    assert_eq!(chip8.register(0), 6);
+
+    // Running on doesn't execute anything
+    assert_eq!(chip8.run(), Ok(RunStatus::InstructionLimit));
+    assert_eq!(chip8.metrics().instructions, 11);
+}
+
+#[test]
+fn halting_ends_instruction_limit_early() {
+    let mut chip8 = Chip8::new(&[0x12, 0x00]);
+    chip8.set_text_output(false);
+    chip8.set_run_policy(RunPolicy::Instructions(100));
+    assert_eq!(chip8.run(), Ok(RunStatus::Halted { pc: 0x200 }));
+    assert_eq!(chip8.metrics().instructions, 1);
+}
+
+#[test]
+fn forever_runs_on_after_halting() {
+    let mut chip8 = Chip8::new(&[0x12, 0x00]);
+    chip8.set_text_output(false);
+    chip8.set_run_policy(RunPolicy::Forever);
+    assert_eq!(chip8.run_with_hooks(&mut StopAfter(3)), Ok(RunStatus::Stopped));
+    assert_eq!(chip8.metrics().frames, 3);
+}
@@ -0,0 +1,29 @@
+//! Pinned hashes of the bundled ROMs after a fixed number of instructions. If one changes, the interpreter behaves
+//! differently, which has to be intended.
+
+use chip8::Chip8;
+
+const CYCLES: u64 = 1000;
+const SEED: u64 = 7;
+
+#[test]
+fn bounce() {
+    assert_eq!(Chip8::run_hash(include_bytes!("../roms/bounce.ch8"), CYCLES, SEED), 0xc29b23519c92393e);
+}
+
+#[test]
+fn keypad() {
+    assert_eq!(Chip8::run_hash(include_bytes!("../roms/keypad.ch8"), CYCLES, SEED), 0x58ede85abaddae7b);
+}
+
+#[test]
+fn maze() {
+    assert_eq!(Chip8::run_hash(include_bytes!("../roms/maze.ch8"), CYCLES, SEED), 0xc170e6f104b03b64);
+}
+
+#[test]
+fn seed_changes_hash() {
+    let rom = include_bytes!("../roms/maze.ch8");
+    assert_eq!(Chip8::run_hash(rom, CYCLES, SEED), Chip8::run_hash(rom, CYCLES, SEED));
+    assert_ne!(Chip8::run_hash(rom, CYCLES, SEED), Chip8::run_hash(rom, CYCLES, SEED + 1));
+}
@@ -0,0 +1,32 @@
+//! Lua scripts change the machine from their callbacks.
+#![cfg(feature = "lua")]
+
+use std::ops::ControlFlow;
+use chip8::script::LuaScript;
+use chip8::{Chip8, RunStatus};
+
+#[test]
+fn on_instruction_changes_registers_and_memory() {
+    let source = r#"
+        function on_instruction(pc, opcode)
+            if pc == 0x200 and opcode == 0x6005 then
+                chip8.set_reg(1, chip8.reg(0) + 1)
+                chip8.write(0x300, 0x42)
+            end
+        end
+    "#;
+    let mut script = LuaScript::from_source(source).expect("The script is valid");
+    // V0 := 5, V0 := 6, halt
+    let mut chip8 = Chip8::new(&[0x60, 0x05, 0x60, 0x06, 0x12, 0x04]);
+    chip8.set_instructions_per_frame(3);
+    let status = chip8.run_frame_with_hooks(&mut script).expect("The program is valid");
+    assert_eq!(status, ControlFlow::Break(RunStatus::Halted { pc: 0x204 }));
+    assert_eq!(chip8.register(0), 6);
+    assert_eq!(chip8.register(1), 6);
+    assert_eq!(chip8.bus().peek(0x300), 0x42);
+}
+
+#[test]
+fn invalid_script_is_an_error() {
+    assert!(LuaScript::from_source("function (").is_err());
+}
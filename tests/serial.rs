@@ -0,0 +1,53 @@
+//! The serial console maps text I/O to one address of memory.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::mpsc;
+use chip8::bus::MEMORY_SIZE;
+use chip8::serial::{SerialConsole, DEFAULT_ADDR};
+use chip8::Chip8;
+
+/// Console output shared with the test.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn echoes_input_to_output() {
+    let rom = [
+        0xAF, 0xFF, // I := 0xFFF, the console
+        0xF0, 0x65, // Read a byte into V0
+        0x70, 0x01, // Increment it
+        0xF0, 0x55, // Write it
+        0xF0, 0x65, // Read again without input
+    ];
+    let output = Output::default();
+    let (input, receiver) = mpsc::channel();
+    let console = SerialConsole::new([0; MEMORY_SIZE], DEFAULT_ADDR, receiver, output.clone());
+    let mut chip8 = Chip8::with_bus(&rom, console);
+    input.send(b'A').unwrap();
+    for _ in 0..2 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.register(0), b'A');
+    assert!(output.0.borrow().is_empty());
+
+    for _ in 0..2 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(*output.0.borrow(), b"B");
+    assert_eq!(chip8.bus().peek(DEFAULT_ADDR), 0);
+
+    chip8.step().expect("The program is valid");
+    assert_eq!(chip8.register(0), 0);
+}
@@ -0,0 +1,20 @@
+use chip8::image::Palette;
+use chip8::sixel;
+use chip8::{Framebuffer, DISPLAY_HEIGHT};
+
+#[test]
+fn encodes_pixels_per_band() {
+    let mut rows = [0; DISPLAY_HEIGHT];
+    // Top left pixel
+    rows[0] = 1 << 63;
+    let image = sixel::encode(&Framebuffer::from_rows(rows), 1, &Palette::default());
+    assert!(image.starts_with("\x1bP0;1;0q\"1;1;64;32#0;2;0;0;0#1;2;100;100;100"));
+    assert!(image.ends_with("-\x1b\\"));
+    let bands: Vec<&str> = image.split('-').collect();
+    // 32 rows fill 6 bands, the last one only partially, followed by the terminator
+    assert_eq!(bands.len(), 7);
+    // Background below the pixel, foreground only at the pixel
+    assert!(bands[0].ends_with("#0}!63~$#1@!63?$"));
+    assert!(bands[1].ends_with("#0!64~$#1!64?$"));
+    assert!(bands[5].ends_with("#0!64B$#1!64?$"));
+}
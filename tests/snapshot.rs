@@ -0,0 +1,22 @@
+//! Comparing snapshots of the machine state.
+
+use chip8::snapshot::Snapshot;
+use chip8::Chip8;
+
+#[test]
+fn diff_lists_registers_and_memory() {
+    let mut first = Chip8::builder().rom(&[0x60, 0x05]).seed(1).build();
+    let mut second = Chip8::builder().rom(&[0x60, 0x07]).seed(1).build();
+    second.load(0x300, &[1, 2]).expect("The data fits");
+    first.step().expect("The program is valid");
+    second.step().expect("The program is valid");
+
+    let diff = Snapshot::of(&first).diff(&Snapshot::of(&second));
+    assert_eq!(diff.values, [(String::from("V0"), 5, 7)]);
+    assert_eq!(diff.memory, [0x201..0x202, 0x300..0x302]);
+    assert!(diff.display.is_empty());
+    assert_eq!(diff.to_string(), "V0: 0x5 != 0x7\nMemory 0x201..0x202 (1 bytes)\nMemory 0x300..0x302 (2 bytes)");
+
+    let snapshot = Snapshot::of(&first);
+    assert!(snapshot.diff(&snapshot).is_empty());
+}
@@ -0,0 +1,24 @@
+//! Subroutine calls and returns on the stack.
+
+use chip8::{Chip8, Chip8Error};
+
+#[test]
+fn return_without_call_underflows() {
+    let mut chip8 = Chip8::new(&[0x60, 0x01, 0x00, 0xEE]);
+    chip8.step().expect("The program is valid");
+    assert!(matches!(chip8.step(), Err(Chip8Error::StackUnderflow { pc: 0x202 })));
+}
+
+#[test]
+fn calls_beyond_stack_depth_overflow() {
+    // Call itself forever
+    let mut chip8 = Chip8::new(&[0x22, 0x00]);
+    chip8.set_stack_depth(4);
+    assert_eq!(chip8.stack_depth(), 4);
+    for depth in 1..=4 {
+        chip8.step().expect("The calls fit on the stack");
+        assert_eq!(chip8.stack().len(), depth);
+    }
+    assert!(matches!(chip8.step(), Err(Chip8Error::StackOverflow)));
+    assert_eq!(chip8.stack(), [0x202; 4]);
+}
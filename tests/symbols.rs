@@ -0,0 +1,44 @@
+//! Symbol files name addresses for the disassembler and the debugger.
+
+use chip8::symbols::Symbols;
+
+#[test]
+fn parses_symbol_file() {
+    let text = "# Generated by hand\n0x200 main\n\n0X20a loop  # Both prefixes\n30C  sprites\n";
+    let symbols = Symbols::parse(text).expect("The file is valid");
+    let expected: Vec<(usize, &str)> = vec![(0x200, "main"), (0x20A, "loop"), (0x30C, "sprites")];
+    assert_eq!(symbols.iter().collect::<Vec<_>>(), expected);
+    assert_eq!(symbols.name(0x20A), Some("loop"));
+    assert_eq!(symbols.addr("sprites"), Some(0x30C));
+    assert_eq!(symbols.addr("missing"), None);
+}
+
+#[test]
+fn rejects_invalid_lines() {
+    let expected = Err(String::from("Line 2: Expected an address and a name like `0x242 main_loop`"));
+    assert_eq!(Symbols::parse("0x200 main\nmain"), expected);
+    assert!(Symbols::parse("0x0x200 main").is_err());
+    assert!(Symbols::parse("main 0x200").is_err());
+}
+
+#[test]
+fn describes_addresses_relative_to_symbols() {
+    let mut symbols = Symbols::new();
+    symbols.insert(0x200, "main");
+    symbols.insert(0x210, "draw");
+    assert_eq!(symbols.describe(0x200), "main");
+    assert_eq!(symbols.describe(0x20E), "main+0xE");
+    assert_eq!(symbols.describe(0x214), "draw+0x4");
+    assert_eq!(symbols.describe(0x100), "0x100");
+}
+
+#[test]
+fn display_round_trips_through_parse() {
+    let mut symbols = Symbols::new();
+    symbols.insert(0x2AE, "loop");
+    symbols.insert(0x200, "main");
+    let text = symbols.to_string();
+    assert_eq!(text, "0x200 main\n0x2AE loop\n");
+    assert_eq!(Symbols::parse(&text), Ok(symbols));
+    assert!(Symbols::parse("").expect("An empty file is valid").is_empty());
+}
@@ -0,0 +1,28 @@
+//! The delay and sound timers count down at 60 Hz, once per frame.
+
+use chip8::Chip8;
+
+#[test]
+fn timers_tick_once_per_frame() {
+    // V0 := 50, DT := V0, ST := V0, then count in V1 forever
+    let rom = [0x60, 0x32, 0xF0, 0x15, 0xF0, 0x18, 0x71, 0x01, 0x12, 0x06];
+    let mut chip8 = Chip8::new(&rom);
+    chip8.set_instructions_per_frame(10);
+    for _ in 0..9 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (50, 50));
+    // The 10th instruction ends the frame
+    chip8.step().expect("The program is valid");
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (49, 49));
+
+    for _ in 0..9 {
+        chip8.step().expect("The program is valid");
+    }
+    assert_eq!(chip8.delay_timer(), 49);
+    for _ in 0..3 {
+        chip8.run_frame().expect("The program is valid");
+    }
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (46, 46));
+    assert_eq!(chip8.metrics().instructions, 49);
+}
@@ -0,0 +1,213 @@
+//! The tracers write the executed instructions and their memory accesses, filtered to what's of interest.
+
+use std::fs;
+use std::io::Cursor;
+use chip8::trace::{Divergence, JsonTracer, MemoryAccess, MemoryTracer, ReferenceTrace, TraceEntry, TraceFilter};
+use chip8::{Chip8, Chip8Error};
+
+/// The state after `LD V0, 0x05` at the start of the program.
+const ENTRY: TraceEntry = TraceEntry {
+    pc: 0x200,
+    opcode: 0x6005,
+    registers: [5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    i: 0,
+    delay_timer: 0,
+    sound_timer: 0,
+};
+
+#[test]
+fn traces_state_after_every_instruction() {
+    let path = std::env::temp_dir().join(format!("chip8-trace-{}.jsonl", std::process::id()));
+    let rom = [
+        0x60, 0x05, // V0 := 5
+        0xA3, 0x00, // I := 0x300
+        0xF0, 0x15, // delay := V0
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    // The frame doesn't end within the program, so the delay timer doesn't tick yet
+    chip8.set_instructions_per_frame(10);
+    chip8.set_tracer(JsonTracer::create(&path).expect("The temp dir is writable"));
+    for _ in 0..3 {
+        chip8.step().expect("The program is valid");
+    }
+    drop(chip8);
+    let trace = fs::read_to_string(&path).expect("The trace was written");
+    fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines, [
+        r#"{"pc":512,"opcode":24581,"v":[5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"i":0,"dt":0,"st":0}"#,
+        r#"{"pc":514,"opcode":41728,"v":[5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"i":768,"dt":0,"st":0}"#,
+        r#"{"pc":516,"opcode":61461,"v":[5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"i":768,"dt":5,"st":0}"#,
+    ]);
+    assert_eq!(TraceEntry::from_json(lines[0]), Ok(ENTRY));
+}
+
+#[test]
+fn entries_round_trip_through_json() {
+    let entry = TraceEntry {
+        pc: 0xFFE,
+        opcode: 0xFFFF,
+        registers: [255, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        i: 0xFFF,
+        delay_timer: 255,
+        sound_timer: 1,
+    };
+    assert_eq!(TraceEntry::from_json(&entry.to_json()), Ok(entry));
+}
+
+#[test]
+fn traces_memory_accesses_in_range() {
+    let path = std::env::temp_dir().join(format!("chip8-memory-trace-{}.jsonl", std::process::id()));
+    let rom = [
+        0x60, 0x2A, // V0 := 42
+        0xA3, 0x00, // I := 0x300
+        0xF0, 0x55, // Store V0 at 0x300
+        0xF1, 0x65, // Load V0 and V1 from 0x300 and 0x301
+    ];
+    let mut chip8 = Chip8::new(&rom);
+    let mut tracer = MemoryTracer::create(&path).expect("The temp dir is writable");
+    tracer.set_range(0x300..0x301);
+    chip8.set_memory_tracer(tracer);
+    for _ in 0..4 {
+        chip8.step().expect("The program is valid");
+    }
+    // Dropping the interpreter flushes the trace
+    drop(chip8);
+    let trace = fs::read_to_string(&path).expect("The trace was written");
+    fs::remove_file(&path).unwrap();
+
+    let expected = [
+        MemoryAccess { pc: 0x204, opcode: 0xF055, addr: 0x300, value: 42, write: true },
+        MemoryAccess { pc: 0x206, opcode: 0xF165, addr: 0x300, value: 42, write: false },
+    ];
+    let expected: Vec<String> = expected.iter().map(MemoryAccess::to_json).collect();
+    assert_eq!(trace.lines().collect::<Vec<_>>(), expected);
+    assert_eq!(expected[0], r#"{"pc":516,"opcode":61525,"access":"write","addr":768,"value":42}"#);
+}
+
+#[test]
+fn filter_traces_everything_by_default() {
+    let filter = TraceFilter::new();
+    assert!(filter.matches(0x200, 0x00E0));
+    assert!(filter.matches(0xFFE, 0xFFFF));
+}
+
+#[test]
+fn filter_parses_groups_and_classes() {
+    let mut filter = TraceFilter::new();
+    filter.set_only("draw, 8xy4").expect("The names are valid");
+    assert!(filter.matches(0x200, 0x00E0));
+    assert!(filter.matches(0x200, 0xD125));
+    assert!(filter.matches(0x200, 0x8AB4));
+    assert!(!filter.matches(0x200, 0x8AB5));
+    assert!(!filter.matches(0x200, 0x1200));
+
+    filter.set_only("KEY").expect("Groups are case-insensitive");
+    assert!(filter.matches(0x200, 0xE09E));
+    assert!(filter.matches(0x200, 0xF30A));
+    assert!(!filter.matches(0x200, 0xD125));
+}
+
+#[test]
+fn filter_rejects_unknown_names() {
+    let mut filter = TraceFilter::new();
+    assert_eq!(filter.set_only("draw,jmp"), Err(String::from("Unknown instruction group or opcode class jmp")));
+    assert!(filter.set_only("????").is_err());
+    assert!(filter.set_only("").is_err());
+    // A failed call keeps the previous filter
+    assert_eq!(filter, TraceFilter::new());
+}
+
+#[test]
+fn filter_limits_addresses() {
+    let mut filter = TraceFilter::new();
+    filter.set_range(0x204..0x208);
+    assert!(!filter.matches(0x202, 0x1200));
+    assert!(filter.matches(0x204, 0x1200));
+    assert!(filter.matches(0x206, 0x1200));
+    assert!(!filter.matches(0x208, 0x1200));
+
+    filter.set_only("jump").expect("The group exists");
+    assert!(filter.matches(0x204, 0xB300));
+    assert!(!filter.matches(0x204, 0x6000));
+    assert!(!filter.matches(0x300, 0xB300));
+}
+
+#[test]
+fn parses_entries_with_any_key_order_and_whitespace() {
+    let line = concat!(
+        r#"  { "st" : 0 , "dt":0,"v" : [ 5, 0,0,0, 0,0,0,0, 0,0,0,0, 0,0,0,0 ] ,"#,
+        r#""i": 0, "opcode":24581,"pc":512 }  "#,
+    );
+    assert_eq!(TraceEntry::from_json(line), Ok(ENTRY));
+}
+
+#[test]
+fn parses_entries_with_unknown_keys() {
+    let line = concat!(
+        r#"{"cycle":1,"mnemonic":"LD V0, 0x05 \"five\"","pc":512,"opcode":24581,"#,
+        r#""v":[5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"stack":[],"i":0,"dt":0,"st":0}"#,
+    );
+    assert_eq!(TraceEntry::from_json(line), Ok(ENTRY));
+}
+
+#[test]
+fn rejects_invalid_entries() {
+    let valid = ENTRY.to_json();
+    assert_eq!(TraceEntry::from_json(&valid.replace(r#""i":0,"#, "")), Err(String::from(r#"Missing "i""#)));
+    assert!(TraceEntry::from_json(&valid.replace("[5,", "[")).is_err());
+    assert!(TraceEntry::from_json(&valid.replace(r#""pc":512"#, r#""pc":"x""#)).is_err());
+    assert!(TraceEntry::from_json(&valid.replace(r#","i""#, r#" "i""#)).is_err());
+    assert!(TraceEntry::from_json(r#"{"mnemonic":"CLS}"#).is_err());
+    assert!(TraceEntry::from_json("[]").is_err());
+}
+
+#[test]
+fn reports_first_divergence_from_reference() {
+    let rom = [
+        0x60, 0x05, // V0 := 5
+        0x61, 0x07, // V1 := 7
+        0x62, 0x01, // V2 := 1
+    ];
+    let second = TraceEntry {
+        pc: 0x202,
+        opcode: 0x6107,
+        registers: [5, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ..ENTRY
+    };
+    // The third entry differs as well, but only the first divergence is reported
+    let third = TraceEntry { pc: 0x204, opcode: 0x6201, ..second };
+    let reference: Vec<String> = [ENTRY, second, third].iter().map(TraceEntry::to_json).collect();
+    let mut chip8 = Chip8::new(&rom);
+    chip8.set_reference_trace(ReferenceTrace::new(Cursor::new(reference.join("\n"))));
+
+    chip8.step().expect("The first instruction matches the reference");
+    let divergence = match chip8.step() {
+        Err(Chip8Error::Divergence(divergence)) => *divergence,
+        other => panic!("Expected a divergence, got {:?}", other),
+    };
+    let actual = TraceEntry { registers: [5, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], ..second };
+    assert_eq!(divergence, Divergence { line: 2, expected: second, actual });
+    assert_eq!(
+        divergence.to_string(),
+        "Diverged from line 2 of the reference trace after 6107 at 0x202:\n  V1: expected 0x6, got 0x7",
+    );
+}
+
+#[test]
+fn reports_different_instructions() {
+    let mut chip8 = Chip8::new(&[0x60, 0x06]);
+    chip8.set_reference_trace(ReferenceTrace::new(Cursor::new(ENTRY.to_json())));
+    match chip8.step() {
+        Err(Chip8Error::Divergence(divergence)) => assert_eq!(
+            divergence.to_string(),
+            "Diverged from line 1 of the reference trace: expected 6005 at 0x200, executed 6006 at 0x200",
+        ),
+        other => panic!("Expected a divergence, got {:?}", other),
+    }
+    // Once the reference ends, everything matches
+    chip8.set_pc(0x200);
+    chip8.set_reference_trace(ReferenceTrace::new(Cursor::new("")));
+    chip8.step().expect("There is nothing to compare with");
+}